@@ -0,0 +1,273 @@
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::{HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+use crate::audit::AuditConfig;
+use crate::batch::BatchConfig;
+use crate::circuit_breaker::CircuitState;
+use crate::config::AppState;
+use crate::error::{ApiError, ErrorFormat, HandlerResult};
+use crate::events::sse_stream;
+use crate::idempotency::IdempotencyConfig;
+use crate::messages::Localizer;
+use crate::middleware::{
+    AccessLogConfig, CompressionConfig, CorsConfig, RateLimitConfig, SecurityHeadersConfig,
+};
+use crate::pinning::SpkiPin;
+use crate::response_cache::ResponseCacheConfig;
+use crate::routes::RouteConfig;
+use crate::scrubber::ScrubberConfig;
+use crate::secrets::{Secret, SharedSecret};
+use crate::server::ServerConfig;
+
+// What `GET /admin/config` returns. Secrets appear as `[REDACTED]` (through
+// `Secret`'s `Serialize`), or only say whether they are set.
+#[derive(Serialize)]
+struct EffectiveConfig<'a> {
+    api_key: &'a SharedSecret,
+    api_key_length: usize,
+    allowed_hosts: Vec<String>,
+    denied_hosts: &'a [String],
+    default_host: Option<&'a str>,
+    max_host_len: usize,
+    allowed_email_domains: &'a [String],
+    trusted_proxies: Vec<String>,
+    error_format: ErrorFormat,
+    log_pii: bool,
+    production_mode: bool,
+    redact_query_params: &'a [String],
+    backend: BackendView<'a>,
+    rate_limit: &'a RateLimitConfig,
+    security_headers: &'a SecurityHeadersConfig,
+    cors: &'a CorsConfig,
+    auth: AuthView,
+    server: &'a ServerConfig,
+    audit: &'a AuditConfig,
+    idempotency: &'a IdempotencyConfig,
+    response_cache: &'a ResponseCacheConfig,
+    access_log: &'a AccessLogConfig,
+    log_scrubber: &'a ScrubberConfig,
+    compression: &'a CompressionConfig,
+    messages: &'a Localizer,
+    routes: &'a RouteConfig,
+    webhook: Option<WebhookView<'a>>,
+    maintenance: bool,
+}
+
+#[derive(Serialize)]
+struct WebhookView<'a> {
+    url: &'a str,
+    secret: &'a Secret,
+    max_in_flight: usize,
+}
+
+#[derive(Serialize)]
+struct BackendView<'a> {
+    timeout_ms: u64,
+    max_retries: u32,
+    readiness_host: Option<&'a str>,
+    path: &'a str,
+    max_concurrent_requests: usize,
+    queue_timeout_ms: u64,
+    in_flight: usize,
+    tls_pins: &'a [SpkiPin],
+    dry_run: bool,
+    circuit_breaker: CircuitBreakerView,
+    batch: &'a BatchConfig,
+}
+
+#[derive(Serialize)]
+struct CircuitBreakerView {
+    enabled: bool,
+    failure_threshold: u32,
+    cooldown_ms: u64,
+    state: &'static str,
+}
+
+#[derive(Serialize)]
+struct AuthView {
+    admin_token_configured: bool,
+}
+
+/// `GET /admin/config`: the effective configuration after the file and the
+/// environment were merged, for checking what a deployment actually loaded.
+/// The API key is shown as `[REDACTED]` with its length; the admin token only
+/// as whether one is set.
+pub async fn effective_config(state: web::Data<AppState>) -> HandlerResult {
+    Ok(HttpResponse::Ok().json(EffectiveConfig {
+        api_key: &state.api_key,
+        api_key_length: state.api_key.current().len(),
+        // The live list, which may have been reloaded since startup.
+        allowed_hosts: state.host_matcher.snapshot().hosts.clone(),
+        denied_hosts: state.host_matcher.denied(),
+        default_host: state.default_host.as_deref(),
+        max_host_len: state.max_host_len,
+        allowed_email_domains: &state.allowed_email_domains,
+        trusted_proxies: state
+            .trusted_proxies
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        error_format: state.error_format,
+        log_pii: state.log_pii,
+        production_mode: state.production_mode,
+        redact_query_params: &state.redact_query_params,
+        backend: BackendView {
+            timeout_ms: state.backend.timeout_ms,
+            max_retries: state.backend.max_retries,
+            readiness_host: state.backend.readiness_host.as_deref(),
+            path: &state.backend.path,
+            max_concurrent_requests: state.backend.max_concurrent_requests,
+            queue_timeout_ms: state.backend.queue_timeout_ms,
+            in_flight: state.backend.max_concurrent_requests
+                - state.backend_permits.available_permits(),
+            tls_pins: &state.backend.tls_pins,
+            dry_run: state.backend.dry_run,
+            circuit_breaker: CircuitBreakerView {
+                enabled: state.backend.circuit_breaker.enabled,
+                failure_threshold: state.backend.circuit_breaker.failure_threshold,
+                cooldown_ms: state.backend.circuit_breaker.cooldown_ms,
+                state: match state.circuit_breaker.state() {
+                    CircuitState::Closed => "closed",
+                    CircuitState::Open => "open",
+                    CircuitState::HalfOpen => "half_open",
+                },
+            },
+            batch: &state.backend.batch,
+        },
+        rate_limit: &state.rate_limit,
+        security_headers: &state.security_headers,
+        cors: &state.cors,
+        auth: AuthView {
+            admin_token_configured: state.auth.admin_token.is_some(),
+        },
+        server: &state.server,
+        audit: &state.audit,
+        idempotency: state.idempotency.config(),
+        response_cache: state.response_cache.config(),
+        access_log: &state.access_log,
+        log_scrubber: &state.log_scrubber,
+        compression: &state.compression,
+        messages: &state.localizer,
+        routes: &state.routes,
+        webhook: state.webhook.as_ref().map(|webhook| WebhookView {
+            url: webhook.config().url.as_str(),
+            secret: &webhook.config().secret,
+            max_in_flight: webhook.config().max_in_flight,
+        }),
+        maintenance: state.maintenance.load(Ordering::Relaxed),
+    }))
+}
+
+#[derive(Serialize)]
+struct WaitlistCount {
+    count: usize,
+}
+
+/// `GET /admin/waitlist/count`: how many addresses are on the waitlist.
+pub async fn waitlist_count(state: web::Data<AppState>) -> HandlerResult {
+    let count = state.waitlist.count().map_err(|e| {
+        log::error!("Failed to count signups: {}", e);
+        ApiError::Internal {
+            reason: e.to_string(),
+        }
+    })?;
+    Ok(HttpResponse::Ok().json(WaitlistCount { count }))
+}
+
+/// The body of `POST /admin/maintenance`.
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceToggle {
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+struct MaintenanceStatus {
+    maintenance: bool,
+}
+
+/// `POST /admin/maintenance`: switches maintenance mode on (`{"enabled": true}`)
+/// or off. While it is on, every route but the health checks, `/metrics` and
+/// `/admin` answers `503`. Returns the new state.
+pub async fn set_maintenance(
+    state: web::Data<AppState>,
+    toggle: web::Json<MaintenanceToggle>,
+) -> HandlerResult {
+    let was = state.maintenance.swap(toggle.enabled, Ordering::Relaxed);
+    if was != toggle.enabled {
+        log::warn!(
+            "Maintenance mode {}",
+            if toggle.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+    Ok(HttpResponse::Ok().json(MaintenanceStatus {
+        maintenance: toggle.enabled,
+    }))
+}
+
+#[derive(Serialize)]
+struct ReloadedHosts {
+    allowed_hosts: Vec<String>,
+}
+
+/// `POST /admin/reload-hosts`: re-reads `allowed_hosts` from the config file
+/// and swaps it in, without a restart (see [`AppState::reload_allowed_hosts`]).
+/// Returns the new list. If the new configuration is invalid the current list
+/// stays, and the reason is only logged.
+pub async fn reload_hosts(state: web::Data<AppState>) -> HandlerResult {
+    let allowed_hosts = state.reload_allowed_hosts().map_err(|e| {
+        log::error!("Kept the current allowed hosts: {}", e);
+        ApiError::Internal {
+            reason: e.to_string(),
+        }
+    })?;
+    log::warn!("Reloaded allowed hosts: {:?}", allowed_hosts);
+    Ok(HttpResponse::Ok().json(ReloadedHosts { allowed_hosts }))
+}
+
+/// The body of `POST /admin/rotate-key`.
+#[derive(Debug, Deserialize)]
+pub struct KeyRotation {
+    pub api_key: Secret,
+}
+
+#[derive(Serialize)]
+struct RotatedKey {
+    api_key_length: usize,
+}
+
+/// `POST /admin/rotate-key`: swaps in the `api_key` of the body for backend
+/// calls from now on, without a restart (see [`AppState::rotate_api_key`]).
+/// Returns the new key's length, never the key. An empty key or the demo key
+/// is refused with a `400` and the current key stays.
+pub async fn rotate_key(
+    state: web::Data<AppState>,
+    rotation: web::Json<KeyRotation>,
+) -> HandlerResult {
+    let api_key = rotation.into_inner().api_key;
+    let api_key_length = api_key.len();
+    state.rotate_api_key(api_key).map_err(|e| {
+        log::error!("Kept the current API key: {}", e);
+        ApiError::BadRequest {
+            reason: e.to_string(),
+        }
+    })?;
+    log::warn!("Rotated the API key");
+    Ok(HttpResponse::Ok().json(RotatedKey { api_key_length }))
+}
+
+/// `GET /admin/events`: a `text/event-stream` with an `event: signup` for
+/// every signup the backend accepts from now on, its `data` the JSON of a
+/// [`SignupEvent`](crate::events::SignupEvent), with the address masked. A
+/// client that reads too slowly misses events rather than holding them up.
+pub async fn events(state: web::Data<AppState>) -> HandlerResult {
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .streaming(sse_stream(state.events.subscribe())))
+}
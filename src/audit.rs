@@ -0,0 +1,166 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use crate::backend::UpstreamFailure;
+use crate::middleware::RequestId;
+
+static GLOBAL: RwLock<Option<AuditLogger>> = RwLock::new(None);
+
+/// A security-relevant event, recorded separately from the general logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEvent {
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    /// The correlation ID of the request the event belongs to, if any.
+    pub request_id: Option<String>,
+    /// The peer address of the client, when the recording site knows it.
+    pub client_ip: Option<IpAddr>,
+    #[serde(flatten)]
+    pub kind: AuditEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// A request's `Host` was missing, malformed, not whitelisted or resolved
+    /// to an internal address.
+    HostRejected { host: Option<String> },
+    /// The sanitizer scrubbed `count` secrets from a response body.
+    SecretRedacted { count: u64 },
+    /// A request to an admin route lacked valid credentials.
+    AuthFailed { path: String },
+    /// A backend request got no answer; a sudden run of `tls` failures may
+    /// mean the connection is being intercepted.
+    UpstreamFailed { failure: UpstreamFailure },
+}
+
+impl AuditEvent {
+    /// An event happening now, tagged with the current request's ID (when
+    /// called within the `CorrelationId` middleware).
+    pub fn new(kind: AuditEventKind) -> Self {
+        AuditEvent {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            request_id: RequestId::current().map(|id| id.to_string()),
+            client_ip: None,
+            kind,
+        }
+    }
+
+    pub fn client_ip(mut self, ip: Option<IpAddr>) -> Self {
+        self.client_ip = ip;
+        self
+    }
+}
+
+/// Where audit events are written.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Writes each event to stdout as one JSON line.
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl AuditSink for StdoutSink {
+    fn record(&self, event: &AuditEvent) {
+        if let Ok(line) = serde_json::to_string(event) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+}
+
+/// Appends each event to a file as one JSON line.
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(FileSink {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, event: &AuditEvent) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            log::error!(
+                "Failed to write audit event to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Which sink the audit log goes to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "sink", content = "path", rename_all = "lowercase")]
+pub enum AuditConfig {
+    /// Audit events are dropped.
+    #[serde(rename = "none")]
+    Disabled,
+    #[default]
+    Stdout,
+    File(PathBuf),
+}
+
+/// Hands audit events to a sink. Cloning is cheap and shares the sink.
+///
+/// The logger is installed process-wide, like the `SecretRegistry`, because
+/// some events (redactions) are detected where no request or state is at hand.
+#[derive(Clone)]
+pub struct AuditLogger {
+    sink: Arc<dyn AuditSink>,
+}
+
+impl AuditLogger {
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        AuditLogger {
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// The logger for `config`, or `None` if auditing is disabled.
+    pub fn from_config(config: &AuditConfig) -> std::io::Result<Option<Self>> {
+        Ok(match config {
+            AuditConfig::Disabled => None,
+            AuditConfig::Stdout => Some(AuditLogger::new(StdoutSink)),
+            AuditConfig::File(path) => Some(AuditLogger::new(FileSink::open(path)?)),
+        })
+    }
+
+    /// Makes this the logger [`record`] writes to.
+    pub fn install(self) {
+        *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+
+    pub fn record(&self, event: &AuditEvent) {
+        self.sink.record(event);
+    }
+}
+
+/// Records `event` with the installed logger. Without one, it is dropped.
+pub fn record(event: AuditEvent) {
+    let logger = GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(logger) = logger {
+        logger.record(&event);
+    }
+}
@@ -0,0 +1,606 @@
+use futures_util::future::LocalBoxFuture;
+use reqwest::header::{CONTENT_LENGTH, HOST};
+use reqwest::{Client, ClientBuilder, StatusCode, Url};
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use std::error::Error as _;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use url::{Position, form_urlencoded};
+
+use crate::audit::{self, AuditEvent, AuditEventKind};
+use crate::batch::BatchConfig;
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::clock::{Clock, SystemClock};
+use crate::error::ApiError;
+use crate::host::{HostMatcher, UpstreamResolver, public_addrs};
+use crate::middleware::RequestDeadline;
+use crate::pinning::{self, PinMismatch, SpkiPin};
+use crate::secrets::SharedSecret;
+
+/// The default backend endpoint, which receives waitlist signups.
+pub const WAITLIST_PATH: &str = "/v1/waitlist";
+
+/// How long to wait for the backend to accept a connection. Capped by the
+/// overall request timeout.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// The delay before the first retry; each further retry doubles it.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+/// Tells the backend, in milliseconds, how long the caller will still wait
+/// for its answer, so it can give up on (or skip) work nobody will see.
+pub const DEADLINE_HEADER: &str = "X-Request-Deadline-Ms";
+
+/// How backend calls are bounded and retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendConfig {
+    /// How long a single backend request may take, so a slow backend cannot
+    /// tie up a worker indefinitely.
+    pub timeout_ms: u64,
+    /// How many times a failed call is retried (so `max_retries + 1` attempts).
+    pub max_retries: u32,
+    /// The host `/readyz` checks for reachability. See `AppState::readiness_host`.
+    pub readiness_host: Option<String>,
+    /// Hosts that may resolve to loopback, private or link-local addresses.
+    /// Every other host is subject to the SSRF guard; see
+    /// [`crate::host::is_safe_upstream_host`].
+    pub private_hosts: HostMatcher,
+    /// The backend endpoint the handlers call, e.g. [`WAITLIST_PATH`]. Must
+    /// start with `/` and carry no query string or fragment.
+    pub path: String,
+    /// When to stop calling a failing backend for a while.
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// How many backend calls may be in flight at once, across all workers.
+    pub max_concurrent_requests: usize,
+    /// How long a request waits for one of those slots before it is refused
+    /// with a `503`.
+    pub queue_timeout_ms: u64,
+    /// The public keys the backend's certificate may carry. When empty, any
+    /// certificate the system trusts is accepted; see [`crate::pinning`] and
+    /// [`submit_waitlist`].
+    pub tls_pins: Vec<SpkiPin>,
+    /// Run the handlers up to the backend call, then report success without
+    /// making it or recording the signup. For load tests and demos.
+    pub dry_run: bool,
+    /// Whether signups are queued and submitted in batches instead of one
+    /// call per request; see [`crate::batch`].
+    pub batch: BatchConfig,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig {
+            timeout_ms: 5_000,
+            max_retries: 2,
+            readiness_host: None,
+            private_hosts: HostMatcher::default(),
+            path: WAITLIST_PATH.to_string(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            max_concurrent_requests: 64,
+            queue_timeout_ms: 100,
+            tls_pins: Vec::new(),
+            dry_run: false,
+            batch: BatchConfig::default(),
+        }
+    }
+}
+
+impl BackendConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn queue_timeout(&self) -> Duration {
+        Duration::from_millis(self.queue_timeout_ms)
+    }
+}
+
+/// Builds the HTTP client used for every backend call. Build it once and share
+/// it: the client holds the connection pool.
+pub fn build_client(config: &BackendConfig) -> Result<Client, reqwest::Error> {
+    client_builder(config).build()
+}
+
+/// The settings [`build_client`] uses, for adding to them, e.g. a test root
+/// certificate. Names are resolved by an [`UpstreamResolver`] that enforces
+/// the SSRF guard, except for `config.private_hosts`.
+pub fn client_builder(config: &BackendConfig) -> ClientBuilder {
+    Client::builder()
+        .dns_resolver(Arc::new(UpstreamResolver::new(
+            config.private_hosts.clone(),
+        )))
+        .connect_timeout(CONNECT_TIMEOUT.min(config.timeout()))
+        .timeout(config.timeout())
+}
+
+/// Makes the backend call of a signup for the secure handlers, which only
+/// pass it a host that is whitelisted and SSRF-checked. The real one is
+/// [`HttpBackendClient`]; tests can substitute their own through
+/// `AppStateBuilder::backend_client`.
+///
+/// The circuit breaker and the concurrency cap are the caller's business, so
+/// they apply to whichever client is in use.
+pub trait BackendClient: Send + Sync + fmt::Debug {
+    /// Submits the signup of `email` to the backend at `host`, on behalf of a
+    /// request that is cancelled at `deadline`, if it has one.
+    fn submit_waitlist<'a>(
+        &'a self,
+        host: &'a str,
+        email: &'a str,
+        deadline: Option<RequestDeadline>,
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>>;
+
+    /// Submits the signups of `emails` to the backend at `host` in one call,
+    /// which the backend takes or refuses as a whole. Queued signups have no
+    /// request waiting, so there is no deadline beyond the backend's own.
+    fn submit_batch<'a>(
+        &'a self,
+        host: &'a str,
+        emails: &'a [&'a str],
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>>;
+}
+
+/// The [`BackendClient`] that calls the backend over HTTP(S): it builds the
+/// URL with [`build_backend_url`] and sends it with [`submit_waitlist_within`].
+#[derive(Debug, Clone)]
+pub struct HttpBackendClient {
+    client: Client,
+    config: BackendConfig,
+    api_key: SharedSecret,
+    clock: Arc<dyn Clock>,
+}
+
+impl HttpBackendClient {
+    /// `client` is shared, connection pool and all, with whoever else holds it.
+    /// Given a [`SharedSecret`], each call uses the key as it is when the call
+    /// starts, so a rotation applies from the next one.
+    pub fn new(client: Client, config: BackendConfig, api_key: impl Into<SharedSecret>) -> Self {
+        HttpBackendClient {
+            client,
+            config,
+            api_key: api_key.into(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Reads the time left before a deadline from `clock` instead of the
+    /// system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl BackendClient for HttpBackendClient {
+    fn submit_waitlist<'a>(
+        &'a self,
+        host: &'a str,
+        email: &'a str,
+        deadline: Option<RequestDeadline>,
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        Box::pin(async move {
+            let api_key = self.api_key.current();
+            let url = build_backend_url(host, &self.config.path, api_key.expose(), email)?;
+            let budget = || deadline_budget(&self.config, deadline, &*self.clock);
+            submit_waitlist_within(&self.client, &self.config, url, budget).await
+        })
+    }
+
+    fn submit_batch<'a>(
+        &'a self,
+        host: &'a str,
+        emails: &'a [&'a str],
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        Box::pin(async move {
+            let api_key = self.api_key.current();
+            let url = build_batch_url(host, &self.config.path, api_key.expose(), emails)?;
+            submit_waitlist(&self.client, &self.config, url).await
+        })
+    }
+}
+
+/// How long the backend has for an attempt starting now, as sent in
+/// [`DEADLINE_HEADER`]: what is left of `deadline` by `clock`, but never more
+/// than the backend timeout, after which the call is abandoned anyway.
+/// Without a deadline, the backend timeout.
+pub fn deadline_budget(
+    config: &BackendConfig,
+    deadline: Option<RequestDeadline>,
+    clock: &dyn Clock,
+) -> Duration {
+    deadline.map_or(config.timeout(), |deadline| {
+        deadline.remaining(clock.now()).min(config.timeout())
+    })
+}
+
+/// Submits a signup by POSTing to `url` (as built by [`build_backend_url`]).
+///
+/// The upstream's response body is never read, let alone passed on: a non-2xx
+/// status becomes an `ApiError::Upstream` with a generic client message.
+///
+/// Failed attempts are retried with exponential backoff, but only when the
+/// backend cannot have acted on the signup: when the connection could not be
+/// established, or when it answered with a 5xx. A timeout means the request
+/// may already have been consumed, so it is reported as `UpstreamTimeout`
+/// straight away rather than risking a duplicate signup. A TLS failure is not
+/// retried either: a bad certificate will not fix itself within milliseconds.
+///
+/// Every failure to get an answer is classified (see [`UpstreamFailure`]) and
+/// recorded in the audit log.
+///
+/// With `tls_pins` configured, the call does not go through `client`: each
+/// attempt opens its own connection, whose TLS handshake fails unless the
+/// certificate carries a pinned key (see [`pinning::client_config`]). A
+/// mismatch is reported as `ApiError::TlsPinMismatch`, and not retried; the
+/// request itself, API key and all, is never sent. Such connections are not
+/// pooled, and their host is resolved with the same SSRF check as the
+/// client's (see [`UpstreamResolver`]).
+///
+/// Each attempt carries the backend timeout in [`DEADLINE_HEADER`]; see
+/// [`submit_waitlist_within`] for a request's own deadline.
+pub async fn submit_waitlist(
+    client: &Client,
+    config: &BackendConfig,
+    url: Url,
+) -> Result<(), ApiError> {
+    submit_waitlist_within(client, config, url, || config.timeout()).await
+}
+
+/// [`submit_waitlist`], telling the backend in [`DEADLINE_HEADER`] what
+/// `budget` returns as each attempt starts, so retries announce less time.
+pub async fn submit_waitlist_within(
+    client: &Client,
+    config: &BackendConfig,
+    url: Url,
+    budget: impl Fn() -> Duration,
+) -> Result<(), ApiError> {
+    let tls = (!config.tls_pins.is_empty())
+        .then(|| TlsConnector::from(Arc::new(pinning::client_config(&config.tls_pins))));
+    let mut attempt = 0;
+    loop {
+        let budget = budget();
+        let outcome = match &tls {
+            Some(tls) => attempt_pinned(tls, config, &url, budget).await,
+            None => {
+                let request = client
+                    .post(url.clone())
+                    .header(DEADLINE_HEADER, budget.as_millis().to_string());
+                match request.send().await {
+                    Ok(response) => Ok(response.status()),
+                    Err(e) => {
+                        let failure = classify_failure(&e);
+                        let retryable = e.is_connect() && failure != UpstreamFailure::Tls;
+                        Err(failed(failure, classify_reqwest_error(&e), retryable))
+                    }
+                }
+            }
+        };
+        let (error, retryable) = match outcome {
+            Ok(status) if status.is_success() => return Ok(()),
+            Ok(status) => (status_error(status), status.is_server_error()),
+            Err(failed) => failed,
+        };
+        if !retryable {
+            return Err(error);
+        }
+        if attempt >= config.max_retries {
+            return Err(if attempt == 0 {
+                error
+            } else {
+                ApiError::RetriesExhausted {
+                    attempts: attempt + 1,
+                    reason: error.to_string(),
+                }
+            });
+        }
+        log::warn!(
+            "Backend attempt {} failed, retrying: {}",
+            attempt + 1,
+            error
+        );
+        actix_web::rt::time::sleep(RETRY_BASE_DELAY * 2u32.saturating_pow(attempt)).await;
+        attempt += 1;
+    }
+}
+
+// An attempt that got no answer, recorded in the audit log: the error and
+// whether another attempt may be made.
+fn failed(failure: UpstreamFailure, error: ApiError, retryable: bool) -> (ApiError, bool) {
+    audit::record(AuditEvent::new(AuditEventKind::UpstreamFailed { failure }));
+    (error, retryable)
+}
+
+// One attempt over a fresh connection whose handshake enforces the pins, with
+// the same timeouts and the same classification of failures as one through
+// reqwest: those before the request is sent are retryable, unless TLS failed.
+async fn attempt_pinned(
+    tls: &TlsConnector,
+    config: &BackendConfig,
+    url: &Url,
+    budget: Duration,
+) -> Result<StatusCode, (ApiError, bool)> {
+    let host = url.host_str().unwrap_or_default();
+    let name = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+    let connect_failure = |failure, e: io::Error| {
+        let reason = e.to_string();
+        match failure {
+            UpstreamFailure::Timeout => failed(failure, ApiError::UpstreamTimeout { reason }, true),
+            _ => failed(failure, ApiError::UpstreamConnect { failure, reason }, true),
+        }
+    };
+
+    let addrs = match name.parse::<IpAddr>() {
+        Ok(ip) => Ok(vec![SocketAddr::new(ip, port)]),
+        Err(_) if config.private_hosts.is_allowed(name) => tokio::net::lookup_host((name, port))
+            .await
+            .map(Iterator::collect),
+        Err(_) => public_addrs(name, port).await,
+    }
+    .map_err(|e| connect_failure(UpstreamFailure::Dns, e))?;
+    let connect_timeout = CONNECT_TIMEOUT.min(config.timeout());
+    let tcp =
+        match actix_web::rt::time::timeout(connect_timeout, TcpStream::connect(&addrs[..])).await {
+            Ok(Ok(tcp)) => tcp,
+            Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                return Err(connect_failure(UpstreamFailure::ConnectionRefused, e));
+            }
+            Ok(Err(e)) => return Err(connect_failure(UpstreamFailure::Other, e)),
+            Err(_) => {
+                let e = io::Error::new(io::ErrorKind::TimedOut, "connect timed out");
+                return Err(connect_failure(UpstreamFailure::Timeout, e));
+            }
+        };
+
+    let exchange = async {
+        let server_name = ServerName::try_from(name.to_string()).map_err(|e| {
+            failed(
+                UpstreamFailure::Tls,
+                ApiError::UpstreamConnect {
+                    failure: UpstreamFailure::Tls,
+                    reason: e.to_string(),
+                },
+                false,
+            )
+        })?;
+        let stream = tls.connect(server_name, tcp).await.map_err(|e| {
+            let error = match PinMismatch::of(&e) {
+                Some(mismatch) => ApiError::TlsPinMismatch {
+                    host: host.to_string(),
+                    presented: mismatch.presented,
+                },
+                None => ApiError::UpstreamConnect {
+                    failure: UpstreamFailure::Tls,
+                    reason: e.to_string(),
+                },
+            };
+            failed(UpstreamFailure::Tls, error, false)
+        })?;
+
+        // From here on, the request may reach the backend.
+        let sent = |e: hyper::Error| {
+            failed(
+                UpstreamFailure::Other,
+                ApiError::Upstream {
+                    status: None,
+                    reason: e.to_string(),
+                },
+                false,
+            )
+        };
+        let (mut sender, connection) =
+            hyper::client::conn::handshake(stream).await.map_err(sent)?;
+        actix_web::rt::spawn(connection);
+        let authority = &url[Position::BeforeHost..Position::AfterPort];
+        let request = hyper::Request::post(&url[Position::BeforePath..Position::AfterQuery])
+            .header(HOST, authority)
+            .header(CONTENT_LENGTH, 0)
+            .header(DEADLINE_HEADER, budget.as_millis().to_string())
+            .body(hyper::Body::empty())
+            .expect("the URL was parsed already");
+        let response = sender.send_request(request).await.map_err(sent)?;
+        Ok(response.status())
+    };
+    match actix_web::rt::time::timeout(config.timeout(), exchange).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(failed(
+            UpstreamFailure::Timeout,
+            ApiError::UpstreamTimeout {
+                reason: format!("no answer within {:?}", config.timeout()),
+            },
+            false,
+        )),
+    }
+}
+
+fn status_error(status: StatusCode) -> ApiError {
+    ApiError::Upstream {
+        status: Some(status.as_u16()),
+        reason: format!("backend responded with {}", status),
+    }
+}
+
+/// Why a backend request got no answer. Operators react differently to each:
+/// a DNS failure points at configuration or the resolver, a refused connection
+/// at the backend being down, a TLS failure at certificates (or interception).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamFailure {
+    Dns,
+    ConnectionRefused,
+    Tls,
+    Timeout,
+    Other,
+}
+
+impl UpstreamFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpstreamFailure::Dns => "dns",
+            UpstreamFailure::ConnectionRefused => "connection_refused",
+            UpstreamFailure::Tls => "tls",
+            UpstreamFailure::Timeout => "timeout",
+            UpstreamFailure::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for UpstreamFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Works out why `e` happened from its kind and its chain of sources.
+///
+/// reqwest only says whether an error happened while connecting; which step
+/// failed is only visible in the underlying `io::Error` or in the messages of
+/// the resolver and the TLS library.
+pub fn classify_failure(e: &reqwest::Error) -> UpstreamFailure {
+    if e.is_timeout() {
+        return UpstreamFailure::Timeout;
+    }
+    let mut source = e.source();
+    let mut messages = Vec::new();
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            match io.kind() {
+                std::io::ErrorKind::ConnectionRefused => {
+                    return UpstreamFailure::ConnectionRefused;
+                }
+                std::io::ErrorKind::TimedOut => return UpstreamFailure::Timeout,
+                _ => {}
+            }
+        }
+        messages.push(err.to_string().to_ascii_lowercase());
+        source = err.source();
+    }
+    let mentions = |needles: &[&str]| {
+        messages
+            .iter()
+            .any(|m| needles.iter().any(|needle| m.contains(needle)))
+    };
+    if mentions(&[
+        "dns error",
+        "failed to lookup address",
+        "name or service not known",
+    ]) {
+        UpstreamFailure::Dns
+    } else if mentions(&["certificate", "ssl", "tls", "handshake"]) {
+        UpstreamFailure::Tls
+    } else {
+        UpstreamFailure::Other
+    }
+}
+
+/// Maps a failed backend request to the `ApiError` it surfaces as:
+/// `UpstreamTimeout` (a `504`), `UpstreamConnect` when no connection could be
+/// made, or `Upstream` otherwise (both `502`). Clients only ever get the
+/// generic message of the variant; the classification and the full cause are
+/// for the logs, and are kept free of the request URL and its API key.
+pub fn classify_reqwest_error(e: &reqwest::Error) -> ApiError {
+    let reason = describe(e);
+    match classify_failure(e) {
+        UpstreamFailure::Timeout => ApiError::UpstreamTimeout { reason },
+        failure if e.is_connect() => ApiError::UpstreamConnect { failure, reason },
+        _ => ApiError::Upstream {
+            status: e.status().map(|s| s.as_u16()),
+            reason,
+        },
+    }
+}
+
+// The error and its causes on one line, without the URL (which reqwest prints
+// as part of the error itself).
+fn describe(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    if let Some(url) = e.url() {
+        message = message.replace(&format!(" for url ({})", url), "");
+    }
+    let mut source = e.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    match e.url() {
+        Some(url) => message.replace(url.as_str(), "[URL]"),
+        None => message,
+    }
+}
+
+/// Builds the URL of the backend endpoint at `path` (see
+/// [`BackendConfig::path`]) on `host`.
+///
+/// The query parameters are percent-encoded by the URL's query builder, so an
+/// email like `a&b=c@example.com` stays a single `email` value.
+///
+/// A bad `host` fails in one of two ways:
+///
+/// - `ApiError::InvalidHost` when it is empty or contains whitespace, a control
+///   character, userinfo (`user:pass@`, or just `evil.com@real.com`), a path
+///   separator, or the start of a query or fragment (`my-app.com?x`, which
+///   would turn `path` into part of the query and call `/` instead). The URL
+///   parser would accept several of these and quietly point the request
+///   somewhere else, so they are refused before parsing.
+/// - `ApiError::UrlParse` when it passes those checks but is still not a valid
+///   authority, e.g. `my-app.com:99999` (port out of range) or `[::1` (an
+///   unclosed IPv6 literal). The error records the URL that was being built,
+///   with the API key in it. That context is for server-side logs only.
+pub fn build_backend_url(
+    host: &str,
+    path: &str,
+    api_key: &str,
+    email: &str,
+) -> Result<Url, ApiError> {
+    build_batch_url(host, path, api_key, &[email])
+}
+
+/// [`build_backend_url`] for a batch of signups: one `email` parameter per
+/// address of `emails`, in order, after the single `api_key`.
+pub fn build_batch_url(
+    host: &str,
+    path: &str,
+    api_key: &str,
+    emails: &[&str],
+) -> Result<Url, ApiError> {
+    check_host(host)?;
+    let mut url =
+        Url::parse(&format!("https://{}{}", host, path)).map_err(|source| ApiError::UrlParse {
+            url: attempted_url(host, path, api_key, emails),
+            source,
+        })?;
+    url.set_fragment(None);
+    url.set_query(None);
+    url.query_pairs_mut()
+        .append_pair("api_key", api_key)
+        .extend_pairs(emails.iter().map(|email| ("email", email)));
+    Ok(url)
+}
+
+fn check_host(host: &str) -> Result<(), ApiError> {
+    let suspicious = host.is_empty()
+        || host.chars().any(|c| {
+            c.is_whitespace() || c.is_control() || matches!(c, '@' | '/' | '\\' | '?' | '#')
+        });
+    if suspicious {
+        return Err(ApiError::InvalidHost {
+            host: Some(host.to_string()),
+        });
+    }
+    Ok(())
+}
+
+// The textual form of the URL `build_backend_url` tried to produce, encoded the
+// same way, for when parsing fails and there is no `Url` to print.
+fn attempted_url(host: &str, path: &str, api_key: &str, emails: &[&str]) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("api_key", api_key)
+        .extend_pairs(emails.iter().map(|email| ("email", email)))
+        .finish();
+    format!("https://{}{}?{}", host, path, query)
+}
@@ -0,0 +1,154 @@
+//! Submits signups to the backend in batches, off the request path.
+//!
+//! With `[backend.batch]` enabled, the secure handlers record a signup, queue
+//! it and answer `202 Accepted` at once. A background task collects the queue
+//! into batches, of up to `max_size` signups or whatever arrived within
+//! `max_delay_ms` of the first, and submits each batch with one backend call
+//! per host (see `BackendClient::submit_batch`). Closing the queue at shutdown
+//! lets the task submit what is still waiting before it ends.
+//!
+//! A batch the backend refuses cannot be reported to clients that already
+//! got their `202`: its signups are taken back off the waitlist and counted
+//! by [`Batcher::failed`], exported as `batched_signups_failed_total`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::error::ApiError;
+
+/// Whether signups are batched, and how batches are cut.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchConfig {
+    pub enabled: bool,
+    /// The most signups submitted in one batch.
+    pub max_size: usize,
+    /// How long the first signup of a batch waits for others to join it.
+    pub max_delay_ms: u64,
+    /// How many signups may wait to be submitted. Beyond that, signups are
+    /// refused with a `503` until the queue drains.
+    pub queue_capacity: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            enabled: false,
+            max_size: 50,
+            max_delay_ms: 100,
+            queue_capacity: 1024,
+        }
+    }
+}
+
+impl BatchConfig {
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_ms)
+    }
+}
+
+/// A signup waiting to be submitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submission {
+    /// The whitelisted, SSRF-checked host to submit it to.
+    pub host: String,
+    /// The address as the client sent it, which is what the backend is given.
+    pub email: String,
+    /// The address as recorded on the waitlist (see `store::normalize_email`).
+    pub key: String,
+}
+
+/// The queue between the handlers and the task that submits batches. Clones
+/// share the queue.
+#[derive(Debug, Clone)]
+pub struct Batcher {
+    config: BatchConfig,
+    sender: Arc<Mutex<Option<mpsc::Sender<Submission>>>>,
+    receiver: Arc<Mutex<Option<mpsc::Receiver<Submission>>>>,
+    failed: Arc<AtomicU64>,
+}
+
+impl Batcher {
+    pub fn new(config: BatchConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        Batcher {
+            config,
+            sender: Arc::new(Mutex::new(Some(sender))),
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+            failed: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &BatchConfig {
+        &self.config
+    }
+
+    /// Queues `submission`, or refuses it with `ApiError::Overloaded` when
+    /// the queue is full or closed.
+    pub fn enqueue(&self, submission: Submission) -> Result<(), ApiError> {
+        let sender = self
+            .sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let refused = ApiError::Overloaded {
+            waited: Duration::ZERO,
+        };
+        match &*sender {
+            Some(sender) => sender.try_send(submission).map_err(|_| refused),
+            None => Err(refused),
+        }
+    }
+
+    /// Closes the queue: later signups are refused, and once the signups
+    /// already queued are handed out, [`next_batch`] returns `None`.
+    pub fn close(&self) {
+        self.sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+    }
+
+    /// Counts `count` queued signups as lost to a failed batch.
+    pub fn record_failed(&self, count: usize) {
+        self.failed.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// How many queued signups were lost to failed batches so far.
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// The receiving end of the queue, handed out once: whoever gets it runs
+    /// the task that submits the batches.
+    pub fn take_receiver(&self) -> Option<mpsc::Receiver<Submission>> {
+        self.receiver
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+    }
+}
+
+/// Waits for the next batch: the first queued signup, and those that follow
+/// it within `max_delay`, up to `max_size` of them. A signup queued twice is
+/// only submitted once. `None` once the queue is closed and empty.
+pub async fn next_batch(
+    receiver: &mut mpsc::Receiver<Submission>,
+    config: &BatchConfig,
+) -> Option<Vec<Submission>> {
+    let mut batch = vec![receiver.recv().await?];
+    let deadline = Instant::now() + config.max_delay();
+    while batch.len() < config.max_size {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match actix_web::rt::time::timeout(left, receiver.recv()).await {
+            Ok(Some(submission)) => {
+                if !batch.iter().any(|queued| queued.key == submission.key) {
+                    batch.push(submission);
+                }
+            }
+            Ok(None) | Err(_) => break,
+        }
+    }
+    Some(batch)
+}
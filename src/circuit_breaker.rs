@@ -0,0 +1,214 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+
+/// When the backend circuit opens, and for how long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+    /// Consecutive failed calls that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit fails calls fast before letting a trial
+    /// call through.
+    pub cooldown_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 5,
+            cooldown_ms: 30_000,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_millis(self.cooldown_ms)
+    }
+}
+
+/// The externally visible state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through; failures are counted.
+    Closed,
+    /// Calls fail fast until the cooldown is over.
+    Open,
+    /// The cooldown is over and one trial call decides what happens next.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_in_flight: bool },
+}
+
+/// Stops calling a backend that keeps failing.
+///
+/// After `failure_threshold` consecutive failures the circuit opens, and for
+/// `cooldown` every call fails immediately with `ApiError::CircuitOpen` (a
+/// `503` whose `Retry-After` is the rest of the cooldown) instead of adding
+/// load to a backend that is already down. Then one trial call is let
+/// through: if it succeeds the circuit closes, otherwise it opens for another
+/// cooldown.
+///
+/// Clones share the same state, so a breaker built once at startup covers
+/// every worker.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Arc<Mutex<State>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            state: Arc::new(Mutex::new(State::Closed { failures: 0 })),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.lock() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if Instant::now() < until => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Runs `call` unless the circuit is open. Only errors that say the
+    /// backend is unhealthy count as failures: timeouts, connection failures
+    /// and 5xx answers, not a 4xx refusal of this particular request.
+    pub async fn call<T, F>(&self, call: F) -> Result<T, ApiError>
+    where
+        F: Future<Output = Result<T, ApiError>>,
+    {
+        if !self.config.enabled {
+            return call.await;
+        }
+        self.acquire()?;
+        // Settles a trial call even if this future is dropped mid-call, so an
+        // abandoned trial cannot keep the circuit half-open forever.
+        let mut guard = Guard {
+            breaker: self,
+            settled: false,
+        };
+        let result = call.await;
+        guard.settled = true;
+        match &result {
+            Err(e) if is_backend_failure(e) => self.record_failure(),
+            _ => self.record_success(),
+        }
+        result
+    }
+
+    fn acquire(&self) -> Result<(), ApiError> {
+        let mut state = self.lock();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(ApiError::CircuitOpen {
+                        retry_after: until - now,
+                    });
+                }
+                log::info!("Backend circuit half-open; letting a trial call through");
+                *state = State::HalfOpen {
+                    trial_in_flight: true,
+                };
+                Ok(())
+            }
+            State::HalfOpen {
+                trial_in_flight: true,
+            } => Err(ApiError::CircuitOpen {
+                retry_after: Duration::ZERO,
+            }),
+            State::HalfOpen {
+                trial_in_flight: false,
+            } => {
+                *state = State::HalfOpen {
+                    trial_in_flight: true,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.lock();
+        if let State::HalfOpen { .. } = *state {
+            log::info!("Backend circuit closed");
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.lock();
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            // A failed trial reopens the circuit straight away.
+            State::HalfOpen { .. } => self.config.failure_threshold,
+            // Calls admitted before the circuit opened; it is open already.
+            State::Open { .. } => return,
+        };
+        if failures >= self.config.failure_threshold {
+            log::warn!(
+                "Backend circuit opened after {} consecutive failure(s); failing fast for {:?}",
+                failures,
+                self.config.cooldown()
+            );
+            *state = State::Open {
+                until: Instant::now() + self.config.cooldown(),
+            };
+        } else {
+            *state = State::Closed { failures };
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new(CircuitBreakerConfig::default())
+    }
+}
+
+struct Guard<'a> {
+    breaker: &'a CircuitBreaker,
+    settled: bool,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let mut state = self.breaker.lock();
+        if let State::HalfOpen { .. } = *state {
+            *state = State::HalfOpen {
+                trial_in_flight: false,
+            };
+        }
+    }
+}
+
+fn is_backend_failure(e: &ApiError) -> bool {
+    match e {
+        ApiError::UpstreamTimeout { .. }
+        | ApiError::UpstreamConnect { .. }
+        | ApiError::TlsPinMismatch { .. }
+        | ApiError::RetriesExhausted { .. } => true,
+        ApiError::Upstream { status, .. } => status.is_none_or(|s| s >= 500),
+        _ => false,
+    }
+}
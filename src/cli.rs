@@ -0,0 +1,38 @@
+use clap::Parser;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Command-line options. Everything else is configured through the config
+/// file and the environment.
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+#[command(version, about = "Waitlist signup demo: the uncaught exception lesson")]
+pub struct Cli {
+    /// Address to listen on, for both HTTP and HTTPS. Overrides
+    /// `server.bind` (127.0.0.1 by default).
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+
+    /// Port for plain HTTP, overriding `server.port` (8080 by default). The
+    /// HTTPS port is `server.tls.port`.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// TOML (or `.json`) config file; environment variables override its
+    /// values.
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Serve HTTPS only, like `TLS_ONLY=true`. Needs a certificate configured
+    /// in `[server.tls]` or through `TLS_CERT_PATH`/`TLS_KEY_PATH`.
+    #[arg(long)]
+    pub tls: bool,
+
+    /// Log filter, e.g. `debug` or `info,actix_web=warn`. Overrides `RUST_LOG`.
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Refuse to start with settings meant only for the lesson, such as a
+    /// build with the `insecure-demo` feature.
+    #[arg(long)]
+    pub production: bool,
+}
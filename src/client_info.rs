@@ -0,0 +1,217 @@
+use actix_web::http::header::HeaderMap;
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use futures_util::future::{Ready, ready};
+use ipnet::IpNet;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::AppState;
+use crate::host::request_host;
+
+/// Where a request came from, as far as the server can trust it.
+///
+/// `Forwarded` and `X-Forwarded-*` headers are only believed when the peer is
+/// one of the configured trusted proxies (`AppState::trusted_proxies`); from
+/// anyone else they are ignored, so a client cannot pick its own address. Use
+/// this rather than `ConnectionInfo::realip_remote_addr`, which trusts those
+/// headers from everyone.
+///
+/// As an extractor it never fails: without an `AppState` no proxy is trusted
+/// and no host is validated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// The client's address: the peer itself, or, behind trusted proxies, the
+    /// nearest forwarded address that is not one of them. `None` only when the
+    /// peer address is unknown.
+    pub ip: Option<IpAddr>,
+    /// `"https"` or `"http"`: how the client reached the first trusted proxy,
+    /// or this server when it connected directly.
+    pub scheme: &'static str,
+    /// The `Host` header, if it is on the whitelist.
+    pub host: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn of(req: &HttpRequest) -> ClientInfo {
+        let state = req.app_data::<web::Data<AppState>>();
+        let (ip, scheme) = forwarded(req);
+        let host = state.and_then(|state| {
+            request_host(req, state.default_host.as_deref(), state.max_host_len)
+                .ok()
+                .filter(|host| state.host_matcher.is_allowed(host))
+                .map(str::to_string)
+        });
+        ClientInfo { ip, scheme, host }
+    }
+}
+
+impl FromRequest for ClientInfo {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(ClientInfo::of(req)))
+    }
+}
+
+/// The scheme the client used, as in [`ClientInfo::scheme`], for code that
+/// needs nothing else: `X-Forwarded-Proto` or the `proto` of `Forwarded` when
+/// the peer is a trusted proxy, and otherwise whether this server's own
+/// listener is TLS.
+pub fn effective_scheme(req: &HttpRequest) -> &'static str {
+    forwarded(req).1
+}
+
+// The client's address and scheme, believing forwarding headers only from a
+// trusted peer.
+fn forwarded(req: &HttpRequest) -> (Option<IpAddr>, &'static str) {
+    let trusted = req
+        .app_data::<web::Data<AppState>>()
+        .map_or(&[][..], |s| s.trusted_proxies.as_slice());
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let direct_scheme = if req.app_config().secure() {
+        "https"
+    } else {
+        "http"
+    };
+    match peer {
+        Some(peer) if is_trusted(peer, trusted) => {
+            let (ip, scheme) = resolve_forwarded(peer, req.headers(), trusted);
+            (Some(ip), scheme.unwrap_or(direct_scheme))
+        }
+        peer => (peer, direct_scheme),
+    }
+}
+
+/// Whether `ip` is in one of the `trusted` ranges.
+pub fn is_trusted(ip: IpAddr, trusted: &[IpNet]) -> bool {
+    let ip = canonical(ip);
+    trusted.iter().any(|net| net.contains(&ip))
+}
+
+// An IPv4 peer on a dual-stack socket shows up as `::ffff:a.b.c.d`.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4),
+        ip => ip,
+    }
+}
+
+// One proxy hop: the address it received the request from, and the scheme
+// it was received over.
+struct Hop {
+    ip: Option<IpAddr>,
+    proto: Option<&'static str>,
+}
+
+// Walks the forwarded chain from the nearest hop outwards, past every trusted
+// proxy. The first untrusted address is the client. A hop that cannot be
+// parsed ends the walk, leaving the last trusted proxy as the client, since
+// nothing behind it can be verified.
+fn resolve_forwarded(
+    peer: IpAddr,
+    headers: &HeaderMap,
+    trusted: &[IpNet],
+) -> (IpAddr, Option<&'static str>) {
+    let (hops, mut scheme) = match parse_forwarded(headers) {
+        Some(hops) => (hops, None),
+        None => (parse_x_forwarded_for(headers), x_forwarded_proto(headers)),
+    };
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        let Some(ip) = hop.ip else {
+            break;
+        };
+        client = ip;
+        scheme = hop.proto.or(scheme);
+        if !is_trusted(ip, trusted) {
+            break;
+        }
+    }
+    (client, scheme)
+}
+
+// RFC 7239: `Forwarded: for=192.0.2.60;proto=https, for="[2001:db8::1]:4711"`.
+fn parse_forwarded(headers: &HeaderMap) -> Option<Vec<Hop>> {
+    let mut values = headers.get_all("forwarded").peekable();
+    values.peek()?;
+    let mut hops = Vec::new();
+    for value in values {
+        let Ok(value) = value.to_str() else {
+            hops.push(Hop {
+                ip: None,
+                proto: None,
+            });
+            continue;
+        };
+        for element in value.split(',') {
+            let mut hop = Hop {
+                ip: None,
+                proto: None,
+            };
+            for pair in element.split(';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.ip = parse_node(value),
+                    "proto" => hop.proto = parse_scheme(value),
+                    _ => {}
+                }
+            }
+            hops.push(hop);
+        }
+    }
+    Some(hops)
+}
+
+fn parse_x_forwarded_for(headers: &HeaderMap) -> Vec<Hop> {
+    headers
+        .get_all("x-forwarded-for")
+        .flat_map(|value| {
+            let entries: Vec<Option<IpAddr>> = match value.to_str() {
+                Ok(value) => value.split(',').map(|e| parse_node(e.trim())).collect(),
+                Err(_) => vec![None],
+            };
+            entries
+        })
+        .map(|ip| Hop { ip, proto: None })
+        .collect()
+}
+
+// The nearest proxy sets (or overwrites) it, so its last value counts.
+fn x_forwarded_proto(headers: &HeaderMap) -> Option<&'static str> {
+    headers
+        .get_all("x-forwarded-proto")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .and_then(|proto| parse_scheme(proto.trim()))
+}
+
+// An address, optionally with a port: `192.0.2.60`, `192.0.2.60:80`,
+// `[2001:db8::1]:4711` or `2001:db8::1`. Obfuscated and `unknown` nodes are
+// not addresses.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| {
+            node.strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+                .unwrap_or(node)
+                .parse::<IpAddr>()
+        })
+        .ok()
+        .map(canonical)
+}
+
+fn parse_scheme(proto: &str) -> Option<&'static str> {
+    if proto.eq_ignore_ascii_case("https") {
+        Some("https")
+    } else if proto.eq_ignore_ascii_case("http") {
+        Some("http")
+    } else {
+        None
+    }
+}
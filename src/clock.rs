@@ -0,0 +1,20 @@
+//! Where the current time comes from, so what measures the time left (such as
+//! the deadline passed to the backend) can be tested without sleeping.
+
+use std::fmt;
+use std::time::Instant;
+
+/// A source of [`Instant`]s. [`SystemClock`] is the real one.
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic clock of the OS, as `Instant::now` reads it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
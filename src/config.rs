@@ -0,0 +1,1942 @@
+use ipnet::IpNet;
+use reqwest::{ClientBuilder, Url};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use tokio::sync::Semaphore;
+
+use crate::audit::AuditConfig;
+use crate::backend::{self, BackendClient, BackendConfig, HttpBackendClient};
+use crate::batch::{BatchConfig, Batcher};
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+use crate::cli::Cli;
+use crate::error::ErrorFormat;
+use crate::events::SignupEvents;
+use crate::host::{DEFAULT_MAX_HOST_LEN, HostMatcher, HostWhitelist, is_valid_host_entry};
+use crate::idempotency::{IdempotencyConfig, IdempotencyStore};
+use crate::messages::{Localizer, Messages};
+use crate::middleware::{
+    AccessLogConfig, AccessLogVerbosity, AuthConfig, CompressionConfig, CorsConfig,
+    RateLimitConfig, SecurityHeadersConfig,
+};
+use crate::pinning::SpkiPin;
+use crate::response_cache::{ResponseCache, ResponseCacheConfig};
+use crate::routes::{ROUTE_PATHS, RouteConfig, RoutePolicy};
+use crate::scrubber::{ScrubRule, Scrubber, ScrubberConfig};
+use crate::secrets::{Secret, SecretRegistry, SharedSecret};
+use crate::server::{ServerConfig, TlsConfig};
+use crate::startup::DEMO_API_KEY;
+use crate::store::{InMemoryStore, SqliteWaitlistStore, WaitlistStore};
+use crate::validation::{SignupValidator, ValidatorChain, is_valid_domain};
+use crate::webhook::{Webhook, WebhookConfig};
+
+/// Represents the application's configuration, including the sensitive API key.
+#[derive(Clone)]
+pub struct AppState {
+    // Never printed: its `Debug` output is `[REDACTED]`. Replaced at runtime
+    // by `POST /admin/rotate-key`; take one snapshot per request.
+    pub api_key: SharedSecret,
+    // A whitelist of allowed hostnames for the secure version, as loaded at
+    // startup.
+    pub allowed_hosts: Vec<String>,
+    // The whitelist the secure handler checks against: `allowed_hosts`
+    // compiled, or whatever `POST /admin/reload-hosts` last swapped in.
+    pub host_matcher: HostWhitelist,
+    // The config file the state was loaded from, if any; re-read by
+    // `reload_allowed_hosts`.
+    pub config_path: Option<PathBuf>,
+    // The host assumed when a request has no `Host` header at all.
+    pub default_host: Option<String>,
+    // Longer hosts are refused with a 400 before they are used or logged.
+    pub max_host_len: usize,
+    // The email domains (`example.com`, `*.example.com`) the secure handlers
+    // accept signups from. Empty allows every domain.
+    pub allowed_email_domains: Vec<String>,
+    // What a signup must pass before the secure handlers act on it: the
+    // built-in checks (`ValidatorChain::builtin`, with `allowed_email_domains`),
+    // then any from `AppStateBuilder::signup_validator`, in order.
+    pub signup_validators: ValidatorChain,
+    // Proxies whose `Forwarded`/`X-Forwarded-For` headers are believed; see
+    // `client_info::ClientInfo`.
+    pub trusted_proxies: Vec<IpNet>,
+    // Timeouts and retries for backend calls.
+    pub backend: BackendConfig,
+    // Per-client-IP request limits.
+    pub rate_limit: RateLimitConfig,
+    // Headers added to every response.
+    pub security_headers: SecurityHeadersConfig,
+    // Which cross-origin callers may read responses.
+    pub cors: CorsConfig,
+    // Credentials for the `/admin` routes.
+    pub auth: AuthConfig,
+    // Whether error bodies are JSON or the original plain text.
+    pub error_format: ErrorFormat,
+    // Log email addresses in full instead of masked. For local development only.
+    pub log_pii: bool,
+    // Whether every error body is the generic one, whatever the handler
+    // returned; see `error::set_production_mode`.
+    pub production_mode: bool,
+    // Query parameters whose values are replaced with `[REDACTED]` in logged
+    // URLs. Always includes `api_key`.
+    pub redact_query_params: Vec<String>,
+    // Process-level settings such as the shutdown drain timeout.
+    pub server: ServerConfig,
+    // Where security-relevant events are recorded.
+    pub audit: AuditConfig,
+    // What the `AccessLog` middleware writes per request.
+    pub access_log: AccessLogConfig,
+    // The rules log lines are rewritten with, and the scrubber compiled from
+    // them when enabled; `main` installs it.
+    pub log_scrubber: ScrubberConfig,
+    pub scrubber: Option<Arc<Scrubber>>,
+    // Which responses are compressed.
+    pub compression: CompressionConfig,
+    // The texts of success (and generic error) responses, by language.
+    pub localizer: Localizer,
+    // Per-route timeouts, body limits and admin requirements.
+    pub routes: RouteConfig,
+    // Notifies an external system of each signup, if configured.
+    pub webhook: Option<Webhook>,
+    // The live feed of signups behind `GET /admin/events`.
+    pub events: SignupEvents,
+    // Shared by every worker, like the client below.
+    pub circuit_breaker: CircuitBreaker,
+    // The queue of signups waiting to be submitted in a batch; unused unless
+    // `BackendConfig::batch` is enabled.
+    pub batcher: Batcher,
+    // One permit per backend call allowed in flight; see
+    // `BackendConfig::max_concurrent_requests`.
+    pub backend_permits: Arc<Semaphore>,
+    // Where signups are recorded (normalized, see `store::normalize_email`).
+    pub waitlist: Arc<dyn WaitlistStore>,
+    // Outcomes of requests that carried an `Idempotency-Key`.
+    pub idempotency: IdempotencyStore,
+    // Signups the backend accepted recently; off unless configured.
+    pub response_cache: ResponseCache,
+    // While set, only health and admin routes are served; toggled through
+    // `POST /admin/maintenance`. Shared by every worker.
+    pub maintenance: Arc<AtomicBool>,
+    // Shared client for backend calls; cloning it shares the connection pool.
+    pub client: reqwest::Client,
+    // What the secure handlers submit signups through: an
+    // `HttpBackendClient` on `client`, unless the builder was given another.
+    pub backend_client: Arc<dyn BackendClient>,
+}
+
+/// Why the configuration could not be loaded.
+///
+/// None of these messages include configuration values, so they are safe to
+/// print even when the failing input contains the API key.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse config file {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+    #[error("no API key configured; set API_KEY or `api_key` in the config file")]
+    MissingApiKey,
+    #[error("failed to read the API key file {}: {source}", path.display())]
+    ApiKeyFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("no allowed hosts configured; set ALLOWED_HOSTS or `allowed_hosts` in the config file")]
+    NoAllowedHosts,
+    #[error("failed to build the backend HTTP client: {0}")]
+    HttpClient(String),
+    #[error("invalid TLS configuration: {0}")]
+    Tls(String),
+    #[error("failed to open the waitlist database: {0}")]
+    Waitlist(String),
+    #[error("invalid value for {name}: {message}")]
+    InvalidValue { name: &'static str, message: String },
+}
+
+// The shape of `config.toml`. Every field is optional so the environment can
+// fill in whatever the file leaves out.
+#[derive(Debug, Default, Deserialize)]
+struct PartialConfig {
+    api_key: Option<String>,
+    // Wins over `api_key`; see `read_api_key_file`.
+    api_key_file: Option<PathBuf>,
+    allowed_hosts: Option<Vec<String>>,
+    denied_hosts: Option<Vec<String>>,
+    ignore_host_port: Option<bool>,
+    default_host: Option<String>,
+    max_host_len: Option<usize>,
+    allowed_email_domains: Option<Vec<String>>,
+    trusted_proxies: Option<Vec<String>>,
+    error_format: Option<ErrorFormat>,
+    log_pii: Option<bool>,
+    production_mode: Option<bool>,
+    redact_query_params: Option<Vec<String>>,
+    #[serde(default)]
+    backend: PartialBackendConfig,
+    #[serde(default)]
+    rate_limit: PartialRateLimitConfig,
+    #[serde(default)]
+    security_headers: PartialSecurityHeadersConfig,
+    #[serde(default)]
+    cors: PartialCorsConfig,
+    #[serde(default)]
+    auth: PartialAuthConfig,
+    #[serde(default)]
+    server: PartialServerConfig,
+    #[serde(default)]
+    audit: PartialAuditConfig,
+    #[serde(default)]
+    waitlist: PartialWaitlistConfig,
+    #[serde(default)]
+    idempotency: PartialIdempotencyConfig,
+    #[serde(default)]
+    response_cache: PartialResponseCacheConfig,
+    #[serde(default)]
+    access_log: PartialAccessLogConfig,
+    #[serde(default)]
+    log_scrubber: PartialScrubberConfig,
+    #[serde(default)]
+    compression: PartialCompressionConfig,
+    #[serde(default)]
+    messages: PartialMessages,
+    #[serde(default)]
+    webhook: PartialWebhookConfig,
+    // `[routes."<path>"]` tables; the policies need no merging of their own.
+    routes: Option<BTreeMap<String, RoutePolicy>>,
+}
+
+// The `[backend]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialBackendConfig {
+    timeout_ms: Option<u64>,
+    max_retries: Option<u32>,
+    readiness_host: Option<String>,
+    private_hosts: Option<Vec<String>>,
+    path: Option<String>,
+    max_concurrent_requests: Option<usize>,
+    queue_timeout_ms: Option<u64>,
+    tls_pins: Option<Vec<String>>,
+    dry_run: Option<bool>,
+    #[serde(default)]
+    circuit_breaker: PartialCircuitBreakerConfig,
+    #[serde(default)]
+    batch: PartialBatchConfig,
+}
+
+// The `[backend.circuit_breaker]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialCircuitBreakerConfig {
+    enabled: Option<bool>,
+    failure_threshold: Option<u32>,
+    cooldown_ms: Option<u64>,
+}
+
+impl PartialCircuitBreakerConfig {
+    fn merge(self, other: PartialCircuitBreakerConfig) -> Self {
+        PartialCircuitBreakerConfig {
+            enabled: other.enabled.or(self.enabled),
+            failure_threshold: other.failure_threshold.or(self.failure_threshold),
+            cooldown_ms: other.cooldown_ms.or(self.cooldown_ms),
+        }
+    }
+
+    fn into_config(self) -> CircuitBreakerConfig {
+        let defaults = CircuitBreakerConfig::default();
+        CircuitBreakerConfig {
+            enabled: self.enabled.unwrap_or(defaults.enabled),
+            failure_threshold: self.failure_threshold.unwrap_or(defaults.failure_threshold),
+            cooldown_ms: self.cooldown_ms.unwrap_or(defaults.cooldown_ms),
+        }
+    }
+}
+
+// The `[backend.batch]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialBatchConfig {
+    enabled: Option<bool>,
+    max_size: Option<usize>,
+    max_delay_ms: Option<u64>,
+    queue_capacity: Option<usize>,
+}
+
+impl PartialBatchConfig {
+    fn merge(self, other: PartialBatchConfig) -> Self {
+        PartialBatchConfig {
+            enabled: other.enabled.or(self.enabled),
+            max_size: other.max_size.or(self.max_size),
+            max_delay_ms: other.max_delay_ms.or(self.max_delay_ms),
+            queue_capacity: other.queue_capacity.or(self.queue_capacity),
+        }
+    }
+
+    fn into_config(self) -> BatchConfig {
+        let defaults = BatchConfig::default();
+        BatchConfig {
+            enabled: self.enabled.unwrap_or(defaults.enabled),
+            max_size: self.max_size.unwrap_or(defaults.max_size),
+            max_delay_ms: self.max_delay_ms.unwrap_or(defaults.max_delay_ms),
+            queue_capacity: self.queue_capacity.unwrap_or(defaults.queue_capacity),
+        }
+    }
+}
+
+impl PartialBackendConfig {
+    fn merge(self, other: PartialBackendConfig) -> Self {
+        PartialBackendConfig {
+            timeout_ms: other.timeout_ms.or(self.timeout_ms),
+            max_retries: other.max_retries.or(self.max_retries),
+            readiness_host: other.readiness_host.or(self.readiness_host),
+            private_hosts: other.private_hosts.or(self.private_hosts),
+            path: other.path.or(self.path),
+            max_concurrent_requests: other
+                .max_concurrent_requests
+                .or(self.max_concurrent_requests),
+            queue_timeout_ms: other.queue_timeout_ms.or(self.queue_timeout_ms),
+            tls_pins: other.tls_pins.or(self.tls_pins),
+            dry_run: other.dry_run.or(self.dry_run),
+            circuit_breaker: self.circuit_breaker.merge(other.circuit_breaker),
+            batch: self.batch.merge(other.batch),
+        }
+    }
+
+    fn into_config(self) -> BackendConfig {
+        let defaults = BackendConfig::default();
+        BackendConfig {
+            timeout_ms: self.timeout_ms.unwrap_or(defaults.timeout_ms),
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            readiness_host: self.readiness_host.or(defaults.readiness_host),
+            // Exemptions are about the host, so any port will do.
+            private_hosts: self
+                .private_hosts
+                .map(|hosts| HostMatcher::new(&hosts, true))
+                .unwrap_or(defaults.private_hosts),
+            path: self.path.unwrap_or(defaults.path),
+            circuit_breaker: self.circuit_breaker.into_config(),
+            max_concurrent_requests: self
+                .max_concurrent_requests
+                .unwrap_or(defaults.max_concurrent_requests),
+            queue_timeout_ms: self.queue_timeout_ms.unwrap_or(defaults.queue_timeout_ms),
+            // Taken out by `into_state`, so `AppStateBuilder::build` validates them.
+            tls_pins: defaults.tls_pins,
+            dry_run: self.dry_run.unwrap_or(defaults.dry_run),
+            batch: self.batch.into_config(),
+        }
+    }
+}
+
+// The `[rate_limit]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialRateLimitConfig {
+    enabled: Option<bool>,
+    requests_per_second: Option<f64>,
+    burst: Option<u32>,
+    trust_forwarded_for: Option<bool>,
+}
+
+impl PartialRateLimitConfig {
+    fn merge(self, other: PartialRateLimitConfig) -> Self {
+        PartialRateLimitConfig {
+            enabled: other.enabled.or(self.enabled),
+            requests_per_second: other.requests_per_second.or(self.requests_per_second),
+            burst: other.burst.or(self.burst),
+            trust_forwarded_for: other.trust_forwarded_for.or(self.trust_forwarded_for),
+        }
+    }
+
+    fn into_config(self) -> Result<RateLimitConfig, ConfigError> {
+        let defaults = RateLimitConfig::default();
+        let config = RateLimitConfig {
+            enabled: self.enabled.unwrap_or(defaults.enabled),
+            requests_per_second: self
+                .requests_per_second
+                .unwrap_or(defaults.requests_per_second),
+            burst: self.burst.unwrap_or(defaults.burst),
+            trust_forwarded_for: self
+                .trust_forwarded_for
+                .unwrap_or(defaults.trust_forwarded_for),
+        };
+        if !(config.requests_per_second.is_finite() && config.requests_per_second > 0.0) {
+            return Err(ConfigError::InvalidValue {
+                name: "rate_limit.requests_per_second",
+                message: "must be a positive number".to_string(),
+            });
+        }
+        if config.burst == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "rate_limit.burst",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        Ok(config)
+    }
+}
+
+// The `[security_headers]` table of `config.toml`. An empty string disables
+// the header.
+#[derive(Debug, Default, Deserialize)]
+struct PartialSecurityHeadersConfig {
+    strict_transport_security: Option<String>,
+    content_type_options: Option<String>,
+    frame_options: Option<String>,
+    referrer_policy: Option<String>,
+    content_security_policy: Option<String>,
+    server: Option<String>,
+}
+
+impl PartialSecurityHeadersConfig {
+    fn merge(self, other: PartialSecurityHeadersConfig) -> Self {
+        PartialSecurityHeadersConfig {
+            strict_transport_security: other
+                .strict_transport_security
+                .or(self.strict_transport_security),
+            content_type_options: other.content_type_options.or(self.content_type_options),
+            frame_options: other.frame_options.or(self.frame_options),
+            referrer_policy: other.referrer_policy.or(self.referrer_policy),
+            content_security_policy: other
+                .content_security_policy
+                .or(self.content_security_policy),
+            server: other.server.or(self.server),
+        }
+    }
+
+    fn into_config(self) -> SecurityHeadersConfig {
+        let defaults = SecurityHeadersConfig::default();
+        let pick = |value: Option<String>, default: Option<String>| match value {
+            Some(value) if value.is_empty() => None,
+            Some(value) => Some(value),
+            None => default,
+        };
+        SecurityHeadersConfig {
+            strict_transport_security: pick(
+                self.strict_transport_security,
+                defaults.strict_transport_security,
+            ),
+            content_type_options: pick(self.content_type_options, defaults.content_type_options),
+            frame_options: pick(self.frame_options, defaults.frame_options),
+            referrer_policy: pick(self.referrer_policy, defaults.referrer_policy),
+            content_security_policy: pick(
+                self.content_security_policy,
+                defaults.content_security_policy,
+            ),
+            server: pick(self.server, defaults.server),
+        }
+    }
+}
+
+// The `[cors]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialCorsConfig {
+    allowed_origins: Option<Vec<String>>,
+    allowed_methods: Option<Vec<String>>,
+    allowed_headers: Option<Vec<String>>,
+    max_age_secs: Option<u64>,
+}
+
+impl PartialCorsConfig {
+    fn merge(self, other: PartialCorsConfig) -> Self {
+        PartialCorsConfig {
+            allowed_origins: other.allowed_origins.or(self.allowed_origins),
+            allowed_methods: other.allowed_methods.or(self.allowed_methods),
+            allowed_headers: other.allowed_headers.or(self.allowed_headers),
+            max_age_secs: other.max_age_secs.or(self.max_age_secs),
+        }
+    }
+
+    fn into_config(self) -> CorsConfig {
+        let defaults = CorsConfig::default();
+        CorsConfig {
+            allowed_origins: self.allowed_origins.unwrap_or(defaults.allowed_origins),
+            allowed_methods: self.allowed_methods.unwrap_or(defaults.allowed_methods),
+            allowed_headers: self.allowed_headers.unwrap_or(defaults.allowed_headers),
+            max_age_secs: self.max_age_secs.unwrap_or(defaults.max_age_secs),
+        }
+    }
+}
+
+// The `[auth]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialAuthConfig {
+    admin_token: Option<String>,
+}
+
+impl PartialAuthConfig {
+    fn merge(self, other: PartialAuthConfig) -> Self {
+        PartialAuthConfig {
+            admin_token: other.admin_token.or(self.admin_token),
+        }
+    }
+
+    fn into_config(self) -> AuthConfig {
+        AuthConfig {
+            admin_token: self.admin_token.filter(|t| !t.is_empty()),
+        }
+    }
+}
+
+// The `[audit]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialAuditConfig {
+    sink: Option<AuditSinkKind>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AuditSinkKind {
+    None,
+    Stdout,
+    File,
+}
+
+impl std::str::FromStr for AuditSinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(AuditSinkKind::None),
+            "stdout" => Ok(AuditSinkKind::Stdout),
+            "file" => Ok(AuditSinkKind::File),
+            _ => Err(format!(
+                "expected \"none\", \"stdout\" or \"file\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+impl PartialAuditConfig {
+    fn merge(self, other: PartialAuditConfig) -> Self {
+        PartialAuditConfig {
+            sink: other.sink.or(self.sink),
+            path: other.path.or(self.path),
+        }
+    }
+
+    fn into_config(self) -> Result<AuditConfig, ConfigError> {
+        match (self.sink, self.path) {
+            (None | Some(AuditSinkKind::Stdout), _) => Ok(AuditConfig::Stdout),
+            (Some(AuditSinkKind::None), _) => Ok(AuditConfig::Disabled),
+            (Some(AuditSinkKind::File), Some(path)) => Ok(AuditConfig::File(path)),
+            (Some(AuditSinkKind::File), None) => Err(ConfigError::InvalidValue {
+                name: "audit.path",
+                message: "must be set when audit.sink is \"file\"".to_string(),
+            }),
+        }
+    }
+}
+
+// The `[waitlist]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialWaitlistConfig {
+    // A SQLite database path. Signups are kept in memory when it is unset.
+    database: Option<String>,
+}
+
+impl PartialWaitlistConfig {
+    fn merge(self, other: PartialWaitlistConfig) -> Self {
+        PartialWaitlistConfig {
+            database: other.database.or(self.database),
+        }
+    }
+
+    fn into_store(self) -> Result<Option<Arc<dyn WaitlistStore>>, ConfigError> {
+        match self.database.filter(|d| !d.is_empty()) {
+            Some(database) => {
+                let store = SqliteWaitlistStore::open(&database)
+                    .map_err(|e| ConfigError::Waitlist(e.to_string()))?;
+                Ok(Some(Arc::new(store)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// The `[idempotency]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialIdempotencyConfig {
+    ttl_secs: Option<u64>,
+}
+
+// The `[response_cache]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialResponseCacheConfig {
+    capacity: Option<usize>,
+    ttl_ms: Option<u64>,
+}
+
+impl PartialResponseCacheConfig {
+    fn merge(self, other: PartialResponseCacheConfig) -> Self {
+        PartialResponseCacheConfig {
+            capacity: other.capacity.or(self.capacity),
+            ttl_ms: other.ttl_ms.or(self.ttl_ms),
+        }
+    }
+
+    fn into_config(self) -> ResponseCacheConfig {
+        let defaults = ResponseCacheConfig::default();
+        ResponseCacheConfig {
+            capacity: self.capacity.unwrap_or(defaults.capacity),
+            ttl_ms: self.ttl_ms.unwrap_or(defaults.ttl_ms),
+        }
+    }
+}
+
+// The `[webhook]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialWebhookConfig {
+    url: Option<String>,
+    secret: Option<String>,
+    max_in_flight: Option<usize>,
+}
+
+impl PartialWebhookConfig {
+    fn merge(self, other: PartialWebhookConfig) -> Self {
+        PartialWebhookConfig {
+            url: other.url.or(self.url),
+            secret: other.secret.or(self.secret),
+            max_in_flight: other.max_in_flight.or(self.max_in_flight),
+        }
+    }
+
+    // No URL means no webhook; the rest is checked by `AppStateBuilder::build`.
+    fn into_config(self) -> Result<Option<WebhookConfig>, ConfigError> {
+        let Some(url) = self.url.filter(|u| !u.is_empty()) else {
+            return Ok(None);
+        };
+        let url = Url::parse(&url).map_err(|e| ConfigError::InvalidValue {
+            name: "webhook.url",
+            message: e.to_string(),
+        })?;
+        Ok(Some(WebhookConfig {
+            max_in_flight: self
+                .max_in_flight
+                .unwrap_or(WebhookConfig::DEFAULT_MAX_IN_FLIGHT),
+            ..WebhookConfig::new(url, self.secret.unwrap_or_default())
+        }))
+    }
+}
+
+impl PartialIdempotencyConfig {
+    fn merge(self, other: PartialIdempotencyConfig) -> Self {
+        PartialIdempotencyConfig {
+            ttl_secs: other.ttl_secs.or(self.ttl_secs),
+        }
+    }
+
+    fn into_config(self) -> IdempotencyConfig {
+        let defaults = IdempotencyConfig::default();
+        IdempotencyConfig {
+            ttl_secs: self.ttl_secs.unwrap_or(defaults.ttl_secs),
+        }
+    }
+}
+
+// The `[compression]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialCompressionConfig {
+    enabled: Option<bool>,
+    min_size_bytes: Option<usize>,
+}
+
+impl PartialCompressionConfig {
+    fn merge(self, other: PartialCompressionConfig) -> Self {
+        PartialCompressionConfig {
+            enabled: other.enabled.or(self.enabled),
+            min_size_bytes: other.min_size_bytes.or(self.min_size_bytes),
+        }
+    }
+
+    fn into_config(self) -> CompressionConfig {
+        let defaults = CompressionConfig::default();
+        CompressionConfig {
+            enabled: self.enabled.unwrap_or(defaults.enabled),
+            min_size_bytes: self.min_size_bytes.unwrap_or(defaults.min_size_bytes),
+        }
+    }
+}
+
+// The `[access_log]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialAccessLogConfig {
+    verbosity: Option<AccessLogVerbosity>,
+    sensitive_headers: Option<Vec<String>>,
+}
+
+impl PartialAccessLogConfig {
+    fn merge(self, other: PartialAccessLogConfig) -> Self {
+        PartialAccessLogConfig {
+            verbosity: other.verbosity.or(self.verbosity),
+            sensitive_headers: other.sensitive_headers.or(self.sensitive_headers),
+        }
+    }
+
+    fn into_config(self) -> AccessLogConfig {
+        AccessLogConfig {
+            verbosity: self.verbosity.unwrap_or_default(),
+            sensitive_headers: self.sensitive_headers.unwrap_or_default(),
+        }
+    }
+}
+
+// The `[log_scrubber]` table of `config.toml`. The rules are only set in the
+// file.
+#[derive(Debug, Default, Deserialize)]
+struct PartialScrubberConfig {
+    enabled: Option<bool>,
+    rules: Option<Vec<ScrubRule>>,
+}
+
+impl PartialScrubberConfig {
+    fn merge(self, other: PartialScrubberConfig) -> Self {
+        PartialScrubberConfig {
+            enabled: other.enabled.or(self.enabled),
+            rules: other.rules.or(self.rules),
+        }
+    }
+
+    fn into_config(self) -> ScrubberConfig {
+        ScrubberConfig {
+            enabled: self.enabled.unwrap_or(false),
+            rules: self.rules.unwrap_or_default(),
+        }
+    }
+}
+
+// The `[messages]` table of `config.toml`, and its `[messages.translations.<lang>]`
+// tables (which cannot have translations of their own). Only set in the file:
+// the texts are too long for environment variables to be convenient.
+#[derive(Debug, Default, Deserialize)]
+struct PartialMessages {
+    signup_success: Option<String>,
+    vulnerable_signup_success: Option<String>,
+    generic_error: Option<String>,
+    translations: Option<BTreeMap<String, PartialMessages>>,
+}
+
+impl PartialMessages {
+    fn merge(self, other: PartialMessages) -> Self {
+        PartialMessages {
+            signup_success: other.signup_success.or(self.signup_success),
+            vulnerable_signup_success: other
+                .vulnerable_signup_success
+                .or(self.vulnerable_signup_success),
+            generic_error: other.generic_error.or(self.generic_error),
+            translations: other.translations.or(self.translations),
+        }
+    }
+
+    // A translation falls back to the built-in one for its language, or else
+    // to the (configured) English texts.
+    fn into_localizer(mut self) -> Localizer {
+        let translations = self.translations.take().unwrap_or_default();
+        let default = self.into_messages(Messages::default());
+        translations.into_iter().fold(
+            Localizer::new(default.clone()),
+            |localizer, (lang, partial)| {
+                let primary = lang.split('-').next().unwrap_or_default();
+                let base = Messages::builtin(&primary.to_ascii_lowercase())
+                    .unwrap_or_else(|| default.clone());
+                localizer.translation(&lang, partial.into_messages(base))
+            },
+        )
+    }
+
+    fn into_messages(self, defaults: Messages) -> Messages {
+        Messages {
+            signup_success: self.signup_success.unwrap_or(defaults.signup_success),
+            vulnerable_signup_success: self
+                .vulnerable_signup_success
+                .unwrap_or(defaults.vulnerable_signup_success),
+            generic_error: self.generic_error.unwrap_or(defaults.generic_error),
+        }
+    }
+}
+
+// The `[server]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialServerConfig {
+    bind: Option<IpAddr>,
+    port: Option<u16>,
+    shutdown_timeout_secs: Option<u64>,
+    max_body_bytes: Option<usize>,
+    max_uri_bytes: Option<usize>,
+    request_deadline_ms: Option<u64>,
+    route_deadlines_ms: Option<BTreeMap<String, u64>>,
+    force_https: Option<bool>,
+    workers: Option<usize>,
+    keep_alive_secs: Option<u64>,
+    client_request_timeout_ms: Option<u64>,
+    client_disconnect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    tls: PartialTlsConfig,
+}
+
+impl PartialServerConfig {
+    fn merge(self, other: PartialServerConfig) -> Self {
+        PartialServerConfig {
+            bind: other.bind.or(self.bind),
+            port: other.port.or(self.port),
+            shutdown_timeout_secs: other.shutdown_timeout_secs.or(self.shutdown_timeout_secs),
+            max_body_bytes: other.max_body_bytes.or(self.max_body_bytes),
+            max_uri_bytes: other.max_uri_bytes.or(self.max_uri_bytes),
+            request_deadline_ms: other.request_deadline_ms.or(self.request_deadline_ms),
+            route_deadlines_ms: other.route_deadlines_ms.or(self.route_deadlines_ms),
+            force_https: other.force_https.or(self.force_https),
+            workers: other.workers.or(self.workers),
+            keep_alive_secs: other.keep_alive_secs.or(self.keep_alive_secs),
+            client_request_timeout_ms: other
+                .client_request_timeout_ms
+                .or(self.client_request_timeout_ms),
+            client_disconnect_timeout_ms: other
+                .client_disconnect_timeout_ms
+                .or(self.client_disconnect_timeout_ms),
+            tls: self.tls.merge(other.tls),
+        }
+    }
+
+    fn into_config(self) -> Result<ServerConfig, ConfigError> {
+        let defaults = ServerConfig::default();
+        Ok(ServerConfig {
+            bind: self.bind.unwrap_or(defaults.bind),
+            port: self.port.unwrap_or(defaults.port),
+            shutdown_timeout_secs: self
+                .shutdown_timeout_secs
+                .unwrap_or(defaults.shutdown_timeout_secs),
+            max_body_bytes: self.max_body_bytes.unwrap_or(defaults.max_body_bytes),
+            max_uri_bytes: self.max_uri_bytes.unwrap_or(defaults.max_uri_bytes),
+            request_deadline_ms: self
+                .request_deadline_ms
+                .unwrap_or(defaults.request_deadline_ms),
+            route_deadlines_ms: self.route_deadlines_ms.unwrap_or_default(),
+            tls: self.tls.into_config()?,
+            force_https: self.force_https.unwrap_or(defaults.force_https),
+            workers: self.workers.or(defaults.workers),
+            keep_alive_secs: self.keep_alive_secs.unwrap_or(defaults.keep_alive_secs),
+            client_request_timeout_ms: self
+                .client_request_timeout_ms
+                .unwrap_or(defaults.client_request_timeout_ms),
+            client_disconnect_timeout_ms: self
+                .client_disconnect_timeout_ms
+                .unwrap_or(defaults.client_disconnect_timeout_ms),
+        })
+    }
+}
+
+// The `[server.tls]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct PartialTlsConfig {
+    cert_path: Option<PathBuf>,
+    key_path: Option<PathBuf>,
+    port: Option<u16>,
+    disable_plain_http: Option<bool>,
+}
+
+impl PartialTlsConfig {
+    fn merge(self, other: PartialTlsConfig) -> Self {
+        PartialTlsConfig {
+            cert_path: other.cert_path.or(self.cert_path),
+            key_path: other.key_path.or(self.key_path),
+            port: other.port.or(self.port),
+            disable_plain_http: other.disable_plain_http.or(self.disable_plain_http),
+        }
+    }
+
+    // TLS is on when both paths are set; setting only one is a mistake.
+    fn into_config(self) -> Result<Option<TlsConfig>, ConfigError> {
+        match (self.cert_path, self.key_path) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(TlsConfig {
+                cert_path,
+                key_path,
+                port: self.port.unwrap_or(TlsConfig::DEFAULT_PORT),
+                disable_plain_http: self.disable_plain_http.unwrap_or(false),
+            })),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(ConfigError::InvalidValue {
+                name: "server.tls.key_path",
+                message: "must be set together with server.tls.cert_path".to_string(),
+            }),
+            (None, Some(_)) => Err(ConfigError::InvalidValue {
+                name: "server.tls.cert_path",
+                message: "must be set together with server.tls.key_path".to_string(),
+            }),
+        }
+    }
+}
+
+impl PartialConfig {
+    // The settings the command-line flags override. A flag that is not given
+    // leaves the setting to the file and the environment; `--production`
+    // only ever turns production mode on.
+    fn from_cli(cli: &Cli) -> Self {
+        PartialConfig {
+            production_mode: cli.production.then_some(true),
+            server: PartialServerConfig {
+                bind: cli.bind,
+                port: cli.port,
+                tls: PartialTlsConfig {
+                    disable_plain_http: cli.tls.then_some(true),
+                    ..PartialTlsConfig::default()
+                },
+                ..PartialServerConfig::default()
+            },
+            ..PartialConfig::default()
+        }
+    }
+
+    // A `.json` file is read as JSON, with the same layout as the TOML file;
+    // anything else as TOML.
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            Self::from_json(path)
+        } else {
+            Self::from_toml(path)
+        }
+    }
+
+    fn from_toml(path: &Path) -> Result<Self, ConfigError> {
+        let contents = read_config_file(path)?;
+        // Only keep the parser's message: its `Display` quotes the offending
+        // line, which could be the one holding the key.
+        toml::from_str(&contents).map_err(|e: toml::de::Error| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.message().to_string(),
+        })
+    }
+
+    fn from_json(path: &Path) -> Result<Self, ConfigError> {
+        let contents = read_config_file(path)?;
+        // Not serde's message either, which can quote the offending value.
+        serde_json::from_str(&contents).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: format!(
+                "{} at line {} column {}",
+                match e.classify() {
+                    serde_json::error::Category::Data => "invalid value",
+                    _ => "invalid JSON",
+                },
+                e.line(),
+                e.column()
+            ),
+        })
+    }
+
+    fn from_env() -> Result<Self, ConfigError> {
+        Ok(PartialConfig {
+            api_key: std::env::var("API_KEY").ok().filter(|k| !k.is_empty()),
+            api_key_file: std::env::var_os("API_KEY_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            allowed_hosts: std::env::var("ALLOWED_HOSTS")
+                .ok()
+                .map(|hosts| parse_list(&hosts)),
+            denied_hosts: std::env::var("DENIED_HOSTS")
+                .ok()
+                .map(|hosts| parse_list(&hosts)),
+            ignore_host_port: std::env::var("IGNORE_HOST_PORT")
+                .ok()
+                .map(|v| parse_bool(&v)),
+            default_host: std::env::var("DEFAULT_HOST").ok().filter(|h| !h.is_empty()),
+            max_host_len: parse_env("MAX_HOST_LEN")?,
+            allowed_email_domains: std::env::var("ALLOWED_EMAIL_DOMAINS")
+                .ok()
+                .map(|domains| parse_list(&domains)),
+            trusted_proxies: std::env::var("TRUSTED_PROXIES")
+                .ok()
+                .map(|proxies| parse_list(&proxies)),
+            error_format: parse_env("ERROR_FORMAT")?,
+            log_pii: std::env::var("LOG_PII").ok().map(|v| parse_bool(&v)),
+            production_mode: std::env::var("PRODUCTION_MODE")
+                .ok()
+                .map(|v| parse_bool(&v)),
+            redact_query_params: std::env::var("REDACT_QUERY_PARAMS")
+                .ok()
+                .map(|params| parse_list(&params)),
+            backend: PartialBackendConfig {
+                timeout_ms: parse_env("BACKEND_TIMEOUT_MS")?,
+                max_retries: parse_env("BACKEND_MAX_RETRIES")?,
+                readiness_host: std::env::var("BACKEND_READINESS_HOST").ok(),
+                private_hosts: std::env::var("BACKEND_PRIVATE_HOSTS")
+                    .ok()
+                    .map(|hosts| parse_list(&hosts)),
+                path: std::env::var("BACKEND_PATH").ok(),
+                max_concurrent_requests: parse_env("BACKEND_MAX_CONCURRENT_REQUESTS")?,
+                queue_timeout_ms: parse_env("BACKEND_QUEUE_TIMEOUT_MS")?,
+                tls_pins: std::env::var("BACKEND_TLS_PINS")
+                    .ok()
+                    .map(|pins| parse_list(&pins)),
+                dry_run: std::env::var("BACKEND_DRY_RUN")
+                    .ok()
+                    .map(|v| parse_bool(&v)),
+                circuit_breaker: PartialCircuitBreakerConfig {
+                    enabled: std::env::var("BACKEND_CIRCUIT_BREAKER_ENABLED")
+                        .ok()
+                        .map(|v| parse_bool(&v)),
+                    failure_threshold: parse_env("BACKEND_CIRCUIT_BREAKER_THRESHOLD")?,
+                    cooldown_ms: parse_env("BACKEND_CIRCUIT_BREAKER_COOLDOWN_MS")?,
+                },
+                batch: PartialBatchConfig {
+                    enabled: std::env::var("BACKEND_BATCH_ENABLED")
+                        .ok()
+                        .map(|v| parse_bool(&v)),
+                    max_size: parse_env("BACKEND_BATCH_MAX_SIZE")?,
+                    max_delay_ms: parse_env("BACKEND_BATCH_MAX_DELAY_MS")?,
+                    queue_capacity: parse_env("BACKEND_BATCH_QUEUE_CAPACITY")?,
+                },
+            },
+            rate_limit: PartialRateLimitConfig {
+                enabled: std::env::var("RATE_LIMIT_ENABLED")
+                    .ok()
+                    .map(|v| parse_bool(&v)),
+                requests_per_second: parse_env("RATE_LIMIT_RPS")?,
+                burst: parse_env("RATE_LIMIT_BURST")?,
+                trust_forwarded_for: std::env::var("RATE_LIMIT_TRUST_FORWARDED_FOR")
+                    .ok()
+                    .map(|v| parse_bool(&v)),
+            },
+            security_headers: PartialSecurityHeadersConfig {
+                content_security_policy: std::env::var("CONTENT_SECURITY_POLICY").ok(),
+                server: std::env::var("SERVER_HEADER").ok(),
+                ..Default::default()
+            },
+            cors: PartialCorsConfig {
+                allowed_origins: std::env::var("CORS_ALLOWED_ORIGINS")
+                    .ok()
+                    .map(|origins| parse_list(&origins)),
+                ..Default::default()
+            },
+            auth: PartialAuthConfig {
+                admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            },
+            server: PartialServerConfig {
+                bind: parse_env("BIND_ADDRESS")?,
+                port: parse_env("PORT")?,
+                shutdown_timeout_secs: parse_env("SHUTDOWN_TIMEOUT_SECS")?,
+                max_body_bytes: parse_env("MAX_BODY_BYTES")?,
+                max_uri_bytes: parse_env("MAX_URI_BYTES")?,
+                request_deadline_ms: parse_env("REQUEST_DEADLINE_MS")?,
+                route_deadlines_ms: None,
+                force_https: std::env::var("FORCE_HTTPS").ok().map(|v| parse_bool(&v)),
+                workers: parse_env("WORKERS")?,
+                keep_alive_secs: parse_env("KEEP_ALIVE_SECS")?,
+                client_request_timeout_ms: parse_env("CLIENT_REQUEST_TIMEOUT_MS")?,
+                client_disconnect_timeout_ms: parse_env("CLIENT_DISCONNECT_TIMEOUT_MS")?,
+                tls: PartialTlsConfig {
+                    cert_path: std::env::var_os("TLS_CERT_PATH").map(PathBuf::from),
+                    key_path: std::env::var_os("TLS_KEY_PATH").map(PathBuf::from),
+                    port: parse_env("TLS_PORT")?,
+                    disable_plain_http: std::env::var("TLS_ONLY").ok().map(|v| parse_bool(&v)),
+                },
+            },
+            audit: PartialAuditConfig {
+                sink: parse_env("AUDIT_SINK")?,
+                path: std::env::var_os("AUDIT_LOG_PATH").map(PathBuf::from),
+            },
+            waitlist: PartialWaitlistConfig {
+                database: std::env::var("WAITLIST_DATABASE").ok(),
+            },
+            idempotency: PartialIdempotencyConfig {
+                ttl_secs: parse_env("IDEMPOTENCY_TTL_SECS")?,
+            },
+            response_cache: PartialResponseCacheConfig {
+                capacity: parse_env("RESPONSE_CACHE_CAPACITY")?,
+                ttl_ms: parse_env("RESPONSE_CACHE_TTL_MS")?,
+            },
+            compression: PartialCompressionConfig {
+                enabled: std::env::var("COMPRESSION_ENABLED")
+                    .ok()
+                    .map(|v| parse_bool(&v)),
+                min_size_bytes: parse_env("COMPRESSION_MIN_SIZE_BYTES")?,
+            },
+            access_log: PartialAccessLogConfig {
+                verbosity: parse_env("ACCESS_LOG")?,
+                sensitive_headers: std::env::var("ACCESS_LOG_SENSITIVE_HEADERS")
+                    .ok()
+                    .map(|headers| parse_list(&headers)),
+            },
+            log_scrubber: PartialScrubberConfig {
+                enabled: std::env::var("LOG_SCRUBBER_ENABLED")
+                    .ok()
+                    .map(|v| parse_bool(&v)),
+                rules: None,
+            },
+            messages: PartialMessages::default(),
+            webhook: PartialWebhookConfig {
+                url: std::env::var("WEBHOOK_URL").ok(),
+                secret: std::env::var("WEBHOOK_SECRET").ok(),
+                max_in_flight: parse_env("WEBHOOK_MAX_IN_FLIGHT")?,
+            },
+            // Only the config file can declare route policies.
+            routes: None,
+        })
+    }
+
+    /// Fields set in `other` win over fields set in `self`.
+    fn merge(self, other: PartialConfig) -> Self {
+        PartialConfig {
+            api_key: other.api_key.or(self.api_key),
+            api_key_file: other.api_key_file.or(self.api_key_file),
+            allowed_hosts: other.allowed_hosts.or(self.allowed_hosts),
+            denied_hosts: other.denied_hosts.or(self.denied_hosts),
+            ignore_host_port: other.ignore_host_port.or(self.ignore_host_port),
+            default_host: other.default_host.or(self.default_host),
+            max_host_len: other.max_host_len.or(self.max_host_len),
+            allowed_email_domains: other.allowed_email_domains.or(self.allowed_email_domains),
+            trusted_proxies: other.trusted_proxies.or(self.trusted_proxies),
+            error_format: other.error_format.or(self.error_format),
+            log_pii: other.log_pii.or(self.log_pii),
+            production_mode: other.production_mode.or(self.production_mode),
+            redact_query_params: other.redact_query_params.or(self.redact_query_params),
+            backend: self.backend.merge(other.backend),
+            rate_limit: self.rate_limit.merge(other.rate_limit),
+            security_headers: self.security_headers.merge(other.security_headers),
+            cors: self.cors.merge(other.cors),
+            auth: self.auth.merge(other.auth),
+            server: self.server.merge(other.server),
+            audit: self.audit.merge(other.audit),
+            waitlist: self.waitlist.merge(other.waitlist),
+            idempotency: self.idempotency.merge(other.idempotency),
+            response_cache: self.response_cache.merge(other.response_cache),
+            access_log: self.access_log.merge(other.access_log),
+            log_scrubber: self.log_scrubber.merge(other.log_scrubber),
+            compression: self.compression.merge(other.compression),
+            messages: self.messages.merge(other.messages),
+            webhook: self.webhook.merge(other.webhook),
+            routes: other.routes.or(self.routes),
+        }
+    }
+
+    fn into_state(mut self) -> Result<AppState, ConfigError> {
+        let tls_pins = self.backend.tls_pins.take().unwrap_or_default();
+        let api_key = match &self.api_key_file {
+            Some(path) => {
+                if self.api_key.is_some() {
+                    log::warn!(
+                        "Both an API key file and an inline API key are configured; using the file {}",
+                        path.display()
+                    );
+                }
+                Some(read_api_key_file(path)?)
+            }
+            None => self.api_key,
+        };
+        AppStateBuilder {
+            api_key,
+            allowed_hosts: self.allowed_hosts.unwrap_or_default(),
+            denied_hosts: self.denied_hosts.unwrap_or_default(),
+            ignore_host_port: self.ignore_host_port.unwrap_or(false),
+            default_host: self.default_host,
+            max_host_len: self.max_host_len,
+            allowed_email_domains: self.allowed_email_domains.unwrap_or_default(),
+            trusted_proxies: self.trusted_proxies.unwrap_or_default(),
+            backend: self.backend.into_config(),
+            private_hosts: Vec::new(),
+            tls_pins,
+            rate_limit: self.rate_limit.into_config()?,
+            security_headers: self.security_headers.into_config(),
+            cors: self.cors.into_config(),
+            auth: self.auth.into_config(),
+            error_format: self.error_format.unwrap_or_default(),
+            log_pii: self.log_pii.unwrap_or(false),
+            production_mode: self.production_mode.unwrap_or(false),
+            redact_query_params: self.redact_query_params.unwrap_or_default(),
+            server: self.server.into_config()?,
+            audit: self.audit.into_config()?,
+            waitlist: self.waitlist.into_store()?,
+            backend_client: None,
+            http_client: None,
+            idempotency: self.idempotency.into_config(),
+            response_cache: self.response_cache.into_config(),
+            access_log: self.access_log.into_config(),
+            log_scrubber: self.log_scrubber.into_config(),
+            compression: self.compression.into_config(),
+            localizer: self.messages.into_localizer(),
+            webhook: self.webhook.into_config()?,
+            signup_validators: Vec::new(),
+            routes: self.routes.map(RouteConfig::from).unwrap_or_default(),
+        }
+        .build()
+    }
+}
+
+/// Builds an [`AppState`] in code, e.g. in tests, with the same validation
+/// as loading it from the config file and environment.
+///
+/// Only the API key and at least one allowed host are required; everything
+/// else starts at its default.
+///
+/// ```
+/// use uncaught_exception::config::AppState;
+///
+/// let state = AppState::builder()
+///     .api_key("test-key")
+///     .allowed_host("my-app.com")
+///     .timeout_ms(1_000)
+///     .build()
+///     .unwrap();
+/// assert!(state.host_matcher.is_allowed("my-app.com"));
+/// ```
+#[derive(Debug, Default)]
+#[must_use]
+pub struct AppStateBuilder {
+    api_key: Option<String>,
+    allowed_hosts: Vec<String>,
+    denied_hosts: Vec<String>,
+    ignore_host_port: bool,
+    default_host: Option<String>,
+    max_host_len: Option<usize>,
+    allowed_email_domains: Vec<String>,
+    trusted_proxies: Vec<String>,
+    backend: BackendConfig,
+    private_hosts: Vec<String>,
+    tls_pins: Vec<String>,
+    rate_limit: RateLimitConfig,
+    security_headers: SecurityHeadersConfig,
+    cors: CorsConfig,
+    auth: AuthConfig,
+    error_format: ErrorFormat,
+    log_pii: bool,
+    production_mode: bool,
+    redact_query_params: Vec<String>,
+    server: ServerConfig,
+    audit: AuditConfig,
+    waitlist: Option<Arc<dyn WaitlistStore>>,
+    backend_client: Option<Arc<dyn BackendClient>>,
+    http_client: Option<ClientBuilder>,
+    idempotency: IdempotencyConfig,
+    response_cache: ResponseCacheConfig,
+    access_log: AccessLogConfig,
+    log_scrubber: ScrubberConfig,
+    compression: CompressionConfig,
+    localizer: Localizer,
+    routes: RouteConfig,
+    webhook: Option<WebhookConfig>,
+    signup_validators: Vec<Arc<dyn SignupValidator>>,
+}
+
+impl AppStateBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Adds one entry to the host whitelist.
+    pub fn allowed_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Adds several entries to the host whitelist.
+    pub fn allowed_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_hosts.extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds one entry to the host denylist. A host matching it is refused
+    /// even if the whitelist allows it; with an empty whitelist, every host
+    /// not on the denylist is allowed.
+    pub fn denied_host(mut self, host: impl Into<String>) -> Self {
+        self.denied_hosts.push(host.into());
+        self
+    }
+
+    /// Adds several entries to the host denylist.
+    pub fn denied_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.denied_hosts.extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn ignore_host_port(mut self, ignore: bool) -> Self {
+        self.ignore_host_port = ignore;
+        self
+    }
+
+    pub fn default_host(mut self, host: impl Into<String>) -> Self {
+        self.default_host = Some(host.into());
+        self
+    }
+
+    /// Adds a domain, e.g. `example.com` or `*.example.com`, to those the
+    /// secure handlers accept signups from. With none, every domain is.
+    pub fn allowed_email_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_email_domains.push(domain.into());
+        self
+    }
+
+    /// Adds a check for the secure handlers to run on every signup, after the
+    /// built-in ones and those added before it; see [`SignupValidator`].
+    pub fn signup_validator(mut self, validator: Arc<dyn SignupValidator>) -> Self {
+        self.signup_validators.push(validator);
+        self
+    }
+
+    /// The longest `Host` accepted, in bytes; [`DEFAULT_MAX_HOST_LEN`]
+    /// unless set.
+    pub fn max_host_len(mut self, max_len: usize) -> Self {
+        self.max_host_len = Some(max_len);
+        self
+    }
+
+    /// Trusts the forwarding headers of a proxy at `proxy`, an address or a
+    /// CIDR range such as `10.0.0.0/8`.
+    pub fn trusted_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.trusted_proxies.push(proxy.into());
+        self
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.backend.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.backend.max_retries = max_retries;
+        self
+    }
+
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.backend.max_concurrent_requests = max;
+        self
+    }
+
+    pub fn queue_timeout_ms(mut self, queue_timeout_ms: u64) -> Self {
+        self.backend.queue_timeout_ms = queue_timeout_ms;
+        self
+    }
+
+    /// See [`BackendConfig::dry_run`].
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.backend.dry_run = dry_run;
+        self
+    }
+
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreakerConfig) -> Self {
+        self.backend.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// See [`BackendConfig::batch`].
+    pub fn batch(mut self, batch: BatchConfig) -> Self {
+        self.backend.batch = batch;
+        self
+    }
+
+    /// The backend endpoint to call; see [`BackendConfig::path`].
+    pub fn backend_path(mut self, path: impl Into<String>) -> Self {
+        self.backend.path = path.into();
+        self
+    }
+
+    /// Exempts `host` (on any port) from the SSRF guard. Exemptions added this
+    /// way replace those of a [`AppStateBuilder::backend`] config.
+    pub fn private_host(mut self, host: impl Into<String>) -> Self {
+        self.private_hosts.push(host.into());
+        self
+    }
+
+    /// Pins the backend's certificate to a public key, given as the base64
+    /// SHA-256 of its SubjectPublicKeyInfo; see [`crate::pinning`]. Pins added
+    /// this way replace those of a [`AppStateBuilder::backend`] config.
+    pub fn tls_pin(mut self, pin: impl Into<String>) -> Self {
+        self.tls_pins.push(pin.into());
+        self
+    }
+
+    pub fn backend(mut self, backend: BackendConfig) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    pub fn security_headers(mut self, security_headers: SecurityHeadersConfig) -> Self {
+        self.security_headers = security_headers;
+        self
+    }
+
+    pub fn cors(mut self, cors: CorsConfig) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    pub fn auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn error_format(mut self, error_format: ErrorFormat) -> Self {
+        self.error_format = error_format;
+        self
+    }
+
+    pub fn log_pii(mut self, log_pii: bool) -> Self {
+        self.log_pii = log_pii;
+        self
+    }
+
+    /// Sends only generic error bodies, even from the vulnerable handler; see
+    /// [`crate::error::set_production_mode`].
+    pub fn production_mode(mut self, production_mode: bool) -> Self {
+        self.production_mode = production_mode;
+        self
+    }
+
+    /// Adds a query parameter to redact from logged URLs, on top of `api_key`.
+    pub fn redact_query_param(mut self, param: impl Into<String>) -> Self {
+        self.redact_query_params.push(param.into());
+        self
+    }
+
+    pub fn server(mut self, server: ServerConfig) -> Self {
+        self.server = server;
+        self
+    }
+
+    /// Where signups are recorded. Defaults to an [`InMemoryStore`].
+    pub fn waitlist_store(mut self, store: Arc<dyn WaitlistStore>) -> Self {
+        self.waitlist = Some(store);
+        self
+    }
+
+    /// What signups are submitted through. Defaults to an
+    /// [`HttpBackendClient`] on the state's HTTP client.
+    pub fn backend_client(mut self, backend_client: Arc<dyn BackendClient>) -> Self {
+        self.backend_client = Some(backend_client);
+        self
+    }
+
+    /// The settings the state's HTTP client is built from, instead of
+    /// [`backend::client_builder`] on the backend config, e.g. with a test
+    /// root certificate added. A client that fails to build fails
+    /// [`AppStateBuilder::build`] with [`ConfigError::HttpClient`].
+    pub fn http_client(mut self, builder: ClientBuilder) -> Self {
+        self.http_client = Some(builder);
+        self
+    }
+
+    pub fn idempotency(mut self, idempotency: IdempotencyConfig) -> Self {
+        self.idempotency = idempotency;
+        self
+    }
+
+    pub fn response_cache(mut self, response_cache: ResponseCacheConfig) -> Self {
+        self.response_cache = response_cache;
+        self
+    }
+
+    pub fn audit(mut self, audit: AuditConfig) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn access_log(mut self, access_log: AccessLogConfig) -> Self {
+        self.access_log = access_log;
+        self
+    }
+
+    pub fn log_scrubber(mut self, log_scrubber: ScrubberConfig) -> Self {
+        self.log_scrubber = log_scrubber;
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The English messages, which are also used for every language without
+    /// a translation. Replaces any translations added before.
+    pub fn messages(mut self, messages: Messages) -> Self {
+        self.localizer = Localizer::new(messages);
+        self
+    }
+
+    /// Adds the messages for `lang`, e.g. `es` or `pt-BR`.
+    pub fn translation(mut self, lang: &str, messages: Messages) -> Self {
+        self.localizer = self.localizer.translation(lang, messages);
+        self
+    }
+
+    /// Notifies `webhook.url` of every successful signup.
+    pub fn webhook(mut self, webhook: WebhookConfig) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    /// Sets the limits of the route at `path`; see [`RouteConfig`].
+    pub fn route_policy(mut self, path: impl Into<String>, policy: RoutePolicy) -> Self {
+        self.routes = self.routes.route(path, policy);
+        self
+    }
+
+    /// Validates the settings and builds the state, including its HTTP client.
+    pub fn build(mut self) -> Result<AppState, ConfigError> {
+        let api_key = self
+            .api_key
+            .filter(|k| !k.is_empty())
+            .map(|k| SharedSecret::new(Secret::new(k)))
+            .ok_or(ConfigError::MissingApiKey)?;
+        if self.allowed_hosts.is_empty() && self.denied_hosts.is_empty() {
+            return Err(ConfigError::NoAllowedHosts);
+        }
+        // Dropping a bad entry, as the matcher would, would widen the list.
+        if let Some(entry) = self
+            .denied_hosts
+            .iter()
+            .find(|entry| !is_valid_host_entry(entry))
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "denied_hosts",
+                message: format!("{:?} is not a valid host", entry),
+            });
+        }
+        // The matcher would drop a bad entry, silently narrowing the list.
+        if let Some(domain) = self
+            .allowed_email_domains
+            .iter()
+            .find(|domain| !is_valid_domain(domain.strip_prefix("*.").unwrap_or(domain)))
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "allowed_email_domains",
+                message: format!("{:?} is not a domain or *.domain", domain),
+            });
+        }
+        let mut signup_validators = ValidatorChain::builtin(&self.allowed_email_domains);
+        for validator in self.signup_validators {
+            signup_validators.push(validator);
+        }
+        let max_host_len = self.max_host_len.unwrap_or(DEFAULT_MAX_HOST_LEN);
+        if max_host_len == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "max_host_len",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if self.backend.timeout_ms == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "backend.timeout_ms",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        let path = &self.backend.path;
+        if !path.starts_with('/') || path.contains(['?', '#']) {
+            return Err(ConfigError::InvalidValue {
+                name: "backend.path",
+                message: "must start with '/' and contain no query string or fragment".to_string(),
+            });
+        }
+        if self.backend.max_concurrent_requests == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "backend.max_concurrent_requests",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if self.backend.circuit_breaker.failure_threshold == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "backend.circuit_breaker.failure_threshold",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        let batch = &self.backend.batch;
+        if batch.enabled {
+            let name = if batch.max_size == 0 {
+                Some("backend.batch.max_size")
+            } else if batch.queue_capacity == 0 {
+                Some("backend.batch.queue_capacity")
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                return Err(ConfigError::InvalidValue {
+                    name,
+                    message: "must be greater than zero".to_string(),
+                });
+            }
+        }
+        for (path, policy) in self.routes.iter() {
+            let message = if !ROUTE_PATHS.contains(&path) {
+                format!("{:?} is not a route", path)
+            } else if policy.timeout_ms == Some(0) || policy.max_body_bytes == Some(0) {
+                format!("limits for {:?} must be greater than zero", path)
+            } else {
+                continue;
+            };
+            return Err(ConfigError::InvalidValue {
+                name: "routes",
+                message,
+            });
+        }
+        if let Some(webhook) = &self.webhook {
+            let message = if !matches!(webhook.url.scheme(), "http" | "https") {
+                Some(("webhook.url", "must be an http or https URL"))
+            } else if webhook.secret.is_empty() {
+                Some(("webhook.secret", "is required with a webhook URL"))
+            } else if webhook.max_in_flight == 0 {
+                Some(("webhook.max_in_flight", "must be greater than zero"))
+            } else {
+                None
+            };
+            if let Some((name, message)) = message {
+                return Err(ConfigError::InvalidValue {
+                    name,
+                    message: message.to_string(),
+                });
+            }
+        }
+        if self.idempotency.ttl_secs == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "idempotency.ttl_secs",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if self.response_cache.capacity > 0 && self.response_cache.ttl_ms == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "response_cache.ttl_ms",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if !self.private_hosts.is_empty() {
+            self.backend.private_hosts = HostMatcher::new(&self.private_hosts, true);
+        }
+        if !self.tls_pins.is_empty() {
+            self.backend.tls_pins = self
+                .tls_pins
+                .iter()
+                .map(|pin| pin.parse::<SpkiPin>())
+                .collect::<Result<_, _>>()
+                .map_err(|message| ConfigError::InvalidValue {
+                    name: "backend.tls_pins",
+                    message,
+                })?;
+        }
+        if !self
+            .redact_query_params
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case("api_key"))
+        {
+            self.redact_query_params.push("api_key".to_string());
+        }
+        let trusted_proxies = self
+            .trusted_proxies
+            .iter()
+            .map(|proxy| parse_proxy(proxy))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.server.validate()?;
+        // Without HTTPS here or a proxy to vouch for it, no request could
+        // ever count as HTTPS, and each would be redirected forever.
+        if self.server.force_https && self.server.tls.is_none() && trusted_proxies.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                name: "server.force_https",
+                message: "needs server.tls or trusted_proxies".to_string(),
+            });
+        }
+        let scrubber = Scrubber::from_config(&self.log_scrubber)
+            .map_err(|message| ConfigError::InvalidValue {
+                name: "log_scrubber.rules",
+                message,
+            })?
+            .map(Arc::new);
+        let host_matcher = HostWhitelist::with_denied(
+            self.allowed_hosts.clone(),
+            self.denied_hosts,
+            self.ignore_host_port,
+        );
+        let client = match self.http_client {
+            Some(builder) => builder.build(),
+            None => backend::build_client(&self.backend),
+        }
+        .map_err(http_client_error)?;
+        let backend_client = self.backend_client.unwrap_or_else(|| {
+            Arc::new(HttpBackendClient::new(
+                client.clone(),
+                self.backend.clone(),
+                api_key.clone(),
+            ))
+        });
+        let circuit_breaker = CircuitBreaker::new(self.backend.circuit_breaker.clone());
+        let batcher = Batcher::new(self.backend.batch.clone());
+        let backend_permits = Arc::new(Semaphore::new(self.backend.max_concurrent_requests));
+        Ok(AppState {
+            api_key,
+            allowed_hosts: self.allowed_hosts,
+            host_matcher,
+            config_path: None,
+            default_host: self.default_host.filter(|h| !h.is_empty()),
+            max_host_len,
+            allowed_email_domains: self.allowed_email_domains,
+            signup_validators,
+            trusted_proxies,
+            backend: self.backend,
+            rate_limit: self.rate_limit,
+            security_headers: self.security_headers,
+            cors: self.cors,
+            auth: self.auth,
+            error_format: self.error_format,
+            log_pii: self.log_pii,
+            production_mode: self.production_mode,
+            redact_query_params: self.redact_query_params,
+            server: self.server,
+            audit: self.audit,
+            access_log: self.access_log,
+            log_scrubber: self.log_scrubber,
+            scrubber,
+            compression: self.compression,
+            localizer: self.localizer,
+            routes: self.routes,
+            webhook: self.webhook.map(Webhook::new),
+            events: SignupEvents::new(),
+            circuit_breaker,
+            batcher,
+            backend_permits,
+            waitlist: self
+                .waitlist
+                .unwrap_or_else(|| Arc::new(InMemoryStore::new())),
+            idempotency: IdempotencyStore::new(self.idempotency),
+            response_cache: ResponseCache::new(self.response_cache),
+            maintenance: Arc::new(AtomicBool::new(false)),
+            client,
+            backend_client,
+        })
+    }
+}
+
+/// Splits a comma-separated list (of hosts, origins, ...), dropping blank
+/// entries.
+fn parse_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a trusted proxy entry: a CIDR range, or a single address.
+fn parse_proxy(proxy: &str) -> Result<IpNet, ConfigError> {
+    let proxy = proxy.trim();
+    proxy
+        .parse::<IpNet>()
+        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| ConfigError::InvalidValue {
+            name: "trusted_proxies",
+            message: format!("{:?} is not an IP address or CIDR range", proxy),
+        })
+}
+
+/// Accepts the usual spellings of a boolean environment variable.
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+fn read_config_file(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// reqwest's own message is only "builder error"; the cause is in its sources.
+// A client is built before any request, so there is no URL (and so no API
+// key) to leak, but drop it anyway in case that ever changes.
+fn http_client_error(e: reqwest::Error) -> ConfigError {
+    let e = e.without_url();
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    ConfigError::HttpClient(message)
+}
+
+// Reads the API key from `path`, as container platforms mount secrets: the
+// whole file, minus the trailing newline editors and `echo` add. An empty key
+// is refused like a missing one would be.
+fn read_api_key_file(path: &Path) -> Result<String, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::ApiKeyFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let key = contents.trim_end_matches(['\n', '\r']);
+    if key.is_empty() {
+        return Err(ConfigError::InvalidValue {
+            name: "api_key_file",
+            message: format!("{} is empty", path.display()),
+        });
+    }
+    Ok(key.to_string())
+}
+
+/// Reads and parses an optional environment variable.
+fn parse_env<T>(name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => {
+            value
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|e: T::Err| ConfigError::InvalidValue {
+                    name,
+                    message: e.to_string(),
+                })
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+impl AppState {
+    /// Starts building a state in code. See [`AppStateBuilder`].
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+
+    /// The host the readiness probe checks: the configured
+    /// `backend.readiness_host`, or else the first allowed host that is not a
+    /// wildcard.
+    pub fn readiness_host(&self) -> Option<&str> {
+        self.backend.readiness_host.as_deref().or_else(|| {
+            self.allowed_hosts
+                .iter()
+                .map(String::as_str)
+                .find(|h| !h.contains('*'))
+        })
+    }
+
+    /// Builds the state from `API_KEY` and a comma-separated `ALLOWED_HOSTS`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        PartialConfig::from_env()?.into_state()
+    }
+
+    /// Builds the state from a TOML file with `api_key` and `allowed_hosts`.
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let mut state = PartialConfig::from_toml(path.as_ref())?.into_state()?;
+        state.config_path = Some(path.as_ref().to_path_buf());
+        Ok(state)
+    }
+
+    /// Reads the optional config file, TOML or (with a `.json` extension)
+    /// JSON, then lets the environment override it.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let file = match path {
+            Some(path) => PartialConfig::from_file(path)?,
+            None => PartialConfig::default(),
+        };
+        let mut state = file.merge(PartialConfig::from_env()?).into_state()?;
+        state.config_path = path.map(Path::to_path_buf);
+        Ok(state)
+    }
+
+    /// Like [`AppState::load`] from `--config`, with the command-line flags
+    /// overriding both the file and the environment. Everything is validated
+    /// once, after the flags are applied, so `--tls` can make valid a config
+    /// that serves HTTP and HTTPS on the same port.
+    pub fn from_cli(cli: &Cli) -> Result<Self, ConfigError> {
+        let path = cli.config.as_deref();
+        let file = match path {
+            Some(path) => PartialConfig::from_file(path)?,
+            None => PartialConfig::default(),
+        };
+        let config = file
+            .merge(PartialConfig::from_env()?)
+            .merge(PartialConfig::from_cli(cli));
+        if cli.tls && config.server.tls.cert_path.is_none() && config.server.tls.key_path.is_none()
+        {
+            return Err(ConfigError::Tls(
+                "--tls needs a certificate; set TLS_CERT_PATH and TLS_KEY_PATH".to_string(),
+            ));
+        }
+        let mut state = config.into_state()?;
+        state.config_path = path.map(Path::to_path_buf);
+        Ok(state)
+    }
+
+    /// Re-reads `allowed_hosts` from `config_path`, with `ALLOWED_HOSTS` still
+    /// overriding it as at startup, and swaps the new list into
+    /// `host_matcher`. Returns the new list.
+    ///
+    /// Nothing changes when this fails: without a config file, when it cannot
+    /// be read or parsed, or when the new list has an entry that is not a
+    /// valid host, or is empty without a denylist. The denylist itself is not
+    /// reloaded.
+    pub fn reload_allowed_hosts(&self) -> Result<Vec<String>, ConfigError> {
+        let path = self
+            .config_path
+            .as_deref()
+            .ok_or_else(|| ConfigError::InvalidValue {
+                name: "allowed_hosts",
+                message: "there is no config file to reload it from".to_string(),
+            })?;
+        let hosts = PartialConfig::from_file(path)?
+            .merge(PartialConfig::from_env()?)
+            .allowed_hosts
+            .unwrap_or_default();
+        if hosts.is_empty() && self.host_matcher.denied().is_empty() {
+            return Err(ConfigError::NoAllowedHosts);
+        }
+        if let Some(entry) = hosts.iter().find(|entry| !is_valid_host_entry(entry)) {
+            return Err(ConfigError::InvalidValue {
+                name: "allowed_hosts",
+                message: format!("{:?} is not a valid host", entry),
+            });
+        }
+        self.host_matcher.replace(hosts.clone());
+        Ok(hosts)
+    }
+
+    /// Swaps in `api_key` for every backend call that starts from now on,
+    /// and registers it with the global [`SecretRegistry`]. Calls already
+    /// running finish with the old key, which is wiped once the last of them
+    /// is done. The registry retires its copy of the old key right away (see
+    /// [`SecretRegistry::retire`]), so an error from one of those calls is
+    /// still scrubbed while the value itself is kept nowhere.
+    ///
+    /// Refuses an empty key and the published [`DEMO_API_KEY`], as startup
+    /// does, keeping the current one.
+    pub fn rotate_api_key(&self, api_key: Secret) -> Result<(), ConfigError> {
+        if api_key.is_empty() {
+            return Err(ConfigError::MissingApiKey);
+        }
+        if api_key.expose() == DEMO_API_KEY {
+            return Err(ConfigError::InvalidValue {
+                name: "api_key",
+                message: "the published demo key cannot be used".to_string(),
+            });
+        }
+        // Registered before it is in use, so no error body can carry it
+        // unscrubbed.
+        let registry = SecretRegistry::global();
+        registry.register(api_key.expose());
+        let unchanged = self.api_key.current().expose() == api_key.expose();
+        let old_key = self.api_key.rotate(api_key);
+        if !unchanged {
+            registry.retire(old_key.expose());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Routes that show developers what the server sees of their request. They
+//! sit behind the admin token, like `/admin`, and never show a secret.
+
+use actix_web::{HttpRequest, HttpResponse, web};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::client_info::ClientInfo;
+use crate::config::AppState;
+use crate::error::HandlerResult;
+use crate::host::request_host;
+use crate::secrets::SecretRegistry;
+
+#[derive(Serialize)]
+struct WhoAmI {
+    host: String,
+    allowed: bool,
+    client_ip: Option<IpAddr>,
+    scheme: &'static str,
+}
+
+/// `GET /debug/whoami`: the host the request addressed and whether it is on
+/// the whitelist, with the client address and scheme as `ClientInfo` works
+/// them out (so forwarding headers only count from trusted proxies).
+///
+/// The host is extracted exactly as the secure handlers do, so a missing or
+/// duplicated `Host` header gets the same `400`. It is echoed back (as JSON,
+/// with any registered secret redacted) even when it is not allowed: that is
+/// what this route is for.
+pub async fn whoami(
+    req: HttpRequest,
+    info: ClientInfo,
+    state: web::Data<AppState>,
+) -> HandlerResult {
+    let host = request_host(&req, state.default_host.as_deref(), state.max_host_len)?;
+    Ok(HttpResponse::Ok().json(WhoAmI {
+        host: SecretRegistry::global().redact(host),
+        allowed: state.host_matcher.is_allowed(host),
+        client_ip: info.ip,
+        scheme: info.scheme,
+    }))
+}
+
+/// `GET /debug/headers`: the request headers as `{"headers": {name: [values]}}`,
+/// with repeated headers listed in the order they arrived.
+///
+/// The values of `Authorization`, `Proxy-Authorization`, `Cookie` and every
+/// header in `access_log.sensitive_headers` are replaced by `[REDACTED]`, and
+/// any registered secret elsewhere is redacted too. A value that is not valid
+/// UTF-8 shows as `[NON-UTF8]`.
+pub async fn headers(req: HttpRequest, state: web::Data<AppState>) -> HandlerResult {
+    let redacted = state.access_log.redacted_headers();
+    let mut headers: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (name, value) in req.headers() {
+        let value = if redacted.iter().any(|r| r == name.as_str()) {
+            "[REDACTED]".to_string()
+        } else {
+            match std::str::from_utf8(value.as_bytes()) {
+                Ok(value) => SecretRegistry::global().redact(value),
+                Err(_) => "[NON-UTF8]".to_string(),
+            }
+        };
+        headers.entry(name.as_str()).or_default().push(value);
+    }
+    Ok(HttpResponse::Ok().json(HeaderEcho { headers }))
+}
+
+#[derive(Serialize)]
+struct HeaderEcho<'a> {
+    headers: BTreeMap<&'a str, Vec<String>>,
+}
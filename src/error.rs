@@ -0,0 +1,554 @@
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::StatusCode,
+    http::header::{ALLOW, Accept, Quality, RETRY_AFTER, VARY, WWW_AUTHENTICATE},
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::audit::{self, AuditEvent, AuditEventKind};
+use crate::backend::{UpstreamFailure, classify_reqwest_error};
+use crate::messages::{Messages, PREFERRED};
+use crate::middleware::RequestId;
+use crate::pinning::SpkiPin;
+use crate::secrets::SecretRegistry;
+
+static ERROR_FORMAT: AtomicU8 = AtomicU8::new(ErrorFormat::Json as u8);
+static PRODUCTION_MODE: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    // The format negotiated from the `Accept` header of the request currently
+    // being polled; set by the `ErrorNegotiation` middleware.
+    pub(crate) static NEGOTIATED: ErrorFormat;
+}
+
+/// How `ApiError` bodies are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// `{"error":{"code":"...","message":"...","request_id":"..."}}`.
+    #[default]
+    Json = 0,
+    /// The message as plain text, followed by `Reference: <request id>`. This
+    /// is the original format, kept for clients that still parse it.
+    Text = 1,
+    /// A minimal HTML page, for browsers.
+    Html = 2,
+}
+
+impl ErrorFormat {
+    /// The process-wide format used by `ApiError::error_response`.
+    pub fn global() -> Self {
+        match ERROR_FORMAT.load(Ordering::Relaxed) {
+            1 => ErrorFormat::Text,
+            2 => ErrorFormat::Html,
+            _ => ErrorFormat::Json,
+        }
+    }
+
+    /// The format negotiated for the current request, or the global one when
+    /// called outside the `ErrorNegotiation` middleware.
+    pub fn current() -> Self {
+        NEGOTIATED
+            .try_with(|format| *format)
+            .unwrap_or_else(|_| ErrorFormat::global())
+    }
+
+    /// The format the client prefers according to its `Accept` header:
+    /// `application/json`, `text/plain` or `text/html`, honouring q-values.
+    /// Wildcards, unknown types and an empty header give `default`.
+    pub fn negotiate(accept: &Accept, default: ErrorFormat) -> Self {
+        let acceptable = Accept(
+            accept
+                .iter()
+                .filter(|item| item.quality > Quality::ZERO)
+                .cloned()
+                .collect(),
+        );
+        acceptable
+            .ranked()
+            .iter()
+            .find_map(
+                |mime| match (mime.type_().as_str(), mime.subtype().as_str()) {
+                    ("*", _) => Some(default),
+                    ("application", "json") => Some(ErrorFormat::Json),
+                    ("text", "plain") => Some(ErrorFormat::Text),
+                    ("text", "html") => Some(ErrorFormat::Html),
+                    ("text", "*") => Some(ErrorFormat::Text),
+                    _ => None,
+                },
+            )
+            .unwrap_or(default)
+    }
+
+    /// Sets the process-wide format. Call it once at startup.
+    pub fn set_global(self) {
+        ERROR_FORMAT.store(self as u8, Ordering::Relaxed);
+    }
+}
+
+/// Turns production mode on or off for the whole process. Call it once at
+/// startup, from `production_mode` (`PRODUCTION_MODE`) or `--production`.
+///
+/// In production mode no error body carries more than the generic message of
+/// its variant: `ApiError::Detailed`, which otherwise reflects its message,
+/// gets the generic `500` message like any internal error. It overrides the
+/// handlers, so even a build with the `insecure-demo` feature cannot leak
+/// through `/vulnerable/waitlist`. Every other body already is generic.
+pub fn set_production_mode(enabled: bool) {
+    PRODUCTION_MODE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether [`set_production_mode`] turned production mode on.
+pub fn production_mode() -> bool {
+    PRODUCTION_MODE.load(Ordering::Relaxed)
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ErrorFormat::Json),
+            "text" => Ok(ErrorFormat::Text),
+            "html" => Ok(ErrorFormat::Html),
+            _ => Err(format!(
+                "expected \"json\", \"text\" or \"html\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+// The JSON error body. Its shape is part of the API: add fields, never rename.
+// The schema is published in the OpenAPI spec, see `crate::openapi`.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorDetail<'a> {
+    /// A stable, machine-readable code such as `INVALID_HOST`.
+    #[schema(example = "INVALID_HOST")]
+    code: &'static str,
+    /// A generic, human-readable message. It never contains internal details.
+    #[schema(example = "Invalid 'Host' header provided.")]
+    message: String,
+    /// The request's correlation ID, to quote when reporting the error.
+    request_id: Option<&'a str>,
+}
+
+/// The error model shared by every handler.
+///
+/// Each variant carries the context needed to log the failure server-side.
+/// Its `Display` output is meant for logs only; what the client receives is
+/// decided by [`ApiError::public_message`], which never includes that context,
+/// and is always passed through [`ApiError::sanitize`] before it is written.
+///
+/// `url::ParseError`, `reqwest::Error` and `serde_json::Error` convert with
+/// `?`. The conversion keeps the source for the logs, but the client still
+/// only sees the generic message of the variant it lands in.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The `Host` header was missing, malformed or not on the whitelist.
+    #[error("invalid host header: {host:?}")]
+    InvalidHost { host: Option<String> },
+    /// The backend URL built from the request could not be parsed.
+    #[error("failed to parse backend URL '{url}': {source}")]
+    UrlParse {
+        url: String,
+        source: url::ParseError,
+    },
+    /// A URL could not be parsed, where there is no attempted URL to record.
+    #[error("failed to parse URL: {source}")]
+    Url {
+        #[from]
+        source: url::ParseError,
+    },
+    /// The backend did not answer in time.
+    #[error("backend timed out: {reason}")]
+    UpstreamTimeout { reason: String },
+    /// No connection to the backend could be made, for the reason in `failure`.
+    #[error("backend connection failed ({failure}): {reason}")]
+    UpstreamConnect {
+        failure: UpstreamFailure,
+        reason: String,
+    },
+    /// The backend's certificate carried none of the configured public key
+    /// pins; `presented` is the pin of the key it did carry, if any.
+    #[error(
+        "backend certificate for {host} matches no configured pin (presented {})",
+        presented.map(|p| p.to_string()).unwrap_or_else(|| "none".to_string())
+    )]
+    TlsPinMismatch {
+        host: String,
+        presented: Option<SpkiPin>,
+    },
+    /// The backend could not be reached or answered with an error status.
+    #[error(
+        "backend error{}: {reason}",
+        status.map(|s| format!(" (status {})", s)).unwrap_or_default()
+    )]
+    Upstream { status: Option<u16>, reason: String },
+    /// Every allowed attempt at a retryable backend call failed.
+    #[error("backend failed after {attempts} attempts: {reason}")]
+    RetriesExhausted { attempts: u32, reason: String },
+    /// The request was understood but refused.
+    #[error("bad request: {reason}")]
+    BadRequest { reason: String },
+    /// JSON could not be read or written. Malformed input is the client's
+    /// fault (a `400`); an I/O failure is ours (a `500`).
+    #[error("JSON error: {source}")]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+    /// The request body was bigger than the configured limit.
+    #[error("request body exceeds the {limit} byte limit")]
+    PayloadTooLarge { limit: usize },
+    /// The request target (path and query string) was longer than the
+    /// configured limit; see `middleware::UriLengthLimit`.
+    #[error("request target exceeds the {limit} byte limit")]
+    UriTooLong { limit: usize },
+    /// The request body was not of a content type the endpoint accepts.
+    #[error("unsupported content type: {content_type:?}")]
+    UnsupportedMediaType { content_type: Option<String> },
+    /// The email address is already on the waitlist.
+    #[error("email already on the waitlist")]
+    AlreadySignedUp,
+    /// The email's domain is not in `allowed_email_domains`.
+    #[error("email domain not allowed")]
+    EmailDomainNotAllowed,
+    /// The `Idempotency-Key` was already used for a different request.
+    #[error("idempotency key {key:?} reused for a different request")]
+    IdempotencyKeyReused { key: String },
+    /// The first request with this `Idempotency-Key` is still being processed.
+    #[error("idempotency key {key:?} still in flight")]
+    IdempotencyKeyInUse { key: String },
+    /// The request lacked valid admin credentials. `reason` says which check
+    /// failed and is for logs only.
+    #[error("unauthorized: {reason}")]
+    Unauthorized { reason: String },
+    /// The backend circuit is open, so the call was not even attempted. The
+    /// circuit lets a trial call through after `retry_after`.
+    #[error("backend circuit open; retry after {retry_after:?}")]
+    CircuitOpen { retry_after: Duration },
+    /// The request was cancelled because it ran past its deadline.
+    #[error("request exceeded its {deadline:?} deadline")]
+    DeadlineExceeded { deadline: Duration },
+    /// Every backend slot stayed taken for `waited`; see
+    /// `BackendConfig::max_concurrent_requests`.
+    #[error("no backend slot free after {waited:?}")]
+    Overloaded { waited: Duration },
+    /// The service is in maintenance mode; see `middleware::Maintenance`.
+    #[error("in maintenance mode")]
+    Maintenance,
+    /// The path exists, but not for this method. `allowed` lists the methods
+    /// it has, as the `Allow` header does (e.g. `GET, POST`).
+    #[error("method not allowed; allowed: {allowed}")]
+    MethodNotAllowed { allowed: &'static str },
+    /// The client sent too many requests; it may try again after `retry_after`.
+    #[error("rate limited; retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+    /// Something went wrong on our side that the client cannot act on.
+    #[error("internal error: {reason}")]
+    Internal { reason: String },
+    /// A failure known only by its status, such as an actix extractor error
+    /// whose body was replaced by [`crate::middleware::sanitize_errors`].
+    #[error("error status {status}")]
+    Status { status: StatusCode },
+    /// An error whose full internal message is reflected to the client.
+    ///
+    /// Only the vulnerable handler uses this variant. It exists so the demo
+    /// can show what leaking an internal error looks like.
+    #[error("{message}")]
+    Detailed { message: String },
+}
+
+/// A failed backend call, classified by [`classify_reqwest_error`].
+/// `reqwest::Error`'s `Display` includes the request URL, and with it the API
+/// key, so the URL is stripped before the error is kept around for logging;
+/// that is why this is not a plain `#[from]`.
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        classify_reqwest_error(&e)
+    }
+}
+
+impl ApiError {
+    /// The client-facing text for this error. It depends only on the variant,
+    /// never on the data it carries (except for the deliberately leaky one,
+    /// outside [`production_mode`]).
+    pub fn public_message(&self) -> String {
+        match self {
+            ApiError::InvalidHost { .. } => "Invalid 'Host' header provided.".to_string(),
+            ApiError::UrlParse { .. } | ApiError::Url { .. } | ApiError::Internal { .. } => {
+                Messages::current().generic_error
+            }
+            ApiError::UpstreamTimeout { .. } => {
+                "The service is taking too long to respond. Please try again later.".to_string()
+            }
+            ApiError::Upstream { .. }
+            | ApiError::UpstreamConnect { .. }
+            | ApiError::TlsPinMismatch { .. }
+            | ApiError::RetriesExhausted { .. }
+            | ApiError::CircuitOpen { .. }
+            | ApiError::Overloaded { .. } => {
+                "The service is temporarily unavailable. Please try again later.".to_string()
+            }
+            ApiError::BadRequest { .. } => "The request could not be processed.".to_string(),
+            ApiError::Json { source } if source.is_io() => Messages::current().generic_error,
+            ApiError::Json { .. } => "The request could not be processed.".to_string(),
+            ApiError::PayloadTooLarge { .. } => "The request body is too large.".to_string(),
+            ApiError::UriTooLong { .. } => "The request URI is too long.".to_string(),
+            ApiError::UnsupportedMediaType { .. } => {
+                "Unsupported content type; expected application/json.".to_string()
+            }
+            ApiError::AlreadySignedUp => {
+                "This email address is already on the waitlist.".to_string()
+            }
+            ApiError::EmailDomainNotAllowed => {
+                "This waitlist is not open to this email address.".to_string()
+            }
+            ApiError::IdempotencyKeyReused { .. } => {
+                "This Idempotency-Key was already used for a different request.".to_string()
+            }
+            ApiError::IdempotencyKeyInUse { .. } => {
+                "A request with this Idempotency-Key is still being processed.".to_string()
+            }
+            ApiError::Unauthorized { .. } => "Authentication required.".to_string(),
+            ApiError::MethodNotAllowed { .. } => {
+                "This method is not allowed for this resource.".to_string()
+            }
+            ApiError::DeadlineExceeded { .. } => {
+                "The request took too long to process. Please try again later.".to_string()
+            }
+            ApiError::Maintenance => {
+                "The service is down for maintenance. Please try again shortly.".to_string()
+            }
+            ApiError::RateLimited { .. } => {
+                "Too many requests. Please try again later.".to_string()
+            }
+            ApiError::Status { status } => match status.as_u16() {
+                400 => "The request could not be processed.".to_string(),
+                404 => "The requested resource was not found.".to_string(),
+                _ if status.is_server_error() => Messages::current().generic_error,
+                _ => format!("{}.", status.canonical_reason().unwrap_or("Request failed")),
+            },
+            ApiError::Detailed { .. } if production_mode() => Messages::current().generic_error,
+            ApiError::Detailed { message } => message.clone(),
+        }
+    }
+
+    /// A stable, machine-readable name for the error, sent as `error.code` in
+    /// JSON bodies. Like the public message, it never reveals more than the
+    /// client already knows: every internal failure is `INTERNAL_ERROR`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidHost { .. } => "INVALID_HOST",
+            ApiError::UrlParse { .. }
+            | ApiError::Url { .. }
+            | ApiError::Internal { .. }
+            | ApiError::Detailed { .. } => "INTERNAL_ERROR",
+            ApiError::Json { source } if source.is_io() => "INTERNAL_ERROR",
+            ApiError::UpstreamTimeout { .. } => "UPSTREAM_TIMEOUT",
+            ApiError::Upstream { .. }
+            | ApiError::UpstreamConnect { .. }
+            | ApiError::TlsPinMismatch { .. }
+            | ApiError::RetriesExhausted { .. }
+            | ApiError::CircuitOpen { .. } => "UPSTREAM_UNAVAILABLE",
+            ApiError::BadRequest { .. } | ApiError::Json { .. } => "BAD_REQUEST",
+            ApiError::PayloadTooLarge { .. } => "PAYLOAD_TOO_LARGE",
+            ApiError::UriTooLong { .. } => "URI_TOO_LONG",
+            ApiError::UnsupportedMediaType { .. } => "UNSUPPORTED_MEDIA_TYPE",
+            ApiError::AlreadySignedUp => "ALREADY_SIGNED_UP",
+            ApiError::EmailDomainNotAllowed => "EMAIL_DOMAIN_NOT_ALLOWED",
+            ApiError::IdempotencyKeyReused { .. } => "IDEMPOTENCY_KEY_REUSED",
+            ApiError::IdempotencyKeyInUse { .. } => "IDEMPOTENCY_KEY_IN_USE",
+            ApiError::Unauthorized { .. } => "UNAUTHORIZED",
+            ApiError::DeadlineExceeded { .. } => "DEADLINE_EXCEEDED",
+            ApiError::Maintenance => "MAINTENANCE",
+            ApiError::Overloaded { .. } => "OVERLOADED",
+            ApiError::MethodNotAllowed { .. } => "METHOD_NOT_ALLOWED",
+            ApiError::RateLimited { .. } => "RATE_LIMITED",
+            ApiError::Status { status } => status_code_name(*status),
+        }
+    }
+
+    /// The client-facing message with every globally registered secret redacted.
+    pub fn sanitize(&self) -> String {
+        self.sanitize_with(SecretRegistry::global())
+    }
+
+    /// Like [`ApiError::sanitize`], but against an explicit registry. Every
+    /// redaction is also recorded in the audit log.
+    pub fn sanitize_with(&self, registry: &SecretRegistry) -> String {
+        let (message, count) = registry.redact_counted(&self.public_message());
+        if count > 0 {
+            audit::record(AuditEvent::new(AuditEventKind::SecretRedacted { count }));
+        }
+        message
+    }
+
+    /// Like `error_response`, but the body carries a reference the client can
+    /// quote when reporting the problem, so it can be matched to the server logs.
+    pub fn error_response_with_reference(&self, reference: &str) -> HttpResponse {
+        self.render(Some(reference))
+    }
+
+    fn render(&self, reference: Option<&str>) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        // Already sanitized; the global error handler can leave it alone.
+        res.extensions_mut().insert(SafeErrorBody);
+        if let ApiError::RateLimited { retry_after } | ApiError::CircuitOpen { retry_after } = self
+        {
+            // Whole seconds, rounded up so an obedient client is never early.
+            // A half-open circuit whose trial is still running says one second.
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            res.insert_header((RETRY_AFTER, secs.max(1)));
+        }
+        if let ApiError::Unauthorized { .. } = self {
+            res.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let ApiError::MethodNotAllowed { allowed } = self {
+            res.insert_header((ALLOW, *allowed));
+        }
+        // The body depends on `Accept` whenever a format was negotiated.
+        if NEGOTIATED.try_with(|_| ()).is_ok() {
+            res.append_header((VARY, "Accept"));
+        }
+        if PREFERRED.try_with(|_| ()).is_ok() {
+            res.append_header((VARY, "Accept-Language"));
+        }
+        match (ErrorFormat::current(), reference) {
+            (ErrorFormat::Json, request_id) => res.json(ErrorBody {
+                error: ErrorDetail {
+                    code: self.code(),
+                    message: self.sanitize(),
+                    request_id,
+                },
+            }),
+            (ErrorFormat::Text, Some(reference)) => res
+                .content_type("text/plain; charset=utf-8")
+                .body(format!("{} Reference: {}", self.sanitize(), reference)),
+            (ErrorFormat::Text, None) => res
+                .content_type("text/plain; charset=utf-8")
+                .body(self.sanitize()),
+            (ErrorFormat::Html, reference) => res
+                .content_type("text/html; charset=utf-8")
+                .body(html_page(self.status_code(), &self.sanitize(), reference)),
+        }
+    }
+}
+
+/// Marks an error response whose body may reach the client as it is.
+///
+/// [`crate::middleware::sanitize_errors`] replaces the body of every 4xx/5xx
+/// response without this marker. `ApiError` responses carry it, and a handler
+/// that deliberately returns a detailed error body (like `/readyz`) inserts it
+/// into the response extensions itself.
+#[derive(Debug, Clone, Copy)]
+pub struct SafeErrorBody;
+
+fn status_code_name(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "BAD_REQUEST",
+        401 => "UNAUTHORIZED",
+        403 => "FORBIDDEN",
+        404 => "NOT_FOUND",
+        405 => "METHOD_NOT_ALLOWED",
+        406 => "NOT_ACCEPTABLE",
+        408 => "REQUEST_TIMEOUT",
+        409 => "CONFLICT",
+        413 => "PAYLOAD_TOO_LARGE",
+        414 => "URI_TOO_LONG",
+        415 => "UNSUPPORTED_MEDIA_TYPE",
+        422 => "UNPROCESSABLE_ENTITY",
+        429 => "RATE_LIMITED",
+        502 => "UPSTREAM_UNAVAILABLE",
+        503 => "SERVICE_UNAVAILABLE",
+        504 => "UPSTREAM_TIMEOUT",
+        _ if status.is_server_error() => "INTERNAL_ERROR",
+        _ => "CLIENT_ERROR",
+    }
+}
+
+// Everything interpolated is escaped: the message can echo request input.
+fn html_page(status: StatusCode, message: &str, reference: Option<&str>) -> String {
+    let title = escape_html(&status.to_string());
+    let reference = reference
+        .map(|id| format!("<p>Reference: <code>{}</code></p>", escape_html(id)))
+        .unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+         <body><h1>{title}</h1><p>{}</p>{reference}</body></html>\n",
+        escape_html(message)
+    )
+}
+
+fn escape_html(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// What every route handler returns. An `Err` is turned into a response by
+/// the `ResponseError` impl of [`ApiError`] alone, so it always gets the
+/// sanitized body; handlers never build an error response of their own.
+pub type HandlerResult = Result<HttpResponse, ApiError>;
+
+// Implementing ResponseError allows actix-web to convert our custom error into an HTTP response.
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidHost { .. } | ApiError::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            ApiError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UriTooLong { .. } => StatusCode::URI_TOO_LONG,
+            ApiError::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::AlreadySignedUp | ApiError::IdempotencyKeyInUse { .. } => {
+                StatusCode::CONFLICT
+            }
+            ApiError::IdempotencyKeyReused { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::EmailDomainNotAllowed => StatusCode::FORBIDDEN,
+            ApiError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            ApiError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::UpstreamTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::CircuitOpen { .. }
+            | ApiError::DeadlineExceeded { .. }
+            | ApiError::Maintenance
+            | ApiError::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Upstream { .. }
+            | ApiError::UpstreamConnect { .. }
+            | ApiError::TlsPinMismatch { .. }
+            | ApiError::RetriesExhausted { .. } => StatusCode::BAD_GATEWAY,
+            ApiError::Json { source } if source.is_io() => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Json { .. } => StatusCode::BAD_REQUEST,
+            ApiError::UrlParse { .. }
+            | ApiError::Url { .. }
+            | ApiError::Internal { .. }
+            | ApiError::Detailed { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Status { status } => *status,
+        }
+    }
+
+    // The body is sanitized unconditionally, so even a handler that puts a secret
+    // into its error (like the vulnerable one) cannot send it to the client.
+    fn error_response(&self) -> HttpResponse {
+        let id = RequestId::current();
+        self.render(id.as_ref().map(RequestId::as_str))
+    }
+}
@@ -0,0 +1,87 @@
+//! A live feed of signups for an admin dashboard, served as server-sent
+//! events by `GET /admin/events`.
+//!
+//! The secure handlers publish to a `tokio::sync::broadcast` channel, which
+//! keeps only the last [`CAPACITY`] events: a subscriber that falls further
+//! behind skips the ones it missed (with a logged warning) instead of making
+//! the channel grow.
+
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::time::SystemTime;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::pii::mask_email;
+
+/// How many events a subscriber may fall behind before it starts missing some.
+pub const CAPACITY: usize = 64;
+
+/// What the feed says about one signup. The address is masked
+/// (`j***@example.com`) whatever `log_pii` says: the feed is for watching
+/// signups come in, not for collecting them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignupEvent {
+    pub email: String,
+    /// When the signup was accepted, in RFC 3339.
+    pub timestamp: String,
+}
+
+/// The sending side of the feed. Clones publish to the same subscribers.
+#[derive(Debug, Clone)]
+pub struct SignupEvents {
+    sender: broadcast::Sender<SignupEvent>,
+}
+
+impl Default for SignupEvents {
+    fn default() -> Self {
+        SignupEvents::new()
+    }
+}
+
+impl SignupEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        SignupEvents { sender }
+    }
+
+    /// Tells every current subscriber about the signup of `email`. Without
+    /// subscribers, the event goes nowhere.
+    pub fn publish(&self, email: &str) {
+        let _ = self.sender.send(SignupEvent {
+            email: mask_email(email),
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        });
+    }
+
+    /// A receiver for the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SignupEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// The body of an event stream: one `event: signup` with the JSON of the
+/// event as its `data` per signup, for as long as the channel is open.
+pub fn sse_stream(
+    receiver: broadcast::Receiver<SignupEvent>,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    let frame = format!("event: signup\ndata: {}\n\n", data);
+                    return Some((Ok(Bytes::from(frame)), receiver));
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    log::warn!(
+                        "Event stream subscriber fell behind; dropped {} event(s)",
+                        missed
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
@@ -0,0 +1,450 @@
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse,
+    dev::Payload,
+    error::JsonPayloadError,
+    http::Method,
+    http::header::{CONTENT_TYPE, VARY},
+    web,
+};
+use futures_util::future::LocalBoxFuture;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::error::Category;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::audit::{self, AuditEvent, AuditEventKind};
+use crate::backend::build_backend_url;
+use crate::client_info::ClientInfo;
+use crate::config::AppState;
+use crate::error::{ApiError, ErrorBody, HandlerResult};
+use crate::host::{ValidatedHost, audit_host_rejected, is_safe_upstream_host, request_host};
+use crate::idempotency::{Begin, fingerprint, idempotency_key};
+use crate::middleware::{RequestDeadline, RequestId};
+use crate::pii::{redact_url_for_logging, url_for_log};
+use crate::service::{SignupCall, SignupOutcome, WaitlistService};
+use crate::validation::{SignupValidator, reject_repeated_param, validate_email};
+
+/// Set on the success response of a dry-run signup, so load tests and demos
+/// can tell it from a real one.
+pub const DRY_RUN_HEADER: &str = "x-dry-run";
+
+// Struct to deserialize query parameters like "?email=test@example.com", or a
+// JSON body like {"email": "test@example.com"}.
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WaitlistParams {
+    /// The address to put on the waitlist.
+    #[schema(example = "user@example.com")]
+    pub email: String,
+}
+
+/// A signup that passed every check the secure handlers rely on: the host is
+/// whitelisted (see [`ValidatedHost`]) and does not resolve to an internal
+/// address, and the email passed `AppState::signup_validators`: it is
+/// well-formed, from one of the `allowed_email_domains`, and whatever else the
+/// deployment checks. The email comes from the query string of a `GET`
+/// and from the JSON body of anything else.
+///
+/// Handlers that take one of these cannot forget a check: there is no other
+/// way to get at the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitlistRequest {
+    pub host: String,
+    pub email: String,
+}
+
+impl WaitlistRequest {
+    /// Checks `email`, and that the already whitelisted `host` is safe to
+    /// call, against the `AppState` of `req`.
+    pub async fn validate(
+        req: &HttpRequest,
+        host: ValidatedHost,
+        email: String,
+    ) -> Result<Self, ApiError> {
+        let request_id = RequestId::of(req);
+        let Some(state) = req.app_data::<web::Data<AppState>>() else {
+            log::error!(
+                "[{}] No AppState to validate the signup against",
+                request_id
+            );
+            return Err(ApiError::Internal {
+                reason: "no AppState".to_string(),
+            });
+        };
+
+        // MITIGATION: Validate the email first, so a malformed value never
+        // gets anywhere near the URL that carries the API key. The built-in
+        // checks come first in the chain, so a custom validator only ever
+        // sees a well-formed address from an allowed domain.
+        let params = WaitlistParams { email };
+        state
+            .signup_validators
+            .validate(&params)
+            .inspect_err(|e| log::warn!("[{}] Rejected signup: {}", request_id, e))?;
+        let email = params.email;
+
+        // MITIGATION: Even a whitelisted name must not lead the backend call
+        // to an internal address (SSRF), e.g. through a wildcard entry or a
+        // changed DNS record, unless that host is explicitly exempted.
+        let host = host.0;
+        if !is_safe_upstream_host(&host, &state.backend.private_hosts).await {
+            log::warn!(
+                "[{}] Rejected host resolving to a non-public address: {:?}",
+                request_id,
+                host
+            );
+            audit_host_rejected(req, Some(&host));
+            return Err(ApiError::InvalidHost { host: Some(host) });
+        }
+        Ok(WaitlistRequest { host, email })
+    }
+}
+
+impl FromRequest for WaitlistRequest {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        // The host is checked first: an unlisted one is refused before the
+        // body is even read.
+        let host = ValidatedHost::of(&req);
+        let params: LocalBoxFuture<'static, Result<WaitlistParams, actix_web::Error>> =
+            if req.method() == Method::GET {
+                // A second `email` is refused outright, whichever one the
+                // deserializer would have kept.
+                if let Err(e) = reject_repeated_param(req.query_string(), "email") {
+                    let request_id = RequestId::of(&req);
+                    return Box::pin(async move {
+                        host?;
+                        log::warn!("[{}] Rejected query string: {}", request_id, e);
+                        Err(e.into())
+                    });
+                }
+                // With the `query_config` error handler, if registered.
+                let query = web::Query::<WaitlistParams>::from_request(&req, payload);
+                Box::pin(async move { query.await.map(web::Query::into_inner) })
+            } else {
+                // With the `json_config` limits and error handler.
+                let json = web::Json::<WaitlistParams>::from_request(&req, payload);
+                Box::pin(async move { json.await.map(web::Json::into_inner) })
+            };
+        Box::pin(async move {
+            let host = host?;
+            let email = params.await?.email;
+            Ok(WaitlistRequest::validate(&req, host, email).await?)
+        })
+    }
+}
+
+/// How the vulnerable handler answers a backend URL that fails to parse, picked
+/// per request with `?mode=`, so the leak and its fix can be compared on the
+/// same endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VulnerableMode {
+    /// Reflect the URL and the parser error to the client: the bug.
+    #[default]
+    Leak,
+    /// Log them and answer the generic error of the secure handlers.
+    Fixed,
+}
+
+// `?mode=` on the vulnerable handler.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct VulnerableQuery {
+    /// `leak` (the default) or `fixed`.
+    #[param(inline)]
+    mode: Option<VulnerableMode>,
+}
+
+/// # Vulnerable Handler
+/// This handler extracts the `Host` header and uses it to construct a backend API URL.
+/// It uses `.unwrap()` to parse the URL, which will cause a `panic` if the host is invalid,
+/// crashing the thread and causing a Denial of Service.
+///
+/// If the URL parsing itself throws a recoverable error, it is returned as
+/// `ApiError::Detailed`, which leaks the constructed URL. The API key inside it
+/// is only saved by the secret redaction in `ApiError::error_response`. With
+/// `?mode=fixed` the same error goes through the sanitizer instead; see
+/// [`VulnerableMode`].
+#[utoipa::path(
+    get,
+    path = "/vulnerable/waitlist",
+    tag = "waitlist",
+    params(WaitlistParams, VulnerableQuery),
+    responses(
+        (status = 200, description = "Signed up", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid host or email", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+pub async fn vulnerable_waitlist(
+    req: HttpRequest,
+    query: web::Query<WaitlistParams>,
+    state: web::Data<AppState>,
+) -> HandlerResult {
+    // Reject malformed addresses before anything else happens.
+    validate_email(&query.email)?;
+    let mode = web::Query::<VulnerableQuery>::from_query(req.query_string())
+        .map_err(|e| ApiError::BadRequest {
+            reason: format!("invalid mode: {}", e),
+        })?
+        .mode
+        .unwrap_or_default();
+
+    // 1. Extract the host header from the user's request. Even this handler
+    // refuses a missing, empty or duplicated one.
+    let host = request_host(&req, state.default_host.as_deref(), state.max_host_len)?;
+
+    // 2. Construct the backend URL with the sensitive API key.
+    // 3. Attempt to parse the URL. This is where the error occurs.
+    // The original JS example had a library that threw an error which Express then
+    // printed to the response. We simulate this by returning `ApiError::Detailed`,
+    // the one variant whose client-facing message is the raw internal message.
+    match build_backend_url(
+        host,
+        &state.backend.path,
+        state.api_key.current().expose(),
+        &query.email,
+    ) {
+        Ok(url) => {
+            log::info!(
+                "[{}] Vulnerable handler attempting to use URL: {}",
+                RequestId::of(&req),
+                loggable_url(&url, &state)
+            );
+            // In a real app, we would make the request here.
+            // For this demo, we assume success if parsing works.
+            let messages = state.localizer.for_request(&req);
+            Ok(HttpResponse::Ok()
+                .insert_header((VARY, "Accept-Language"))
+                .body(messages.vulnerable_signup_success.clone()))
+        }
+        Err(e @ ApiError::UrlParse { .. }) if mode == VulnerableMode::Fixed => {
+            // The fix, for comparison: the details go to the log (without
+            // the key), and the client gets the variant's generic message.
+            if let ApiError::UrlParse { url, source } = &e {
+                log::error!(
+                    "[{}] Failed to parse backend URL '{}': {}",
+                    RequestId::of(&req),
+                    redact_url_for_logging(url, &state.redact_query_params),
+                    source
+                );
+            }
+            Err(e)
+        }
+        Err(ApiError::UrlParse { url, source }) => {
+            // VULNERABILITY: The error returned to the user includes the full URL
+            // and the internal error message. The API key in it gets redacted by
+            // `ApiError::sanitize`, but the rest of the internals are still exposed.
+            let error_message = format!(
+                "Failed to construct backend request. URL: '{}', Error: {}",
+                url, source
+            );
+            Err(ApiError::Detailed {
+                message: error_message,
+            })
+        }
+        Err(e) if mode == VulnerableMode::Fixed => Err(e),
+        Err(e) => Err(ApiError::Detailed {
+            message: e.to_string(),
+        }),
+    }
+}
+
+/// # Secure Handler
+/// This handler follows best practices to prevent the vulnerability. It never
+/// sees a host that is not whitelisted, or an email that is malformed:
+/// [`WaitlistRequest`] refuses the request before the handler runs.
+#[utoipa::path(
+    get,
+    path = "/secure/waitlist",
+    tag = "waitlist",
+    params(WaitlistParams),
+    responses(
+        (status = 200, description = "Signed up", body = String, content_type = "text/plain"),
+        (status = 202, description = "Signed up; queued for the next batch (with `[backend.batch]` enabled)", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid host or email", body = ErrorBody),
+        (status = 403, description = "Email domain not allowed", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+pub async fn secure_waitlist(
+    req: HttpRequest,
+    signup: WaitlistRequest,
+    state: web::Data<AppState>,
+) -> HandlerResult {
+    secure_signup(&req, &signup, &state).await
+}
+
+/// # Secure Handler (JSON)
+/// The same signup as [`secure_waitlist`], but the email arrives in a JSON body
+/// (`{"email": "..."}`) instead of the query string, so it stays out of URLs
+/// and access logs. `web::Json` refuses any other content type with a `415`.
+///
+/// A request with an `Idempotency-Key` header is processed once; repeating it
+/// returns the first response again. See [`crate::idempotency::IdempotencyStore`].
+#[utoipa::path(
+    post,
+    path = "/secure/waitlist",
+    tag = "waitlist",
+    request_body = WaitlistParams,
+    responses(
+        (status = 200, description = "Signed up", body = String, content_type = "text/plain"),
+        (status = 202, description = "Signed up; queued for the next batch (with `[backend.batch]` enabled)", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid host or email", body = ErrorBody),
+        (status = 403, description = "Email domain not allowed", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+pub async fn secure_waitlist_json(
+    req: HttpRequest,
+    signup: WaitlistRequest,
+    state: web::Data<AppState>,
+) -> HandlerResult {
+    let Some(key) = idempotency_key(&req)? else {
+        return secure_signup(&req, &signup, &state).await;
+    };
+    match state.idempotency.begin(key, fingerprint(&signup.email))? {
+        Begin::Replay(response) => {
+            log::info!(
+                "[{}] Replayed response for idempotency key",
+                RequestId::of(&req)
+            );
+            Ok(response)
+        }
+        Begin::New(reservation) => {
+            let outcome = secure_signup(&req, &signup, &state).await;
+            reservation.complete(outcome).await
+        }
+    }
+}
+
+/// The `web::Json` settings for [`secure_waitlist_json`]. Bodies over
+/// `limit` bytes get a `413`, and a body that is not `application/json` a
+/// `415` rather than actix's `400`. A body that does not deserialize gets the
+/// generic `400` of `ApiError::BadRequest`; the log says whether it was not
+/// JSON at all or JSON of the wrong shape, but never quotes it.
+pub fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(move |err, req| match err {
+            JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                ApiError::PayloadTooLarge { limit }.into()
+            }
+            JsonPayloadError::ContentType => ApiError::UnsupportedMediaType {
+                content_type: req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string),
+            }
+            .into(),
+            JsonPayloadError::Deserialize(e) => {
+                let reason = json_error_kind(&e);
+                log::warn!(
+                    "[{}] Rejected JSON body: {} at line {} column {}",
+                    RequestId::of(req),
+                    reason,
+                    e.line(),
+                    e.column()
+                );
+                ApiError::BadRequest {
+                    reason: reason.to_string(),
+                }
+                .into()
+            }
+            err => err.into(),
+        })
+}
+
+// What was wrong with a JSON body, for the log. Not serde's message: that can
+// quote the offending value, e.g. `invalid type: string "..."`.
+fn json_error_kind(err: &serde_json::Error) -> &'static str {
+    match err.classify() {
+        Category::Syntax | Category::Eof => "invalid JSON syntax",
+        Category::Data if err.to_string().starts_with("missing field") => "missing field",
+        Category::Data => "unexpected field or value",
+        Category::Io => "unreadable body",
+    }
+}
+
+/// The `web::Query` settings for the handlers. A query string that does not
+/// deserialize, e.g. one without `email`, gets the generic `400` body of
+/// `ApiError::BadRequest`; what was wrong with it is only logged.
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, req| {
+        log::warn!("[{}] Rejected query string: {}", RequestId::of(req), err);
+        ApiError::BadRequest {
+            reason: err.to_string(),
+        }
+        .into()
+    })
+}
+
+// `?dry_run=` on a secure signup; see `BackendConfig::dry_run`.
+#[derive(Deserialize)]
+struct DryRunQuery {
+    dry_run: Option<bool>,
+}
+
+// Whether this signup is a dry run: `backend.dry_run`, unless the request
+// overrides it with `?dry_run=true|false`, which takes the admin token.
+fn is_dry_run(req: &HttpRequest, state: &AppState) -> Result<bool, ApiError> {
+    let Some(dry_run) = web::Query::<DryRunQuery>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.dry_run)
+    else {
+        return Ok(state.backend.dry_run);
+    };
+    state.auth.authorize(req.headers()).inspect_err(|e| {
+        log::warn!("[{}] Refused dry_run override: {}", RequestId::of(req), e);
+        audit::record(
+            AuditEvent::new(AuditEventKind::AuthFailed {
+                path: req.path().to_string(),
+            })
+            .client_ip(ClientInfo::of(req).ip),
+        );
+    })?;
+    Ok(dry_run)
+}
+
+// The steps shared by both secure handlers, for a signup that passed every
+// check of `WaitlistRequest`: a thin adapter over `WaitlistService`, which
+// does the work. Only who may ask for a dry run, and the wording of the
+// answer, are up to the request.
+async fn secure_signup(
+    req: &HttpRequest,
+    signup: &WaitlistRequest,
+    state: &web::Data<AppState>,
+) -> HandlerResult {
+    let call = SignupCall::new(signup.clone())
+        .dry_run(is_dry_run(req, state)?)
+        .request_id(RequestId::of(req).as_str())
+        .deadline(RequestDeadline::of(req));
+    let outcome = WaitlistService::new(state.clone().into_inner())
+        .signup(call)
+        .await?;
+
+    // The text follows the client's `Accept-Language`.
+    let messages = state.localizer.for_request(req);
+    let mut response = match outcome {
+        SignupOutcome::Queued => HttpResponse::Accepted(),
+        SignupOutcome::Submitted | SignupOutcome::Cached | SignupOutcome::DryRun => {
+            HttpResponse::Ok()
+        }
+    };
+    response.insert_header((VARY, "Accept-Language"));
+    if outcome == SignupOutcome::DryRun {
+        response.insert_header((DRY_RUN_HEADER, "true"));
+    }
+    Ok(response.body(messages.signup_success.clone()))
+}
+
+// The form of a backend URL that goes into the logs: the email masked (unless
+// `log_pii` is set) and every sensitive parameter redacted.
+pub(crate) fn loggable_url(url: &Url, state: &AppState) -> String {
+    redact_url_for_logging(&url_for_log(url, state.log_pii), &state.redact_query_params)
+}
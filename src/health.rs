@@ -0,0 +1,71 @@
+use actix_web::{HttpResponse, web};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::backend::classify_failure;
+use crate::config::AppState;
+use crate::error::{HandlerResult, SafeErrorBody};
+
+/// How long the readiness probe waits for the backend. Kept well below any
+/// sensible probe interval so a hanging backend cannot stall the probe.
+pub const READINESS_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct HealthStatus {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+struct ReadinessStatus {
+    status: &'static str,
+    backend: &'static str,
+}
+
+/// # Liveness Probe
+/// Always answers `200 {"status":"ok"}` while the server can handle requests.
+/// It deliberately touches no shared state, so it cannot fail because of it.
+pub async fn healthz() -> HandlerResult {
+    Ok(HttpResponse::Ok().json(HealthStatus { status: "ok" }))
+}
+
+/// # Readiness Probe
+/// Checks that the backend host is reachable with a `HEAD` request. Any HTTP
+/// answer counts as reachable; only a connection failure or timeout makes the
+/// probe return `503`. Without a host to check, the server reports ready.
+pub async fn readyz(state: web::Data<AppState>) -> HandlerResult {
+    let Some(host) = state.readiness_host() else {
+        return Ok(HttpResponse::Ok().json(ReadinessStatus {
+            status: "ok",
+            backend: "unchecked",
+        }));
+    };
+
+    let url = format!("https://{}/", host);
+    match state
+        .client
+        .head(&url)
+        .timeout(READINESS_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().json(ReadinessStatus {
+            status: "ok",
+            backend: "reachable",
+        })),
+        Err(e) => {
+            log::warn!(
+                "Readiness check failed ({}): {}",
+                classify_failure(&e),
+                e.without_url()
+            );
+            let mut res = HttpResponse::ServiceUnavailable().json(ReadinessStatus {
+                status: "unavailable",
+                backend: "unreachable",
+            });
+            // Probes parse this body; it carries nothing internal. It is an
+            // answer, not an error, so it is not rendered from `ApiError`.
+            res.extensions_mut().insert(SafeErrorBody);
+            Ok(res)
+        }
+    }
+}
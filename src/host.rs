@@ -0,0 +1,547 @@
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header::HOST, web};
+use futures_util::future::{Ready, ready};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+
+use crate::audit::{self, AuditEvent, AuditEventKind};
+use crate::client_info::ClientInfo;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::middleware::RequestId;
+
+/// Decides whether a `Host` header value is on the whitelist.
+///
+/// Entries are either exact hosts (`my-app.com`, `my-app.com:8080`) or
+/// wildcards (`*.my-app.com`), which match any subdomain but not the bare
+/// domain itself. Comparison is case-insensitive and ignores a trailing dot.
+/// An entry with a port only matches that port, unless the matcher was built
+/// to ignore ports entirely.
+///
+/// Entries added with [`HostMatcher::deny`] take precedence: a host matching
+/// one is refused even if the whitelist has it. With a denylist but an empty
+/// whitelist, every other host is allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostMatcher {
+    patterns: Vec<HostPattern>,
+    denied: Vec<HostPattern>,
+    ignore_port: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostPattern {
+    host: HostName,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostName {
+    Exact(String),
+    /// The parent domain of a `*.` entry, e.g. `my-app.com` for `*.my-app.com`.
+    Wildcard(String),
+}
+
+impl HostMatcher {
+    /// Builds a matcher from whitelist entries. Entries that are not valid
+    /// `host[:port]` values are dropped with a warning.
+    pub fn new<I, S>(allowed_hosts: I, ignore_port: bool) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        HostMatcher {
+            patterns: parse_patterns(allowed_hosts, "allowed"),
+            denied: Vec::new(),
+            ignore_port,
+        }
+    }
+
+    /// Refuses the hosts matching `denied_hosts`, entries of the same syntax.
+    /// Invalid entries are dropped with a warning, like whitelist entries.
+    pub fn deny<I, S>(mut self, denied_hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.denied.extend(parse_patterns(denied_hosts, "denied"));
+        self
+    }
+
+    pub fn is_allowed(&self, host: &str) -> bool {
+        let Some((name, port)) = split_host_port(host) else {
+            return false;
+        };
+        let allow_all = self.patterns.is_empty() && !self.denied.is_empty();
+        (allow_all || self.matches(&self.patterns, &name, port))
+            && !self.matches(&self.denied, &name, port)
+    }
+
+    /// Like [`is_allowed`](Self::is_allowed), but only for hosts the
+    /// whitelist names: an empty one lists nothing, denylist or not. For uses,
+    /// like CORS, where "every host but these" would be too broad.
+    pub fn is_listed(&self, host: &str) -> bool {
+        let Some((name, port)) = split_host_port(host) else {
+            return false;
+        };
+        self.matches(&self.patterns, &name, port) && !self.matches(&self.denied, &name, port)
+    }
+
+    fn matches(&self, patterns: &[HostPattern], name: &str, port: Option<u16>) -> bool {
+        patterns.iter().any(|pattern| {
+            let host_matches = match &pattern.host {
+                HostName::Exact(exact) => exact == name,
+                HostName::Wildcard(parent) => name
+                    .strip_suffix(parent.as_str())
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            };
+            host_matches && (self.ignore_port || pattern.port == port)
+        })
+    }
+}
+
+fn parse_patterns<I, S>(entries: I, kind: &str) -> Vec<HostPattern>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let entry = entry.as_ref();
+            let pattern = HostPattern::parse(entry);
+            if pattern.is_none() {
+                log::warn!("Ignoring invalid {} host entry: {:?}", kind, entry);
+            }
+            pattern
+        })
+        .collect()
+}
+
+/// The host whitelist of a running server: a [`HostMatcher`] that
+/// `POST /admin/reload-hosts` can replace without a restart. Clones share the
+/// list.
+///
+/// Each check runs against one snapshot of the list, so a request never sees
+/// half of an old list and half of a new one.
+///
+/// Its denylist is fixed at startup; a reload only replaces the whitelist.
+#[derive(Debug, Clone, Default)]
+pub struct HostWhitelist {
+    current: Arc<RwLock<Arc<WhitelistSnapshot>>>,
+    denied: Arc<[String]>,
+    ignore_port: bool,
+}
+
+/// One version of a [`HostWhitelist`]: the entries as configured, and the
+/// matcher compiled from them.
+#[derive(Debug, Default)]
+pub struct WhitelistSnapshot {
+    pub hosts: Vec<String>,
+    pub matcher: HostMatcher,
+}
+
+impl HostWhitelist {
+    /// See [`HostMatcher::new`]; `ignore_port` stays the same across reloads.
+    pub fn new(hosts: Vec<String>, ignore_port: bool) -> Self {
+        HostWhitelist::with_denied(hosts, Vec::new(), ignore_port)
+    }
+
+    /// A whitelist that also refuses every host matching `denied`; see
+    /// [`HostMatcher::deny`].
+    pub fn with_denied(hosts: Vec<String>, denied: Vec<String>, ignore_port: bool) -> Self {
+        let whitelist = HostWhitelist {
+            current: Arc::default(),
+            denied: denied.into(),
+            ignore_port,
+        };
+        whitelist.replace(hosts);
+        whitelist
+    }
+
+    /// The denylist, as configured.
+    pub fn denied(&self) -> &[String] {
+        &self.denied
+    }
+
+    /// The list as it is right now.
+    pub fn snapshot(&self) -> Arc<WhitelistSnapshot> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn is_allowed(&self, host: &str) -> bool {
+        self.snapshot().matcher.is_allowed(host)
+    }
+
+    /// See [`HostMatcher::is_listed`].
+    pub fn is_listed(&self, host: &str) -> bool {
+        self.snapshot().matcher.is_listed(host)
+    }
+
+    /// Swaps in `hosts` for every clone at once. Checks already running
+    /// finish against the list they started with.
+    pub fn replace(&self, hosts: Vec<String>) {
+        let snapshot = Arc::new(WhitelistSnapshot {
+            matcher: HostMatcher::new(&hosts, self.ignore_port).deny(self.denied.iter()),
+            hosts,
+        });
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = snapshot;
+    }
+}
+
+/// Whether `entry` is a valid whitelist entry: `host[:port]` or `*.domain`.
+pub fn is_valid_host_entry(entry: &str) -> bool {
+    HostPattern::parse(entry).is_some()
+}
+
+impl HostPattern {
+    fn parse(entry: &str) -> Option<Self> {
+        let (name, port) = split_host_port(entry)?;
+        let host = match name.strip_prefix("*.") {
+            Some(parent) if !parent.is_empty() => HostName::Wildcard(parent.to_string()),
+            Some(_) => return None,
+            None => HostName::Exact(name),
+        };
+        Some(HostPattern { host, port })
+    }
+}
+
+/// The host the client addressed: the `Host` header, or for HTTP/2 (which
+/// carries it in the `:authority` pseudo-header instead) the request URI's
+/// authority.
+///
+/// This only reads the request; whether the host is acceptable is for
+/// [`HostMatcher`] to decide. Fails with `ApiError::InvalidHost` when there is
+/// no host at all, when it is empty, not valid UTF-8 or not
+/// [`is_header_safe`], and when the request carries more than one `Host`
+/// header: proxies disagree on which one wins, which is the raw material of
+/// request smuggling, so neither is trusted.
+pub fn resolve_host(req: &HttpRequest) -> Result<&str, ApiError> {
+    let mut values = req.headers().get_all(HOST);
+    let host = match (values.next(), values.next()) {
+        (Some(_), Some(_)) => return Err(ApiError::InvalidHost { host: None }),
+        (Some(host), None) => match host.to_str() {
+            Ok(host) => Some(host),
+            // Not echoed back or logged: the bytes are attacker-chosen.
+            Err(_) => return Err(ApiError::InvalidHost { host: None }),
+        },
+        (None, _) => req.uri().authority().map(|authority| authority.as_str()),
+    };
+    match host {
+        // Not echoed back either: it is exactly what an injection looks like.
+        Some(host) if !is_header_safe(host) => Err(ApiError::InvalidHost { host: None }),
+        Some(host) if !host.trim().is_empty() => Ok(host),
+        host => Err(ApiError::InvalidHost {
+            host: host.map(str::to_string),
+        }),
+    }
+}
+
+/// Whether `host` has no control characters: no CR or LF that could end a
+/// header or a log line early, and nothing else (tabs included) that has no
+/// business in a host name. Every host [`resolve_host`] returns passes, whether
+/// or not it is whitelisted, so it is safe to put in a header or a log line.
+pub fn is_header_safe(host: &str) -> bool {
+    !host.chars().any(char::is_control)
+}
+
+/// The longest host accepted unless configured otherwise: the longest name
+/// DNS allows.
+pub const DEFAULT_MAX_HOST_LEN: usize = 253;
+
+/// [`resolve_host`], with `default_host` standing in when the request names
+/// no host at all. A host that is present but invalid is still refused; one
+/// that is not valid UTF-8 is logged with the request ID, but not its bytes.
+///
+/// A host longer than `max_len` bytes is refused before anything else looks
+/// at it, and logged by its length only, so an oversized header never makes
+/// it into a URL or a log line.
+pub fn request_host<'a>(
+    req: &'a HttpRequest,
+    default_host: Option<&'a str>,
+    max_len: usize,
+) -> Result<&'a str, ApiError> {
+    let len = match req.headers().get(HOST) {
+        Some(value) => Some(value.len()),
+        None => req
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str().len()),
+    };
+    if let Some(len) = len.filter(|&len| len > max_len) {
+        log::warn!(
+            "[{}] Rejected a host of {} bytes, over the limit of {}",
+            RequestId::of(req),
+            len,
+            max_len
+        );
+        return Err(ApiError::InvalidHost { host: None });
+    }
+    if let Some(value) = req.headers().get(HOST) {
+        match value.to_str() {
+            Err(_) => log::warn!(
+                "[{}] Rejected a Host header that is not valid UTF-8 ({} bytes)",
+                RequestId::of(req),
+                value.len()
+            ),
+            Ok(host) if !is_header_safe(host) => log::warn!(
+                "[{}] Rejected a Host header with control characters ({} bytes)",
+                RequestId::of(req),
+                value.len()
+            ),
+            Ok(_) => {}
+        }
+    }
+    let absent = !req.headers().contains_key(HOST) && req.uri().authority().is_none();
+    match default_host {
+        Some(host) if absent && !host.trim().is_empty() && is_header_safe(host) => Ok(host),
+        _ => resolve_host(req),
+    }
+}
+
+/// The request's host (see [`request_host`]), checked against the whitelist
+/// (`AppState::host_matcher`) as it stands when the request arrives.
+///
+/// Take this as a handler argument instead of reading the `Host` header: the
+/// handler only runs once the host is known to be allowed. Extraction fails
+/// with `ApiError::InvalidHost`, after logging and auditing the rejection.
+/// Without an `AppState` every host is refused.
+///
+/// Whitelisted is not the same as safe to call: the SSRF guard,
+/// [`is_safe_upstream_host`], still has to run before the host is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatedHost(pub String);
+
+impl ValidatedHost {
+    pub fn of(req: &HttpRequest) -> Result<Self, ApiError> {
+        let request_id = RequestId::of(req);
+        let Some(state) = req.app_data::<web::Data<AppState>>() else {
+            log::error!("[{}] No AppState to validate the host against", request_id);
+            return Err(ApiError::InvalidHost { host: None });
+        };
+        let host = request_host(req, state.default_host.as_deref(), state.max_host_len)
+            .inspect_err(|e| {
+                log::warn!("[{}] Rejected request: {}", request_id, e);
+                audit_host_rejected(req, None);
+            })?;
+        // The matcher handles wildcards, case and (optionally) ports, so no
+        // ad-hoc string comparison here.
+        if !state.host_matcher.is_allowed(host) {
+            log::warn!(
+                "[{}] Rejected request with invalid host header: {:?}",
+                request_id,
+                host
+            );
+            audit_host_rejected(req, Some(host));
+            return Err(ApiError::InvalidHost {
+                host: Some(host.to_string()),
+            });
+        }
+        Ok(ValidatedHost(host.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for ValidatedHost {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(ValidatedHost::of(req))
+    }
+}
+
+/// Records a `host_rejected` audit event for `req`.
+pub(crate) fn audit_host_rejected(req: &HttpRequest, host: Option<&str>) {
+    audit::record(
+        AuditEvent::new(AuditEventKind::HostRejected {
+            host: host.map(str::to_string),
+        })
+        .client_ip(ClientInfo::of(req).ip),
+    );
+}
+
+/// The SSRF guard: whether the backend may be called at `host` (a
+/// `host[:port]` value).
+///
+/// The host is resolved and refused if *any* of its addresses is loopback,
+/// private, link-local (e.g. the `169.254.169.254` metadata service) or
+/// otherwise not publicly routable, so a name with one public and one internal
+/// record cannot slip through. Hosts matching `exempt` skip the check; that is
+/// how a backend on an internal network is allowed. A host that does not
+/// resolve is refused too.
+///
+/// This is the early check, answered with a `400` before anything is
+/// recorded. The HTTP client resolves a name again when it connects, so a DNS
+/// server could change its answer in between; the client's
+/// [`UpstreamResolver`] repeats the check on the addresses it actually
+/// connects to, which closes that gap. An IP literal is never resolved again.
+pub async fn is_safe_upstream_host(host: &str, exempt: &HostMatcher) -> bool {
+    if exempt.is_allowed(host) {
+        return true;
+    }
+    let Some((name, port)) = split_host_port(host) else {
+        return false;
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    match public_addrs(name, port.unwrap_or(443)).await {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Refusing upstream host {:?}: {}", host, e);
+            false
+        }
+    }
+}
+
+/// Resolves `name` for a connection to `port`, failing unless every address
+/// is publicly routable (see [`is_public_ip`]) and there is at least one.
+pub async fn public_addrs(name: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    let addrs = tokio::net::lookup_host((name, port))
+        .await?
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no addresses", name),
+        ));
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} resolves to a non-public address", name),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// The DNS resolver of the HTTP client that calls the backend and the
+/// webhook. It hands the client only addresses [`public_addrs`] accepts, so
+/// the addresses that were checked are the ones connected to, and a name that
+/// starts resolving to an internal address after [`is_safe_upstream_host`]
+/// let it through (DNS rebinding) fails to connect. Names matching `exempt`
+/// resolve as usual.
+#[derive(Debug, Clone)]
+pub struct UpstreamResolver {
+    exempt: Arc<HostMatcher>,
+}
+
+impl UpstreamResolver {
+    /// `exempt` should ignore ports, as `BackendConfig::private_hosts` does:
+    /// the resolver only sees names.
+    pub fn new(exempt: HostMatcher) -> Self {
+        UpstreamResolver {
+            exempt: Arc::new(exempt),
+        }
+    }
+}
+
+impl Resolve for UpstreamResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let exempt = self.exempt.clone();
+        Box::pin(async move {
+            // The client fills in the port of the URL.
+            let addrs = if exempt.is_allowed(name.as_str()) {
+                tokio::net::lookup_host((name.as_str(), 0))
+                    .await?
+                    .collect::<Vec<_>>()
+            } else {
+                public_addrs(name.as_str(), 0).await.inspect_err(|e| {
+                    log::warn!("Refusing to connect upstream: {}", e);
+                })?
+            };
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is a publicly routable unicast address. An IPv6 address that
+/// carries an IPv4 one (mapped, IPv4-compatible, NAT64 or 6to4) is judged by
+/// the IPv4 address, which is where the traffic ends up.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                // 100.64.0.0/10, carrier-grade NAT.
+                || (a == 100 && (b & 0xc0) == 64)
+                // 198.18.0.0/15, benchmarking.
+                || (a == 198 && (b & 0xfe) == 18)
+                // 240.0.0.0/4, reserved.
+                || a >= 240
+                // 0.0.0.0/8, "this network".
+                || a == 0)
+        }
+        IpAddr::V6(v6) => match embedded_ipv4(v6) {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let [a, b, ..] = v6.segments();
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local()
+                    // 2001:db8::/32, documentation.
+                    || (a == 0x2001 && b == 0x0db8))
+            }
+        },
+    }
+}
+
+// The IPv4 address an IPv6 one stands for: `::ffff:a.b.c.d` (mapped),
+// `::a.b.c.d` (the deprecated IPv4-compatible form), `64:ff9b::a.b.c.d`
+// (NAT64) and `2002:aabb:ccdd::/48` (6to4). `::` and `::1` are left alone.
+fn embedded_ipv4(v6: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = v6.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let octets = v6.octets();
+    let last = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match v6.segments() {
+        [0, 0, 0, 0, 0, 0, ..] if !v6.is_loopback() && !v6.is_unspecified() => Some(last),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(last),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Splits `host[:port]` into a normalized (lowercased, no trailing dot) host
+/// and an optional port. Bracketed IPv6 literals keep their brackets.
+pub(crate) fn split_host_port(value: &str) -> Option<(String, Option<u16>)> {
+    let value = value.trim();
+    let (name, port) = if value.starts_with('[') {
+        let end = value.find(']')?;
+        let (name, rest) = value.split_at(end + 1);
+        match rest.strip_prefix(':') {
+            Some(port) => (name, Some(port)),
+            None if rest.is_empty() => (name, None),
+            None => return None,
+        }
+    } else {
+        match value.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (value, None),
+        }
+    };
+    let port = match port {
+        Some(port) => Some(port.parse::<u16>().ok()?),
+        None => None,
+    };
+    let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, port))
+}
@@ -0,0 +1,262 @@
+use actix_web::body::{self, BoxBody};
+use actix_web::http::StatusCode;
+use actix_web::http::header::HeaderMap;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use lru::LruCache;
+use ring::digest::{SHA256, digest};
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{ApiError, HandlerResult, SafeErrorBody};
+
+/// The request header that carries the client's idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Set on a response that was replayed from the cache instead of processed.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+/// Longer keys are refused with a `400`.
+pub const MAX_KEY_LEN: usize = 255;
+
+/// At most this many keys are remembered. Past it, the oldest key is
+/// forgotten to make room, live or not, so a flood of fresh keys costs memory
+/// only up to this bound.
+pub const MAX_KEYS: usize = 10_000;
+
+/// How long idempotency keys are remembered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IdempotencyConfig {
+    pub ttl_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig { ttl_secs: 86_400 }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// A response as it was first sent, to be sent again for a repeated key.
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl CachedResponse {
+    fn replay(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status);
+        for (name, value) in &self.headers {
+            res.append_header((name.clone(), value.clone()));
+        }
+        res.insert_header((IDEMPOTENT_REPLAYED, "true"));
+        // It was an `ApiError` rendering (or a success) the first time too.
+        res.extensions_mut().insert(SafeErrorBody);
+        res.body(self.body.clone())
+    }
+}
+
+#[derive(Debug)]
+enum Outcome {
+    InFlight,
+    Done(CachedResponse),
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: Fingerprint,
+    outcome: Outcome,
+    expires: Instant,
+}
+
+/// What [`IdempotencyStore::begin`] decided about a request.
+#[derive(Debug)]
+pub enum Begin {
+    /// The key is new: process the request and hand the outcome to
+    /// [`Reservation::complete`].
+    New(Reservation),
+    /// The key was seen with the same request; send this response again.
+    Replay(HttpResponse),
+}
+
+/// Remembers the outcome of requests by their `Idempotency-Key`, so a client
+/// that retries after a timeout gets the original answer instead of a second
+/// signup (or a `409` for its own first one).
+///
+/// A key is bound to the [`Fingerprint`] of the request it first came with. Reusing
+/// it for a different request is refused with a `422`, and reusing it while the
+/// first request is still being processed with a `409`. Only final outcomes are
+/// remembered: a `5xx` forgets the key again, so the retry is processed afresh.
+///
+/// Keys are global rather than per client, so they must be unguessable (a
+/// UUID, say); a replay only ever returns what the original request got back.
+/// At most [`MAX_KEYS`] are remembered, oldest first out. Clones share the
+/// same table.
+#[derive(Debug, Clone)]
+pub struct IdempotencyStore {
+    config: IdempotencyConfig,
+    entries: Arc<Mutex<LruCache<String, Entry>>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(config: IdempotencyConfig) -> Self {
+        let capacity = NonZeroUsize::new(MAX_KEYS).expect("a non-zero capacity");
+        IdempotencyStore {
+            config,
+            entries: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// How many keys are remembered right now, expired ones included.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn config(&self) -> &IdempotencyConfig {
+        &self.config
+    }
+
+    /// Looks `key` up for a request with the given `fingerprint`, reserving it
+    /// if it is new.
+    ///
+    /// Lookups don't refresh a key, so the table stays in the order keys were
+    /// reserved, which is also the order they expire in.
+    pub fn begin(&self, key: &str, fingerprint: Fingerprint) -> Result<Begin, ApiError> {
+        let now = Instant::now();
+        let mut entries = self.lock();
+        match entries.peek(key) {
+            Some(entry) if entry.expires > now => {
+                if entry.fingerprint != fingerprint {
+                    return Err(ApiError::IdempotencyKeyReused {
+                        key: key.to_string(),
+                    });
+                }
+                match &entry.outcome {
+                    Outcome::InFlight => Err(ApiError::IdempotencyKeyInUse {
+                        key: key.to_string(),
+                    }),
+                    Outcome::Done(response) => Ok(Begin::Replay(response.replay())),
+                }
+            }
+            _ => {
+                // Replaces an expired entry, or else evicts the oldest once
+                // the table is full.
+                entries.put(
+                    key.to_string(),
+                    Entry {
+                        fingerprint,
+                        outcome: Outcome::InFlight,
+                        expires: now + self.config.ttl(),
+                    },
+                );
+                Ok(Begin::New(Reservation {
+                    store: self.clone(),
+                    key: Some(key.to_string()),
+                }))
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A key reserved by [`IdempotencyStore::begin`]. Dropping it without calling
+/// [`Reservation::complete`] (e.g. because the client went away) releases the
+/// key again.
+#[derive(Debug)]
+pub struct Reservation {
+    store: IdempotencyStore,
+    key: Option<String>,
+}
+
+impl Reservation {
+    /// Records the outcome of the request and passes it on. Server errors are
+    /// not recorded, so the key can be retried.
+    pub async fn complete(mut self, outcome: HandlerResult) -> HandlerResult {
+        // An error is cached as rendered, but returned as the error itself so
+        // the middleware still sees it. Every early return drops `self`, which
+        // releases the key.
+        let (response, error) = match outcome {
+            Ok(response) => (response, None),
+            Err(e) if e.status_code().is_server_error() => return Err(e),
+            Err(e) => (e.error_response(), Some(e)),
+        };
+        let status = response.status();
+        let headers = response.headers().clone();
+        let (response, body) = response.into_parts();
+        let body = body::to_bytes(body).await.map_err(|e| ApiError::Internal {
+            reason: format!("failed to read the response to cache: {}", e),
+        })?;
+
+        if let Some(key) = self.key.take()
+            && let Some(entry) = self.store.lock().peek_mut(&key)
+        {
+            entry.outcome = Outcome::Done(CachedResponse {
+                status,
+                headers,
+                body: body.clone(),
+            });
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(response.set_body(BoxBody::new(body))),
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.lock().pop(&key);
+        }
+    }
+}
+
+/// The request's `Idempotency-Key`, if it sent one. A key must be 1 to
+/// [`MAX_KEY_LEN`] visible ASCII characters.
+pub fn idempotency_key(req: &HttpRequest) -> Result<Option<&str>, ApiError> {
+    let mut values = req.headers().get_all(IDEMPOTENCY_KEY);
+    let Some(value) = values.next() else {
+        return Ok(None);
+    };
+    if values.next().is_some() {
+        return Err(ApiError::BadRequest {
+            reason: "more than one Idempotency-Key header".to_string(),
+        });
+    }
+    let key = value
+        .to_str()
+        .ok()
+        .filter(|k| (1..=MAX_KEY_LEN).contains(&k.len()))
+        .filter(|k| k.bytes().all(|b| b.is_ascii_graphic()))
+        .ok_or_else(|| ApiError::BadRequest {
+            reason: "malformed Idempotency-Key header".to_string(),
+        })?;
+    Ok(Some(key))
+}
+
+/// The SHA-256 of the parts of a request that decide its outcome, to tell a
+/// genuine retry from a reused key. Unlike a 64-bit hash, two different
+/// requests can't be made to collide, and the email itself isn't kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint([u8; 32]);
+
+/// The [`Fingerprint`] of a signup for `email`.
+pub fn fingerprint(email: &str) -> Fingerprint {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(digest(&SHA256, email.as_bytes()).as_ref());
+    Fingerprint(hash)
+}
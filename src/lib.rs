@@ -0,0 +1,39 @@
+//! Building blocks for the uncaught exception lesson.
+//!
+//! The binary in `main.rs` wires these into an actix-web server that exposes a
+//! vulnerable and a secure version of the same endpoint.
+
+pub mod admin;
+pub mod audit;
+pub mod backend;
+pub mod batch;
+pub mod circuit_breaker;
+pub mod cli;
+pub mod client_info;
+pub mod clock;
+pub mod config;
+pub mod debug;
+pub mod error;
+pub mod events;
+pub mod handlers;
+pub mod health;
+pub mod host;
+pub mod idempotency;
+pub mod logging;
+pub mod messages;
+pub mod metrics;
+pub mod middleware;
+pub mod openapi;
+pub mod pii;
+pub mod pinning;
+pub mod response_cache;
+pub mod routes;
+pub mod scrubber;
+pub mod secrets;
+pub mod server;
+pub mod service;
+pub mod startup;
+pub mod store;
+pub mod validation;
+pub mod version;
+pub mod webhook;
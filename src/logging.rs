@@ -0,0 +1,88 @@
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+use crate::scrubber::ScrubbingWriter;
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per line, for log ingestion.
+    #[default]
+    Json,
+    /// Human-readable lines, for local development.
+    Text,
+}
+
+impl LogFormat {
+    /// Reads `LOG_FORMAT`. Logging starts before the configuration is loaded
+    /// (so configuration errors can be logged), hence an environment variable
+    /// rather than a config file setting.
+    pub fn from_env() -> Self {
+        std::env::var("LOG_FORMAT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "text" => Ok(LogFormat::Text),
+            _ => Err(format!("expected \"json\" or \"text\", got {:?}", s)),
+        }
+    }
+}
+
+/// The filter used when neither `--log-level` nor `RUST_LOG` is set.
+pub const DEFAULT_FILTER: &str = "info";
+
+/// The filter directives to log with: `cli` (the `--log-level` flag) wins
+/// over `rust_log` (the `RUST_LOG` variable), and [`DEFAULT_FILTER`] applies
+/// when neither is set. Blank values count as unset.
+///
+/// Both take the `RUST_LOG` syntax, e.g. `debug` or `info,actix_web=warn`.
+pub fn resolve_filter<'a>(cli: Option<&'a str>, rust_log: Option<&'a str>) -> &'a str {
+    [cli, rust_log]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|filter| !filter.is_empty())
+        .unwrap_or(DEFAULT_FILTER)
+}
+
+/// Installs the global `tracing` subscriber, filtered by `filter` (see
+/// [`resolve_filter`]). An invalid filter falls back to [`DEFAULT_FILTER`],
+/// with a warning once logging is up.
+///
+/// Records from the `log` crate (ours and actix's) are forwarded to it as
+/// events at the same level, inside whatever request span is current. Lines
+/// go through the scrubber installed later, if any (see `Scrubber::install`).
+pub fn init(format: LogFormat, filter: &str) {
+    let (env_filter, invalid) = match EnvFilter::try_new(filter) {
+        Ok(env_filter) => (env_filter, None),
+        Err(e) => (EnvFilter::new(DEFAULT_FILTER), Some(e)),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(env_filter)
+        .with_writer(ScrubbingWriter::stdout);
+    match format {
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_span_list(false)
+            .init(),
+        LogFormat::Text => builder.init(),
+    }
+    if let Some(e) = invalid {
+        log::warn!(
+            "Invalid log filter {:?} ({}); using {:?}",
+            filter,
+            e,
+            DEFAULT_FILTER
+        );
+    }
+}
@@ -1,185 +1,245 @@
-use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, ResponseError, web};
-use serde::Deserialize;
-use std::fmt;
-
-// A custom error type to demonstrate how error responses can leak information.
-#[derive(Debug)]
-struct ApiError {
-    message: String,
-}
+use actix_web::middleware::{Compress, Condition};
+use actix_web::{App, HttpServer, web};
+use clap::Parser;
+use std::net::{SocketAddr, TcpListener};
+use uncaught_exception::audit::AuditLogger;
+use uncaught_exception::cli::Cli;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::{production_mode, set_production_mode};
+use uncaught_exception::handlers::{json_config, query_config};
+use uncaught_exception::logging::{self, LogFormat};
+use uncaught_exception::metrics::Metrics;
+use uncaught_exception::middleware::{
+    AccessLog, CatchPanic, CompressionThreshold, CorrelationId, Cors, ErrorNegotiation,
+    HttpsRedirect, Maintenance, RateLimiter, RequestMetrics, RequestTracing, SecurityHeaders,
+    UriLengthLimit, install_panic_hook, sanitize_errors,
+};
+use uncaught_exception::routes;
+use uncaught_exception::secrets::SecretRegistry;
+use uncaught_exception::server::{apply_server_tuning, bind_or_explain, shutdown_signal};
+use uncaught_exception::service::flush_batches;
+use uncaught_exception::startup::{security_posture_summary, validate_startup_config};
 
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
-    }
-}
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    // Invalid arguments print the usage and exit with status 2.
+    let cli = Cli::parse();
+
+    // Set up logging: `--log-level` wins over `RUST_LOG`, which wins over `info`.
+    let rust_log = std::env::var("RUST_LOG").ok();
+    logging::init(
+        LogFormat::from_env(),
+        logging::resolve_filter(cli.log_level.as_deref(), rust_log.as_deref()),
+    );
+
+    // Load the configuration from `--config`/`CONFIG_FILE` (if set) and the
+    // environment, with the flags having the last word. There is deliberately
+    // no default API key: refuse to start without one.
+    let state = match AppState::from_cli(&cli) {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    log::info!("Security posture: {}", security_posture_summary(&state));
 
-// Implementing ResponseError allows actix-web to convert our custom error into an HTTP response.
-// In the vulnerable case, we will deliberately include sensitive data in the response.
-impl ResponseError for ApiError {
-    fn error_response(&self) -> HttpResponse {
-        // This is where the information leak happens!
-        // The error message, containing the sensitive URL, is sent to the client.
-        HttpResponse::InternalServerError().body(self.message.clone())
+    // Refuse the lesson's defaults, and the leaky build in production.
+    if let Err(e) = validate_startup_config(&state, cli.production) {
+        log::error!("Refusing to start: {}", e);
+        std::process::exit(1);
     }
-}
 
-// Represents the application's configuration, including the sensitive API key.
-#[derive(Clone)]
-struct AppState {
-    api_key: String,
-    // A whitelist of allowed hostnames for the secure version.
-    allowed_hosts: Vec<String>,
-}
+    // From here on, log lines go through the configured scrubber.
+    if let Some(scrubber) = &state.scrubber {
+        scrubber.clone().install();
+    }
 
-// Struct to deserialize query parameters like "?email=test@example.com"
-#[derive(Deserialize)]
-struct WaitlistParams {
-    email: String,
-}
+    state.error_format.set_global();
+    set_production_mode(state.production_mode);
+    state.localizer.set_global();
+
+    if routes::INSECURE_DEMO && production_mode() {
+        log::warn!(
+            "Built with the insecure-demo feature; production mode keeps /vulnerable/waitlist's \
+             error bodies generic. Never deploy this build."
+        );
+    } else if routes::INSECURE_DEMO {
+        log::warn!(
+            "Built with the insecure-demo feature: /vulnerable/waitlist leaks the API key in its \
+             error bodies. Never deploy this build."
+        );
+    }
 
-/// # Vulnerable Handler
-/// This handler extracts the `Host` header and uses it to construct a backend API URL.
-/// It uses `.unwrap()` to parse the URL, which will cause a `panic` if the host is invalid,
-/// crashing the thread and causing a Denial of Service.
-///
-/// If the URL parsing itself throws a recoverable error, our custom `ApiError`
-/// will leak the constructed URL, including the API key.
-async fn vulnerable_waitlist(
-    req: HttpRequest,
-    query: web::Query<WaitlistParams>,
-    state: web::Data<AppState>,
-) -> Result<impl Responder, ApiError> {
-    // 1. Extract the host header from the user's request.
-    let host = req
-        .headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or(""); // Use empty string if host is not present, similar to the scenario.
-
-    // 2. Construct the backend URL with the sensitive API key.
-    let backend_url_str = format!(
-        "https://{}/v1/waitlist?api_key={}&email={}",
-        host, &state.api_key, &query.email
-    );
-    log::info!(
-        "Vulnerable handler attempting to use URL: {}",
-        backend_url_str
-    );
+    // Register the keys so they are scrubbed from every error body, and from
+    // panic messages before they are logged.
+    SecretRegistry::global().register(state.api_key.current().expose());
+    if let Some(token) = &state.auth.admin_token {
+        SecretRegistry::global().register(token.clone());
+    }
+    if let Some(webhook) = &state.webhook {
+        SecretRegistry::global().register(webhook.config().secret.expose());
+    }
+    install_panic_hook();
 
-    // 3. Attempt to parse the URL. This is where the error occurs.
-    // The original JS example had a library that threw an error which Express then
-    // printed to the response. We simulate this by returning our custom ApiError,
-    // whose ResponseError implementation leaks the details.
-    match reqwest::Url::parse(&backend_url_str) {
-        Ok(_) => {
-            // In a real app, we would make the request here.
-            // For this demo, we assume success if parsing works.
-            Ok(HttpResponse::Ok()
-                .body("Thank you for your interest. You have been added to the waitlist."))
-        }
+    // Record security-relevant events (rejected hosts, redactions, failed
+    // admin logins) to the configured audit sink.
+    match AuditLogger::from_config(&state.audit) {
+        Ok(Some(logger)) => logger.install(),
+        Ok(None) => log::info!("Audit logging is disabled"),
         Err(e) => {
-            // VULNERABILITY: The error returned to the user includes the full URL
-            // with the API key, and the internal error message.
-            let error_message = format!(
-                "Failed to construct backend request. URL: '{}', Error: {}",
-                backend_url_str, e
-            );
-            Err(ApiError {
-                message: error_message,
-            })
+            log::error!("Failed to open the audit log: {}", e);
+            std::process::exit(1);
         }
     }
-}
 
-/// # Secure Handler
-/// This handler follows best practices to prevent the vulnerability.
-async fn secure_waitlist(
-    req: HttpRequest,
-    query: web::Query<WaitlistParams>,
-    state: web::Data<AppState>,
-) -> impl Responder {
-    // 1. Extract the host header.
-    let host_header = req.headers().get("host").and_then(|h| h.to_str().ok());
-
-    // 2. MITIGATION: Perform input validation.
-    // Check if the host is present and is in our whitelist.
-    match host_header {
-        Some(host) if state.allowed_hosts.contains(&host.to_string()) => {
-            // Host is valid, proceed.
-        }
-        _ => {
-            log::warn!(
-                "Rejected request with invalid or missing host header: {:?}",
-                host_header
-            );
-            return HttpResponse::BadRequest().body("Invalid 'Host' header provided.");
+    // Create shared application state
+    let app_state = web::Data::new(state);
+    let metrics_registry = match Metrics::new().and_then(|metrics| {
+        metrics.track_batcher(&app_state.batcher)?;
+        Ok(metrics)
+    }) {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            log::error!("Failed to set up metrics: {}", e);
+            std::process::exit(1);
         }
     };
 
-    // We can safely unwrap here because we've already validated the host.
-    let host = host_header.unwrap();
+    // Built once so every worker shares the same buckets.
+    let rate_limiter = RateLimiter::new(app_state.rate_limit.clone());
+
+    // Batches are submitted from here rather than from a worker, which is
+    // stopped along with the server, so the signups still queued then can be
+    // submitted before the process exits.
+    let batcher = app_state.batcher.clone();
+    let batch_flusher = batcher
+        .take_receiver()
+        .filter(|_| batcher.config().enabled)
+        .map(|receiver| {
+            actix_web::rt::spawn(flush_batches(app_state.clone().into_inner(), receiver))
+        });
+
+    let shutdown_timeout = app_state.server.shutdown_timeout();
+    let server_config = app_state.server.clone();
+    let (bind, port) = (app_state.server.bind, app_state.server.port);
+    let shutdown_metrics = metrics_registry.clone();
+
+    // Load the certificate up front, so a bad path fails at startup.
+    let tls = match &app_state.server.tls {
+        Some(tls) => match tls.load() {
+            Ok(rustls_config) => Some((tls.clone(), rustls_config)),
+            Err(e) => {
+                log::error!("Invalid configuration: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-    // 3. Construct the backend URL.
-    let backend_url_str = format!(
-        "https://{}/v1/waitlist?api_key={}&email={}",
-        host, &state.api_key, &query.email
-    );
-    log::info!("Secure handler attempting to use URL: {}", backend_url_str);
-
-    // 4. MITIGATION: Handle the `Result` gracefully instead of using `unwrap()`.
-    // We use a `match` statement to handle both success and failure cases.
-    match reqwest::Url::parse(&backend_url_str) {
-        Ok(_) => {
-            // The URL is valid. We would make the backend `reqwest` call here.
-            HttpResponse::Ok().body(
-                "Thank you for your interest. We will notify you when we are ready to launch.",
-            )
-        }
-        Err(e) => {
-            // Log the detailed error for debugging purposes on the server-side only.
-            log::error!(
-                "Internal error during URL parsing: {}. URL was: {}",
-                e,
-                backend_url_str
-            );
+    let mut server = HttpServer::new(move || {
+        App::new()
+            // Cancel requests that run past their deadline with a 503.
+            .wrap(app_state.routes.deadline(&app_state.server))
+            // Turn handler panics into a 500 instead of a dropped connection.
+            .wrap(CatchPanic)
+            // During maintenance, answer everything but health and admin with a 503.
+            .wrap(Maintenance::new(app_state.maintenance.clone()))
+            // With FORCE_HTTPS, send plain-HTTP requests (but not the probes) to HTTPS.
+            .wrap(Condition::new(
+                app_state.server.force_https,
+                HttpsRedirect::new(&app_state.server),
+            ))
+            // Shed over-limit clients before they reach any handler.
+            .wrap(rate_limiter.clone())
+            .wrap(RequestMetrics::new(metrics_registry.clone()))
+            // Answer preflights before the rate limiter or any route sees them.
+            .wrap(Cors::new(&app_state.cors, app_state.host_matcher.clone()))
+            .wrap(AccessLog::new(&app_state.access_log))
+            // Refuse oversized request targets before anything parses or logs
+            // them, inside the layers that format the 414 and add its headers.
+            .wrap(UriLengthLimit::new(app_state.server.max_uri_bytes))
+            .wrap(SecurityHeaders::new(&app_state.security_headers))
+            // Give every error response not built from `ApiError` a generic body.
+            .wrap(sanitize_errors())
+            // Render errors as JSON, text or HTML depending on `Accept`.
+            .wrap(ErrorNegotiation)
+            // Compress large successful responses; errors and small bodies stay as they are.
+            .wrap(CompressionThreshold::new(&app_state.compression))
+            .wrap(Condition::new(
+                app_state.compression.enabled,
+                Compress::default(),
+            ))
+            .wrap(RequestTracing)
+            // Outermost: tag every request, log line and error body with an ID.
+            .wrap(CorrelationId)
+            .app_data(app_state.clone())
+            .app_data(web::Data::new(metrics_registry.clone()))
+            // Cap request bodies, as JSON and for any other extractor.
+            .app_data(json_config(app_state.server.max_body_bytes))
+            .app_data(query_config())
+            .app_data(web::PayloadConfig::new(app_state.server.max_body_bytes))
+            .configure(|cfg| routes::configure_with(cfg, &app_state.auth, &app_state.routes))
+    })
+    // Signals are handled below instead, so the drain can be logged.
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout.as_secs());
+    // Workers, keep-alive and the client timeouts that free slow connections.
+    server = apply_server_tuning(server, &server_config);
+
+    if !tls.as_ref().is_some_and(|(tls, _)| tls.disable_plain_http) {
+        let addr = SocketAddr::new(bind, port);
+        log::info!("Starting server at http://{}", addr);
+        server = server.listen(listen_or_exit(addr, "--port or PORT"))?;
+    }
+    if let Some((tls, rustls_config)) = tls {
+        // Remember to allow `<host>:<port>` for this port in `allowed_hosts`
+        // (or set `ignore_host_port`): browsers send it in the `Host` header.
+        let addr = SocketAddr::new(bind, tls.port);
+        log::info!("Starting server at https://{}", addr);
+        server = server.listen_rustls_0_23(listen_or_exit(addr, "TLS_PORT"), rustls_config)?;
+    }
+    let server = server.run();
+
+    // On SIGTERM/SIGINT, stop accepting connections and give in-flight
+    // requests up to `shutdown_timeout` to finish.
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        let signal = shutdown_signal().await;
+        log::info!(
+            "Received {}; draining {} in-flight request(s) for up to {:?}",
+            signal,
+            shutdown_metrics.in_flight(),
+            shutdown_timeout
+        );
+        handle.stop(true).await;
+    });
 
-            // Return a generic error message to the user, hiding internal details.
-            HttpResponse::InternalServerError()
-                .body("Oops! Something went wrong. Please try again later.")
+    server.await?;
+    log::info!("Server stopped");
+    if let Some(flusher) = batch_flusher {
+        batcher.close();
+        if actix_web::rt::time::timeout(shutdown_timeout, flusher)
+            .await
+            .is_err()
+        {
+            log::warn!(
+                "Gave up on the queued signups after {:?}; they stay recorded",
+                shutdown_timeout
+            );
         }
     }
+    Ok(())
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    // Set up logging. This is marked unsafe because modifying environment variables
-    // can cause race conditions in highly concurrent code. Since we do it here,
-    // at the start of a single-threaded main function, it is safe.
-    unsafe {
-        std::env::set_var("RUST_LOG", "info");
+/// Opens a listener, or logs why it could not and exits with the error's status.
+fn listen_or_exit(addr: SocketAddr, setting: &'static str) -> TcpListener {
+    match bind_or_explain(addr, setting) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Cannot start: {}", e);
+            std::process::exit(e.exit_code());
+        }
     }
-    env_logger::init();
-
-    // Create shared application state
-    let app_state = web::Data::new(AppState {
-        api_key: "88665751-288d-4175-852f-6519d79fdf1f".to_string(),
-        allowed_hosts: vec![
-            "my-app.com:8080".to_string(),
-            "prod.my-app.com:8080".to_string(),
-            "127.0.0.1:8080".to_string(),
-        ],
-    });
-
-    log::info!("Starting server at http://127.0.0.1:8080");
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(app_state.clone())
-            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist))
-            .route("/secure/waitlist", web::get().to(secure_waitlist))
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
 }
@@ -0,0 +1,195 @@
+//! The texts clients see, so they can be reworded or translated without
+//! recompiling.
+
+use actix_web::HttpMessage;
+use actix_web::http::header::{AcceptLanguage, Header, Preference, Quality};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+static GLOBAL: LazyLock<RwLock<Arc<Localizer>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Localizer::default())));
+
+tokio::task_local! {
+    // The languages of the request currently being polled, most preferred
+    // first; set by the `ErrorNegotiation` middleware.
+    pub(crate) static PREFERRED: Arc<[String]>;
+}
+
+pub const DEFAULT_SIGNUP_SUCCESS: &str =
+    "Thank you for your interest. We will notify you when we are ready to launch.";
+pub const DEFAULT_VULNERABLE_SIGNUP_SUCCESS: &str =
+    "Thank you for your interest. You have been added to the waitlist.";
+pub const DEFAULT_GENERIC_ERROR: &str = "Oops! Something went wrong. Please try again later.";
+
+/// Response texts in one language, from the `[messages]` table of the config
+/// file (or one of its `[messages.translations.<lang>]` tables).
+///
+/// The success texts are picked by the handlers through `AppState::localizer`.
+/// The generic error text is what every internal failure renders as; since
+/// `ApiError` bodies are built without access to the state, it comes from the
+/// process-wide [`Localizer`] (see [`Localizer::set_global`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Messages {
+    /// The body of a successful signup through the secure handlers.
+    pub signup_success: String,
+    /// The body of a successful signup through `/vulnerable/waitlist`.
+    pub vulnerable_signup_success: String,
+    /// The message of every `500` (and unknown `5xx`) error body.
+    pub generic_error: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages {
+            signup_success: DEFAULT_SIGNUP_SUCCESS.to_string(),
+            vulnerable_signup_success: DEFAULT_VULNERABLE_SIGNUP_SUCCESS.to_string(),
+            generic_error: DEFAULT_GENERIC_ERROR.to_string(),
+        }
+    }
+}
+
+impl Messages {
+    /// The built-in translation for `lang` (a lowercase primary language
+    /// subtag), if there is one. English is the default instead.
+    pub fn builtin(lang: &str) -> Option<Messages> {
+        match lang {
+            "es" => Some(Messages {
+                signup_success: "Gracias por tu interés. Te avisaremos cuando estemos listos \
+                                 para el lanzamiento."
+                    .to_string(),
+                vulnerable_signup_success:
+                    "Gracias por tu interés. Te hemos añadido a la lista de espera.".to_string(),
+                generic_error: "¡Vaya! Algo salió mal. Por favor, inténtalo de nuevo más tarde."
+                    .to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The messages for the request currently being handled: in its
+    /// preferred language when called within the `ErrorNegotiation`
+    /// middleware, otherwise the global default.
+    pub fn current() -> Messages {
+        let localizer = Localizer::global();
+        PREFERRED
+            .try_with(|preferred| localizer.negotiate(preferred).clone())
+            .unwrap_or_else(|_| localizer.fallback().clone())
+    }
+}
+
+/// Picks the [`Messages`] for a request from its `Accept-Language` header.
+///
+/// Languages are tried in order of their quality values (`q=0` means "not
+/// this one"); a tag such as `es-MX` matches a bundle for `es-mx` or else
+/// `es`. English, `*` and languages without a bundle get the default
+/// messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Localizer {
+    /// English, and the fallback for every language without a bundle.
+    default: Messages,
+    /// Keyed by lowercase language tag, e.g. `es` or `pt-br`.
+    translations: BTreeMap<String, Messages>,
+}
+
+impl Default for Localizer {
+    fn default() -> Self {
+        Localizer::new(Messages::default())
+    }
+}
+
+impl Localizer {
+    /// A localizer with `default` as its English messages and the built-in
+    /// translations (see [`Messages::builtin`]).
+    pub fn new(default: Messages) -> Self {
+        let translations = ["es"]
+            .into_iter()
+            .filter_map(|lang| Some((lang.to_string(), Messages::builtin(lang)?)))
+            .collect();
+        Localizer {
+            default,
+            translations,
+        }
+    }
+
+    /// Adds or replaces the bundle for `lang`.
+    pub fn translation(mut self, lang: &str, messages: Messages) -> Self {
+        self.translations
+            .insert(lang.to_ascii_lowercase(), messages);
+        self
+    }
+
+    /// The English messages.
+    pub fn fallback(&self) -> &Messages {
+        &self.default
+    }
+
+    /// The bundle for one language tag, without falling back to English.
+    pub fn get(&self, tag: &str) -> Option<&Messages> {
+        let tag = tag.to_ascii_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default();
+        self.translations
+            .get(&tag)
+            .or_else(|| self.translations.get(primary))
+    }
+
+    /// The bundle for the first of `preferred` that has one, up to the first
+    /// English or `*` entry.
+    pub fn negotiate(&self, preferred: &[String]) -> &Messages {
+        for tag in preferred {
+            let primary = tag.split('-').next().unwrap_or_default();
+            if tag == "*" || primary.eq_ignore_ascii_case("en") {
+                break;
+            }
+            if let Some(messages) = self.get(tag) {
+                return messages;
+            }
+        }
+        &self.default
+    }
+
+    /// The messages for `req`, according to its `Accept-Language` header.
+    pub fn for_request(&self, req: &impl HttpMessage) -> &Messages {
+        self.negotiate(&preferred_languages(req))
+    }
+
+    /// The localizer used for `ApiError` bodies.
+    pub fn global() -> Arc<Localizer> {
+        GLOBAL
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Makes this the localizer used for `ApiError` bodies. Call it once at
+    /// startup, like `ErrorFormat::set_global`.
+    pub fn set_global(&self) {
+        *GLOBAL
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(self.clone());
+    }
+}
+
+/// The languages in `req`'s `Accept-Language` header, most preferred first,
+/// without those it refuses (`q=0`). A missing or unparsable header gives an
+/// empty list.
+pub fn preferred_languages(req: &impl HttpMessage) -> Vec<String> {
+    let Ok(accept) = AcceptLanguage::parse(req) else {
+        return Vec::new();
+    };
+    let acceptable = AcceptLanguage(
+        accept
+            .iter()
+            .filter(|item| item.quality > Quality::ZERO)
+            .cloned()
+            .collect(),
+    );
+    acceptable
+        .ranked()
+        .into_iter()
+        .map(|preference| match preference {
+            Preference::Any => "*".to_string(),
+            Preference::Specific(tag) => tag.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,196 @@
+use actix_web::{HttpResponse, web};
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::batch::Batcher;
+use crate::error::{ApiError, HandlerResult};
+use crate::secrets::SecretRegistry;
+
+/// The Prometheus metrics of the server, shared by the `RequestMetrics`
+/// middleware and the `/metrics` handler. Cloning is cheap and shares the
+/// underlying metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    in_flight: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "HTTP requests by route, method and status.",
+            ),
+            &["route", "method", "status"],
+        )?;
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and method.",
+            ),
+            &["route", "method"],
+        )?;
+        let in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "HTTP requests currently being handled.",
+        )?;
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(in_flight.clone()))?;
+        registry.register(Box::new(latency.clone()))?;
+        registry.register(Box::new(RedactionCollector::new(SecretRegistry::global())?))?;
+        Ok(Metrics {
+            registry,
+            requests,
+            latency,
+            in_flight,
+        })
+    }
+
+    /// Exports `batched_signups_failed_total` from `batcher`'s count of the
+    /// queued signups lost to failed batches. Call it once per batcher.
+    pub fn track_batcher(&self, batcher: &Batcher) -> Result<(), prometheus::Error> {
+        self.registry
+            .register(Box::new(BatchFailureCollector::new(batcher.clone())?))
+    }
+
+    /// Records one finished request. `route` should be the matched pattern
+    /// (e.g. `/secure/waitlist`), never the raw path, to bound label cardinality.
+    pub fn observe(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        self.requests
+            .with_label_values(&[route, method, &status.to_string()])
+            .inc();
+        self.latency
+            .with_label_values(&[route, method])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped, which
+    /// also covers requests whose connection goes away mid-handler.
+    pub fn track_in_flight(&self) -> InFlightGuard {
+        self.in_flight.inc();
+        InFlightGuard {
+            gauge: self.in_flight.clone(),
+        }
+    }
+
+    /// How many requests are being handled right now.
+    pub fn in_flight(&self) -> i64 {
+        self.in_flight.get()
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// Decrements `http_requests_in_flight` when dropped. See
+/// [`Metrics::track_in_flight`].
+pub struct InFlightGuard {
+    gauge: IntGauge,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
+/// # Metrics Endpoint
+/// Exposes the server's metrics for Prometheus to scrape.
+pub async fn metrics(metrics: web::Data<Metrics>) -> HandlerResult {
+    let body = metrics.render().map_err(|e| {
+        log::error!("Failed to render metrics: {}", e);
+        ApiError::Internal {
+            reason: e.to_string(),
+        }
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(body))
+}
+
+// Exports `secret_redactions_total` from a `SecretRegistry`'s own count. The
+// registry cannot depend on the metrics, so the counter catches up with it
+// whenever metrics are gathered.
+struct RedactionCollector {
+    source: &'static SecretRegistry,
+    counter: Mutex<IntCounter>,
+    desc: Vec<Desc>,
+}
+
+impl RedactionCollector {
+    fn new(source: &'static SecretRegistry) -> Result<Self, prometheus::Error> {
+        let counter = IntCounter::new(
+            "secret_redactions_total",
+            "Secrets scrubbed from responses; each one is a near-miss leak.",
+        )?;
+        let desc = counter.desc().into_iter().cloned().collect();
+        Ok(RedactionCollector {
+            source,
+            counter: Mutex::new(counter),
+            desc,
+        })
+    }
+}
+
+impl Collector for RedactionCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.desc.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let counter = self.counter.lock().unwrap_or_else(|e| e.into_inner());
+        let total = self.source.redaction_count();
+        counter.inc_by(total.saturating_sub(counter.get()));
+        counter.collect()
+    }
+}
+
+// Exports `batched_signups_failed_total` from a `Batcher`'s own count, caught
+// up with whenever metrics are gathered, like `RedactionCollector`.
+struct BatchFailureCollector {
+    source: Batcher,
+    counter: Mutex<IntCounter>,
+    desc: Vec<Desc>,
+}
+
+impl BatchFailureCollector {
+    fn new(source: Batcher) -> Result<Self, prometheus::Error> {
+        let counter = IntCounter::new(
+            "batched_signups_failed_total",
+            "Queued signups lost to a failed batch after their client got a 202.",
+        )?;
+        let desc = counter.desc().into_iter().cloned().collect();
+        Ok(BatchFailureCollector {
+            source,
+            counter: Mutex::new(counter),
+            desc,
+        })
+    }
+}
+
+impl Collector for BatchFailureCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.desc.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let counter = self.counter.lock().unwrap_or_else(|e| e.into_inner());
+        let total = self.source.failed();
+        counter.inc_by(total.saturating_sub(counter.get()));
+        counter.collect()
+    }
+}
@@ -0,0 +1,190 @@
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::HeaderMap,
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Request headers whose values are never logged, whatever the configuration.
+pub const ALWAYS_REDACTED_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+/// How much [`AccessLog`] writes per request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogVerbosity {
+    /// Nothing; `RequestTracing` still logs its `request completed` line.
+    #[default]
+    Off,
+    /// Method, path, status and latency.
+    Basic,
+    /// `Basic` plus the request headers, with sensitive ones redacted. For
+    /// local debugging.
+    Verbose,
+}
+
+impl std::str::FromStr for AccessLogVerbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(AccessLogVerbosity::Off),
+            "basic" => Ok(AccessLogVerbosity::Basic),
+            "verbose" => Ok(AccessLogVerbosity::Verbose),
+            _ => Err(format!(
+                "expected \"off\", \"basic\" or \"verbose\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AccessLogConfig {
+    pub verbosity: AccessLogVerbosity,
+    /// Headers redacted in `Verbose` mode on top of
+    /// [`ALWAYS_REDACTED_HEADERS`], e.g. `X-Api-Key`. Case-insensitive.
+    pub sensitive_headers: Vec<String>,
+}
+
+/// Writes one `access` line per request, under the `access_log` target.
+///
+/// Only the path is logged, never the query string (which carries email
+/// addresses) or the body. In `Verbose` mode the request headers are included,
+/// with the values of `Authorization`, `Cookie` and any configured sensitive
+/// header replaced by `[REDACTED]`.
+///
+/// Wrap it inside `RequestTracing`, so its lines carry the request's span.
+#[derive(Clone)]
+pub struct AccessLog {
+    verbosity: AccessLogVerbosity,
+    redacted: Rc<Vec<String>>,
+}
+
+impl AccessLog {
+    pub fn new(config: &AccessLogConfig) -> Self {
+        AccessLog {
+            verbosity: config.verbosity,
+            redacted: Rc::new(config.redacted_headers()),
+        }
+    }
+}
+
+impl AccessLogConfig {
+    /// The lowercased names of every header whose value must not be shown:
+    /// [`ALWAYS_REDACTED_HEADERS`] and `sensitive_headers`.
+    pub fn redacted_headers(&self) -> Vec<String> {
+        ALWAYS_REDACTED_HEADERS
+            .iter()
+            .map(|h| h.to_string())
+            .chain(
+                self.sensitive_headers
+                    .iter()
+                    .map(|h| h.to_ascii_lowercase()),
+            )
+            .collect()
+    }
+}
+
+// The headers as a JSON object, one entry per name (repeated values joined
+// with `, `), with sensitive values redacted.
+fn describe_headers(headers: &HeaderMap, redacted: &[String]) -> String {
+    let mut described: BTreeMap<&str, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let value = if redacted.iter().any(|r| r == name.as_str()) {
+            "[REDACTED]".into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        described
+            .entry(name.as_str())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    serde_json::to_string(&described).unwrap_or_default()
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AccessLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware {
+            service,
+            config: self.clone(),
+        }))
+    }
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: S,
+    config: AccessLog,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let verbosity = self.config.verbosity;
+        if verbosity == AccessLogVerbosity::Off {
+            return Box::pin(self.service.call(req));
+        }
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let headers = (verbosity == AccessLogVerbosity::Verbose)
+            .then(|| describe_headers(req.headers(), &self.config.redacted));
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let status = match &res {
+                Ok(res) => res.status().as_u16(),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+            match headers {
+                Some(headers) => tracing::info!(
+                    target: "access_log",
+                    method,
+                    path,
+                    status,
+                    latency_ms,
+                    headers,
+                    "access"
+                ),
+                None => tracing::info!(
+                    target: "access_log",
+                    method,
+                    path,
+                    status,
+                    latency_ms,
+                    "access"
+                ),
+            }
+            res
+        })
+    }
+}
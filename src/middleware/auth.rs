@@ -0,0 +1,147 @@
+use actix_web::{
+    Error,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{AUTHORIZATION, HeaderMap},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::rc::Rc;
+
+use crate::audit::{self, AuditEvent, AuditEventKind};
+use crate::client_info::ClientInfo;
+use crate::error::ApiError;
+
+/// Credentials for the administrative routes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthConfig {
+    /// The bearer token admin requests must present. It is unrelated to the
+    /// backend `api_key`. With no token configured, every admin request is
+    /// refused.
+    pub admin_token: Option<String>,
+}
+
+impl AuthConfig {
+    /// The check [`AdminAuth`] makes, for a handler that only needs admin
+    /// credentials for some requests, e.g. to honour an override.
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<(), ApiError> {
+        verify_bearer(
+            self.admin_token.as_deref().filter(|t| !t.is_empty()),
+            headers,
+        )
+    }
+}
+
+/// Requires `Authorization: Bearer <admin_token>` on every request it wraps.
+///
+/// Meant for a `web::scope`, so the public routes stay open. Every failure
+/// (no header, wrong scheme, wrong token) gets the same generic `401`; the
+/// specific reason only reaches the server log.
+#[derive(Clone)]
+pub struct AdminAuth {
+    token: Option<Rc<str>>,
+}
+
+impl AdminAuth {
+    pub fn new(config: &AuthConfig) -> Self {
+        AdminAuth {
+            token: config
+                .admin_token
+                .as_deref()
+                .filter(|t| !t.is_empty())
+                .map(Rc::from),
+        }
+    }
+
+    fn check(&self, req: &ServiceRequest) -> Result<(), ApiError> {
+        verify_bearer(self.token.as_deref(), req.headers())
+    }
+}
+
+fn verify_bearer(expected: Option<&str>, headers: &HeaderMap) -> Result<(), ApiError> {
+    let unauthorized = |reason: &str| ApiError::Unauthorized {
+        reason: reason.to_string(),
+    };
+    let Some(expected) = expected else {
+        return Err(unauthorized("no admin token is configured"));
+    };
+    let header = headers
+        .get(AUTHORIZATION)
+        .ok_or_else(|| unauthorized("missing Authorization header"))?;
+    // The scheme name is case-insensitive; the token itself is not.
+    let presented = match header.as_bytes().split_at_checked(7) {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case(b"bearer ") => token,
+        _ => return Err(unauthorized("Authorization header is not a bearer token")),
+    };
+    if constant_time_eq(presented, expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(unauthorized("bearer token does not match"))
+    }
+}
+
+/// Compares two byte strings in time that depends only on their lengths, not
+/// on where they first differ, so response timing cannot be used to guess the
+/// token a byte at a time.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().max(b.len());
+    let mut diff = u8::from(a.len() != b.len());
+    for i in 0..len {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= x ^ y;
+    }
+    std::hint::black_box(diff) == 0
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AdminAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AdminAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminAuthMiddleware {
+            service,
+            auth: self.clone(),
+        }))
+    }
+}
+
+pub struct AdminAuthMiddleware<S> {
+    service: S,
+    auth: AdminAuth,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Err(e) = self.auth.check(&req) {
+            log::warn!("Rejected admin request to {}: {}", req.path(), e);
+            audit::record(
+                AuditEvent::new(AuditEventKind::AuthFailed {
+                    path: req.path().to_string(),
+                })
+                .client_ip(ClientInfo::of(req.request()).ip),
+            );
+            let res = req.error_response(e);
+            return Box::pin(ready(Ok(res.map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -0,0 +1,118 @@
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::FutureExt;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+
+use crate::error::ApiError;
+use crate::middleware::RequestId;
+use crate::secrets::SecretRegistry;
+
+/// Turns a panicking handler into a generic `500 Internal Server Error`.
+///
+/// Without this, a panic unwinds through the worker and the client just sees
+/// the connection drop; enough of them and the service is effectively down.
+/// The panic payload is never sent to the client. It is logged, with every
+/// registered secret redacted and its location, by the hook that
+/// [`install_panic_hook`] sets up; this only logs which request it was.
+///
+/// The panic surfaces as an `ApiError::Internal` service error rather than a
+/// response, because the request has already been moved into the panicking
+/// service and cannot be cloned ahead of routing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CatchPanicMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware { service }))
+    }
+}
+
+pub struct CatchPanicMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // The panic may happen while the handler future is being created...
+        let fut = match std::panic::catch_unwind(AssertUnwindSafe(|| self.service.call(req))) {
+            Ok(fut) => fut,
+            Err(_) => return Box::pin(ready(Err(panic_error()))),
+        };
+
+        // ...or while it is being polled.
+        Box::pin(async move {
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(res) => res,
+                Err(_) => Err(panic_error()),
+            }
+        })
+    }
+}
+
+// The payload was logged by the panic hook as the panic happened.
+fn panic_error() -> Error {
+    let request_id = RequestId::current();
+    log::error!(
+        "[{}] Handler panicked; answering with a 500",
+        request_id.as_ref().map_or("-", RequestId::as_str)
+    );
+    ApiError::Internal {
+        reason: "handler panicked".to_string(),
+    }
+    .into()
+}
+
+/// The message of a panic payload, fit for the logs: every secret in the
+/// global [`SecretRegistry`] is replaced by `[REDACTED]`. A panic message is
+/// whatever the panicking code formatted, which may well be a backend URL
+/// with the API key in it.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>");
+    SecretRegistry::global().redact(message)
+}
+
+/// Replaces the default panic hook, which prints the raw message to stderr,
+/// with one that logs it through [`panic_message`]. Panics outside a request,
+/// which `CatchPanic` never sees, are redacted too.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "an unknown location".to_string());
+        log::error!(
+            "Panicked at {}: {}",
+            location,
+            panic_message(info.payload())
+        );
+    }));
+}
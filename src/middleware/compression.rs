@@ -0,0 +1,115 @@
+use actix_web::{
+    Error,
+    body::{BodySize, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{CONTENT_ENCODING, HeaderMap, HeaderValue},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use serde::Serialize;
+
+use super::map_error_headers;
+
+/// Whether responses are compressed, and from what size on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompressionConfig {
+    /// Compresses responses with gzip, brotli or zstd, as the client's
+    /// `Accept-Encoding` allows.
+    pub enabled: bool,
+    /// Bodies smaller than this are sent as they are: compressing them
+    /// saves little, and a small body reflecting a secret next to
+    /// attacker-chosen input is what BREACH-style attacks need.
+    pub min_size_bytes: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            enabled: true,
+            min_size_bytes: 1024,
+        }
+    }
+}
+
+/// Decides which responses actix's `Compress` may touch; wrap it just inside
+/// `Compress`.
+///
+/// Responses smaller than `min_size_bytes` (or of unknown size) and every
+/// error response get `Content-Encoding: identity`, which `Compress` leaves
+/// alone. Error bodies carry the request ID and echo parts of the request,
+/// so they are never compressed, whatever their size.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionThreshold {
+    min_size_bytes: usize,
+}
+
+impl CompressionThreshold {
+    pub fn new(config: &CompressionConfig) -> Self {
+        CompressionThreshold {
+            min_size_bytes: config.min_size_bytes,
+        }
+    }
+}
+
+fn keep_identity(headers: &mut HeaderMap) {
+    if !headers.contains_key(CONTENT_ENCODING) {
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CompressionThreshold
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CompressionThresholdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionThresholdMiddleware {
+            service,
+            min_size_bytes: self.min_size_bytes,
+        }))
+    }
+}
+
+pub struct CompressionThresholdMiddleware<S> {
+    service: S,
+    min_size_bytes: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionThresholdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let min_size_bytes = self.min_size_bytes as u64;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match fut.await {
+                Ok(mut res) => {
+                    let large = match res.response().body().size() {
+                        BodySize::Sized(len) => len >= min_size_bytes,
+                        BodySize::None | BodySize::Stream => false,
+                    };
+                    let status = res.status();
+                    if !large || status.is_client_error() || status.is_server_error() {
+                        keep_identity(res.headers_mut());
+                    }
+                    Ok(res)
+                }
+                Err(err) => Err(map_error_headers(err, keep_identity)),
+            }
+        })
+    }
+}
@@ -0,0 +1,199 @@
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::{
+        Method,
+        header::{self, HeaderMap, HeaderValue},
+    },
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use serde::Serialize;
+use std::rc::Rc;
+
+use crate::host::HostWhitelist;
+
+/// Which cross-origin callers are allowed, and what they may send.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://app.my-app.com`. When empty, an origin is
+    /// allowed if its `host[:port]` passes the host whitelist instead.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight answer, in seconds.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec![
+                "Content-Type".to_string(),
+                "X-Request-Id".to_string(),
+                "Idempotency-Key".to_string(),
+            ],
+            max_age_secs: 600,
+        }
+    }
+}
+
+/// Answers CORS preflights and tags responses for allowed origins.
+///
+/// An allowed origin is reflected back verbatim, never as `*`. A disallowed
+/// origin gets no CORS headers at all: its preflight is refused with a `403`,
+/// and its other requests are processed as usual but stay unreadable to the
+/// calling page.
+#[derive(Clone)]
+pub struct Cors {
+    inner: Rc<CorsInner>,
+}
+
+struct CorsInner {
+    allowed_origins: Vec<String>,
+    host_matcher: HostWhitelist,
+    allow_methods: HeaderValue,
+    allow_headers: HeaderValue,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    /// `host_matcher` is the fallback used when no explicit origins are set;
+    /// reloading it changes which origins are allowed, too.
+    pub fn new(config: &CorsConfig, host_matcher: HostWhitelist) -> Self {
+        let join = |values: &[String]| {
+            HeaderValue::from_str(&values.join(", ")).unwrap_or_else(|_| {
+                log::warn!("Ignoring invalid CORS method/header list: {:?}", values);
+                HeaderValue::from_static("")
+            })
+        };
+        Cors {
+            inner: Rc::new(CorsInner {
+                allowed_origins: config
+                    .allowed_origins
+                    .iter()
+                    .map(|o| normalize_origin(o))
+                    .collect(),
+                host_matcher,
+                allow_methods: join(&config.allowed_methods),
+                allow_headers: join(&config.allowed_headers),
+                max_age: HeaderValue::from(config.max_age_secs),
+            }),
+        }
+    }
+}
+
+impl CorsInner {
+    fn is_allowed(&self, origin: &str) -> bool {
+        let origin = normalize_origin(origin);
+        if !self.allowed_origins.is_empty() {
+            return self.allowed_origins.contains(&origin);
+        }
+        // Fall back to the host whitelist: `https://my-app.com:8080` is allowed
+        // if `my-app.com:8080` is listed (and not denied). An allow-all-except
+        // whitelist allows no origins this way.
+        let Ok(url) = url::Url::parse(&origin) else {
+            return false;
+        };
+        match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => self.host_matcher.is_listed(&format!("{}:{}", host, port)),
+            (Some(host), None) => self.host_matcher.is_listed(host),
+            _ => false,
+        }
+    }
+
+    fn add_origin_headers(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static("x-request-id"),
+        );
+        headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+}
+
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Cors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorsMiddleware {
+            service,
+            inner: Rc::clone(&self.inner),
+        }))
+    }
+}
+
+pub struct CorsMiddleware<S> {
+    service: S,
+    inner: Rc<CorsInner>,
+}
+
+impl<S, B> Service<ServiceRequest> for CorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let origin = req.headers().get(header::ORIGIN).cloned();
+        let allowed = origin
+            .as_ref()
+            .and_then(|o| o.to_str().ok())
+            .is_some_and(|o| self.inner.is_allowed(o));
+        let is_preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+        if let (true, Some(origin)) = (is_preflight, &origin) {
+            let res = if allowed {
+                let mut res = HttpResponse::NoContent().finish();
+                let headers = res.headers_mut();
+                self.inner.add_origin_headers(origin, headers);
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_METHODS,
+                    self.inner.allow_methods.clone(),
+                );
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                    self.inner.allow_headers.clone(),
+                );
+                headers.insert(header::ACCESS_CONTROL_MAX_AGE, self.inner.max_age.clone());
+                res
+            } else {
+                log::warn!("Refused CORS preflight from disallowed origin {:?}", origin);
+                HttpResponse::Forbidden().finish()
+            };
+            return Box::pin(ready(Ok(req.into_response(res).map_into_right_body())));
+        }
+
+        let inner = Rc::clone(&self.inner);
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let (true, Some(origin)) = (allowed, &origin) {
+                inner.add_origin_headers(origin, res.headers_mut());
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
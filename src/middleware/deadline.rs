@@ -0,0 +1,153 @@
+use actix_web::{
+    Error, HttpMessage, HttpRequest,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::RequestId;
+use crate::error::ApiError;
+use crate::server::ServerConfig;
+
+/// Puts a hard ceiling on how long a request may take, whatever the handler
+/// is waiting for.
+///
+/// A request still running at its deadline is cancelled (its future is
+/// dropped, so the work stops at the next `.await`) and answered with a
+/// generic `503`. Routes can get their own deadline, keyed by route pattern;
+/// alternatively wrap a single resource in its own `Deadline`.
+///
+/// Like `CatchPanic`, the timeout surfaces as an `ApiError` service error, as
+/// the request has been moved into the inner service by then.
+///
+/// The moment the request is cancelled is recorded in its extensions as a
+/// [`RequestDeadline`], for the handlers to pass on to the backend.
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    default: Duration,
+    routes: Rc<HashMap<String, Duration>>,
+}
+
+impl Deadline {
+    pub fn new(default: Duration) -> Self {
+        Deadline {
+            default,
+            routes: Rc::new(HashMap::new()),
+        }
+    }
+
+    /// The `request_deadline_ms` and `route_deadlines_ms` of `config`.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        config.route_deadlines_ms.iter().fold(
+            Deadline::new(config.request_deadline()),
+            |deadline, (route, &ms)| deadline.route(route.clone(), Duration::from_millis(ms)),
+        )
+    }
+
+    /// Gives requests matching the route `pattern` (as registered, e.g.
+    /// `/users/{id}`) their own deadline.
+    pub fn route(mut self, pattern: impl Into<String>, deadline: Duration) -> Self {
+        Rc::make_mut(&mut self.routes).insert(pattern.into(), deadline);
+        self
+    }
+
+    fn for_request(&self, req: &ServiceRequest) -> Duration {
+        let pattern = req.match_pattern();
+        let route = pattern.as_deref().unwrap_or(req.path());
+        self.routes.get(route).copied().unwrap_or(self.default)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Deadline
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeadlineMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeadlineMiddleware {
+            service,
+            deadline: self.clone(),
+        }))
+    }
+}
+
+/// When the `Deadline` middleware cancels a request. Nested `Deadline`s keep
+/// the earliest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestDeadline {
+    expires: Instant,
+}
+
+impl RequestDeadline {
+    pub fn new(expires: Instant) -> Self {
+        RequestDeadline { expires }
+    }
+
+    /// The deadline of `req`, if a `Deadline` middleware is mounted.
+    pub fn of(req: &HttpRequest) -> Option<RequestDeadline> {
+        req.extensions().get::<RequestDeadline>().copied()
+    }
+
+    pub fn expires(&self) -> Instant {
+        self.expires
+    }
+
+    /// How long is left as of `now`; zero once the deadline has passed.
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.expires.saturating_duration_since(now)
+    }
+}
+
+pub struct DeadlineMiddleware<S> {
+    service: S,
+    deadline: Deadline,
+}
+
+impl<S, B> Service<ServiceRequest> for DeadlineMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let deadline = self.deadline.for_request(&req);
+        let expires = RequestDeadline::new(Instant::now() + deadline);
+        let earliest = match req.extensions().get::<RequestDeadline>() {
+            Some(outer) if outer.expires <= expires.expires => *outer,
+            _ => expires,
+        };
+        req.extensions_mut().insert(earliest);
+        let request_id = RequestId::of(req.request());
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match actix_web::rt::time::timeout(deadline, fut).await {
+                Ok(res) => res,
+                Err(_) => {
+                    log::warn!(
+                        "[{}] Request to {} exceeded its {:?} deadline and was cancelled",
+                        request_id,
+                        path,
+                        deadline
+                    );
+                    Err(ApiError::DeadlineExceeded { deadline }.into())
+                }
+            }
+        })
+    }
+}
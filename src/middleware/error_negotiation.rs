@@ -0,0 +1,77 @@
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{Accept, Header},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::sync::Arc;
+
+use super::map_error_headers;
+use crate::error::{ErrorFormat, NEGOTIATED};
+use crate::messages::{PREFERRED, preferred_languages};
+
+/// Picks the `ApiError` body format from each request's `Accept` header, and
+/// the language of its generic messages from `Accept-Language` (see
+/// `messages::Localizer`).
+///
+/// `application/json`, `text/plain` and `text/html` select JSON, plain text
+/// and an HTML page; anything else (including no header) keeps the configured
+/// `error_format`. The choice applies to every error rendered while the
+/// request runs, including those of inner middleware, so mount this outside
+/// every layer that can fail.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorNegotiation;
+
+impl<S, B> Transform<S, ServiceRequest> for ErrorNegotiation
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ErrorNegotiationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ErrorNegotiationMiddleware { service }))
+    }
+}
+
+pub struct ErrorNegotiationMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ErrorNegotiationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // An unparsable header counts as no preference.
+        let format = match Accept::parse(&req) {
+            Ok(accept) => ErrorFormat::negotiate(&accept, ErrorFormat::global()),
+            Err(_) => ErrorFormat::global(),
+        };
+        let languages: Arc<[String]> = preferred_languages(&req).into();
+        let fut = NEGOTIATED.sync_scope(format, || {
+            PREFERRED.sync_scope(languages.clone(), || self.service.call(req))
+        });
+
+        Box::pin(NEGOTIATED.scope(
+            format,
+            PREFERRED.scope(languages, async move {
+                // Render errors from inner layers here, while the format and
+                // language are in scope.
+                fut.await.map_err(|err| map_error_headers(err, |_| {}))
+            }),
+        ))
+    }
+}
@@ -0,0 +1,106 @@
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::LOCATION,
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+
+use crate::client_info::effective_scheme;
+use crate::error::ApiError;
+use crate::host::{ValidatedHost, split_host_port};
+use crate::server::ServerConfig;
+
+/// The probes answer over plain HTTP too, so they work without TLS.
+const EXEMPT_PATHS: &[&str] = &["/healthz", "/readyz"];
+
+/// Redirects every plain-HTTP request to the same path on HTTPS with a
+/// `308 Permanent Redirect`, which keeps the method and body. Wrap it in a
+/// `Condition` on `ServerConfig::force_https`.
+///
+/// Whether a request is plain HTTP is [`effective_scheme`]'s call, so one
+/// that reached a trusted TLS-terminating proxy is left alone. The
+/// `Location` is built from the [`ValidatedHost`]: a request for a host off
+/// the whitelist gets a `400` rather than a redirect to wherever it named.
+/// The target port is `server.tls.port` when this server terminates TLS
+/// itself, and HTTPS's default otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpsRedirect {
+    https_port: Option<u16>,
+}
+
+impl HttpsRedirect {
+    pub fn new(server: &ServerConfig) -> Self {
+        HttpsRedirect {
+            https_port: server
+                .tls
+                .as_ref()
+                .map(|tls| tls.port)
+                .filter(|&p| p != 443),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HttpsRedirect
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HttpsRedirectMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpsRedirectMiddleware {
+            service,
+            https_port: self.https_port,
+        }))
+    }
+}
+
+pub struct HttpsRedirectMiddleware<S> {
+    service: S,
+    https_port: Option<u16>,
+}
+
+// The HTTPS URL of `req`, or why there is none.
+fn https_location(req: &ServiceRequest, https_port: Option<u16>) -> Result<String, ApiError> {
+    let ValidatedHost(host) = ValidatedHost::of(req.request())?;
+    let (name, _) = split_host_port(&host).ok_or(ApiError::InvalidHost { host: Some(host) })?;
+    let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+    Ok(match https_port {
+        Some(port) => format!("https://{}:{}{}", name, port, path),
+        None => format!("https://{}{}", name, path),
+    })
+}
+
+impl<S, B> Service<ServiceRequest> for HttpsRedirectMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if effective_scheme(req.request()) == "http" && !EXEMPT_PATHS.contains(&req.path()) {
+            let res = match https_location(&req, self.https_port) {
+                Ok(location) => req.into_response(
+                    HttpResponse::PermanentRedirect()
+                        .insert_header((LOCATION, location))
+                        .finish(),
+                ),
+                Err(e) => req.error_response(e),
+            };
+            return Box::pin(ready(Ok(res.map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -0,0 +1,80 @@
+use actix_web::{
+    Error,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::ApiError;
+
+/// Paths that stay reachable in maintenance mode: the probes, so the
+/// orchestrator does not restart the instance, and the admin scope, so
+/// maintenance can be switched off again.
+const EXEMPT_PATHS: &[&str] = &["/healthz", "/readyz", "/metrics"];
+const EXEMPT_PREFIX: &str = "/admin/";
+
+/// Answers every request with a `503` while `flag` is set (see
+/// `AppState::maintenance` and `POST /admin/maintenance`), except for the
+/// health, metrics and admin routes.
+#[derive(Clone)]
+pub struct Maintenance {
+    flag: Arc<AtomicBool>,
+}
+
+impl Maintenance {
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Maintenance { flag }
+    }
+}
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PATHS.contains(&path) || path.starts_with(EXEMPT_PREFIX)
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Maintenance
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = MaintenanceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddleware {
+            service,
+            flag: self.flag.clone(),
+        }))
+    }
+}
+
+pub struct MaintenanceMiddleware<S> {
+    service: S,
+    flag: Arc<AtomicBool>,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.flag.load(Ordering::Relaxed) && !is_exempt(req.path()) {
+            let res = req.error_response(ApiError::Maintenance);
+            return Box::pin(ready(Ok(res.map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
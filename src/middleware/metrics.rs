@@ -0,0 +1,105 @@
+use actix_web::http::Method;
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::time::Instant;
+
+use crate::metrics::Metrics;
+
+/// Records every request's route, method, status and latency in [`Metrics`],
+/// and how many requests are in flight.
+///
+/// Wrap it outside `CatchPanic` (i.e. call `.wrap(RequestMetrics)` after
+/// `.wrap(CatchPanic)`), so panics are counted as the `500`s the client sees.
+#[derive(Clone)]
+pub struct RequestMetrics {
+    metrics: Metrics,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Metrics) -> Self {
+        RequestMetrics { metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+// The methods of RFC 9110, plus PATCH. Anything else a client makes up is
+// counted as `other`, so it cannot add series either.
+const KNOWN_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+fn method_label(method: &Method) -> &'static str {
+    KNOWN_METHODS
+        .into_iter()
+        .find(|known| *known == method.as_str())
+        .unwrap_or("other")
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+    metrics: Metrics,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = method_label(req.method());
+        let metrics = self.metrics.clone();
+        let in_flight = metrics.track_in_flight();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            drop(in_flight);
+            let (route, status) = match &res {
+                // Unmatched paths share one label so scanners cannot blow up
+                // the number of series.
+                Ok(res) => (
+                    res.request()
+                        .match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_string()),
+                    res.status().as_u16(),
+                ),
+                // The request is gone by the time an error surfaces here.
+                Err(e) => (
+                    "unknown".to_string(),
+                    e.as_response_error().status_code().as_u16(),
+                ),
+            };
+            metrics.observe(&route, method, status, started.elapsed());
+            res
+        })
+    }
+}
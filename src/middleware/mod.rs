@@ -0,0 +1,49 @@
+//! Middleware that hardens every route, independent of individual handlers.
+
+mod access_log;
+mod auth;
+mod catch_panic;
+mod compression;
+mod cors;
+mod deadline;
+mod error_negotiation;
+mod https_redirect;
+mod maintenance;
+mod metrics;
+mod rate_limit;
+mod request_id;
+mod request_tracing;
+mod sanitize_errors;
+mod security_headers;
+mod uri_length;
+
+pub use access_log::{ALWAYS_REDACTED_HEADERS, AccessLog, AccessLogConfig, AccessLogVerbosity};
+pub use auth::{AdminAuth, AuthConfig, constant_time_eq};
+pub use catch_panic::{CatchPanic, install_panic_hook, panic_message};
+pub use compression::{CompressionConfig, CompressionThreshold};
+pub use cors::{Cors, CorsConfig};
+pub use deadline::{Deadline, RequestDeadline};
+pub use error_negotiation::ErrorNegotiation;
+pub use https_redirect::HttpsRedirect;
+pub use maintenance::Maintenance;
+pub use metrics::RequestMetrics;
+pub use rate_limit::{MAX_TRACKED_CLIENTS, RateLimitConfig, RateLimiter};
+pub use request_id::{CorrelationId, REQUEST_ID_HEADER, RequestId};
+pub use request_tracing::RequestTracing;
+pub use sanitize_errors::sanitize_errors;
+pub use security_headers::{SecurityHeaders, SecurityHeadersConfig};
+pub use uri_length::UriLengthLimit;
+
+use actix_web::{Error, HttpResponse, error::InternalError, http::header::HeaderMap};
+
+/// Lets a middleware adjust the headers of an error that an inner layer
+/// returned instead of a response (e.g. a caught panic).
+///
+/// Such errors are only rendered by actix after every middleware has run, so
+/// this renders the error now, applies `f`, and wraps the result in a new error
+/// that renders to exactly that response.
+pub(crate) fn map_error_headers(err: Error, f: impl FnOnce(&mut HeaderMap)) -> Error {
+    let mut rendered: HttpResponse = err.error_response();
+    f(rendered.headers_mut());
+    InternalError::from_response(err, rendered).into()
+}
@@ -0,0 +1,166 @@
+use actix_web::{
+    Error,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use lru::LruCache;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client_info::ClientInfo;
+use crate::error::ApiError;
+
+/// How many clients have a bucket at once. Past it, the client seen least
+/// recently loses its bucket (and starts over with a full one), so a flood of
+/// distinct addresses costs constant time per request and bounded memory.
+pub const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// How requests are rate limited per client IP.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Tokens added to each client's bucket per second.
+    pub requests_per_second: f64,
+    /// Bucket capacity: how many requests a client may send in a burst.
+    pub burst: u32,
+    /// Key on the client address forwarded by a trusted proxy
+    /// (`AppState::trusted_proxies`, see `ClientInfo`) instead of the peer
+    /// address. Forwarding headers from any other peer are ignored, so a
+    /// client cannot pick its own key.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            requests_per_second: 10.0,
+            burst: 20,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token-bucket rate limiter keyed by client IP.
+///
+/// Over-limit requests get a `429 Too Many Requests` with a `Retry-After`
+/// header and a generic body, and never reach the handlers. The bucket table is
+/// shared behind an `Arc`, so one limiter covers every worker; build it once,
+/// outside the `HttpServer::new` closure.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Arc<Mutex<LruCache<IpAddr, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let capacity = NonZeroUsize::new(MAX_TRACKED_CLIENTS).expect("a non-zero capacity");
+        RateLimiter {
+            config,
+            buckets: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// How many clients currently have a bucket; at most
+    /// [`MAX_TRACKED_CLIENTS`].
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Takes a token from `client`'s bucket, or returns how long until one is
+    /// available.
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.config.burst.max(1));
+        let rate = self.config.requests_per_second;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.get_or_insert_mut(client, || Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if rate > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        } else {
+            Err(Duration::from_secs(60))
+        }
+    }
+
+    fn client_ip(&self, req: &ServiceRequest) -> Option<IpAddr> {
+        if self.config.trust_forwarded_for {
+            return ClientInfo::of(req.request()).ip;
+        }
+        req.peer_addr().map(|addr| addr.ip())
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service,
+            limiter: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: S,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Without a peer address (e.g. a Unix socket) there is nothing to key on.
+        if self.limiter.config.enabled
+            && let Some(ip) = self.limiter.client_ip(&req)
+            && let Err(retry_after) = self.limiter.check(ip)
+        {
+            log::warn!("Rate limit exceeded for {}", ip);
+            let res = req.error_response(ApiError::RateLimited { retry_after });
+            return Box::pin(ready(Ok(res.map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -0,0 +1,139 @@
+use actix_web::{
+    Error, HttpMessage, HttpRequest,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{HeaderName, HeaderValue},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::fmt;
+
+use super::map_error_headers;
+
+/// The header carrying the correlation ID, in both directions.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Inbound IDs longer than this, or with other characters, are replaced rather
+// than trusted: they end up in logs and response headers verbatim.
+const MAX_INBOUND_LEN: usize = 128;
+
+tokio::task_local! {
+    // The ID of the request whose service future is currently being polled.
+    static CURRENT: RequestId;
+}
+
+/// The correlation ID of the current request, stored in its extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// The ID assigned to `req`, or `-` if the `CorrelationId` middleware is not
+    /// mounted. Handlers use this to tag their log lines.
+    pub fn of(req: &HttpRequest) -> RequestId {
+        req.extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(|| RequestId("-".to_string()))
+    }
+
+    /// The ID of the request being processed, if called from within the
+    /// `CorrelationId` middleware. This is how `ApiError::error_response`,
+    /// which has no access to the request, finds the reference to print.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(RequestId::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // Reuse the client's ID if it is a sane token, otherwise mint a UUID.
+    fn for_request(req: &ServiceRequest) -> RequestId {
+        let inbound = req
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| is_valid_inbound(id));
+        match inbound {
+            Some(id) => RequestId(id.to_string()),
+            None => RequestId(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn is_valid_inbound(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_INBOUND_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Assigns every request a correlation ID.
+///
+/// The ID is taken from an inbound `X-Request-Id` header when it looks safe,
+/// or generated as a UUID otherwise. It is stored in the request extensions
+/// (see [`RequestId::of`]) and made available to the code running the request
+/// (see [`RequestId::current`]), which is how `ApiError` bodies end with
+/// `Reference: <id>`. It is also echoed in the `X-Request-Id` response header.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CorrelationId;
+
+impl<S, B> Transform<S, ServiceRequest> for CorrelationId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CorrelationIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorrelationIdMiddleware { service }))
+    }
+}
+
+pub struct CorrelationIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CorrelationIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = RequestId::for_request(&req);
+        req.extensions_mut().insert(id.clone());
+        let fut = CURRENT.sync_scope(id.clone(), || self.service.call(req));
+
+        Box::pin(CURRENT.scope(id.clone(), async move {
+            let header =
+                HeaderValue::from_str(id.as_str()).expect("request IDs are valid header values");
+            match fut.await {
+                Ok(mut res) => {
+                    res.headers_mut().insert(REQUEST_ID_HEADER, header);
+                    Ok(res)
+                }
+                // Errors from inner middleware (e.g. a caught panic) never became a
+                // response; render them here, while the ID is still current.
+                Err(err) => Err(map_error_headers(err, |headers| {
+                    headers.insert(REQUEST_ID_HEADER, header);
+                })),
+            }
+        }))
+    }
+}
@@ -0,0 +1,89 @@
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::time::Instant;
+use tracing::Instrument;
+
+use super::RequestId;
+
+/// Runs every request inside a `request` span tagged with its correlation ID,
+/// and ends it with a single `request completed` event carrying the route,
+/// status and latency.
+///
+/// Wrap it inside `CorrelationId` (i.e. call `.wrap(RequestTracing)` before
+/// `.wrap(CorrelationId)`), so the ID has been assigned when the span opens.
+#[derive(Clone, Copy)]
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let span = tracing::info_span!(
+            "request",
+            request_id = %RequestId::of(req.request()),
+            method = %req.method(),
+        );
+        let fut = span.in_scope(|| self.service.call(req));
+
+        Box::pin(
+            async move {
+                let res = fut.await;
+                let (route, status) = match &res {
+                    // Like the metrics, log the route pattern rather than the
+                    // raw path, which can carry user input.
+                    Ok(res) => (
+                        res.request()
+                            .match_pattern()
+                            .unwrap_or_else(|| "unmatched".to_string()),
+                        res.status().as_u16(),
+                    ),
+                    Err(e) => (
+                        "unknown".to_string(),
+                        e.as_response_error().status_code().as_u16(),
+                    ),
+                };
+                tracing::info!(
+                    route,
+                    status,
+                    latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+                    "request completed"
+                );
+                res
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,44 @@
+use actix_web::{
+    ResponseError,
+    dev::ServiceResponse,
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE},
+    middleware::{ErrorHandlerResponse, ErrorHandlers},
+};
+
+use crate::error::{ApiError, SafeErrorBody};
+
+/// Replaces the body of every 4xx/5xx response with the generic `ApiError`
+/// body for its status, unless the response is marked [`SafeErrorBody`].
+///
+/// This is defence in depth for errors that never went through `ApiError`:
+/// actix's own extractor failures, for instance, describe exactly which field
+/// failed to deserialize. The status and headers are kept (except the content
+/// type and length, which belong to the old body). Mount it inside
+/// `ErrorNegotiation` and `CorrelationId` so the new body is in the format the
+/// client asked for and carries the request ID.
+pub fn sanitize_errors<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().default_handler(replace_body)
+}
+
+fn replace_body<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    if res.response().extensions().contains::<SafeErrorBody>() {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+    let status = res.status();
+    let (req, original) = res.into_parts();
+    log::debug!(
+        "Replaced the body of a {} response to {} {}",
+        status,
+        req.method(),
+        req.path()
+    );
+    let mut generic = ApiError::Status { status }.error_response();
+    for (name, value) in original.headers() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH && !generic.headers().contains_key(name) {
+            generic.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, generic).map_into_right_body(),
+    ))
+}
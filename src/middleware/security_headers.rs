@@ -0,0 +1,155 @@
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{
+        CONTENT_SECURITY_POLICY, HeaderMap, HeaderName, HeaderValue, REFERRER_POLICY, SERVER,
+        STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use serde::Serialize;
+use std::rc::Rc;
+
+use super::map_error_headers;
+
+/// The values [`SecurityHeaders`] sets. `None` leaves a header out entirely.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecurityHeadersConfig {
+    pub strict_transport_security: Option<String>,
+    pub content_type_options: Option<String>,
+    pub frame_options: Option<String>,
+    pub referrer_policy: Option<String>,
+    pub content_security_policy: Option<String>,
+    /// Unlike the others, replaces whatever `Server` header a handler set, and
+    /// with `None` (the default) removes it, so no response names the
+    /// software or its version.
+    pub server: Option<String>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        SecurityHeadersConfig {
+            strict_transport_security: Some("max-age=31536000; includeSubDomains".to_string()),
+            content_type_options: Some("nosniff".to_string()),
+            frame_options: Some("DENY".to_string()),
+            referrer_policy: Some("no-referrer".to_string()),
+            // The API serves no documents, so nothing should load from it.
+            content_security_policy: Some("default-src 'none'; frame-ancestors 'none'".to_string()),
+            server: None,
+        }
+    }
+}
+
+/// Adds security headers to every response, errors included.
+///
+/// A header the handler already set is left alone, so a route can opt into a
+/// different policy; only `Server` is always replaced (or removed). Values
+/// that are not valid header values are dropped with a warning when the
+/// middleware is built.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    headers: Rc<Headers>,
+}
+
+#[derive(Debug)]
+struct Headers {
+    defaults: Vec<(HeaderName, HeaderValue)>,
+    server: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Self {
+        let configured = [
+            (STRICT_TRANSPORT_SECURITY, &config.strict_transport_security),
+            (X_CONTENT_TYPE_OPTIONS, &config.content_type_options),
+            (X_FRAME_OPTIONS, &config.frame_options),
+            (REFERRER_POLICY, &config.referrer_policy),
+            (CONTENT_SECURITY_POLICY, &config.content_security_policy),
+        ];
+        let defaults = configured
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.clone(), header_value(&name, value)?)))
+            .collect();
+        SecurityHeaders {
+            headers: Rc::new(Headers {
+                defaults,
+                server: header_value(&SERVER, &config.server),
+            }),
+        }
+    }
+}
+
+fn header_value(name: &HeaderName, value: &Option<String>) -> Option<HeaderValue> {
+    let value = value.as_deref()?;
+    match HeaderValue::from_str(value) {
+        Ok(value) => Some(value),
+        Err(_) => {
+            log::warn!("Ignoring invalid value for the {} header", name);
+            None
+        }
+    }
+}
+
+fn apply(headers: &Headers, target: &mut HeaderMap) {
+    for (name, value) in &headers.defaults {
+        if !target.contains_key(name) {
+            target.insert(name.clone(), value.clone());
+        }
+    }
+    target.remove(SERVER);
+    if let Some(server) = &headers.server {
+        target.insert(SERVER, server.clone());
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service,
+            headers: Rc::clone(&self.headers),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    headers: Rc<Headers>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let headers = Rc::clone(&self.headers);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match fut.await {
+                Ok(mut res) => {
+                    apply(&headers, res.headers_mut());
+                    Ok(res)
+                }
+                Err(err) => Err(map_error_headers(err, |target| apply(&headers, target))),
+            }
+        })
+    }
+}
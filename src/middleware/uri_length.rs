@@ -0,0 +1,85 @@
+use actix_web::{
+    Error,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+
+use crate::error::ApiError;
+use crate::middleware::RequestId;
+
+/// Answers a request whose target (path and query string) is longer than
+/// `max_bytes` with a `414`, before routing, so an oversized query string is
+/// never parsed, copied into a handler or written to a log. See
+/// `ServerConfig::max_uri_bytes`.
+#[derive(Clone, Copy)]
+pub struct UriLengthLimit {
+    max_bytes: usize,
+}
+
+impl UriLengthLimit {
+    pub fn new(max_bytes: usize) -> Self {
+        UriLengthLimit { max_bytes }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for UriLengthLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = UriLengthLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(UriLengthLimitMiddleware {
+            service,
+            max_bytes: self.max_bytes,
+        }))
+    }
+}
+
+pub struct UriLengthLimitMiddleware<S> {
+    service: S,
+    max_bytes: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for UriLengthLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // The request target, as the client sent it: not the scheme or
+        // authority an HTTP/2 request also carries in its URI.
+        let len = req
+            .uri()
+            .path_and_query()
+            .map_or(0, |target| target.as_str().len());
+        if len > self.max_bytes {
+            // Only the length: the target itself is what is too big to log.
+            log::warn!(
+                "[{}] Rejected a request target of {} bytes, over the limit of {}",
+                RequestId::of(req.request()),
+                len,
+                self.max_bytes
+            );
+            let res = req.error_response(ApiError::UriTooLong {
+                limit: self.max_bytes,
+            });
+            return Box::pin(ready(Ok(res.map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
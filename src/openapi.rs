@@ -0,0 +1,36 @@
+//! The OpenAPI description of the signup endpoints, for client generators.
+
+use actix_web::HttpResponse;
+use utoipa::OpenApi;
+
+use crate::error::{ErrorBody, ErrorDetail, HandlerResult};
+use crate::handlers::{self, WaitlistParams};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "uncaught_exception", description = "Waitlist signup API."),
+    paths(handlers::secure_waitlist, handlers::secure_waitlist_json),
+    components(schemas(WaitlistParams, ErrorBody, ErrorDetail))
+)]
+struct ApiDoc;
+
+#[cfg(feature = "insecure-demo")]
+#[derive(OpenApi)]
+#[openapi(paths(handlers::vulnerable_waitlist))]
+struct InsecureDemoDoc;
+
+/// The spec of the routes this build mounts: `/vulnerable/waitlist` only
+/// appears with the `insecure-demo` feature.
+pub fn spec() -> utoipa::openapi::OpenApi {
+    #[allow(unused_mut)]
+    let mut spec = ApiDoc::openapi();
+    #[cfg(feature = "insecure-demo")]
+    spec.merge(InsecureDemoDoc::openapi());
+    spec
+}
+
+/// # OpenAPI Spec
+/// Serves [`spec`] as JSON at `/api-docs/openapi.json`.
+pub async fn openapi_json() -> HandlerResult {
+    Ok(HttpResponse::Ok().json(spec()))
+}
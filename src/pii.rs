@@ -0,0 +1,83 @@
+use reqwest::Url;
+use url::form_urlencoded;
+
+use crate::secrets::REDACTED;
+
+/// Masks an email address for logging, keeping only the first character of
+/// the local part and the domain: `jane+news@example.com` becomes
+/// `j***@example.com`.
+///
+/// The mask is always three asterisks, so it does not reveal the length of the
+/// local part (or a `+tag` in it). A one-character local part is masked
+/// entirely, since keeping it would keep all of it. A value without an `@`
+/// comes back as just `***`.
+pub fn mask_email(email: &str) -> String {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return "***".to_string();
+    };
+    let mut chars = local.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(_)) => format!("{}***@{}", first, domain),
+        _ => format!("***@{}", domain),
+    }
+}
+
+/// The form of a backend URL that goes into the logs: the `email` query
+/// parameter is masked with [`mask_email`], unless `log_pii` is set.
+///
+/// Only the returned string is changed; `url` itself, and therefore what is
+/// sent to the backend, is untouched.
+pub fn url_for_log(url: &Url, log_pii: bool) -> String {
+    if log_pii || url.query().is_none() {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = match key.as_ref() {
+                "email" => mask_email(&value),
+                _ => value.into_owned(),
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+    let mut masked = url.clone();
+    masked.query_pairs_mut().clear().extend_pairs(pairs);
+    masked.to_string()
+}
+
+/// Replaces the value of every query parameter named in `params` with
+/// [`REDACTED`], for logging. Names match case-insensitively, after
+/// percent-decoding, so `API_KEY` and `api%5Fkey` are caught too.
+///
+/// `url` is handled as text rather than parsed, so this also works on a URL
+/// that failed to parse, such as the one in `ApiError::UrlParse`.
+pub fn redact_url_for_logging(url: &str, params: &[String]) -> String {
+    let Some((base, rest)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+    let query = query
+        .split('&')
+        .map(|pair| {
+            let raw_name = pair.split_once('=').map_or(pair, |(name, _)| name);
+            let name = form_urlencoded::parse(raw_name.as_bytes())
+                .next()
+                .map(|(name, _)| name.into_owned())
+                .unwrap_or_default();
+            if params.iter().any(|p| p.eq_ignore_ascii_case(&name)) {
+                format!("{}={}", raw_name, REDACTED)
+            } else {
+                pair.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    match fragment {
+        Some(fragment) => format!("{}?{}#{}", base, query, fragment),
+        None => format!("{}?{}", base, query),
+    }
+}
@@ -0,0 +1,220 @@
+//! Public key pinning for the backend's TLS certificate.
+//!
+//! A pin is the base64 of the SHA-256 of a certificate's DER-encoded
+//! SubjectPublicKeyInfo, the same form HPKP used. It can be computed with:
+//!
+//! ```text
+//! openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+//!     | openssl dgst -sha256 -binary | base64
+//! ```
+//!
+//! Pinning the key rather than the whole certificate lets the backend renew
+//! its certificate without a config change, as long as it keeps the key.
+//!
+//! Pins are enforced during the TLS handshake, by the verifier of
+//! [`client_config`]: a certificate with another key aborts the handshake,
+//! so not a byte of the request, whose URL carries the API key, is sent.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::digest::{SHA256, digest};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::verify_server_name;
+use rustls::crypto::{
+    WebPkiSupportedAlgorithms, ring as provider, verify_tls12_signature, verify_tls13_signature,
+};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, OtherError, SignatureScheme};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The SHA-256 of a SubjectPublicKeyInfo. Parsed from, and displayed as,
+/// base64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpkiPin([u8; 32]);
+
+impl SpkiPin {
+    /// The pin of a DER-encoded X.509 certificate, or `None` if it cannot be
+    /// parsed far enough to find its public key.
+    pub fn of_certificate(der: &[u8]) -> Option<SpkiPin> {
+        let spki = subject_public_key_info(der)?;
+        let hash = digest(&SHA256, spki);
+        Some(SpkiPin(hash.as_ref().try_into().ok()?))
+    }
+}
+
+impl FromStr for SpkiPin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        STANDARD
+            .decode(s.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(SpkiPin)
+            .ok_or_else(|| format!("expected a base64-encoded SHA-256 hash, got {:?}", s))
+    }
+}
+
+impl fmt::Display for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&STANDARD.encode(self.0))
+    }
+}
+
+impl Serialize for SpkiPin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Certificate ::= SEQUENCE { tbsCertificate, ... }, and tbsCertificate is
+// SEQUENCE { [0] version OPTIONAL, serialNumber, signature, issuer, validity,
+// subject, subjectPublicKeyInfo, ... }. Returns the whole SPKI element,
+// header included, since that is what the pin hashes.
+fn subject_public_key_info(der: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let (certificate, _) = element(der, SEQUENCE)?;
+    let (tbs, _) = element(contents(certificate)?, SEQUENCE)?;
+    let mut rest = contents(tbs)?;
+    if rest.first() == Some(&VERSION) {
+        rest = element(rest, VERSION)?.1;
+    }
+    // serialNumber, signature, issuer, validity, subject.
+    for _ in 0..5 {
+        rest = element(rest, *rest.first()?)?.1;
+    }
+    let (spki, _) = element(rest, SEQUENCE)?;
+    Some(spki)
+}
+
+// Splits the DER element at the start of `input`, which must carry `tag`,
+// from what follows it.
+fn element(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    if *input.first()? != tag {
+        return None;
+    }
+    let (header, len) = match *input.get(1)? {
+        short @ 0..=0x7f => (2, usize::from(short)),
+        long => {
+            let octets = usize::from(long & 0x7f);
+            if octets == 0 || octets > 4 {
+                return None;
+            }
+            let len = input
+                .get(2..2 + octets)?
+                .iter()
+                .fold(0usize, |len, &b| len << 8 | usize::from(b));
+            (2 + octets, len)
+        }
+    };
+    let end = header.checked_add(len)?;
+    (end <= input.len()).then(|| input.split_at(end))
+}
+
+// The contents of a single DER element, without its tag and length.
+fn contents(element: &[u8]) -> Option<&[u8]> {
+    let header = match element.get(1)? {
+        0..=0x7f => 2,
+        long => 2 + usize::from(long & 0x7f),
+    };
+    element.get(header..)
+}
+
+/// Why [`client_config`]'s verifier refused a certificate: its key is not
+/// pinned. `presented` is the pin of the key it carried, if it had a
+/// readable one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("the certificate's public key is not pinned")]
+pub struct PinMismatch {
+    pub presented: Option<SpkiPin>,
+}
+
+impl PinMismatch {
+    /// The mismatch behind a failed handshake, if that is why it failed.
+    pub fn of(e: &io::Error) -> Option<PinMismatch> {
+        let tls = e.get_ref()?.downcast_ref::<rustls::Error>()?;
+        match tls {
+            rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(cause))) => {
+                cause.downcast_ref::<PinMismatch>().copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A TLS client configuration (HTTP/1.1 only) that accepts a server only if
+/// its certificate carries one of `pins` and is valid for the name connected
+/// to, and the server proves it holds the matching private key.
+///
+/// The pins replace the system's roots: the certificate chain, and so its
+/// expiry, is not checked, since a pinned key is trusted whoever signed it.
+pub fn client_config(pins: &[SpkiPin]) -> ClientConfig {
+    let provider = provider::default_provider();
+    let verifier = PinVerifier {
+        pins: pins.to_vec(),
+        algorithms: provider.signature_verification_algorithms,
+    };
+    let mut config = ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()
+        .expect("the ring provider supports the default protocol versions")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    config
+}
+
+#[derive(Debug)]
+struct PinVerifier {
+    pins: Vec<SpkiPin>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = SpkiPin::of_certificate(end_entity);
+        if !presented.is_some_and(|pin| self.pins.contains(&pin)) {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(PinMismatch { presented })),
+            )));
+        }
+        verify_server_name(&ParsedCertificate::try_from(end_entity)?, server_name)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
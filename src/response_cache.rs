@@ -0,0 +1,77 @@
+//! Remembers recent successful signups, so a repeated one is answered without
+//! another backend call.
+
+use lru::LruCache;
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many signups the [`ResponseCache`] remembers, and for how long.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResponseCacheConfig {
+    /// The most addresses remembered; beyond that the least recently used
+    /// one is forgotten. `0` turns the cache off.
+    pub capacity: usize,
+    pub ttl_ms: u64,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        ResponseCacheConfig {
+            capacity: 0,
+            ttl_ms: 300_000,
+        }
+    }
+}
+
+/// The normalized addresses whose signup the backend accepted recently.
+///
+/// Only the address and when it was accepted are kept: nothing of the
+/// backend URL, which carries the API key. Clones share the cache.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    entries: Option<Arc<Mutex<LruCache<String, Instant>>>>,
+}
+
+impl ResponseCache {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        let entries = NonZeroUsize::new(config.capacity)
+            .map(|capacity| Arc::new(Mutex::new(LruCache::new(capacity))));
+        ResponseCache { config, entries }
+    }
+
+    pub fn config(&self) -> &ResponseCacheConfig {
+        &self.config
+    }
+
+    /// Whether the backend accepted `email` less than `ttl_ms` ago. An entry
+    /// that has expired is dropped.
+    pub fn contains(&self, email: &str) -> bool {
+        let Some(entries) = &self.entries else {
+            return false;
+        };
+        let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(email) {
+            Some(accepted) if accepted.elapsed() < self.ttl() => true,
+            Some(_) => {
+                entries.pop(email);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records that the backend accepted `email`.
+    pub fn insert(&self, email: &str) {
+        if let Some(entries) = &self.entries {
+            let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+            entries.put(email.to_string(), Instant::now());
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_millis(self.config.ttl_ms)
+    }
+}
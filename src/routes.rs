@@ -0,0 +1,265 @@
+use actix_web::dev::HttpServiceFactory;
+use actix_web::middleware::Condition;
+use actix_web::{Resource, Route, web};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::admin::{
+    effective_config, events, reload_hosts, rotate_key, set_maintenance, waitlist_count,
+};
+use crate::debug::{headers, whoami};
+use crate::error::{ApiError, HandlerResult};
+use crate::handlers::{json_config, secure_waitlist, secure_waitlist_json};
+use crate::health::{healthz, readyz};
+use crate::metrics::metrics;
+use crate::middleware::{AdminAuth, AuthConfig, Deadline};
+use crate::openapi::openapi_json;
+use crate::server::ServerConfig;
+use crate::version::version;
+
+/// Whether this build mounts `/vulnerable/waitlist`. Only builds with the
+/// `insecure-demo` feature do; it is off by default, so a release binary
+/// cannot ship the leaky handler by accident.
+pub const INSECURE_DEMO: bool = cfg!(feature = "insecure-demo");
+
+/// Every path [`configure`] can mount, and so every key a [`RouteConfig`] may
+/// have. `/vulnerable/waitlist` is listed even in builds without it.
+pub const ROUTE_PATHS: &[&str] = &[
+    "/vulnerable/waitlist",
+    "/secure/waitlist",
+    "/healthz",
+    "/readyz",
+    "/version",
+    "/metrics",
+    "/admin/config",
+    "/admin/waitlist/count",
+    "/admin/maintenance",
+    "/admin/reload-hosts",
+    "/admin/rotate-key",
+    "/admin/events",
+    "/debug/whoami",
+    "/debug/headers",
+    "/api-docs/openapi.json",
+];
+
+/// The limits of one route, from its `[routes."<path>"]` table in the config
+/// file. Whatever is left unset falls back to the server-wide setting.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutePolicy {
+    /// Replaces `server.request_deadline_ms` (and any `route_deadlines_ms`
+    /// entry) for this route.
+    pub timeout_ms: Option<u64>,
+    /// Replaces `server.max_body_bytes` for this route.
+    pub max_body_bytes: Option<usize>,
+    /// Requires the admin token, as every route under `/admin` and `/debug`
+    /// already does.
+    #[serde(default)]
+    pub require_admin: bool,
+}
+
+/// The [`RoutePolicy`] of each route that has one, keyed by its path (e.g.
+/// `/secure/waitlist` or `/admin/config`), so every route's limits can be
+/// read off one table. Unlisted routes get the server-wide defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct RouteConfig {
+    routes: BTreeMap<String, RoutePolicy>,
+}
+
+impl RouteConfig {
+    /// Sets the policy of the route at `path`.
+    pub fn route(mut self, path: impl Into<String>, policy: RoutePolicy) -> Self {
+        self.routes.insert(path.into(), policy);
+        self
+    }
+
+    /// The policy of the route at `path`, if it has one.
+    pub fn policy(&self, path: &str) -> Option<&RoutePolicy> {
+        self.routes.get(path)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RoutePolicy)> {
+        self.routes
+            .iter()
+            .map(|(path, policy)| (path.as_str(), policy))
+    }
+
+    /// The `Deadline` middleware for the app: `server`'s deadlines, with
+    /// each route's `timeout_ms` on top.
+    pub fn deadline(&self, server: &ServerConfig) -> Deadline {
+        self.iter()
+            .filter_map(|(path, policy)| Some((path, policy.timeout_ms?)))
+            .fold(Deadline::from_config(server), |deadline, (path, ms)| {
+                deadline.route(path, Duration::from_millis(ms))
+            })
+    }
+}
+
+impl From<BTreeMap<String, RoutePolicy>> for RouteConfig {
+    fn from(routes: BTreeMap<String, RoutePolicy>) -> Self {
+        RouteConfig { routes }
+    }
+}
+
+/// Registers every route with the default policies. `auth` guards the
+/// `/admin` and `/debug` scopes.
+///
+/// The app data the handlers need (`AppState`, `Metrics`) is added by the
+/// caller, as are the middleware.
+pub fn configure(cfg: &mut web::ServiceConfig, auth: &AuthConfig) {
+    configure_with(cfg, auth, &RouteConfig::default());
+}
+
+/// Like [`configure`], applying the body limit and admin requirement of each
+/// route in `routes`. Their timeouts are enforced by the app-wide
+/// `Deadline`, which must come from [`RouteConfig::deadline`].
+pub fn configure_with(cfg: &mut web::ServiceConfig, auth: &AuthConfig, routes: &RouteConfig) {
+    let policy = |path| Policy { path, routes, auth };
+    #[cfg(feature = "insecure-demo")]
+    cfg.service(
+        policy("/vulnerable/waitlist").apply(
+            web::resource("/vulnerable/waitlist")
+                .route(web::get().to(crate::handlers::vulnerable_waitlist))
+                .default_service(method_not_allowed("GET")),
+        ),
+    );
+    cfg.service(
+        policy("/secure/waitlist").apply(
+            web::resource("/secure/waitlist")
+                .route(web::get().to(secure_waitlist))
+                .route(web::post().to(secure_waitlist_json))
+                .default_service(method_not_allowed("GET, POST")),
+        ),
+    )
+    .service(
+        policy("/healthz").apply(
+            web::resource("/healthz")
+                .route(web::get().to(healthz))
+                .default_service(method_not_allowed("GET")),
+        ),
+    )
+    .service(
+        policy("/readyz").apply(
+            web::resource("/readyz")
+                .route(web::get().to(readyz))
+                .default_service(method_not_allowed("GET")),
+        ),
+    )
+    .service(
+        policy("/version").apply(
+            web::resource("/version")
+                .route(web::get().to(version))
+                .default_service(method_not_allowed("GET")),
+        ),
+    )
+    .service(
+        policy("/metrics").apply(
+            web::resource("/metrics")
+                .route(web::get().to(metrics))
+                .default_service(method_not_allowed("GET")),
+        ),
+    )
+    .service(
+        policy("/api-docs/openapi.json").apply(
+            web::resource("/api-docs/openapi.json")
+                .route(web::get().to(openapi_json))
+                .default_service(method_not_allowed("GET")),
+        ),
+    )
+    // Administrative routes; everything under here needs the admin token.
+    .service(
+        web::scope("/admin")
+            .wrap(AdminAuth::new(auth))
+            .service(
+                policy("/admin/config").apply(
+                    web::resource("/config")
+                        .route(web::get().to(effective_config))
+                        .default_service(method_not_allowed("GET")),
+                ),
+            )
+            .service(
+                policy("/admin/waitlist/count").apply(
+                    web::resource("/waitlist/count")
+                        .route(web::get().to(waitlist_count))
+                        .default_service(method_not_allowed("GET")),
+                ),
+            )
+            .service(
+                policy("/admin/maintenance").apply(
+                    web::resource("/maintenance")
+                        .route(web::post().to(set_maintenance))
+                        .default_service(method_not_allowed("POST")),
+                ),
+            )
+            .service(
+                policy("/admin/reload-hosts").apply(
+                    web::resource("/reload-hosts")
+                        .route(web::post().to(reload_hosts))
+                        .default_service(method_not_allowed("POST")),
+                ),
+            )
+            .service(
+                policy("/admin/rotate-key").apply(
+                    web::resource("/rotate-key")
+                        .route(web::post().to(rotate_key))
+                        .default_service(method_not_allowed("POST")),
+                ),
+            )
+            .service(
+                policy("/admin/events").apply(
+                    web::resource("/events")
+                        .route(web::get().to(events))
+                        .default_service(method_not_allowed("GET")),
+                ),
+            ),
+    )
+    // Request introspection for developers; as sensitive as `/admin`.
+    .service(
+        web::scope("/debug")
+            .wrap(AdminAuth::new(auth))
+            .service(
+                policy("/debug/whoami").apply(
+                    web::resource("/whoami")
+                        .route(web::get().to(whoami))
+                        .default_service(method_not_allowed("GET")),
+                ),
+            )
+            .service(
+                policy("/debug/headers").apply(
+                    web::resource("/headers")
+                        .route(web::get().to(headers))
+                        .default_service(method_not_allowed("GET")),
+                ),
+            ),
+    );
+}
+
+// What a resource answers a method it has no route for: a `405` whose `Allow`
+// header lists the methods it does have.
+fn method_not_allowed(allowed: &'static str) -> Route {
+    web::to(move || async move { HandlerResult::Err(ApiError::MethodNotAllowed { allowed }) })
+}
+
+// The policy of the route mounted at `path`.
+struct Policy<'a> {
+    path: &'static str,
+    routes: &'a RouteConfig,
+    auth: &'a AuthConfig,
+}
+
+impl Policy<'_> {
+    fn apply(self, mut resource: Resource) -> impl HttpServiceFactory + use<> {
+        let policy = self.routes.policy(self.path).cloned().unwrap_or_default();
+        if let Some(limit) = policy.max_body_bytes {
+            resource = resource
+                .app_data(json_config(limit))
+                .app_data(web::PayloadConfig::new(limit));
+        }
+        resource.wrap(Condition::new(
+            policy.require_admin,
+            AdminAuth::new(self.auth),
+        ))
+    }
+}
@@ -0,0 +1,153 @@
+//! Regex rules that rewrite every log line before it is written, for secrets
+//! the fixed redactions (`pii`, `SecretRegistry`) know nothing about.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::{Arc, OnceLock};
+
+use crate::secrets::REDACTED;
+
+/// One rule: every match of `pattern` is replaced with `replacement`, which
+/// may refer to capture groups as `$1` or `${name}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubRule {
+    pub pattern: String,
+    #[serde(default = "redacted")]
+    pub replacement: String,
+}
+
+fn redacted() -> String {
+    REDACTED.to_string()
+}
+
+impl ScrubRule {
+    pub fn new(pattern: impl Into<String>, replacement: impl Into<String>) -> Self {
+        ScrubRule {
+            pattern: pattern.into(),
+            replacement: replacement.into(),
+        }
+    }
+}
+
+/// Whether log lines are scrubbed, and by which rules on top of
+/// [`builtin_rules`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScrubberConfig {
+    pub enabled: bool,
+    pub rules: Vec<ScrubRule>,
+}
+
+/// The rules every enabled [`Scrubber`] starts with: bearer tokens, UUIDs
+/// given as a key, token or secret, and card-like numbers of 13 to 19 digits,
+/// optionally grouped with spaces or dashes.
+pub fn builtin_rules() -> Vec<ScrubRule> {
+    vec![
+        ScrubRule::new(r"(?i)\b(bearer\s+)[A-Za-z0-9\-._~+/]+=*", "${1}[REDACTED]"),
+        ScrubRule::new(
+            r#"(?i)\b((?:api[_-]?)?(?:key|token|secret)["']?\s*[=:]\s*["']?)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b"#,
+            "${1}[REDACTED]",
+        ),
+        ScrubRule::new(r"\b\d(?:[ -]?\d){12,18}\b", REDACTED),
+    ]
+}
+
+/// Compiled [`ScrubRule`]s, applied in order.
+#[derive(Debug)]
+pub struct Scrubber {
+    rules: Vec<(Regex, String)>,
+}
+
+static GLOBAL: OnceLock<Arc<Scrubber>> = OnceLock::new();
+
+impl Scrubber {
+    /// Compiles `rules`. The error names the first invalid one by its index,
+    /// e.g. `rules[1]: ...`.
+    pub fn new(rules: &[ScrubRule]) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| (regex, rule.replacement.clone()))
+                    .map_err(|e| format!("rules[{}]: {}", i, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Scrubber { rules })
+    }
+
+    /// The [`builtin_rules`] followed by `config.rules`, or `None` when the
+    /// config does not enable scrubbing.
+    pub fn from_config(config: &ScrubberConfig) -> Result<Option<Self>, String> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let configured = Scrubber::new(&config.rules)?;
+        let mut scrubber = Scrubber::new(&builtin_rules()).expect("the built-in rules compile");
+        scrubber.rules.extend(configured.rules);
+        Ok(Some(scrubber))
+    }
+
+    /// `input` with every rule applied.
+    pub fn scrub<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        self.rules.iter().fold(
+            Cow::Borrowed(input),
+            |acc, (regex, replacement)| match regex.replace_all(&acc, replacement.as_str()) {
+                Cow::Borrowed(_) => acc,
+                Cow::Owned(replaced) => Cow::Owned(replaced),
+            },
+        )
+    }
+
+    /// Makes this the scrubber [`ScrubbingWriter::stdout`] applies. Only the
+    /// first call has an effect.
+    pub fn install(self: Arc<Self>) {
+        let _ = GLOBAL.set(self);
+    }
+
+    /// The installed scrubber, if any.
+    pub fn global() -> Option<Arc<Scrubber>> {
+        GLOBAL.get().cloned()
+    }
+}
+
+/// Writes through `inner`, scrubbed by `scrubber` when there is one. The
+/// `tracing` formatter writes each event in one go, so every line is scrubbed
+/// whole.
+pub struct ScrubbingWriter<W> {
+    inner: W,
+    scrubber: Option<Arc<Scrubber>>,
+}
+
+impl<W: Write> ScrubbingWriter<W> {
+    pub fn new(inner: W, scrubber: Option<Arc<Scrubber>>) -> Self {
+        ScrubbingWriter { inner, scrubber }
+    }
+}
+
+impl ScrubbingWriter<io::Stdout> {
+    /// Standard output, scrubbed by the installed scrubber. Logging starts
+    /// before the configuration is loaded, so this looks the scrubber up on
+    /// every line rather than once.
+    pub fn stdout() -> Self {
+        ScrubbingWriter::new(io::stdout(), Scrubber::global())
+    }
+}
+
+impl<W: Write> Write for ScrubbingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.scrubber {
+            Some(scrubber) => {
+                let line = String::from_utf8_lossy(buf);
+                self.inner.write_all(scrubber.scrub(&line).as_bytes())?;
+                Ok(buf.len())
+            }
+            None => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,261 @@
+use ring::digest::{SHA256, digest};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+use zeroize::Zeroize;
+
+/// The placeholder written in place of any registered secret.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A secret string, such as the API key.
+///
+/// Its `Debug` output is [`REDACTED`] and it has no `Display`, so it cannot end
+/// up in a log line by accident; call [`Secret::expose`] where the value itself
+/// is needed. The memory is wiped when the secret is dropped.
+#[derive(Clone, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Secret(secret.into())
+    }
+
+    /// The secret value. Keep every call site easy to audit.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+// Serialized like `Debug`, so a config dump cannot leak the value either.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+// For taking a key in a request body, e.g. `POST /admin/rotate-key`.
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// A [`Secret`] that can be replaced at runtime, such as the API key after
+/// `POST /admin/rotate-key`. Clones share it, so a rotation reaches them all.
+///
+/// Readers take a snapshot with [`SharedSecret::current`] and keep using it
+/// for as long as they hold it; a rotation only affects later snapshots. The
+/// replaced value is wiped once the last snapshot of it is dropped.
+#[derive(Clone, Default)]
+pub struct SharedSecret {
+    current: Arc<RwLock<Arc<Secret>>>,
+}
+
+impl SharedSecret {
+    pub fn new(secret: Secret) -> Self {
+        SharedSecret {
+            current: Arc::new(RwLock::new(Arc::new(secret))),
+        }
+    }
+
+    /// The secret as it is right now.
+    pub fn current(&self) -> Arc<Secret> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Swaps in `secret` for every clone at once, returning the value it
+    /// replaced.
+    pub fn rotate(&self, secret: Secret) -> Arc<Secret> {
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, Arc::new(secret))
+    }
+}
+
+impl From<Secret> for SharedSecret {
+    fn from(secret: Secret) -> Self {
+        SharedSecret::new(secret)
+    }
+}
+
+impl Serialize for SharedSecret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+static GLOBAL: LazyLock<SecretRegistry> = LazyLock::new(SecretRegistry::new);
+
+/// A set of secret strings that must never reach a client.
+///
+/// `ApiError` scrubs every response body through the global registry, so a
+/// secret only has to be registered once (at startup) to be protected
+/// everywhere. Separate instances can be created to test redaction in isolation.
+#[derive(Debug, Default)]
+pub struct SecretRegistry {
+    secrets: RwLock<Vec<Secret>>,
+    // Secrets that were retired, known only by their fingerprint.
+    retired: RwLock<Vec<Fingerprint>>,
+    // How many secret occurrences `redact` has replaced so far.
+    redactions: AtomicU64,
+}
+
+// The length and SHA-256 of a retired secret: enough to recognize it in a
+// message without keeping the value itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    len: usize,
+    digest: [u8; 32],
+}
+
+impl Fingerprint {
+    fn of(value: &str) -> Self {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(digest(&SHA256, value.as_bytes()).as_ref());
+        Fingerprint {
+            len: value.len(),
+            digest: hash,
+        }
+    }
+
+    // `input` with every stretch that hashes to this fingerprint replaced by
+    // `REDACTED`, and how many there were.
+    fn redact(&self, input: &str) -> (String, u64) {
+        let (mut output, mut found) = (String::with_capacity(input.len()), 0);
+        let (mut start, mut copied) = (0, 0);
+        while start + self.len <= input.len() {
+            let end = start + self.len;
+            if input.is_char_boundary(start)
+                && input.is_char_boundary(end)
+                && Fingerprint::of(&input[start..end]) == *self
+            {
+                output.push_str(&input[copied..start]);
+                output.push_str(REDACTED);
+                found += 1;
+                (start, copied) = (end, end);
+            } else {
+                start += 1;
+            }
+        }
+        output.push_str(&input[copied..]);
+        (output, found)
+    }
+}
+
+impl SecretRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide registry consulted by `ApiError::sanitize`.
+    pub fn global() -> &'static SecretRegistry {
+        &GLOBAL
+    }
+
+    /// Registers a secret. Empty strings are ignored, since redacting them
+    /// would mangle every message.
+    pub fn register(&self, secret: impl Into<String>) {
+        let secret = Secret::new(secret);
+        if secret.is_empty() {
+            return;
+        }
+        let mut secrets = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        if !secrets.iter().any(|s| s.expose() == secret.expose()) {
+            secrets.push(secret);
+            // Longest first, so a secret that contains another is replaced whole.
+            secrets.sort_by_key(|s| std::cmp::Reverse(s.expose().len()));
+        }
+    }
+
+    /// Forgets `secret`, wiping the registry's copy.
+    pub fn unregister(&self, secret: &str) {
+        let mut secrets = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        secrets.retain(|s| s.expose() != secret);
+    }
+
+    /// Keeps scrubbing `secret`, e.g. a rotated-out key, without keeping it:
+    /// the registry's copy is wiped and only its length and SHA-256 remain,
+    /// against which every stretch of that length is checked. Slower than a
+    /// registered secret, so for values that should no longer turn up.
+    pub fn retire(&self, secret: &str) {
+        if secret.is_empty() {
+            return;
+        }
+        self.unregister(secret);
+        let fingerprint = Fingerprint::of(secret);
+        let mut retired = self.retired.write().unwrap_or_else(|e| e.into_inner());
+        if !retired.contains(&fingerprint) {
+            retired.push(fingerprint);
+        }
+    }
+
+    /// Whether the registry holds a copy of `secret`, as it does of every
+    /// registered secret and of none it retired.
+    pub fn holds(&self, secret: &str) -> bool {
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        secrets.iter().any(|s| s.expose() == secret)
+    }
+
+    /// Returns `input` with every registered secret replaced by [`REDACTED`].
+    pub fn redact(&self, input: &str) -> String {
+        self.redact_counted(input).0
+    }
+
+    /// Like [`SecretRegistry::redact`], but also says how many secrets were
+    /// replaced in this call.
+    pub fn redact_counted(&self, input: &str) -> (String, u64) {
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        let mut total = 0;
+        let redacted = secrets.iter().fold(input.to_string(), |acc, secret| {
+            let found = acc.matches(secret.expose()).count() as u64;
+            if found == 0 {
+                return acc;
+            }
+            total += found;
+            acc.replace(secret.expose(), REDACTED)
+        });
+        let retired = self.retired.read().unwrap_or_else(|e| e.into_inner());
+        let redacted = retired.iter().fold(redacted, |acc, fingerprint| {
+            let (acc, found) = fingerprint.redact(&acc);
+            total += found;
+            acc
+        });
+        self.redactions.fetch_add(total, Ordering::Relaxed);
+        (redacted, total)
+    }
+
+    /// The total number of secret occurrences [`SecretRegistry::redact`] has
+    /// scrubbed. Every one of them is a leak that was about to happen.
+    pub fn redaction_count(&self) -> u64 {
+        self.redactions.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,327 @@
+use actix_http::{KeepAlive, Request, Response};
+use actix_service::{IntoServiceFactory, Service, ServiceFactory};
+use actix_web::HttpServer;
+use actix_web::body::MessageBody;
+use actix_web::dev::AppConfig;
+use futures_util::future::{Either, select};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::ConfigError;
+
+/// How the HTTP server itself behaves, as opposed to the requests it serves.
+/// It is one part of [`crate::config::AppState`], which holds the rest of the
+/// configuration (API key, allowed hosts, backend timeouts, feature flags)
+/// and is loaded, flags included, and validated as a whole by
+/// [`crate::config::AppState::from_cli`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServerConfig {
+    /// The address to listen on, for both HTTP and HTTPS.
+    pub bind: IpAddr,
+    /// The port for plain HTTP. The HTTPS port is `tls.port`.
+    pub port: u16,
+    /// How long in-flight requests may keep running after a shutdown signal
+    /// before their connections are dropped.
+    pub shutdown_timeout_secs: u64,
+    /// The largest request body accepted, in bytes. Bigger bodies are refused
+    /// with a `413` before they are buffered, so they cannot exhaust memory.
+    pub max_body_bytes: usize,
+    /// The longest request target (path and query string) accepted, in
+    /// bytes. Longer ones are refused with a `414` before routing; see
+    /// [`crate::middleware::UriLengthLimit`].
+    pub max_uri_bytes: usize,
+    /// The longest a request may take from routing to response before it is
+    /// cancelled with a `503`; see [`crate::middleware::Deadline`].
+    pub request_deadline_ms: u64,
+    /// Per-route overrides of `request_deadline_ms`, keyed by route pattern
+    /// (e.g. `/secure/waitlist`).
+    pub route_deadlines_ms: BTreeMap<String, u64>,
+    /// HTTPS settings. `None` serves plain HTTP only.
+    pub tls: Option<TlsConfig>,
+    /// Redirects plain-HTTP requests to HTTPS; see
+    /// [`crate::middleware::HttpsRedirect`].
+    pub force_https: bool,
+    /// How many worker threads serve requests. `None` starts one per
+    /// physical CPU core, like actix-web.
+    pub workers: Option<usize>,
+    /// How long an idle keep-alive connection stays open. `0` closes every
+    /// connection after its response.
+    pub keep_alive_secs: u64,
+    /// How long a client has to send the head of a request (request line and
+    /// headers) before it is answered with a `408` and disconnected, so slow
+    /// clients cannot hold connections open. `0` waits forever.
+    pub client_request_timeout_ms: u64,
+    /// How long a connection is given to shut down cleanly once the server
+    /// is done with it, before it is dropped. `0` waits forever.
+    pub client_disconnect_timeout_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            shutdown_timeout_secs: 30,
+            max_body_bytes: 16 * 1024,
+            max_uri_bytes: 4 * 1024,
+            // Comfortably above the worst case of the default backend
+            // settings: three 5 s attempts plus backoff.
+            request_deadline_ms: 30_000,
+            route_deadlines_ms: BTreeMap::new(),
+            tls: None,
+            force_https: false,
+            workers: None,
+            // actix-web's own defaults.
+            keep_alive_secs: 5,
+            client_request_timeout_ms: 5_000,
+            client_disconnect_timeout_ms: 1_000,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Checks the settings that must hold whatever else is configured: a
+    /// non-zero body and URI limits, deadlines and worker count, and a usable port for
+    /// each listener.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_body_bytes == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "server.max_body_bytes",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if self.max_uri_bytes == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "server.max_uri_bytes",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if self.request_deadline_ms == 0 || self.route_deadlines_ms.values().any(|&ms| ms == 0) {
+            return Err(ConfigError::InvalidValue {
+                name: "server.request_deadline_ms",
+                message: "deadlines must be greater than zero".to_string(),
+            });
+        }
+        if self.workers == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                name: "server.workers",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        let serves_http = !self.tls.as_ref().is_some_and(|tls| tls.disable_plain_http);
+        if serves_http && self.port == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "server.port",
+                message: "must be greater than zero".to_string(),
+            });
+        }
+        if let Some(tls) = &self.tls
+            && serves_http
+            && tls.port == self.port
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "server.tls.port",
+                message: "must differ from server.port".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Where to find the certificate and key, and where to serve HTTPS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, leaf first.
+    pub cert_path: PathBuf,
+    /// PEM file with the private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_path: PathBuf,
+    pub port: u16,
+    /// Serve HTTPS only, instead of alongside the plain HTTP port.
+    pub disable_plain_http: bool,
+}
+
+impl TlsConfig {
+    pub const DEFAULT_PORT: u16 = 8443;
+
+    /// Reads the certificate and key into a rustls server configuration.
+    pub fn load(&self) -> Result<rustls::ServerConfig, ConfigError> {
+        let certs = rustls_pemfile::certs(&mut open(&self.cert_path)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|source| ConfigError::Io {
+                path: self.cert_path.clone(),
+                source,
+            })?;
+        if certs.is_empty() {
+            return Err(ConfigError::Tls(format!(
+                "no certificates found in {}",
+                self.cert_path.display()
+            )));
+        }
+        let key = rustls_pemfile::private_key(&mut open(&self.key_path)?)
+            .map_err(|source| ConfigError::Io {
+                path: self.key_path.clone(),
+                source,
+            })?
+            .ok_or_else(|| {
+                ConfigError::Tls(format!(
+                    "no private key found in {}",
+                    self.key_path.display()
+                ))
+            })?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|e| ConfigError::Tls(e.to_string()))
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ConfigError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+impl ServerConfig {
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    pub fn request_deadline(&self) -> Duration {
+        Duration::from_millis(self.request_deadline_ms)
+    }
+
+    pub fn keep_alive(&self) -> KeepAlive {
+        match self.keep_alive_secs {
+            0 => KeepAlive::Disabled,
+            secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+        }
+    }
+
+    pub fn client_request_timeout(&self) -> Duration {
+        Duration::from_millis(self.client_request_timeout_ms)
+    }
+
+    pub fn client_disconnect_timeout(&self) -> Duration {
+        Duration::from_millis(self.client_disconnect_timeout_ms)
+    }
+}
+
+/// Applies the worker count, keep-alive and client timeouts of `config` to
+/// `server`. Everything else about it is left as it was.
+pub fn apply_server_tuning<F, I, S, B>(
+    server: HttpServer<F, I, S, B>,
+    config: &ServerConfig,
+) -> HttpServer<F, I, S, B>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S, Request>,
+    S: ServiceFactory<Request, Config = AppConfig> + 'static,
+    S::Error: Into<actix_web::Error> + 'static,
+    S::InitError: fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service<Request>>::Future: 'static,
+    S::Service: 'static,
+    B: MessageBody + 'static,
+{
+    let server = server
+        .keep_alive(config.keep_alive())
+        .client_request_timeout(config.client_request_timeout())
+        .client_disconnect_timeout(config.client_disconnect_timeout());
+    match config.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    }
+}
+
+/// Why a listener could not be opened, worded so the operator knows what to
+/// change. `setting` names the flag or variable that picks the port.
+#[derive(Debug, thiserror::Error)]
+pub enum BindError {
+    #[error("port {} already in use; set {setting}", addr.port())]
+    AddrInUse {
+        addr: SocketAddr,
+        setting: &'static str,
+    },
+    #[error(
+        "not allowed to listen on port {}; use a port above 1023 or grant the binary \
+         CAP_NET_BIND_SERVICE",
+        addr.port()
+    )]
+    PermissionDenied { addr: SocketAddr },
+    #[error("{} is not an address of this machine; set --bind or BIND_ADDRESS", addr.ip())]
+    AddrNotAvailable { addr: SocketAddr },
+    #[error("failed to listen on {addr}: {source}")]
+    Other { addr: SocketAddr, source: io::Error },
+}
+
+impl BindError {
+    /// The process exit status for this error, distinct per kind so scripts
+    /// can tell them apart from a configuration error (1) or bad usage (2).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BindError::AddrInUse { .. } => 3,
+            BindError::PermissionDenied { .. } => 4,
+            BindError::AddrNotAvailable { .. } => 5,
+            BindError::Other { .. } => 6,
+        }
+    }
+}
+
+/// Opens a listener on `addr`, turning the common failures into a
+/// [`BindError`] that says what to do about them.
+pub fn bind_or_explain(addr: SocketAddr, setting: &'static str) -> Result<TcpListener, BindError> {
+    TcpListener::bind(addr).map_err(|source| match source.kind() {
+        io::ErrorKind::AddrInUse => BindError::AddrInUse { addr, setting },
+        io::ErrorKind::PermissionDenied => BindError::PermissionDenied { addr },
+        io::ErrorKind::AddrNotAvailable => BindError::AddrNotAvailable { addr },
+        _ => BindError::Other { addr, source },
+    })
+}
+
+/// Resolves with the signal's name once the process is asked to stop: on
+/// `SIGTERM` (what Kubernetes sends) or `SIGINT` (Ctrl-C).
+pub async fn shutdown_signal() -> &'static str {
+    let ctrl_c = Box::pin(async {
+        if let Err(e) = actix_web::rt::signal::ctrl_c().await {
+            log::error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    });
+    match select(ctrl_c, Box::pin(terminate())).await {
+        Either::Left(_) => "SIGINT",
+        Either::Right(_) => "SIGTERM",
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    use actix_web::rt::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            term.recv().await;
+        }
+        Err(e) => {
+            log::error!("Failed to listen for SIGTERM: {}", e);
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate() {
+    std::future::pending::<()>().await;
+}
@@ -0,0 +1,359 @@
+//! The secure signup flow as a [`tower_service::Service`], for composing it
+//! with Tower middleware or serving it from something other than actix-web.
+//!
+//! [`WaitlistService`] takes a [`SignupCall`], a signup that already passed
+//! every check of [`WaitlistRequest`], and answers with a [`SignupOutcome`] or
+//! the [`ApiError`] to render. It never sees an `HttpRequest`: the actix
+//! handlers in [`crate::handlers`] extract and validate the request, call the
+//! service, and turn the outcome into a response.
+//!
+//! Like [`BackendClient`](crate::backend::BackendClient), its futures are not
+//! `Send`, and with `[backend.batch]` enabled the first queued signup spawns
+//! the batch task with `actix_web::rt::spawn` (unless [`flush_batches`] was
+//! already started with the queue's receiver): run it on a single-threaded
+//! runtime inside a `LocalSet`, as actix's own runtime is.
+
+use futures_util::future::{LocalBoxFuture, join_all};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::mpsc::Receiver;
+use tower_service::Service;
+
+use crate::backend::build_backend_url;
+use crate::batch::{Submission, next_batch};
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::handlers::{WaitlistRequest, loggable_url};
+use crate::middleware::RequestDeadline;
+use crate::pii::redact_url_for_logging;
+use crate::store::normalize_email;
+
+/// One signup for [`WaitlistService`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignupCall {
+    pub signup: WaitlistRequest,
+    /// Go through every step but the backend call, recording nothing; see
+    /// `BackendConfig::dry_run`. Who may ask for one is up to the caller.
+    pub dry_run: bool,
+    /// The correlation ID to tag the log lines with.
+    pub request_id: String,
+    /// When the caller gives up on the answer, passed on to the backend; see
+    /// `backend::DEADLINE_HEADER`.
+    pub deadline: Option<RequestDeadline>,
+}
+
+impl SignupCall {
+    /// A real (not dry-run) signup, logged with the request ID `-`, without a
+    /// deadline.
+    pub fn new(signup: WaitlistRequest) -> Self {
+        SignupCall {
+            signup,
+            dry_run: false,
+            request_id: "-".to_string(),
+            deadline: None,
+        }
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = request_id.into();
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Option<RequestDeadline>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+}
+
+/// How a signup was accepted. Refusals are the `Err` of the service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignupOutcome {
+    /// The backend accepted it.
+    Submitted,
+    /// The backend accepted the same address moments ago, and was not called
+    /// again; see [`crate::response_cache`].
+    Cached,
+    /// Recorded and queued for the next batch.
+    Queued,
+    /// A dry run that would have been accepted.
+    DryRun,
+}
+
+/// The secure signup flow over an [`AppState`]: build the backend URL, refuse
+/// duplicates, record the signup and submit it (or queue it) under the
+/// concurrency cap and the circuit breaker. Clones share the state.
+#[derive(Clone)]
+pub struct WaitlistService {
+    state: Arc<AppState>,
+}
+
+impl WaitlistService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        WaitlistService { state }
+    }
+
+    /// Runs one signup. This is what [`Service::call`] does, without the
+    /// `'static` future.
+    pub async fn signup(&self, call: SignupCall) -> Result<SignupOutcome, ApiError> {
+        let state = &*self.state;
+        let request_id = call.request_id.as_str();
+        let (host, email) = (call.signup.host.as_str(), call.signup.email.as_str());
+
+        // 1. and 2. MITIGATION: The host, and the email, were checked by
+        // `WaitlistRequest`; a malformed, unlisted or internal one never gets
+        // this far.
+
+        // 3. Construct the backend URL.
+        // 4. MITIGATION: Handle the `Result` gracefully instead of using `unwrap()`.
+        // `ApiError::UrlParse` only ever renders a generic, variant-specific message.
+        // The key as of now; a rotation mid-call applies from the next one.
+        let api_key = state.api_key.current();
+        let backend_url = build_backend_url(host, &state.backend.path, api_key.expose(), email)
+            .inspect_err(|e| {
+                // Log the detailed error for debugging purposes on the
+                // server-side only, without the key that is in the
+                // attempted URL.
+                let detail = match e {
+                    ApiError::UrlParse { url, source } => format!(
+                        "failed to parse backend URL '{}': {}",
+                        redact_url_for_logging(url, &state.redact_query_params),
+                        source
+                    ),
+                    e => e.to_string(),
+                };
+                log::error!(
+                    "[{}] Internal error during URL construction: {}",
+                    request_id,
+                    detail
+                );
+            })?;
+        log::info!(
+            "[{}] Secure handler attempting to use URL: {}",
+            request_id,
+            loggable_url(&backend_url, state)
+        );
+
+        // In a dry run, stop here: duplicates are still refused, but nothing is
+        // recorded and the backend is never called.
+        let key = normalize_email(email);
+        if call.dry_run {
+            let duplicate = state.waitlist.contains(&key).map_err(|e| {
+                log::error!("[{}] {}", request_id, e);
+                ApiError::Internal {
+                    reason: e.to_string(),
+                }
+            })?;
+            if duplicate {
+                log::info!("[{}] Rejected duplicate signup (dry run)", request_id);
+                return Err(ApiError::AlreadySignedUp);
+            }
+            log::info!("[{}] Dry run: skipped the backend call", request_id);
+            return Ok(SignupOutcome::DryRun);
+        }
+
+        // A signup the backend accepted moments ago is answered as it was then,
+        // without calling it again (or telling the webhook twice).
+        if state.response_cache.contains(&key) {
+            log::info!("[{}] Answered a repeated signup from the cache", request_id);
+            return Ok(SignupOutcome::Cached);
+        }
+
+        // 5. Record the signup, refusing duplicates. It is recorded before the
+        // backend call so two concurrent requests cannot both get through.
+        let inserted = state.waitlist.insert(&key).map_err(|e| {
+            log::error!("[{}] {}", request_id, e);
+            ApiError::Internal {
+                reason: e.to_string(),
+            }
+        })?;
+        if !inserted {
+            log::info!("[{}] Rejected duplicate signup", request_id);
+            return Err(ApiError::AlreadySignedUp);
+        }
+
+        // With batching on, queue the signup and answer right away; the backend
+        // call, the cache and the webhook are up to `flush_batches`.
+        if state.backend.batch.enabled {
+            return self.queue(request_id, host, email, key);
+        }
+
+        // 6. Call the backend, unless it has been failing and the circuit is
+        // open, or too many calls are already in flight. Failures are logged
+        // here and reach the client only as a generic 502/503/504 body; the
+        // signup is taken back so the client can retry.
+        let submitted = match acquire_backend_permit(state).await {
+            Ok(_permit) => {
+                state
+                    .circuit_breaker
+                    .call(
+                        state
+                            .backend_client
+                            .submit_waitlist(host, email, call.deadline),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = submitted {
+            log::error!("[{}] Backend call failed: {}", request_id, e);
+            if let Err(e) = state.waitlist.remove(&key) {
+                log::error!("[{}] {}", request_id, e);
+            }
+            return Err(e);
+        }
+        // 7. Tell the webhook and the event feed, without waiting for them.
+        signup_accepted(state, &key);
+        Ok(SignupOutcome::Submitted)
+    }
+
+    // Queues a recorded signup for `flush_batches`, starting that task with
+    // the first one. A full queue takes the signup back and answers `503`,
+    // like a backend out of slots.
+    fn queue(
+        &self,
+        request_id: &str,
+        host: &str,
+        email: &str,
+        key: String,
+    ) -> Result<SignupOutcome, ApiError> {
+        let state = &*self.state;
+        if let Some(receiver) = state.batcher.take_receiver() {
+            actix_web::rt::spawn(flush_batches(self.state.clone(), receiver));
+        }
+        let submission = Submission {
+            host: host.to_string(),
+            email: email.to_string(),
+            key,
+        };
+        if let Err(e) = state.batcher.enqueue(submission.clone()) {
+            log::warn!("[{}] Signup queue is full", request_id);
+            if let Err(e) = state.waitlist.remove(&submission.key) {
+                log::error!("[{}] {}", request_id, e);
+            }
+            return Err(e);
+        }
+        log::info!("[{}] Queued the signup for the next batch", request_id);
+        Ok(SignupOutcome::Queued)
+    }
+}
+
+impl Service<SignupCall> for WaitlistService {
+    type Response = SignupOutcome;
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<SignupOutcome, ApiError>>;
+
+    // Always ready: the backend cap is waited for (up to the queue timeout)
+    // inside the call, where a full cap can be answered with a `503`.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), ApiError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, call: SignupCall) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move { service.signup(call).await })
+    }
+}
+
+/// Submits the signups queued on `state.batcher` a batch at a time, until the
+/// queue is closed with [`Batcher::close`](crate::batch::Batcher::close) and
+/// everything queued before that has been submitted.
+pub async fn flush_batches(state: Arc<AppState>, mut receiver: Receiver<Submission>) {
+    while let Some(batch) = next_batch(&mut receiver, &state.backend.batch).await {
+        log::info!("Submitting a batch of {} signup(s)", batch.len());
+        join_all(
+            by_host(&batch)
+                .into_iter()
+                .map(|signups| submit_queued(&state, signups)),
+        )
+        .await;
+    }
+}
+
+// The signups of a batch grouped by the host they go to, each group in the
+// order it was queued.
+fn by_host(batch: &[Submission]) -> Vec<Vec<&Submission>> {
+    let mut groups: Vec<Vec<&Submission>> = Vec::new();
+    for submission in batch {
+        match groups
+            .iter_mut()
+            .find(|group| group[0].host == submission.host)
+        {
+            Some(group) => group.push(submission),
+            None => groups.push(vec![submission]),
+        }
+    }
+    groups
+}
+
+// The signups of a batch bound for one host, in one backend call under the
+// same concurrency cap and circuit breaker as a direct call, and retried by
+// the client as one would be. There is no one left to answer, so a failure
+// is logged (as the error, never the URL) and counted, and the signups taken
+// back so their clients can sign up again.
+async fn submit_queued(state: &AppState, signups: Vec<&Submission>) {
+    let host = &signups[0].host;
+    let emails: Vec<&str> = signups.iter().map(|s| s.email.as_str()).collect();
+    let submitted = match state.backend_permits.clone().acquire_owned().await {
+        Ok(_permit) => {
+            state
+                .circuit_breaker
+                .call(state.backend_client.submit_batch(host, &emails))
+                .await
+        }
+        Err(closed) => Err(ApiError::Internal {
+            reason: closed.to_string(),
+        }),
+    };
+    match submitted {
+        Ok(()) => {
+            for submission in &signups {
+                signup_accepted(state, &submission.key);
+            }
+        }
+        Err(e) => {
+            log::error!(
+                "Batched backend call for {} signup(s) failed: {}",
+                signups.len(),
+                e
+            );
+            state.batcher.record_failed(signups.len());
+            for submission in &signups {
+                if let Err(e) = state.waitlist.remove(&submission.key) {
+                    log::error!("{}", e);
+                }
+            }
+        }
+    }
+}
+
+// What follows a signup the backend accepted, however it was submitted: it is
+// remembered by the response cache, and announced to the webhook (if there is
+// one) and to the subscribers of `GET /admin/events`.
+fn signup_accepted(state: &AppState, key: &str) {
+    state.response_cache.insert(key);
+    if let Some(webhook) = &state.webhook {
+        webhook.notify(&state.client, &state.backend.private_hosts, key);
+    }
+    state.events.publish(key);
+}
+
+// Waits up to the queue timeout for a free backend slot, so a burst of signups
+// cannot open unbounded connections to the upstream. The slot is released
+// when the permit is dropped.
+async fn acquire_backend_permit(state: &AppState) -> Result<OwnedSemaphorePermit, ApiError> {
+    let waited = state.backend.queue_timeout();
+    match actix_web::rt::time::timeout(waited, state.backend_permits.clone().acquire_owned()).await
+    {
+        Ok(Ok(permit)) => Ok(permit),
+        Ok(Err(closed)) => Err(ApiError::Internal {
+            reason: closed.to_string(),
+        }),
+        Err(_) => Err(ApiError::Overloaded { waited }),
+    }
+}
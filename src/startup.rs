@@ -0,0 +1,96 @@
+//! Checks run once the configuration has loaded, refusing settings that only
+//! make sense for the lesson, and the summary of protections logged at boot.
+
+use std::fmt;
+
+use crate::config::AppState;
+use crate::routes;
+
+/// The API key of `config.example.toml` and the lesson's walkthrough. Anyone
+/// who has read the README knows it, so the server refuses to run with it.
+pub const DEMO_API_KEY: &str = "88665751-288d-4175-852f-6519d79fdf1f";
+
+/// Why the server refuses to start with an otherwise valid configuration.
+///
+/// Like `ConfigError`, the messages never include configuration values.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum StartupError {
+    #[error("the API key is the published demo key; set API_KEY to a key of your own")]
+    DemoApiKey,
+    #[error("no allowed hosts configured; set ALLOWED_HOSTS or `allowed_hosts` in the config file")]
+    NoAllowedHosts,
+    #[error(
+        "--production was given to a build with the insecure-demo feature, which leaks the API \
+         key; rebuild without it"
+    )]
+    InsecureDemoInProduction,
+}
+
+/// Refuses a configuration that is clearly not fit to run: one using the
+/// [`DEMO_API_KEY`] or with an empty host whitelist (and no denylist), or,
+/// when `production` is set (`--production`), a build with the
+/// `insecure-demo` feature.
+pub fn validate_startup_config(state: &AppState, production: bool) -> Result<(), StartupError> {
+    if state.api_key.current().expose() == DEMO_API_KEY {
+        return Err(StartupError::DemoApiKey);
+    }
+    if state.host_matcher.snapshot().hosts.is_empty() && state.host_matcher.denied().is_empty() {
+        return Err(StartupError::NoAllowedHosts);
+    }
+    if production && routes::INSECURE_DEMO {
+        return Err(StartupError::InsecureDemoInProduction);
+    }
+    Ok(())
+}
+
+/// The protections a configuration turns on, as logged once at startup so a
+/// misconfiguration shows at a glance. Only flags and counts: nothing here is
+/// secret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostureSummary {
+    /// Built with the `insecure-demo` feature, which mounts the leaky handler.
+    pub insecure_demo: bool,
+    pub production_mode: bool,
+    pub tls: bool,
+    pub rate_limit: bool,
+    pub rate_limit_rps: f64,
+    pub rate_limit_burst: u32,
+    /// Entries in the host whitelist in force, wildcards included.
+    pub allowed_hosts: usize,
+    /// Whether the API key is the published [`DEMO_API_KEY`].
+    pub demo_api_key: bool,
+}
+
+/// Sums up `config` for the startup banner. The API key is only compared with
+/// [`DEMO_API_KEY`], never copied.
+pub fn security_posture_summary(config: &AppState) -> PostureSummary {
+    PostureSummary {
+        insecure_demo: routes::INSECURE_DEMO,
+        production_mode: config.production_mode,
+        tls: config.server.tls.is_some(),
+        rate_limit: config.rate_limit.enabled,
+        rate_limit_rps: config.rate_limit.requests_per_second,
+        rate_limit_burst: config.rate_limit.burst,
+        allowed_hosts: config.host_matcher.snapshot().hosts.len(),
+        demo_api_key: config.api_key.current().expose() == DEMO_API_KEY,
+    }
+}
+
+/// One line of `key=value` pairs, for log search.
+impl fmt::Display for PostureSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "insecure_demo={} production_mode={} tls={} rate_limit={} rate_limit_rps={} \
+             rate_limit_burst={} allowed_hosts={} demo_api_key={}",
+            self.insecure_demo,
+            self.production_mode,
+            self.tls,
+            self.rate_limit,
+            self.rate_limit_rps,
+            self.rate_limit_burst,
+            self.allowed_hosts,
+            self.demo_api_key,
+        )
+    }
+}
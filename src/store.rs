@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{Connection, ErrorCode, OptionalExtension};
+
+/// Why a store operation failed. The message is for logs only.
+#[derive(Debug, thiserror::Error)]
+#[error("waitlist store failed: {0}")]
+pub struct StoreError(pub String);
+
+/// Where signups are recorded.
+///
+/// Implementations receive addresses already passed through
+/// [`normalize_email`], so they can compare them as plain strings.
+pub trait WaitlistStore: Send + Sync + fmt::Debug {
+    /// Adds `email`. Returns `false` if it is already on the waitlist.
+    fn insert(&self, email: &str) -> Result<bool, StoreError>;
+    /// Takes `email` off the waitlist again, e.g. when the backend call for a
+    /// new signup failed.
+    fn remove(&self, email: &str) -> Result<(), StoreError>;
+    /// Whether `email` is on the waitlist.
+    fn contains(&self, email: &str) -> Result<bool, StoreError>;
+    /// The number of signups.
+    fn count(&self) -> Result<usize, StoreError>;
+}
+
+/// The key signups are stored and deduplicated under: trimmed and lowercased,
+/// so `Jane@Example.com` and `jane@example.com` are the same signup.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// A [`WaitlistStore`] in process memory. Signups are lost on restart.
+/// Clones share the same set.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    emails: Arc<Mutex<HashSet<String>>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn emails(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.emails.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl WaitlistStore for InMemoryStore {
+    fn insert(&self, email: &str) -> Result<bool, StoreError> {
+        Ok(self.emails().insert(email.to_string()))
+    }
+
+    fn remove(&self, email: &str) -> Result<(), StoreError> {
+        self.emails().remove(email);
+        Ok(())
+    }
+
+    fn contains(&self, email: &str) -> Result<bool, StoreError> {
+        Ok(self.emails().contains(email))
+    }
+
+    fn count(&self) -> Result<usize, StoreError> {
+        Ok(self.emails().len())
+    }
+}
+
+/// A [`WaitlistStore`] in a SQLite database, so signups survive a restart.
+/// Clones share the same connection.
+///
+/// Signups live in a `waitlist(email TEXT UNIQUE, created_at)` table, created
+/// on open if needed. The `UNIQUE` constraint is what detects a duplicate, so
+/// two servers sharing one database file cannot both accept the same address.
+#[derive(Debug, Clone)]
+pub struct SqliteWaitlistStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteWaitlistStore {
+    /// Opens (or creates) the database at `path`. `":memory:"` opens a private
+    /// in-memory database, see [`SqliteWaitlistStore::open_in_memory`].
+    pub fn open(path: &str) -> Result<Self, StoreError> {
+        Self::init(Connection::open(path).map_err(store_error)?)
+    }
+
+    /// A database that lives only as long as the store, e.g. for tests.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::init(Connection::open_in_memory().map_err(store_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS waitlist (
+                email TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+            )",
+        )
+        .map_err(store_error)?;
+        Ok(SqliteWaitlistStore {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl WaitlistStore for SqliteWaitlistStore {
+    fn insert(&self, email: &str) -> Result<bool, StoreError> {
+        match self
+            .conn()
+            .execute("INSERT INTO waitlist (email) VALUES (?1)", [email])
+        {
+            Ok(_) => Ok(true),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == ErrorCode::ConstraintViolation =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(store_error(e)),
+        }
+    }
+
+    fn remove(&self, email: &str) -> Result<(), StoreError> {
+        self.conn()
+            .execute("DELETE FROM waitlist WHERE email = ?1", [email])
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    fn contains(&self, email: &str) -> Result<bool, StoreError> {
+        self.conn()
+            .query_row("SELECT 1 FROM waitlist WHERE email = ?1", [email], |_| {
+                Ok(())
+            })
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(store_error)
+    }
+
+    fn count(&self) -> Result<usize, StoreError> {
+        let count: i64 = self
+            .conn()
+            .query_row("SELECT COUNT(*) FROM waitlist", [], |row| row.get(0))
+            .map_err(store_error)?;
+        Ok(count as usize)
+    }
+}
+
+fn store_error(e: rusqlite::Error) -> StoreError {
+    StoreError(e.to_string())
+}
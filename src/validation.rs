@@ -0,0 +1,249 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::ApiError;
+use crate::handlers::WaitlistParams;
+use crate::host::HostMatcher;
+
+/// The longest address that fits in an SMTP path (RFC 5321).
+pub const MAX_EMAIL_LEN: usize = 254;
+/// The longest local part (before the `@`) allowed by RFC 5321.
+pub const MAX_LOCAL_PART_LEN: usize = 64;
+
+/// Refuses a query string that carries the parameter `name` more than once,
+/// e.g. `?email=a@x.com&email=b@y.com`. Which of the values a parser keeps is
+/// an implementation detail, so a second one could be smuggled past whatever
+/// looked at the first; the request is refused instead. Names are compared
+/// after percent-decoding, so `%65mail` counts as `email`.
+pub fn reject_repeated_param(query: &str, name: &str) -> Result<(), ApiError> {
+    let count = url::form_urlencoded::parse(query.as_bytes())
+        .filter(|(key, _)| key == name)
+        .count();
+    if count > 1 {
+        return Err(ApiError::BadRequest {
+            reason: format!("{} query parameters named {:?}", count, name),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that `email` is a plausible address before it goes anywhere near a URL.
+///
+/// This is deliberately stricter than RFC 5322: no quoted local parts, no
+/// comments, no IP-literal domains, and no surrounding whitespace (the value is
+/// rejected rather than trimmed, so what we validate is what we send).
+pub fn validate_email(email: &str) -> Result<(), ApiError> {
+    let reject = |reason: &str| {
+        Err(ApiError::BadRequest {
+            reason: format!("invalid email: {}", reason),
+        })
+    };
+
+    if email.is_empty() {
+        return reject("empty");
+    }
+    if email.len() > MAX_EMAIL_LEN {
+        return reject("too long");
+    }
+    if email.trim() != email {
+        return reject("surrounding whitespace");
+    }
+    let Some((local, domain)) = email.split_once('@') else {
+        return reject("missing '@'");
+    };
+    if domain.contains('@') {
+        return reject("more than one '@'");
+    }
+    if local.is_empty() || local.len() > MAX_LOCAL_PART_LEN {
+        return reject("local part length");
+    }
+    if !local.chars().all(is_local_char)
+        || local.starts_with('.')
+        || local.ends_with('.')
+        || local.contains("..")
+    {
+        return reject("local part characters");
+    }
+    if !is_valid_domain(domain) {
+        return reject("domain");
+    }
+    Ok(())
+}
+
+// The unquoted "atext" characters of RFC 5322, plus the dot separator.
+fn is_local_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c)
+}
+
+// At least two dot-separated labels of letters, digits and inner hyphens.
+pub(crate) fn is_valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// One check a signup has to pass before the secure handlers act on it, e.g.
+/// refusing disposable domains. `AppState::signup_validators` runs a
+/// [`ValidatorChain`] of them in order; add your own with
+/// `AppStateBuilder::signup_validator`.
+///
+/// Validators run on the worker thread, so one that looks something up
+/// should answer from a cache rather than block on the network.
+pub trait SignupValidator: Send + Sync + fmt::Debug {
+    /// Refuses the signup with the error its client should see, e.g.
+    /// `ApiError::BadRequest` for a `400`.
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError>;
+}
+
+/// Validators run in order, stopping at the first that refuses the signup.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorChain {
+    validators: Vec<Arc<dyn SignupValidator>>,
+}
+
+impl ValidatorChain {
+    pub fn new(validators: Vec<Arc<dyn SignupValidator>>) -> Self {
+        ValidatorChain { validators }
+    }
+
+    /// What every deployment checks: [`NonEmptyEmail`], [`EmailSyntax`], then
+    /// the `allowed_email_domains` through [`EmailDomainAllowlist`].
+    pub fn builtin<I, S>(allowed_email_domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        ValidatorChain::new(vec![
+            Arc::new(NonEmptyEmail),
+            Arc::new(EmailSyntax),
+            Arc::new(EmailDomainAllowlist::new(allowed_email_domains)),
+        ])
+    }
+
+    /// Appends `validator`, to run after those already in the chain.
+    pub fn push(&mut self, validator: Arc<dyn SignupValidator>) {
+        self.validators.push(validator);
+    }
+
+    pub fn len(&self) -> usize {
+        self.validators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+}
+
+impl SignupValidator for ValidatorChain {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        self.validators
+            .iter()
+            .try_for_each(|validator| validator.validate(params))
+    }
+}
+
+/// Refuses an empty email with a `400`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonEmptyEmail;
+
+impl SignupValidator for NonEmptyEmail {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        if params.email.is_empty() {
+            return Err(ApiError::BadRequest {
+                reason: "invalid email: empty".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Refuses an email [`validate_email`] refuses, with a `400`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmailSyntax;
+
+impl SignupValidator for EmailSyntax {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        validate_email(&params.email)
+    }
+}
+
+/// Refuses an email from a domain not among `domains` (`example.com`,
+/// `*.example.com`), with a `403` (`ApiError::EmailDomainNotAllowed`). With no
+/// domains, every one is allowed, as with an empty `allowed_email_domains`.
+#[derive(Debug, Clone, Default)]
+pub struct EmailDomainAllowlist {
+    domains: HostMatcher,
+    allow_all: bool,
+}
+
+impl EmailDomainAllowlist {
+    pub fn new<I, S>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let domains: Vec<S> = domains.into_iter().collect();
+        EmailDomainAllowlist {
+            allow_all: domains.is_empty(),
+            domains: HostMatcher::new(domains, true),
+        }
+    }
+}
+
+impl SignupValidator for EmailDomainAllowlist {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        let allowed = self.allow_all
+            || params
+                .email
+                .rsplit_once('@')
+                .is_some_and(|(_, domain)| self.domains.is_allowed(domain));
+        if !allowed {
+            return Err(ApiError::EmailDomainNotAllowed);
+        }
+        Ok(())
+    }
+}
+
+/// Answers whether a domain can receive mail, for [`MxRecords`]. The crate
+/// has no resolver of its own: plug in the deployment's, ideally with a cache.
+pub trait MxLookup: Send + Sync + fmt::Debug {
+    /// Whether `domain` has at least one MX record. What a failed lookup
+    /// means (refuse, or give the benefit of the doubt) is up to the lookup.
+    fn has_mx_records(&self, domain: &str) -> bool;
+}
+
+/// Refuses an email whose domain has no MX records, with a `400`. Not part of
+/// [`ValidatorChain::builtin`]; add it with `AppStateBuilder::signup_validator`.
+/// Put it after [`EmailSyntax`], which it relies on to have found the domain.
+#[derive(Debug, Clone)]
+pub struct MxRecords {
+    lookup: Arc<dyn MxLookup>,
+}
+
+impl MxRecords {
+    pub fn new(lookup: Arc<dyn MxLookup>) -> Self {
+        MxRecords { lookup }
+    }
+}
+
+impl SignupValidator for MxRecords {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        let Some((_, domain)) = params.email.rsplit_once('@') else {
+            return Err(ApiError::BadRequest {
+                reason: "invalid email: missing '@'".to_string(),
+            });
+        };
+        if !self.lookup.has_mx_records(domain) {
+            return Err(ApiError::BadRequest {
+                reason: "invalid email: the domain has no MX records".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
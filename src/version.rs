@@ -0,0 +1,45 @@
+//! `GET /version`: which build is running, for checking a deployment.
+
+use actix_web::HttpResponse;
+use serde::Serialize;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::HandlerResult;
+
+/// The crate version, from `Cargo.toml`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The (abbreviated) commit the binary was built from, or `unknown` outside a
+/// git checkout. `build.rs` prefers `GIT_COMMIT` if it is set.
+pub const GIT_COMMIT: &str = env!("BUILD_GIT_COMMIT");
+
+// Seconds since the epoch, as `build.rs` found them (or `SOURCE_DATE_EPOCH`).
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+/// What `GET /version` reports. Only what identifies the build: nothing of
+/// the configuration, the host or the toolchain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    /// When the binary was built, in RFC 3339.
+    pub built_at: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let secs = BUILD_TIMESTAMP.parse().unwrap_or(0);
+        BuildInfo {
+            version: VERSION,
+            commit: GIT_COMMIT,
+            built_at: humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs))
+                .to_string(),
+        }
+    }
+}
+
+/// # Build Version
+/// Answers `200` with the [`BuildInfo`] of the running binary.
+pub async fn version() -> HandlerResult {
+    Ok(HttpResponse::Ok().json(BuildInfo::current()))
+}
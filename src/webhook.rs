@@ -0,0 +1,147 @@
+//! Tells an external system about each new signup, with an HMAC-signed POST.
+//!
+//! The receiver can check that a notification came from us by computing
+//! [`signature`] over the raw body with the shared secret and comparing it to
+//! the `X-Signature` header, in constant time.
+
+use reqwest::{Client, Url, header::CONTENT_TYPE};
+use ring::hmac;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::error::ApiError;
+use crate::host::{HostMatcher, is_safe_upstream_host};
+use crate::secrets::Secret;
+
+/// The header that carries the body's signature, `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Where signup notifications go and what they are signed with.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// An `http` or `https` URL. Like the backend host, it must not resolve
+    /// to an internal address unless its host is one of
+    /// `BackendConfig::private_hosts`.
+    pub url: Url,
+    /// The HMAC-SHA256 key shared with the receiver.
+    pub secret: Secret,
+    /// How many notifications may be in flight at once. Beyond that, new
+    /// ones are dropped (and logged) rather than queued.
+    pub max_in_flight: usize,
+}
+
+impl WebhookConfig {
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+    pub fn new(url: Url, secret: impl Into<String>) -> Self {
+        WebhookConfig {
+            url,
+            secret: Secret::new(secret),
+            max_in_flight: Self::DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    email: &'a str,
+}
+
+/// Delivers notifications for a [`WebhookConfig`]. Cloning it shares the
+/// in-flight limit.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    config: WebhookConfig,
+    permits: Arc<Semaphore>,
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_in_flight));
+        Webhook { config, permits }
+    }
+
+    pub fn config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    /// Sends the notification for `email` in the background. Whatever
+    /// happens to it is only logged: a failed notification never fails the
+    /// signup it is about.
+    pub fn notify(&self, client: &Client, private_hosts: &HostMatcher, email: &str) {
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            log::warn!(
+                "Dropped a webhook notification: {} already in flight",
+                self.config.max_in_flight
+            );
+            return;
+        };
+        let webhook = self.clone();
+        let client = client.clone();
+        let private_hosts = private_hosts.clone();
+        let email = email.to_string();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = webhook.deliver(&client, &private_hosts, &email).await {
+                log::warn!("Webhook notification failed: {}", e);
+            }
+            drop(permit);
+        });
+    }
+
+    /// Sends the notification for `email` and waits for the answer, which
+    /// must be a 2xx.
+    ///
+    /// The URL's host is checked by the SSRF guard first. Give it a `client`
+    /// built with `backend::client_builder`, as `AppState::client` is: its
+    /// resolver checks the addresses it connects to again, so the host cannot
+    /// rebind to an internal address in between.
+    pub async fn deliver(
+        &self,
+        client: &Client,
+        private_hosts: &HostMatcher,
+        email: &str,
+    ) -> Result<(), ApiError> {
+        let url = &self.config.url;
+        let host = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            _ => return Err(ApiError::InvalidHost { host: None }),
+        };
+        if !is_safe_upstream_host(&host, private_hosts).await {
+            return Err(ApiError::InvalidHost { host: Some(host) });
+        }
+        let body = serde_json::to_vec(&Notification { email })?;
+        let response = client
+            .post(url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(
+                SIGNATURE_HEADER,
+                signature(self.config.secret.expose(), &body),
+            )
+            .body(body)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(ApiError::Upstream {
+                status: Some(response.status().as_u16()),
+                reason: "webhook answered with an error status".to_string(),
+            })
+        }
+    }
+}
+
+/// The `X-Signature` value for `body`: `sha256=` and the hex HMAC-SHA256 of
+/// the body under `secret`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    tag.as_ref()
+        .iter()
+        .fold(String::from("sha256="), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
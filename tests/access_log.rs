@@ -0,0 +1,123 @@
+//! `AccessLog` writes one line per request at the configured verbosity, and
+//! never logs credentials.
+
+use actix_web::{App, HttpResponse, test, web};
+use serde_json::Value;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use uncaught_exception::middleware::{AccessLog, AccessLogConfig, AccessLogVerbosity};
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn lines(&self) -> Vec<Value> {
+        let bytes = self.0.lock().unwrap();
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("every line is JSON"))
+            .collect()
+    }
+}
+
+// Sends `req` through an app logging at `config`, and returns the lines logged.
+async fn log_of(config: AccessLogConfig, req: test::TestRequest) -> Vec<Value> {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_writer(move || writer.clone())
+        .finish();
+    // The test runtime is single-threaded, so a thread-local default suffices.
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(AccessLog::new(&config))
+            .route("/ping", web::post().to(HttpResponse::NoContent)),
+    )
+    .await;
+    test::call_service(&app, req.to_request()).await;
+    captured.lines()
+}
+
+fn config(verbosity: AccessLogVerbosity) -> AccessLogConfig {
+    AccessLogConfig {
+        verbosity,
+        ..AccessLogConfig::default()
+    }
+}
+
+#[actix_web::test]
+async fn basic_logs_one_line_without_the_query() {
+    let req = test::TestRequest::post()
+        .uri("/ping?email=user@good.com")
+        .insert_header(("Authorization", "Bearer access-canary"));
+    let lines = log_of(config(AccessLogVerbosity::Basic), req).await;
+    assert_eq!(lines.len(), 1, "{lines:?}");
+    let line = &lines[0];
+    assert_eq!(line["message"], "access");
+    assert_eq!(line["target"], "access_log");
+    assert_eq!(line["method"], "POST");
+    assert_eq!(line["path"], "/ping");
+    assert_eq!(line["status"], 204);
+    assert!(line["latency_ms"].as_f64().is_some_and(|ms| ms >= 0.0));
+    assert!(line.get("headers").is_none());
+
+    let raw = line.to_string();
+    assert!(!raw.contains("user@good.com"), "{raw}");
+    assert!(!raw.contains("access-canary"), "{raw}");
+}
+
+#[actix_web::test]
+async fn verbose_redacts_credentials() {
+    let req = test::TestRequest::post()
+        .uri("/ping")
+        .insert_header(("Authorization", "Bearer access-canary"))
+        .insert_header(("Cookie", "session=cookie-canary"))
+        .insert_header(("X-Api-Key", "key-canary"))
+        .insert_header(("User-Agent", "access-log-test"))
+        .set_payload("body-canary");
+    let config = AccessLogConfig {
+        verbosity: AccessLogVerbosity::Verbose,
+        sensitive_headers: vec!["X-API-KEY".to_string()],
+    };
+    let lines = log_of(config, req).await;
+    assert_eq!(lines.len(), 1, "{lines:?}");
+
+    let headers: Value = serde_json::from_str(lines[0]["headers"].as_str().unwrap()).unwrap();
+    assert_eq!(headers["authorization"], "[REDACTED]");
+    assert_eq!(headers["cookie"], "[REDACTED]");
+    assert_eq!(headers["x-api-key"], "[REDACTED]");
+    assert_eq!(headers["user-agent"], "access-log-test");
+
+    let raw = lines[0].to_string();
+    for canary in [
+        "access-canary",
+        "cookie-canary",
+        "key-canary",
+        "body-canary",
+    ] {
+        assert!(!raw.contains(canary), "{canary} in {raw}");
+    }
+}
+
+#[actix_web::test]
+async fn off_logs_nothing() {
+    let req = test::TestRequest::post().uri("/ping");
+    let lines = log_of(config(AccessLogVerbosity::Off), req).await;
+    assert!(lines.is_empty(), "{lines:?}");
+}
@@ -0,0 +1,79 @@
+//! `AdminAuth` lets through only `Authorization: Bearer <admin_token>`, and
+//! every other request gets the same generic `401`.
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::middleware::{AdminAuth, AuthConfig, constant_time_eq};
+
+const TOKEN: &str = "admin-token-7d8e9f";
+
+async fn call(admin_token: Option<&str>, authorization: Option<&str>) -> (StatusCode, Value) {
+    let config = AuthConfig {
+        admin_token: admin_token.map(str::to_string),
+    };
+    let app = test::init_service(
+        App::new()
+            .service(
+                web::scope("/admin")
+                    .wrap(AdminAuth::new(&config))
+                    .route("/status", web::get().to(HttpResponse::Ok)),
+            )
+            .route("/public", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let mut req = test::TestRequest::get().uri("/admin/status");
+    if let Some(value) = authorization {
+        req = req.insert_header(("Authorization", value));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn the_right_token_is_let_through() {
+    let (status, _) = call(Some(TOKEN), Some(&format!("Bearer {TOKEN}"))).await;
+    assert_eq!(status, StatusCode::OK);
+    // The scheme is case-insensitive.
+    let (status, _) = call(Some(TOKEN), Some(&format!("bearer {TOKEN}"))).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn every_failure_gets_the_same_generic_401() {
+    let near_miss = format!("Bearer {}", &TOKEN[..TOKEN.len() - 1]);
+    let cases = [
+        (Some(TOKEN), None),
+        (Some(TOKEN), Some("Bearer wrong-token")),
+        (Some(TOKEN), Some(near_miss.as_str())),
+        (Some(TOKEN), Some("Basic YWRtaW46YWRtaW4=")),
+        (Some(TOKEN), Some(TOKEN)),
+        // Without a configured token, nothing gets in, not even an empty one.
+        (None, Some("Bearer ")),
+        (Some(""), Some("Bearer ")),
+    ];
+    let mut bodies = Vec::new();
+    for (configured, presented) in cases {
+        let (status, body) = call(configured, presented).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{presented:?}");
+        assert_eq!(body["error"]["code"], "UNAUTHORIZED", "{body}");
+        assert!(!body.to_string().contains(TOKEN), "{body}");
+        bodies.push(body["error"]["message"].clone());
+    }
+    // No hint which check failed, or how close the token was.
+    assert!(
+        bodies.windows(2).all(|pair| pair[0] == pair[1]),
+        "{bodies:?}"
+    );
+}
+
+#[actix_web::test]
+async fn the_comparison_checks_every_byte_and_the_length() {
+    assert!(constant_time_eq(b"same-token", b"same-token"));
+    assert!(!constant_time_eq(b"same-token", b"same-tokeN"));
+    assert!(!constant_time_eq(b"same-token", b"Same-token"));
+    assert!(!constant_time_eq(b"same-token", b"same-token-longer"));
+    assert!(!constant_time_eq(b"", b"x"));
+    assert!(constant_time_eq(b"", b""));
+}
@@ -0,0 +1,64 @@
+//! `GET /admin/config` needs the admin token and never shows the secrets.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::admin::effective_config;
+use uncaught_exception::config::AppState;
+use uncaught_exception::middleware::{AdminAuth, AuthConfig};
+
+const API_KEY: &str = "test-key-9d3f0b2a-config-canary";
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+async fn get(authorization: Option<&str>) -> (StatusCode, String) {
+    let auth = AuthConfig {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+    };
+    let state = AppState::builder()
+        .api_key(API_KEY)
+        .allowed_host("my-app.com")
+        .timeout_ms(2_500)
+        .auth(auth.clone())
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new().app_data(web::Data::new(state)).service(
+            web::scope("/admin")
+                .wrap(AdminAuth::new(&auth))
+                .route("/config", web::get().to(effective_config)),
+        ),
+    )
+    .await;
+    let mut req = test::TestRequest::get().uri("/admin/config");
+    if let Some(value) = authorization {
+        req = req.insert_header(("Authorization", value));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    (status, body)
+}
+
+#[actix_web::test]
+async fn requires_the_admin_token() {
+    for authorization in [None, Some("Bearer wrong"), Some(API_KEY)] {
+        let (status, body) = get(authorization).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{authorization:?}");
+        assert!(!body.contains("allowed_hosts"), "{body}");
+    }
+}
+
+#[actix_web::test]
+async fn shows_the_effective_config_without_secrets() {
+    let (status, body) = get(Some(&format!("Bearer {ADMIN_TOKEN}"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains(API_KEY), "{body}");
+    assert!(!body.contains(ADMIN_TOKEN), "{body}");
+
+    let config: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(config["api_key"], "[REDACTED]");
+    assert_eq!(config["api_key_length"], API_KEY.len());
+    assert_eq!(config["allowed_hosts"], serde_json::json!(["my-app.com"]));
+    assert_eq!(config["backend"]["timeout_ms"], 2_500);
+    assert_eq!(config["auth"]["admin_token_configured"], true);
+    assert_eq!(config["audit"]["sink"], "stdout");
+}
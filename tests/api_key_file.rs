@@ -0,0 +1,71 @@
+//! `api_key_file` (`API_KEY_FILE`) reads the key from a mounted secret,
+//! winning over an inline `api_key`.
+
+use std::path::PathBuf;
+
+use uncaught_exception::config::{AppState, ConfigError};
+
+// Files of their own for each test, since they run in parallel.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("api-key-file-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn config_with(name: &str, lines: &str) -> PathBuf {
+    temp_file(
+        &format!("{name}.toml"),
+        &format!("allowed_hosts = [\"my-app.com\"]\n{lines}\n"),
+    )
+}
+
+#[actix_web::test]
+async fn the_key_is_read_from_the_file_without_its_newline() {
+    let key_file = temp_file("plain", "file-key-0a1b2c\n");
+    let config = config_with("plain", &format!("api_key_file = {:?}", key_file));
+    let state = AppState::from_toml(&config).unwrap();
+    assert_eq!(state.api_key.current().expose(), "file-key-0a1b2c");
+}
+
+#[actix_web::test]
+async fn the_file_wins_over_an_inline_key() {
+    let key_file = temp_file("both", "file-key-3d4e5f\r\n");
+    let config = config_with(
+        "both",
+        &format!("api_key = \"inline-key\"\napi_key_file = {:?}", key_file),
+    );
+    let state = AppState::from_toml(&config).unwrap();
+    assert_eq!(state.api_key.current().expose(), "file-key-3d4e5f");
+}
+
+#[actix_web::test]
+async fn a_missing_file_fails_naming_the_path() {
+    let missing = std::env::temp_dir().join("api-key-file-that-does-not-exist");
+    let config = config_with(
+        "missing",
+        &format!("api_key = \"inline-key\"\napi_key_file = {:?}", missing),
+    );
+    let err = AppState::from_toml(&config).err().unwrap();
+    assert!(matches!(err, ConfigError::ApiKeyFile { .. }), "{err}");
+    assert!(
+        err.to_string().contains(&missing.display().to_string()),
+        "{err}"
+    );
+}
+
+#[actix_web::test]
+async fn an_empty_file_is_refused() {
+    let key_file = temp_file("empty", "\n");
+    let config = config_with("empty", &format!("api_key_file = {:?}", key_file));
+    let err = AppState::from_toml(&config).err().unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "api_key_file",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,119 @@
+//! `AppStateBuilder` validation.
+
+use uncaught_exception::config::{AppState, ConfigError};
+
+#[test]
+fn builds_with_the_required_fields() {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com:8080")
+        .allowed_host("*.my-app.com")
+        .timeout_ms(1_500)
+        .build()
+        .unwrap();
+    assert_eq!(state.api_key.current().expose(), "test-key");
+    assert_eq!(state.allowed_hosts, ["my-app.com:8080", "*.my-app.com"]);
+    assert!(state.host_matcher.is_allowed("api.my-app.com"));
+    assert_eq!(state.backend.timeout_ms, 1_500);
+}
+
+#[test]
+fn rejects_a_missing_api_key() {
+    let err = AppState::builder()
+        .allowed_host("my-app.com")
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err, ConfigError::MissingApiKey), "{err}");
+}
+
+#[test]
+fn rejects_an_empty_api_key() {
+    let err = AppState::builder()
+        .api_key("")
+        .allowed_host("my-app.com")
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err, ConfigError::MissingApiKey), "{err}");
+}
+
+#[test]
+fn rejects_an_empty_host_whitelist() {
+    let err = AppState::builder()
+        .api_key("test-key")
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err, ConfigError::NoAllowedHosts), "{err}");
+}
+
+#[test]
+fn rejects_a_zero_timeout() {
+    let err = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .timeout_ms(0)
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "backend.timeout_ms",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn always_redacts_the_api_key_in_logged_urls() {
+    let base = || {
+        AppState::builder()
+            .api_key("test-key")
+            .allowed_host("my-app.com")
+    };
+    let state = base().build().unwrap();
+    assert_eq!(state.redact_query_params, ["api_key"]);
+    let state = base()
+        .redact_query_param("signature")
+        .redact_query_param("API_KEY")
+        .build()
+        .unwrap();
+    assert_eq!(state.redact_query_params, ["signature", "API_KEY"]);
+}
+
+#[test]
+fn validates_the_backend_path() {
+    let base = || {
+        AppState::builder()
+            .api_key("test-key")
+            .allowed_host("my-app.com")
+    };
+    assert_eq!(base().build().unwrap().backend.path, "/v1/waitlist");
+    assert_eq!(
+        base()
+            .backend_path("/v2/signups")
+            .build()
+            .unwrap()
+            .backend
+            .path,
+        "/v2/signups"
+    );
+    for path in ["v1/waitlist", "", "/v1/waitlist?debug=1", "/v1/waitlist#x"] {
+        let err = base().backend_path(path).build().err().unwrap();
+        assert!(
+            matches!(
+                err,
+                ConfigError::InvalidValue {
+                    name: "backend.path",
+                    ..
+                }
+            ),
+            "{path:?}: {err}"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Security-relevant events reach the audit sink.
+
+use actix_web::{App, http::StatusCode, test, web};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use uncaught_exception::audit::{AuditEvent, AuditEventKind, AuditLogger, AuditSink, FileSink};
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::secure_waitlist;
+use uncaught_exception::middleware::CorrelationId;
+
+#[derive(Clone, Default)]
+struct MemorySink(Arc<Mutex<Vec<AuditEvent>>>);
+
+impl AuditSink for MemorySink {
+    fn record(&self, event: &AuditEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[actix_web::test]
+async fn rejected_host_emits_host_rejected() {
+    let sink = MemorySink::default();
+    AuditLogger::new(sink.clone()).install();
+
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(CorrelationId)
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    let peer: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+    let req = test::TestRequest::get()
+        .uri("/secure/waitlist?email=user@good.com")
+        .insert_header(("Host", "evil.com"))
+        .insert_header(("X-Request-Id", "audit-host-rejected"))
+        .peer_addr(peer)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let events = sink.0.lock().unwrap();
+    let event = events
+        .iter()
+        .find(|e| e.request_id.as_deref() == Some("audit-host-rejected"))
+        .expect("no audit event for the request");
+    assert_eq!(
+        event.kind,
+        AuditEventKind::HostRejected {
+            host: Some("evil.com".to_string())
+        }
+    );
+    assert_eq!(event.client_ip, Some(IpAddr::from([203, 0, 113, 7])));
+    assert!(!event.timestamp.is_empty());
+}
+
+#[actix_web::test]
+async fn file_sink_appends_json_lines() {
+    let path = std::env::temp_dir().join(format!("audit-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let sink = FileSink::open(&path).unwrap();
+    sink.record(&AuditEvent::new(AuditEventKind::SecretRedacted {
+        count: 2,
+    }));
+    sink.record(&AuditEvent::new(AuditEventKind::AuthFailed {
+        path: "/admin/config".to_string(),
+    }));
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["event"], "secret_redacted");
+    assert_eq!(lines[0]["count"], 2);
+    assert_eq!(lines[1]["event"], "auth_failed");
+    assert_eq!(lines[1]["path"], "/admin/config");
+}
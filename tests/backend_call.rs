@@ -0,0 +1,52 @@
+//! The secure handler really POSTs the signup to the backend, and an upstream
+//! error becomes a generic `502` that never echoes the upstream's answer.
+
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::Value;
+
+use common::{answering_https_backend, local_backend_builder, request_with_host, test_app};
+
+const UPSTREAM_DETAIL: &str = "stack trace from the backend, db=prod-7";
+
+// Signs up once against a backend that answers with `status`. Returns the
+// client's status and body, and how many calls the backend got.
+async fn sign_up(status: StatusCode) -> (StatusCode, Value, usize) {
+    let (host, calls) = answering_https_backend(status, UPSTREAM_DETAIL);
+    let mut state = local_backend_builder(&host).build().unwrap();
+    common::trust_fixture_cert(&mut state);
+    let app = test_app(state).await;
+
+    let res =
+        test::call_service(&app, request_with_host(&host, "user@good.com").to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (
+        status,
+        serde_json::from_slice(&body).unwrap_or(Value::Null),
+        calls.load(Ordering::SeqCst),
+    )
+}
+
+#[actix_web::test]
+async fn a_signup_reaches_the_backend() {
+    let (status, _, calls) = sign_up(StatusCode::OK).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(calls, 1);
+}
+
+#[actix_web::test]
+async fn an_upstream_error_is_a_generic_502() {
+    for upstream in [StatusCode::BAD_REQUEST, StatusCode::SERVICE_UNAVAILABLE] {
+        let (status, body, calls) = sign_up(upstream).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY, "{upstream}");
+        assert_eq!(calls, 1, "{upstream}");
+        assert_eq!(body["error"]["code"], "UPSTREAM_UNAVAILABLE", "{body}");
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(!message.contains(upstream.as_str()), "{message}");
+        assert!(!body.to_string().contains("prod-7"), "{body}");
+    }
+}
@@ -0,0 +1,100 @@
+//! The secure handler submits signups through `AppState::backend_client`, so a
+//! fake one can stand in for the backend without any network.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::Value;
+use uncaught_exception::error::ApiError;
+
+use common::{FakeBackend, local_backend_builder, request_with_host, test_app};
+
+// Loopback, so the SSRF check passes without DNS.
+const HOST: &str = "127.0.0.1";
+
+async fn sign_up(backend: &std::sync::Arc<FakeBackend>, times: usize) -> Vec<(StatusCode, Value)> {
+    let state = local_backend_builder(HOST)
+        .backend_client(backend.clone())
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+    let mut answers = Vec::new();
+    for _ in 0..times {
+        let req = request_with_host(HOST, "User@Good.com").to_request();
+        let res = test::call_service(&app, req).await;
+        let status = res.status();
+        let body = test::read_body(res).await;
+        answers.push((status, serde_json::from_slice(&body).unwrap_or(Value::Null)));
+    }
+    answers
+}
+
+#[actix_web::test]
+async fn a_signup_the_backend_accepts_succeeds() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let answers = sign_up(&backend, 1).await;
+    assert_eq!(answers[0].0, StatusCode::OK);
+    assert_eq!(
+        backend.calls(),
+        [(HOST.to_string(), "User@Good.com".to_string())]
+    );
+}
+
+#[actix_web::test]
+async fn a_backend_timeout_is_a_504_and_the_signup_can_be_retried() {
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::UpstreamTimeout {
+            reason: "operation timed out".to_string(),
+        })
+    });
+    let answers = sign_up(&backend, 2).await;
+    for (status, body) in &answers {
+        assert_eq!(*status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["error"]["code"], "UPSTREAM_TIMEOUT");
+    }
+    // The failed signup was taken back, so the retry reached the backend too.
+    assert_eq!(backend.calls().len(), 2);
+}
+
+#[actix_web::test]
+async fn a_backend_5xx_is_a_generic_502() {
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::Upstream {
+            status: Some(503),
+            reason: "backend answered 503".to_string(),
+        })
+    });
+    let answers = sign_up(&backend, 1).await;
+    let (status, body) = &answers[0];
+    assert_eq!(*status, StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        body["error"]["message"],
+        "The service is temporarily unavailable. Please try again later."
+    );
+    assert!(!body.to_string().contains("503"), "{body}");
+}
+
+#[actix_web::test]
+async fn an_internal_error_gets_only_the_sanitized_json_body() {
+    // An error whose detail names the backend URL, key and all.
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::Internal {
+            reason: "GET https://127.0.0.1/v1/waitlist?api_key=test-key failed".to_string(),
+        })
+    });
+    let answers = sign_up(&backend, 1).await;
+    let (status, body) = &answers[0];
+    assert_eq!(*status, StatusCode::INTERNAL_SERVER_ERROR);
+    let error = body["error"].as_object().expect("a JSON error body");
+    assert_eq!(error["code"], "INTERNAL_ERROR");
+    assert_eq!(
+        error["message"],
+        "Oops! Something went wrong. Please try again later."
+    );
+    assert!(error.contains_key("request_id"));
+    let body = body.to_string();
+    assert!(
+        !body.contains("api_key") && !body.contains("127.0.0.1"),
+        "{body}"
+    );
+}
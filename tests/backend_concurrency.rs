@@ -0,0 +1,78 @@
+//! Backend calls are capped; a request that finds every slot taken gets a
+//! quick 503 instead of queueing behind them.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::ConfigError;
+use uncaught_exception::handlers::secure_waitlist;
+
+use common::{local_backend_builder, silent_backend, test_builder};
+
+#[actix_web::test]
+async fn the_overflow_request_gets_a_quick_503() {
+    // Every call holds its slot until the backend timeout.
+    let host = silent_backend();
+    let state = local_backend_builder(&host)
+        .timeout_ms(1_000)
+        .max_concurrent_requests(1)
+        .queue_timeout_ms(50)
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    let request = |email: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/secure/waitlist?email={email}"))
+            .insert_header(("Host", host.as_str()))
+            .to_request()
+    };
+
+    let first = test::call_service(&app, request("first@good.com"));
+    let overflow = async {
+        // Let the first request take the only slot.
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+        let started = Instant::now();
+        let res = test::call_service(&app, request("second@good.com")).await;
+        (res, started.elapsed())
+    };
+    let (first, (overflow, elapsed)) = futures_util::future::join(first, overflow).await;
+
+    assert_eq!(overflow.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(elapsed < Duration::from_millis(500), "{elapsed:?}");
+    let body: Value = test::read_body_json(overflow).await;
+    assert_eq!(body["error"]["code"], "OVERLOADED");
+    assert_eq!(
+        body["error"]["message"],
+        "The service is temporarily unavailable. Please try again later."
+    );
+
+    // The first request did get its turn, and timed out at the backend.
+    assert_eq!(first.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[actix_web::test]
+async fn a_zero_limit_is_refused() {
+    let err = test_builder()
+        .max_concurrent_requests(0)
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "backend.max_concurrent_requests",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,96 @@
+//! How `build_backend_url` classifies the `Host` values it is given.
+
+use uncaught_exception::backend::{WAITLIST_PATH, build_backend_url, build_batch_url};
+use uncaught_exception::error::ApiError;
+
+const API_KEY: &str = "test-key";
+const EMAIL: &str = "user@good.com";
+
+#[test]
+fn rejects_hosts_with_whitespace() {
+    let err = build_backend_url("bad host", WAITLIST_PATH, API_KEY, EMAIL).unwrap_err();
+    assert!(matches!(err, ApiError::InvalidHost { .. }), "{err}");
+}
+
+#[test]
+fn rejects_hosts_with_userinfo() {
+    // Parsed leniently, this would send the request (and the key) to real.com.
+    for host in ["evil.com@real.com", "user:pass@my-app.com"] {
+        let err = build_backend_url(host, WAITLIST_PATH, API_KEY, EMAIL).unwrap_err();
+        assert!(matches!(err, ApiError::InvalidHost { .. }), "{host}: {err}");
+    }
+}
+
+#[test]
+fn rejects_hosts_that_start_a_query_or_fragment() {
+    // Parsed, `path` would end up in the query or fragment and the call go to `/`.
+    for host in ["my-app.com?x", "my-app.com#x"] {
+        let err = build_backend_url(host, WAITLIST_PATH, API_KEY, EMAIL).unwrap_err();
+        assert!(matches!(err, ApiError::InvalidHost { .. }), "{host}: {err}");
+    }
+}
+
+#[test]
+fn rejects_empty_and_control_character_hosts() {
+    for host in [
+        "",
+        "my-app.com\r\nX-Injected: 1",
+        "my-app.com\0",
+        "my-app.com/evil",
+    ] {
+        let err = build_backend_url(host, WAITLIST_PATH, API_KEY, EMAIL).unwrap_err();
+        assert!(
+            matches!(err, ApiError::InvalidHost { .. }),
+            "{host:?}: {err}"
+        );
+    }
+}
+
+#[test]
+fn reports_unparsable_hosts_as_url_parse_errors() {
+    for host in ["my-app.com:99999", "[::1"] {
+        let err = build_backend_url(host, WAITLIST_PATH, API_KEY, EMAIL).unwrap_err();
+        assert!(matches!(err, ApiError::UrlParse { .. }), "{host}: {err}");
+    }
+}
+
+#[test]
+fn builds_the_url_for_a_valid_host() {
+    let url = build_backend_url("my-app.com:8080", WAITLIST_PATH, API_KEY, EMAIL).unwrap();
+    assert_eq!(
+        url.as_str(),
+        "https://my-app.com:8080/v1/waitlist?api_key=test-key&email=user%40good.com"
+    );
+}
+
+#[test]
+fn builds_urls_for_the_given_path() {
+    for (path, expected) in [
+        (
+            "/v2/signups",
+            "https://my-app.com/v2/signups?api_key=test-key&email=user%40good.com",
+        ),
+        (
+            "/api/beta/waitlist",
+            "https://my-app.com/api/beta/waitlist?api_key=test-key&email=user%40good.com",
+        ),
+    ] {
+        let url = build_backend_url("my-app.com", path, API_KEY, EMAIL).unwrap();
+        assert_eq!(url.as_str(), expected);
+    }
+}
+
+#[test]
+fn builds_one_url_for_a_batch() {
+    let url = build_batch_url(
+        "my-app.com",
+        WAITLIST_PATH,
+        API_KEY,
+        &["a@good.com", "b&c@good.com"],
+    )
+    .unwrap();
+    assert_eq!(
+        url.as_str(),
+        "https://my-app.com/v1/waitlist?api_key=test-key&email=a%40good.com&email=b%26c%40good.com"
+    );
+}
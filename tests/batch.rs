@@ -0,0 +1,216 @@
+//! With `[backend.batch]` enabled, signups are answered with a 202 and
+//! submitted to the backend in batches by a background task.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use std::sync::Arc;
+use std::time::Duration;
+use uncaught_exception::batch::{BatchConfig, Batcher, Submission, next_batch};
+use uncaught_exception::config::ConfigError;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::metrics::Metrics;
+use uncaught_exception::service::flush_batches;
+
+use common::{
+    FakeBackend, local_backend_builder, recording_https_backend, request_with_host, test_app,
+    trust_fixture_cert,
+};
+
+// Loopback, so the SSRF check passes without DNS.
+const HOST: &str = "127.0.0.1";
+
+fn batching() -> BatchConfig {
+    BatchConfig {
+        enabled: true,
+        max_size: 10,
+        max_delay_ms: 20,
+        queue_capacity: 16,
+    }
+}
+
+fn submission(email: &str) -> Submission {
+    Submission {
+        host: HOST.to_string(),
+        email: email.to_string(),
+        key: email.to_lowercase(),
+    }
+}
+
+#[actix_web::test]
+async fn signups_are_accepted_with_a_202_and_submitted_later() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let state = local_backend_builder(HOST)
+        .backend_client(backend.clone())
+        .batch(batching())
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+
+    for email in ["a@good.com", "b@good.com"] {
+        let res = test::call_service(&app, request_with_host(HOST, email).to_request()).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+    }
+    // A duplicate is still refused up front.
+    let res = test::call_service(&app, request_with_host(HOST, "A@good.com").to_request()).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        backend.calls(),
+        [
+            (HOST.to_string(), "a@good.com".to_string()),
+            (HOST.to_string(), "b@good.com".to_string()),
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn a_failed_batch_takes_its_signups_back() {
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::Upstream {
+            status: Some(503),
+            reason: "backend answered 503".to_string(),
+        })
+    });
+    let state = local_backend_builder(HOST)
+        .backend_client(backend.clone())
+        .batch(batching())
+        .build()
+        .unwrap();
+    let waitlist = state.waitlist.clone();
+    let metrics = Metrics::new().unwrap();
+    metrics.track_batcher(&state.batcher).unwrap();
+    let app = test_app(state).await;
+
+    let res = test::call_service(&app, request_with_host(HOST, "a@good.com").to_request()).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(backend.calls().len(), 1);
+    assert!(!waitlist.contains("a@good.com").unwrap());
+    // The client got its 202, so the loss is counted where operators see it.
+    let scraped = metrics.render().unwrap();
+    assert!(
+        scraped.contains("batched_signups_failed_total 1"),
+        "{scraped}"
+    );
+}
+
+#[actix_web::test]
+async fn a_batch_is_one_backend_call_per_host() {
+    let (host, queries) = recording_https_backend();
+    let mut state = local_backend_builder(&host)
+        .api_key("batch-key")
+        .batch(BatchConfig {
+            max_delay_ms: 200,
+            ..batching()
+        })
+        .build()
+        .unwrap();
+    trust_fixture_cert(&mut state);
+    let app = test_app(state).await;
+
+    for email in ["a@good.com", "b@good.com"] {
+        let res = test::call_service(&app, request_with_host(&host, email).to_request()).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+    }
+    for _ in 0..50 {
+        if !queries.lock().unwrap().is_empty() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        *queries.lock().unwrap(),
+        ["api_key=batch-key&email=a%40good.com&email=b%40good.com"]
+    );
+}
+
+#[actix_web::test]
+async fn closing_the_queue_submits_what_is_still_in_it() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let state = local_backend_builder(HOST)
+        .backend_client(backend.clone())
+        // Longer than the test waits, so only the close cuts the batch short.
+        .batch(BatchConfig {
+            max_delay_ms: 60_000,
+            ..batching()
+        })
+        .build()
+        .unwrap();
+    // Started ahead of the handlers, as `main` does.
+    let batcher = state.batcher.clone();
+    let receiver = batcher.take_receiver().unwrap();
+    let state = Arc::new(state);
+    let flusher = actix_web::rt::spawn(flush_batches(state.clone(), receiver));
+    let app = test_app(state.as_ref().clone()).await;
+
+    for email in ["a@good.com", "b@good.com"] {
+        let res = test::call_service(&app, request_with_host(HOST, email).to_request()).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+    }
+    batcher.close();
+    actix_web::rt::time::timeout(Duration::from_secs(5), flusher)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(backend.batches(), [2]);
+    assert!(state.waitlist.contains("a@good.com").unwrap());
+
+    // Once closed, the queue refuses signups, and takes them back.
+    let res = test::call_service(&app, request_with_host(HOST, "c@good.com").to_request()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!state.waitlist.contains("c@good.com").unwrap());
+}
+
+#[actix_web::test]
+async fn queued_signups_are_collected_into_deduplicated_batches() {
+    let config = BatchConfig {
+        max_size: 2,
+        ..batching()
+    };
+    let batcher = Batcher::new(config.clone());
+    for email in ["a@good.com", "A@good.com", "b@good.com", "c@good.com"] {
+        batcher.enqueue(submission(email)).unwrap();
+    }
+    let mut receiver = batcher.take_receiver().unwrap();
+    assert!(batcher.take_receiver().is_none());
+
+    let first = next_batch(&mut receiver, &config).await.unwrap();
+    assert_eq!(first, [submission("a@good.com"), submission("b@good.com")]);
+    let second = next_batch(&mut receiver, &config).await.unwrap();
+    assert_eq!(second, [submission("c@good.com")]);
+}
+
+#[actix_web::test]
+async fn a_full_queue_is_refused_as_overloaded() {
+    let batcher = Batcher::new(BatchConfig {
+        queue_capacity: 1,
+        ..batching()
+    });
+    batcher.enqueue(submission("a@good.com")).unwrap();
+    assert!(matches!(
+        batcher.enqueue(submission("b@good.com")),
+        Err(ApiError::Overloaded { .. })
+    ));
+}
+
+#[actix_web::test]
+async fn an_empty_batch_size_is_refused() {
+    let err = local_backend_builder(HOST)
+        .batch(BatchConfig {
+            max_size: 0,
+            ..batching()
+        })
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        ConfigError::InvalidValue {
+            name: "backend.batch.max_size",
+            ..
+        }
+    ));
+}
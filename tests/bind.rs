@@ -0,0 +1,55 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use uncaught_exception::server::{BindError, bind_or_explain};
+
+#[test]
+fn binds_a_free_port() {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let listener = bind_or_explain(addr, "--port or PORT").unwrap();
+    assert_ne!(listener.local_addr().unwrap().port(), 0);
+}
+
+#[test]
+fn explains_a_port_in_use() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = taken.local_addr().unwrap();
+
+    let err = bind_or_explain(addr, "--port or PORT").unwrap_err();
+    assert!(matches!(err, BindError::AddrInUse { .. }));
+    assert_eq!(
+        err.to_string(),
+        format!("port {} already in use; set --port or PORT", addr.port())
+    );
+    assert_eq!(err.exit_code(), 3);
+}
+
+#[test]
+fn explains_an_address_not_on_this_machine() {
+    // TEST-NET-1 is never assigned to a local interface.
+    let addr: SocketAddr = "192.0.2.1:0".parse().unwrap();
+
+    let err = bind_or_explain(addr, "--port or PORT").unwrap_err();
+    assert!(matches!(err, BindError::AddrNotAvailable { .. }));
+    assert!(err.to_string().contains("set --bind or BIND_ADDRESS"));
+    assert_eq!(err.exit_code(), 5);
+}
+
+#[test]
+fn exit_codes_differ_from_configuration_errors() {
+    let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+    let errors = [
+        BindError::AddrInUse {
+            addr,
+            setting: "TLS_PORT",
+        },
+        BindError::PermissionDenied { addr },
+        BindError::AddrNotAvailable { addr },
+        BindError::Other {
+            addr,
+            source: std::io::Error::other("boom"),
+        },
+    ];
+    let mut codes: Vec<i32> = errors.iter().map(BindError::exit_code).collect();
+    assert!(codes.iter().all(|&code| code > 2));
+    codes.dedup();
+    assert_eq!(codes.len(), errors.len());
+}
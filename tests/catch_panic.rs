@@ -0,0 +1,52 @@
+//! A panicking handler answers a generic `500` instead of dropping the
+//! connection, and the worker goes on serving.
+
+use std::net::TcpListener;
+
+use actix_web::{App, HttpResponse, HttpServer, web};
+use serde_json::Value;
+use uncaught_exception::messages::DEFAULT_GENERIC_ERROR;
+use uncaught_exception::middleware::CatchPanic;
+
+async fn panicking_handler() -> HttpResponse {
+    panic!("internal detail: index out of range in worker state")
+}
+
+#[actix_web::test]
+async fn a_panic_is_a_generic_500_and_the_worker_survives() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(|| {
+        App::new()
+            .wrap(CatchPanic)
+            .route("/boom", web::get().to(panicking_handler))
+            .route("/ok", web::get().to(HttpResponse::Ok))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_web::rt::spawn(server);
+    let client = reqwest::Client::new();
+
+    for _ in 0..3 {
+        let res = client
+            .get(format!("http://127.0.0.1:{port}/boom"))
+            .send()
+            .await
+            .expect("an answer, not a dropped connection");
+        assert_eq!(res.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+        assert_eq!(body["error"]["message"], DEFAULT_GENERIC_ERROR);
+        assert!(!body.to_string().contains("index out of range"), "{body}");
+    }
+
+    // The only worker is still there.
+    let res = client
+        .get(format!("http://127.0.0.1:{port}/ok"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
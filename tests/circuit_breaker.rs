@@ -0,0 +1,161 @@
+//! The circuit breaker opens after repeated backend failures, fails fast while
+//! open, and closes again after a successful trial call.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use uncaught_exception::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::secure_waitlist;
+
+const COOLDOWN: Duration = Duration::from_millis(50);
+
+fn breaker(failure_threshold: u32) -> CircuitBreaker {
+    CircuitBreaker::new(CircuitBreakerConfig {
+        enabled: true,
+        failure_threshold,
+        cooldown_ms: COOLDOWN.as_millis() as u64,
+    })
+}
+
+fn unavailable() -> ApiError {
+    ApiError::Upstream {
+        status: None,
+        reason: "connection refused".to_string(),
+    }
+}
+
+// Runs one call through `breaker`, counting whether it actually ran.
+async fn call(
+    breaker: &CircuitBreaker,
+    calls: &AtomicU32,
+    result: Result<(), ApiError>,
+) -> Result<(), ApiError> {
+    breaker
+        .call(async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            result
+        })
+        .await
+}
+
+#[actix_web::test]
+async fn opens_after_the_threshold_and_fails_fast() {
+    let breaker = breaker(3);
+    let calls = AtomicU32::new(0);
+    for _ in 0..3 {
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        call(&breaker, &calls, Err(unavailable()))
+            .await
+            .unwrap_err();
+    }
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    let err = call(&breaker, &calls, Ok(())).await.unwrap_err();
+    assert!(matches!(err, ApiError::CircuitOpen { .. }), "{err}");
+    assert_eq!(
+        calls.load(Ordering::SeqCst),
+        3,
+        "an open circuit must not call"
+    );
+}
+
+#[actix_web::test]
+async fn a_successful_trial_closes_the_circuit() {
+    let breaker = breaker(1);
+    let calls = AtomicU32::new(0);
+    call(&breaker, &calls, Err(unavailable()))
+        .await
+        .unwrap_err();
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    actix_web::rt::time::sleep(COOLDOWN).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    call(&breaker, &calls, Ok(())).await.unwrap();
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    call(&breaker, &calls, Ok(())).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn a_failed_trial_reopens_the_circuit() {
+    let breaker = breaker(2);
+    let calls = AtomicU32::new(0);
+    for _ in 0..2 {
+        call(&breaker, &calls, Err(unavailable()))
+            .await
+            .unwrap_err();
+    }
+    actix_web::rt::time::sleep(COOLDOWN).await;
+    call(&breaker, &calls, Err(unavailable()))
+        .await
+        .unwrap_err();
+    assert_eq!(breaker.state(), CircuitState::Open);
+    let err = call(&breaker, &calls, Ok(())).await.unwrap_err();
+    assert!(matches!(err, ApiError::CircuitOpen { .. }), "{err}");
+}
+
+#[actix_web::test]
+async fn client_errors_and_successes_reset_the_count() {
+    let breaker = breaker(2);
+    let calls = AtomicU32::new(0);
+    let refused = || ApiError::Upstream {
+        status: Some(400),
+        reason: "backend responded with 400".to_string(),
+    };
+    for _ in 0..3 {
+        call(&breaker, &calls, Err(refused())).await.unwrap_err();
+    }
+    call(&breaker, &calls, Err(unavailable()))
+        .await
+        .unwrap_err();
+    call(&breaker, &calls, Ok(())).await.unwrap();
+    call(&breaker, &calls, Err(unavailable()))
+        .await
+        .unwrap_err();
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[actix_web::test]
+async fn the_handler_answers_503_while_the_circuit_is_open() {
+    // Nothing listens on port 1, so every backend call fails.
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("127.0.0.1:1")
+        .private_host("127.0.0.1")
+        .max_retries(0)
+        .circuit_breaker(CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown_ms: 60_000,
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    let get = || {
+        test::TestRequest::get()
+            .uri("/secure/waitlist?email=user@good.com")
+            .insert_header(("Host", "127.0.0.1:1"))
+            .to_request()
+    };
+
+    for _ in 0..2 {
+        let res = test::call_service(&app, get()).await;
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    }
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "UPSTREAM_UNAVAILABLE");
+    assert_eq!(
+        body["error"]["message"],
+        "The service is temporarily unavailable. Please try again later."
+    );
+}
@@ -0,0 +1,74 @@
+//! Command-line parsing, without binding anything.
+
+use clap::{Parser, error::ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use uncaught_exception::cli::Cli;
+use uncaught_exception::server::ServerConfig;
+
+fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(std::iter::once("uncaught_exception").chain(args.iter().copied()))
+}
+
+#[test]
+fn defaults_match_the_historical_address() {
+    // Without the flags, the `[server]` settings apply, and default to these.
+    let cli = parse(&[]).unwrap();
+    assert_eq!(cli.bind, None);
+    assert_eq!(cli.port, None);
+    let server = ServerConfig::default();
+    assert_eq!(server.bind, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert_eq!(server.port, 8080);
+    assert!(!cli.tls);
+    assert_eq!(cli.log_level, None);
+    assert!(!cli.production);
+}
+
+#[test]
+fn parses_every_option() {
+    let cli = parse(&[
+        "--bind",
+        "::1",
+        "--port=9000",
+        "--config",
+        "prod.toml",
+        "--tls",
+        "--log-level",
+        "debug",
+        "--production",
+    ])
+    .unwrap();
+    assert_eq!(cli.bind, Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    assert_eq!(cli.port, Some(9000));
+    assert_eq!(cli.config.as_deref(), Some(Path::new("prod.toml")));
+    assert!(cli.tls);
+    assert_eq!(cli.log_level.as_deref(), Some("debug"));
+    assert!(cli.production);
+}
+
+#[test]
+fn rejects_invalid_values() {
+    for args in [
+        &["--bind", "localhost"][..],
+        &["--bind", "10.0.0"],
+        &["--port", "99999"],
+        &["--port", "http"],
+    ] {
+        let err = parse(args).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation, "{args:?}");
+    }
+}
+
+#[test]
+fn rejects_unknown_and_incomplete_arguments() {
+    assert_eq!(
+        parse(&["--verbose"]).unwrap_err().kind(),
+        ErrorKind::UnknownArgument
+    );
+    assert_eq!(
+        parse(&["--port"]).unwrap_err().kind(),
+        ErrorKind::InvalidValue
+    );
+    // A usage error exits non-zero.
+    assert_eq!(parse(&["--verbose"]).unwrap_err().exit_code(), 2);
+}
@@ -0,0 +1,182 @@
+//! `ClientInfo` believes forwarding headers only from trusted proxies.
+
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::{App, HttpResponse, test, web};
+use uncaught_exception::client_info::{ClientInfo, effective_scheme};
+use uncaught_exception::config::{AppState, ConfigError};
+
+const PROXY: &str = "10.0.0.5:443";
+const CLIENT: &str = "203.0.113.9:50000";
+
+fn state() -> web::Data<AppState> {
+    web::Data::new(
+        AppState::builder()
+            .api_key("test-key")
+            .allowed_host("my-app.com")
+            .trusted_proxy("10.0.0.0/8")
+            .trusted_proxy("192.0.2.1")
+            .build()
+            .unwrap(),
+    )
+}
+
+fn request(peer: &str, headers: &[(&str, &str)]) -> ClientInfo {
+    let mut req = test::TestRequest::get()
+        .peer_addr(peer.parse::<SocketAddr>().unwrap())
+        .insert_header(("Host", "my-app.com"))
+        .app_data(state());
+    for header in headers {
+        req = req.append_header(*header);
+    }
+    ClientInfo::of(&req.to_http_request())
+}
+
+fn ip(ip: &str) -> Option<IpAddr> {
+    Some(ip.parse().unwrap())
+}
+
+#[actix_web::test]
+async fn a_direct_connection_is_the_client() {
+    let info = request(CLIENT, &[]);
+    assert_eq!(info.ip, ip("203.0.113.9"));
+    assert_eq!(info.scheme, "http");
+    assert_eq!(info.host.as_deref(), Some("my-app.com"));
+}
+
+#[actix_web::test]
+async fn forwarding_headers_from_untrusted_peers_are_ignored() {
+    let info = request(
+        CLIENT,
+        &[
+            ("X-Forwarded-For", "198.51.100.1"),
+            ("X-Forwarded-Proto", "https"),
+            ("Forwarded", "for=198.51.100.1;proto=https"),
+        ],
+    );
+    assert_eq!(info.ip, ip("203.0.113.9"));
+    assert_eq!(info.scheme, "http");
+}
+
+fn scheme(peer: &str, headers: &[(&str, &str)]) -> &'static str {
+    let mut req = test::TestRequest::get()
+        .peer_addr(peer.parse::<SocketAddr>().unwrap())
+        .app_data(state());
+    for header in headers {
+        req = req.append_header(*header);
+    }
+    effective_scheme(&req.to_http_request())
+}
+
+#[actix_web::test]
+async fn x_forwarded_proto_counts_only_from_a_trusted_proxy() {
+    let https = [("X-Forwarded-Proto", "https")];
+    assert_eq!(scheme(PROXY, &https), "https");
+    assert_eq!(scheme(CLIENT, &https), "http");
+    // A proxy that does not say keeps the scheme of its own connection.
+    assert_eq!(scheme(PROXY, &[]), "http");
+    assert_eq!(scheme(PROXY, &[("X-Forwarded-Proto", "gopher")]), "http");
+}
+
+#[actix_web::test]
+async fn only_the_nearest_x_forwarded_proto_counts() {
+    // The client sent its own value; the trusted proxy appended the real one.
+    let headers = [
+        ("X-Forwarded-Proto", "https"),
+        ("X-Forwarded-Proto", "http"),
+    ];
+    assert_eq!(scheme(PROXY, &headers), "http");
+    assert_eq!(
+        scheme(PROXY, &[("X-Forwarded-Proto", "https, http")]),
+        "http"
+    );
+}
+
+#[actix_web::test]
+async fn a_trusted_proxy_chain_is_walked_to_the_first_untrusted_hop() {
+    // The client prepended a forged entry; the proxies appended the rest.
+    let info = request(
+        PROXY,
+        &[
+            ("X-Forwarded-For", "198.51.100.1, 203.0.113.9, 192.0.2.1"),
+            ("X-Forwarded-For", "10.1.2.3"),
+            ("X-Forwarded-Proto", "https"),
+        ],
+    );
+    assert_eq!(info.ip, ip("203.0.113.9"));
+    assert_eq!(info.scheme, "https");
+}
+
+#[actix_web::test]
+async fn the_forwarded_header_is_preferred() {
+    let info = request(
+        PROXY,
+        &[
+            (
+                "Forwarded",
+                r#"for="[2001:db8::7]:4711";proto=https, for=10.1.2.3;proto=http"#,
+            ),
+            ("X-Forwarded-For", "198.51.100.1"),
+        ],
+    );
+    assert_eq!(info.ip, ip("2001:db8::7"));
+    assert_eq!(info.scheme, "https");
+}
+
+#[actix_web::test]
+async fn an_unparsable_hop_leaves_the_last_trusted_proxy() {
+    let info = request(PROXY, &[("X-Forwarded-For", "not-an-ip, 10.1.2.3")]);
+    assert_eq!(info.ip, ip("10.1.2.3"));
+
+    let info = request(PROXY, &[("Forwarded", "for=unknown")]);
+    assert_eq!(info.ip, ip("10.0.0.5"));
+}
+
+#[actix_web::test]
+async fn only_whitelisted_hosts_are_reported() {
+    let req = test::TestRequest::get()
+        .peer_addr(CLIENT.parse().unwrap())
+        .insert_header(("Host", "evil.com"))
+        .app_data(state())
+        .to_http_request();
+    assert_eq!(ClientInfo::of(&req).host, None);
+}
+
+#[actix_web::test]
+async fn works_as_an_extractor() {
+    let app = test::init_service(App::new().app_data(state()).route(
+        "/",
+        web::get().to(|info: ClientInfo| async move {
+            HttpResponse::Ok().body(info.ip.map(|ip| ip.to_string()).unwrap_or_default())
+        }),
+    ))
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/")
+        .peer_addr(PROXY.parse().unwrap())
+        .insert_header(("X-Forwarded-For", "203.0.113.9"))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert_eq!(body, "203.0.113.9");
+}
+
+#[actix_web::test]
+async fn invalid_proxy_entries_are_refused() {
+    let err = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .trusted_proxy("10.0.0.0/33")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "trusted_proxies",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
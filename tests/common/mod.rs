@@ -0,0 +1,240 @@
+//! Helpers shared by the integration tests. A test file that needs them
+//! declares `mod common;`; none uses all of them, hence `dead_code`.
+
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, test, web};
+use futures_util::future::LocalBoxFuture;
+use uncaught_exception::backend::{BackendClient, HttpBackendClient, client_builder};
+use uncaught_exception::config::{AppState, AppStateBuilder};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::RequestDeadline;
+use uncaught_exception::routes;
+use uncaught_exception::server::TlsConfig;
+
+/// The host [`test_builder`] whitelists.
+pub const ALLOWED_HOST: &str = "my-app.com";
+
+/// A builder with just what `build` requires: an API key and
+/// [`ALLOWED_HOST`] on the whitelist.
+pub fn test_builder() -> AppStateBuilder {
+    AppState::builder()
+        .api_key("test-key")
+        .allowed_host(ALLOWED_HOST)
+}
+
+pub fn test_state() -> AppState {
+    test_builder().build().unwrap()
+}
+
+/// A builder for signups against a test backend at `host` on the loopback
+/// interface: whitelisted, exempt from the SSRF guard, and without retries.
+pub fn local_backend_builder(host: &str) -> AppStateBuilder {
+    AppState::builder()
+        .api_key("test-key")
+        .allowed_host(host)
+        .private_host("127.0.0.1")
+        .max_retries(0)
+}
+
+/// Every route, mounted as `main` does, with `state` as app data but none of
+/// the middleware.
+pub async fn test_app(
+    state: AppState,
+) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error> {
+    let auth = state.auth.clone();
+    let routes = state.routes.clone();
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(|cfg| routes::configure_with(cfg, &auth, &routes)),
+    )
+    .await
+}
+
+/// A query-string signup for `email`, addressed to `host`.
+pub fn request_with_host(host: &str, email: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(&format!("/secure/waitlist?email={}", email))
+        .insert_header(("Host", host))
+}
+
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// A backend that accepts connections and never answers, so a signup runs
+/// until something cancels it. Returns its `host:port`.
+pub fn silent_backend() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+    std::thread::spawn(move || {
+        let mut open = Vec::new();
+        for stream in listener.incoming().flatten() {
+            open.push(stream);
+        }
+    });
+    host
+}
+
+/// An HTTPS backend that accepts every signup, with the fixture certificate.
+/// Returns its `host:port`; `state` must [`trust_fixture_cert`] to call it.
+pub fn https_backend() -> String {
+    counting_https_backend().0
+}
+
+/// Like [`https_backend`], also returning how many signups it has received.
+pub fn counting_https_backend() -> (String, Arc<AtomicUsize>) {
+    answering_https_backend(StatusCode::OK, "")
+}
+
+/// Like [`counting_https_backend`], but answering every signup with `status`
+/// and `body`.
+pub fn answering_https_backend(
+    status: StatusCode,
+    body: &'static str,
+) -> (String, Arc<AtomicUsize>) {
+    let tls = TlsConfig {
+        cert_path: fixture("backend-cert.pem"),
+        key_path: fixture("backend-key.pem"),
+        port: 0,
+        disable_plain_http: true,
+    };
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let server = HttpServer::new(move || {
+        let counted = counted.clone();
+        App::new().route(
+            "/v1/waitlist",
+            web::post().to(move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                async move { HttpResponse::build(status).body(body) }
+            }),
+        )
+    })
+    .workers(1)
+    .bind_rustls_0_23("127.0.0.1:0", tls.load().unwrap())
+    .unwrap();
+    let port = server.addrs()[0].port();
+    actix_web::rt::spawn(server.run());
+    (format!("127.0.0.1:{port}"), calls)
+}
+
+/// Like [`https_backend`], also returning the query string of every signup
+/// it has received, `api_key` and all.
+pub fn recording_https_backend() -> (String, Arc<Mutex<Vec<String>>>) {
+    recording_https_backend_with(|req| req.query_string().to_string())
+}
+
+/// Like [`https_backend`], also returning what `record` took from every
+/// signup it has received.
+pub fn recording_https_backend_with(
+    record: fn(&HttpRequest) -> String,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let tls = TlsConfig {
+        cert_path: fixture("backend-cert.pem"),
+        key_path: fixture("backend-key.pem"),
+        port: 0,
+        disable_plain_http: true,
+    };
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let recorded = records.clone();
+    let server = HttpServer::new(move || {
+        let recorded = recorded.clone();
+        App::new().route(
+            "/v1/waitlist",
+            web::post().to(move |req: HttpRequest| {
+                recorded.lock().unwrap().push(record(&req));
+                async { HttpResponse::Ok().finish() }
+            }),
+        )
+    })
+    .workers(1)
+    .bind_rustls_0_23("127.0.0.1:0", tls.load().unwrap())
+    .unwrap();
+    let port = server.addrs()[0].port();
+    actix_web::rt::spawn(server.run());
+    (format!("127.0.0.1:{port}"), records)
+}
+
+/// Rebuilds `state`'s client to trust the self-signed fixture certificate,
+/// on top of its backend settings.
+pub fn trust_fixture_cert(state: &mut AppState) {
+    let root = reqwest::Certificate::from_pem(&std::fs::read(fixture("backend-cert.pem")).unwrap())
+        .unwrap();
+    state.client = client_builder(&state.backend)
+        .add_root_certificate(root)
+        .build()
+        .unwrap();
+    state.backend_client = Arc::new(HttpBackendClient::new(
+        state.client.clone(),
+        state.backend.clone(),
+        state.api_key.clone(),
+    ));
+}
+
+/// A [`BackendClient`] that answers every signup (or batch) with `outcome`,
+/// without any network, and remembers the `(host, email)` of each signup and
+/// the size of each batch.
+#[derive(Debug)]
+pub struct FakeBackend {
+    outcome: fn() -> Result<(), ApiError>,
+    calls: Mutex<Vec<(String, String)>>,
+    batches: Mutex<Vec<usize>>,
+}
+
+impl FakeBackend {
+    pub fn new(outcome: fn() -> Result<(), ApiError>) -> Arc<Self> {
+        Arc::new(FakeBackend {
+            outcome,
+            calls: Mutex::default(),
+            batches: Mutex::default(),
+        })
+    }
+
+    pub fn calls(&self) -> Vec<(String, String)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn batches(&self) -> Vec<usize> {
+        self.batches.lock().unwrap().clone()
+    }
+}
+
+impl BackendClient for FakeBackend {
+    fn submit_waitlist<'a>(
+        &'a self,
+        host: &'a str,
+        email: &'a str,
+        _deadline: Option<RequestDeadline>,
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((host.to_string(), email.to_string()));
+        Box::pin(std::future::ready((self.outcome)()))
+    }
+
+    fn submit_batch<'a>(
+        &'a self,
+        host: &'a str,
+        emails: &'a [&'a str],
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        self.calls.lock().unwrap().extend(
+            emails
+                .iter()
+                .map(|email| (host.to_string(), email.to_string())),
+        );
+        self.batches.lock().unwrap().push(emails.len());
+        Box::pin(std::future::ready((self.outcome)()))
+    }
+}
@@ -0,0 +1,70 @@
+//! Large successful responses are compressed; small ones and error responses,
+//! which carry the request ID, are not.
+
+use actix_web::middleware::Compress;
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::{
+    CompressionConfig, CompressionThreshold, CorrelationId, ErrorNegotiation,
+};
+
+async fn get(path: &str) -> (StatusCode, Option<String>) {
+    let app = test::init_service(
+        App::new()
+            .wrap(ErrorNegotiation)
+            .wrap(CompressionThreshold::new(&CompressionConfig::default()))
+            .wrap(Compress::default())
+            .wrap(CorrelationId)
+            .route(
+                "/large",
+                web::get().to(|| async { HttpResponse::Ok().body("metric 1\n".repeat(500)) }),
+            )
+            .route(
+                "/small",
+                web::get().to(|| async { HttpResponse::Ok().body("ok") }),
+            )
+            .route(
+                "/large-error",
+                web::get().to(|| async { HttpResponse::BadRequest().body("x".repeat(5000)) }),
+            )
+            .route(
+                "/error",
+                web::get()
+                    .to(|| async { Err::<HttpResponse, _>(ApiError::InvalidHost { host: None }) }),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(path)
+        .insert_header(("Accept-Encoding", "gzip"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let encoding = res
+        .headers()
+        .get("content-encoding")
+        .map(|v| v.to_str().unwrap().to_string());
+    (res.status(), encoding)
+}
+
+#[actix_web::test]
+async fn a_large_response_is_compressed() {
+    let (status, encoding) = get("/large").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(encoding.as_deref(), Some("gzip"));
+}
+
+#[actix_web::test]
+async fn a_small_response_is_not() {
+    let (status, encoding) = get("/small").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(encoding.as_deref(), Some("identity"));
+}
+
+#[actix_web::test]
+async fn an_error_response_is_not() {
+    for path in ["/error", "/large-error"] {
+        let (status, encoding) = get(path).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+        assert_ne!(encoding.as_deref(), Some("gzip"), "{path}");
+    }
+}
@@ -0,0 +1,73 @@
+//! `AppState` loads from a TOML file and from the environment, which wins
+//! over the file, and refuses to start without an API key.
+
+use uncaught_exception::config::{AppState, ConfigError};
+
+fn config_file(name: &str, contents: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("config-loading-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn loads_from_a_toml_file() {
+    let path = config_file(
+        "file.toml",
+        r#"
+            api_key = "file-key"
+            allowed_hosts = ["my-app.com", "*.my-app.com"]
+        "#,
+    );
+    let state = AppState::from_toml(&path).unwrap();
+    assert_eq!(state.api_key.current().expose(), "file-key");
+    assert_eq!(state.allowed_hosts, ["my-app.com", "*.my-app.com"]);
+    assert_eq!(state.config_path.as_deref(), Some(path.as_path()));
+}
+
+#[test]
+fn a_file_without_a_key_is_refused() {
+    let path = config_file("no-key.toml", r#"allowed_hosts = ["my-app.com"]"#);
+    let err = AppState::from_toml(&path).err().unwrap();
+    assert!(matches!(err, ConfigError::MissingApiKey), "{err}");
+}
+
+// The only test in this file that touches the environment, so no other test
+// can see it half set.
+#[test]
+fn the_environment_overrides_the_file() {
+    let path = config_file(
+        "overridden.toml",
+        r#"
+            api_key = "file-key"
+            allowed_hosts = ["file.my-app.com"]
+        "#,
+    );
+    // SAFETY: no other thread of this test binary reads or writes the
+    // environment.
+    unsafe {
+        std::env::set_var("API_KEY", "env-key");
+        std::env::set_var("ALLOWED_HOSTS", "env.my-app.com, other.my-app.com");
+    }
+    let from_env = AppState::from_env();
+    let loaded = AppState::load(Some(&path));
+    unsafe {
+        std::env::remove_var("API_KEY");
+        std::env::remove_var("ALLOWED_HOSTS");
+    }
+
+    let from_env = from_env.unwrap();
+    assert_eq!(from_env.api_key.current().expose(), "env-key");
+    assert_eq!(
+        from_env.allowed_hosts,
+        ["env.my-app.com", "other.my-app.com"]
+    );
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.api_key.current().expose(), "env-key");
+    assert_eq!(loaded.allowed_hosts, ["env.my-app.com", "other.my-app.com"]);
+
+    // Without them, the file applies again, and without either, nothing does.
+    let loaded = AppState::load(Some(&path)).unwrap();
+    assert_eq!(loaded.api_key.current().expose(), "file-key");
+    let err = AppState::from_env().err().unwrap();
+    assert!(matches!(err, ConfigError::MissingApiKey), "{err}");
+}
@@ -0,0 +1,97 @@
+//! Cross-origin requests: an allowed origin is reflected back, a disallowed
+//! one gets no CORS headers, and its preflight is refused.
+
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::middleware::{Cors, CorsConfig};
+
+use common::test_builder;
+
+const ALLOWED: &str = "https://app.my-app.com";
+const DISALLOWED: &str = "https://evil.example";
+
+// Calls `req` through the CORS middleware, with `allowed_origins` configured.
+async fn call(allowed_origins: &[&str], req: actix_http::Request) -> ServiceResponse {
+    let state = test_builder()
+        .cors(CorsConfig {
+            allowed_origins: allowed_origins.iter().map(|o| o.to_string()).collect(),
+            ..CorsConfig::default()
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(Cors::new(&state.cors, state.host_matcher.clone()))
+            .route("/secure/waitlist", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+    test::call_service(&app, req).await.map_into_boxed_body()
+}
+
+fn signup(origin: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/secure/waitlist")
+        .insert_header(("Origin", origin))
+        .to_request()
+}
+
+fn preflight(origin: &str) -> actix_http::Request {
+    test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/secure/waitlist")
+        .insert_header(("Origin", origin))
+        .insert_header(("Access-Control-Request-Method", "POST"))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn an_allowed_origin_is_reflected_back() {
+    let res = call(&[ALLOWED], signup(ALLOWED)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("access-control-allow-origin").unwrap(),
+        ALLOWED
+    );
+    assert_eq!(res.headers().get("vary").unwrap(), "Origin");
+}
+
+#[actix_web::test]
+async fn a_disallowed_origin_gets_no_cors_headers() {
+    let res = call(&[ALLOWED], signup(DISALLOWED)).await;
+    // Processed as usual, but unreadable to the calling page.
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!res.headers().contains_key("access-control-allow-origin"));
+}
+
+#[actix_web::test]
+async fn an_allowed_preflight_is_answered() {
+    let res = call(&[ALLOWED], preflight(ALLOWED)).await;
+    assert!(res.status().is_success(), "{}", res.status());
+    let headers = res.headers();
+    assert_eq!(headers.get("access-control-allow-origin").unwrap(), ALLOWED);
+    assert_eq!(
+        headers.get("access-control-allow-methods").unwrap(),
+        "GET, POST"
+    );
+    assert_eq!(headers.get("access-control-max-age").unwrap(), "600");
+}
+
+#[actix_web::test]
+async fn a_disallowed_preflight_is_refused_with_403() {
+    let res = call(&[ALLOWED], preflight(DISALLOWED)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(!res.headers().contains_key("access-control-allow-origin"));
+}
+
+#[actix_web::test]
+async fn without_explicit_origins_the_host_whitelist_decides() {
+    let res = call(&[], signup("https://my-app.com")).await;
+    assert_eq!(
+        res.headers().get("access-control-allow-origin").unwrap(),
+        "https://my-app.com"
+    );
+    let res = call(&[], signup(DISALLOWED)).await;
+    assert!(!res.headers().contains_key("access-control-allow-origin"));
+}
@@ -0,0 +1,107 @@
+//! A request that runs past its deadline is cancelled with a generic 503.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    App, HttpResponse,
+    body::to_bytes,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test, web,
+};
+use serde_json::Value;
+use uncaught_exception::middleware::{CorrelationId, Deadline};
+
+// Records whether the slow handler finished, and whether it was dropped.
+#[derive(Clone, Default)]
+struct Progress {
+    finished: Arc<AtomicBool>,
+    dropped: Arc<AtomicBool>,
+}
+
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+// What the client receives: the timeout surfaces as a service error, which actix
+// renders into the response.
+async fn call<S>(app: &S, req: actix_http::Request) -> (StatusCode, Value)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let res = match test::try_call_service(app, req).await {
+        Ok(res) => res.into_parts().1,
+        Err(e) => e.error_response(),
+    };
+    let status = res.status();
+    let body = to_bytes(res.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn slow(progress: web::Data<Progress>) -> HttpResponse {
+    let _guard = SetOnDrop(progress.dropped.clone());
+    actix_web::rt::time::sleep(Duration::from_millis(300)).await;
+    progress.finished.store(true, Ordering::SeqCst);
+    HttpResponse::Ok().body("done")
+}
+
+#[actix_web::test]
+async fn a_slow_handler_is_cancelled_with_503() {
+    let progress = Progress::default();
+    let app = test::init_service(
+        App::new()
+            .wrap(Deadline::new(Duration::from_millis(50)))
+            .wrap(CorrelationId)
+            .app_data(web::Data::new(progress.clone()))
+            .route("/slow", web::get().to(slow)),
+    )
+    .await;
+
+    let started = Instant::now();
+    let req = test::TestRequest::get()
+        .uri("/slow")
+        .insert_header(("X-Request-Id", "deadline-test"))
+        .to_request();
+    let (status, body) = call(&app, req).await;
+    assert!(started.elapsed() < Duration::from_millis(250));
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "DEADLINE_EXCEEDED");
+    assert_eq!(body["error"]["request_id"], "deadline-test");
+
+    // The handler was dropped at its `.await`, not left running.
+    assert!(progress.dropped.load(Ordering::SeqCst));
+    actix_web::rt::time::sleep(Duration::from_millis(400)).await;
+    assert!(!progress.finished.load(Ordering::SeqCst));
+}
+
+#[actix_web::test]
+async fn routes_can_have_their_own_deadline() {
+    let progress = Progress::default();
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                Deadline::new(Duration::from_millis(50))
+                    .route("/slow/{id}", Duration::from_secs(5)),
+            )
+            .app_data(web::Data::new(progress.clone()))
+            .route("/slow/{id}", web::get().to(slow))
+            .route("/other", web::get().to(slow)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/slow/7").to_request();
+    let (status, _) = call(&app, req).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(progress.finished.load(Ordering::SeqCst));
+
+    let req = test::TestRequest::get().uri("/other").to_request();
+    let (status, body) = call(&app, req).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "DEADLINE_EXCEEDED");
+}
@@ -0,0 +1,146 @@
+//! The backend is told, in `X-Request-Deadline-Ms`, how much of the request's
+//! deadline is left, never more than its own timeout.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{App, HttpRequest, HttpResponse, test, web};
+use uncaught_exception::backend::{
+    BackendClient, BackendConfig, DEADLINE_HEADER, HttpBackendClient, client_builder,
+    deadline_budget,
+};
+use uncaught_exception::clock::Clock;
+use uncaught_exception::middleware::{Deadline, RequestDeadline};
+use uncaught_exception::secrets::Secret;
+
+use common::{fixture, recording_https_backend_with};
+
+// A clock that only moves when told to.
+#[derive(Debug)]
+struct FakeClock(Mutex<Instant>);
+
+impl FakeClock {
+    fn new(now: Instant) -> Arc<Self> {
+        Arc::new(FakeClock(Mutex::new(now)))
+    }
+
+    fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+fn config() -> BackendConfig {
+    BackendConfig {
+        timeout_ms: 5_000,
+        max_retries: 0,
+        ..BackendConfig::default()
+    }
+}
+
+#[actix_web::test]
+async fn the_budget_shrinks_with_time_and_is_clamped_to_the_backend_timeout() {
+    let start = Instant::now();
+    let clock = FakeClock::new(start);
+    let deadline = Some(RequestDeadline::new(start + Duration::from_secs(3)));
+
+    assert_eq!(
+        deadline_budget(&config(), deadline, &*clock),
+        Duration::from_secs(3)
+    );
+    clock.advance(Duration::from_millis(1_250));
+    assert_eq!(
+        deadline_budget(&config(), deadline, &*clock),
+        Duration::from_millis(1_750)
+    );
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(
+        deadline_budget(&config(), deadline, &*clock),
+        Duration::ZERO
+    );
+
+    let far = Some(RequestDeadline::new(start + Duration::from_secs(60)));
+    assert_eq!(
+        deadline_budget(&config(), far, &*clock),
+        Duration::from_secs(5)
+    );
+    assert_eq!(
+        deadline_budget(&config(), None, &*clock),
+        Duration::from_secs(5)
+    );
+}
+
+#[actix_web::test]
+async fn each_backend_call_announces_what_is_left() {
+    let (host, headers) = recording_https_backend_with(|req| {
+        req.headers()
+            .get(DEADLINE_HEADER)
+            .map_or("missing", |value| value.to_str().unwrap())
+            .to_string()
+    });
+    let root = reqwest::Certificate::from_pem(&std::fs::read(fixture("backend-cert.pem")).unwrap())
+        .unwrap();
+    let client = client_builder(&config())
+        .add_root_certificate(root)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    let clock = FakeClock::new(start);
+    let backend =
+        HttpBackendClient::new(client, config(), Secret::new("test-key")).with_clock(clock.clone());
+    let deadline = Some(RequestDeadline::new(start + Duration::from_secs(4)));
+
+    for _ in 0..2 {
+        backend
+            .submit_waitlist(&host, "user@example.com", deadline)
+            .await
+            .unwrap();
+        clock.advance(Duration::from_millis(1_500));
+    }
+    backend
+        .submit_waitlist(&host, "user@example.com", None)
+        .await
+        .unwrap();
+    assert_eq!(*headers.lock().unwrap(), ["4000", "2500", "5000"]);
+}
+
+// What is left of the request's deadline, as the handler sees it.
+async fn remaining(req: HttpRequest) -> HttpResponse {
+    let left = RequestDeadline::of(&req).map_or(u128::MAX, |deadline| {
+        deadline.remaining(Instant::now()).as_millis()
+    });
+    HttpResponse::Ok().body(left.to_string())
+}
+
+#[actix_web::test]
+async fn the_middleware_records_the_earliest_deadline() {
+    let app = test::init_service(
+        App::new()
+            .wrap(Deadline::new(Duration::from_millis(800)))
+            .route("/outer", web::get().to(remaining))
+            .service(
+                web::resource("/nested")
+                    .wrap(Deadline::new(Duration::from_secs(60)))
+                    .route(web::get().to(remaining)),
+            )
+            .service(
+                web::resource("/tighter")
+                    .wrap(Deadline::new(Duration::from_millis(200)))
+                    .route(web::get().to(remaining)),
+            ),
+    )
+    .await;
+    for (path, at_most) in [("/outer", 800), ("/nested", 800), ("/tighter", 200)] {
+        let req = test::TestRequest::get().uri(path).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let left: u128 = std::str::from_utf8(&body).unwrap().parse().unwrap();
+        assert!(left <= at_most && left > at_most - 150, "{path}: {left}");
+    }
+}
@@ -0,0 +1,90 @@
+//! `denied_hosts` refuses hosts even when the whitelist allows them, with the
+//! same wildcard syntax; with an empty whitelist it allows every other host.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use uncaught_exception::config::{AppState, ConfigError};
+use uncaught_exception::host::HostMatcher;
+
+use common::{request_with_host, test_app};
+
+#[actix_web::test]
+async fn a_host_both_allowed_and_denied_is_denied() {
+    let matcher = HostMatcher::new(["*.my-app.com", "api.my-app.com"], true)
+        .deny(["internal.my-app.com", "api.my-app.com"]);
+    assert!(matcher.is_allowed("www.my-app.com"));
+    assert!(!matcher.is_allowed("internal.my-app.com"));
+    // Listed exactly and by wildcard, and denied exactly.
+    assert!(!matcher.is_allowed("api.my-app.com"));
+    // Not on the whitelist at all.
+    assert!(!matcher.is_allowed("evil.com"));
+}
+
+#[actix_web::test]
+async fn denylist_wildcards_match_subdomains_only() {
+    let matcher = HostMatcher::new(["my-app.com", "*.my-app.com"], false).deny(["*.my-app.com"]);
+    assert!(matcher.is_allowed("my-app.com"));
+    assert!(!matcher.is_allowed("a.my-app.com"));
+    assert!(!matcher.is_allowed("A.B.My-App.com."));
+}
+
+#[actix_web::test]
+async fn with_an_empty_whitelist_everything_but_the_denylist_is_allowed() {
+    let matcher = HostMatcher::new(Vec::<String>::new(), true).deny(["*.internal", "localhost"]);
+    assert!(matcher.is_allowed("my-app.com"));
+    assert!(matcher.is_allowed("anything.example:8080"));
+    assert!(!matcher.is_allowed("db.internal"));
+    assert!(!matcher.is_allowed("localhost:9000"));
+    // Not named by the whitelist, so not good enough for CORS.
+    assert!(!matcher.is_listed("my-app.com"));
+
+    // Without a denylist, an empty whitelist still allows nothing.
+    assert!(!HostMatcher::new(Vec::<String>::new(), true).is_allowed("my-app.com"));
+}
+
+#[actix_web::test]
+async fn the_secure_handler_refuses_a_denied_host() {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("*.my-app.com")
+        .denied_host("admin.my-app.com")
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+    let req = request_with_host("admin.my-app.com", "user@good.com").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn a_denylist_alone_is_enough_to_build() {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .denied_host("*.internal")
+        .build()
+        .unwrap();
+    assert!(state.host_matcher.is_allowed("my-app.com"));
+    assert!(!state.host_matcher.is_allowed("db.internal"));
+}
+
+#[actix_web::test]
+async fn an_invalid_denylist_entry_is_refused() {
+    let err = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .denied_host("my-app.com:http")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "denied_hosts",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,93 @@
+//! With `allowed_email_domains` set, the secure handler only takes signups
+//! from those domains; without it, from any.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::Value;
+use uncaught_exception::config::{AppStateBuilder, ConfigError};
+
+use common::{local_backend_builder, request_with_host, test_app};
+
+// Loopback, so the SSRF check passes without DNS.
+const HOST: &str = "127.0.0.1";
+
+fn test_builder() -> AppStateBuilder {
+    local_backend_builder(HOST)
+}
+
+// Dry runs, so an accepted signup needs no backend.
+async fn sign_up(builder: AppStateBuilder, email: &str) -> (StatusCode, Value) {
+    let app = test_app(builder.dry_run(true).build().unwrap()).await;
+    let res = test::call_service(&app, request_with_host(HOST, email).to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn corporate() -> AppStateBuilder {
+    test_builder()
+        .allowed_email_domain("good.com")
+        .allowed_email_domain("*.corp.example")
+}
+
+#[actix_web::test]
+async fn a_listed_domain_is_accepted_in_any_case() {
+    for email in ["user@good.com", "user@GOOD.Com"] {
+        let (status, _) = sign_up(corporate(), email).await;
+        assert_eq!(status, StatusCode::OK, "{email}");
+    }
+}
+
+#[actix_web::test]
+async fn another_domain_is_forbidden_with_a_generic_message() {
+    for email in [
+        "user@evil.com",
+        "user@good.com.evil.com",
+        "user@corp.example",
+    ] {
+        let (status, body) = sign_up(corporate(), email).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{email}");
+        assert_eq!(body["error"]["code"], "EMAIL_DOMAIN_NOT_ALLOWED");
+        assert_eq!(
+            body["error"]["message"],
+            "This waitlist is not open to this email address."
+        );
+        assert!(!body.to_string().contains("good.com"), "{body}");
+    }
+}
+
+#[actix_web::test]
+async fn a_wildcard_allows_subdomains() {
+    for email in ["user@eu.corp.example", "user@mail.EU.corp.example"] {
+        let (status, _) = sign_up(corporate(), email).await;
+        assert_eq!(status, StatusCode::OK, "{email}");
+    }
+}
+
+#[actix_web::test]
+async fn without_a_list_every_domain_is_accepted() {
+    let (status, _) = sign_up(test_builder(), "user@anything.example").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn an_invalid_entry_is_refused() {
+    for domain in ["good.com:25", "*.", "user@good.com"] {
+        let err = test_builder()
+            .allowed_email_domain(domain)
+            .build()
+            .err()
+            .unwrap();
+        assert!(
+            matches!(
+                err,
+                ConfigError::InvalidValue {
+                    name: "allowed_email_domains",
+                    ..
+                }
+            ),
+            "{domain}: {err}"
+        );
+    }
+}
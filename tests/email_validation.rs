@@ -0,0 +1,79 @@
+//! Both handlers refuse a malformed email with a `400` before any backend URL
+//! is built.
+
+mod common;
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::handlers::{secure_waitlist, vulnerable_waitlist};
+use uncaught_exception::validation::validate_email;
+
+use common::{FakeBackend, local_backend_builder};
+
+// Percent-encoded where the raw value would not survive a query string.
+const INVALID: [&str; 8] = [
+    "",
+    "user.good.com",
+    "%20user@good.com",
+    "user@good.com%20",
+    "user@@good.com",
+    "user@good",
+    ".user@good.com",
+    "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa@good.com",
+];
+
+#[actix_web::test]
+async fn both_handlers_refuse_malformed_emails_with_400() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let state = local_backend_builder("127.0.0.1")
+        .backend_client(backend.clone())
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+
+    for path in ["/vulnerable/waitlist", "/secure/waitlist"] {
+        for email in INVALID {
+            let req = test::TestRequest::get()
+                .uri(&format!("{path}?email={email}"))
+                .insert_header(("Host", "127.0.0.1"))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{path} {email:?}");
+            let body: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+            assert_eq!(body["error"]["code"], "BAD_REQUEST", "{path} {email:?}");
+        }
+    }
+    assert!(backend.calls().is_empty());
+}
+
+#[actix_web::test]
+async fn plausible_addresses_pass() {
+    for email in [
+        "user@good.com",
+        "first.last+tag@mail.good.co.uk",
+        "o'brien@good-domain.com",
+    ] {
+        assert!(validate_email(email).is_ok(), "{email}");
+    }
+}
+
+#[actix_web::test]
+async fn the_edge_cases_are_refused() {
+    let long_local = format!("{}@good.com", "a".repeat(65));
+    for email in [
+        "user",
+        " user@good.com",
+        "user@good.com\n",
+        "user..name@good.com",
+        "user@-good.com",
+        long_local.as_str(),
+    ] {
+        assert!(validate_email(email).is_err(), "{email:?}");
+    }
+}
@@ -0,0 +1,118 @@
+//! Every `ApiError` renders as `{"error":{"code","message","request_id"}}`,
+//! with a code fixed per variant and a message that never carries a secret.
+
+use actix_web::body::to_bytes;
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{App, HttpResponse, ResponseError, test, web};
+use serde::Deserialize;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::CorrelationId;
+use uncaught_exception::secrets::SecretRegistry;
+
+const SECRET: &str = "error-body-key-2b3c4d";
+
+// The published schema, and nothing more.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ErrorDetail {
+    code: String,
+    message: String,
+    request_id: Option<String>,
+}
+
+async fn render(error: &ApiError) -> ErrorBody {
+    let res = error.error_response();
+    assert_eq!(
+        res.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json",
+        "{error}"
+    );
+    let body = to_bytes(res.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[actix_web::test]
+async fn each_variant_has_its_code_and_no_secret() {
+    SecretRegistry::global().register(SECRET);
+    let reason = format!("https://my-app.com/v1/waitlist?api_key={SECRET}");
+    let cases = [
+        (
+            ApiError::InvalidHost {
+                host: Some(format!("{SECRET}.evil.com")),
+            },
+            "INVALID_HOST",
+        ),
+        (
+            ApiError::Internal {
+                reason: reason.clone(),
+            },
+            "INTERNAL_ERROR",
+        ),
+        (
+            ApiError::Detailed {
+                message: reason.clone(),
+            },
+            "INTERNAL_ERROR",
+        ),
+        (
+            ApiError::UpstreamTimeout {
+                reason: reason.clone(),
+            },
+            "UPSTREAM_TIMEOUT",
+        ),
+        (
+            ApiError::Upstream {
+                status: Some(500),
+                reason: reason.clone(),
+            },
+            "UPSTREAM_UNAVAILABLE",
+        ),
+        (
+            ApiError::BadRequest {
+                reason: reason.clone(),
+            },
+            "BAD_REQUEST",
+        ),
+        (ApiError::Unauthorized { reason }, "UNAUTHORIZED"),
+        (ApiError::AlreadySignedUp, "ALREADY_SIGNED_UP"),
+    ];
+    for (error, code) in cases {
+        let body = render(&error).await;
+        assert_eq!(body.error.code, code, "{error}");
+        assert_eq!(body.error.code, error.code());
+        assert!(!body.error.message.is_empty());
+        assert!(!body.error.message.contains(SECRET), "{body:?}");
+        assert_eq!(body.error.request_id, None);
+    }
+}
+
+#[actix_web::test]
+async fn a_json_error_from_the_extractor_has_a_stable_code() {
+    let source = serde_json::from_str::<u32>("not json").unwrap_err();
+    let body = render(&ApiError::Json { source }).await;
+    assert_eq!(body.error.code, "BAD_REQUEST");
+    assert!(!body.error.message.contains("not json"), "{body:?}");
+}
+
+#[actix_web::test]
+async fn the_request_id_is_filled_in_behind_the_correlation_id() {
+    let app = test::init_service(App::new().wrap(CorrelationId).route(
+        "/error",
+        web::get().to(|| async { Err::<HttpResponse, _>(ApiError::AlreadySignedUp) }),
+    ))
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/error")
+        .insert_header(("X-Request-Id", "error-body-1"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let body: ErrorBody = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body.error.code, "ALREADY_SIGNED_UP");
+    assert_eq!(body.error.request_id.as_deref(), Some("error-body-1"));
+}
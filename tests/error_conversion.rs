@@ -0,0 +1,87 @@
+//! Library errors convert into `ApiError` with `?`, and the client still sees
+//! only the generic message of the variant, never the source error.
+
+use std::error::Error as _;
+
+use actix_web::{ResponseError, body::to_bytes, http::StatusCode};
+use serde::Deserialize;
+use uncaught_exception::backend::UpstreamFailure;
+use uncaught_exception::error::ApiError;
+
+const SECRET: &str = "conversion-canary-5e0d";
+
+async fn body_of(err: &ApiError) -> (StatusCode, String) {
+    let res = err.error_response();
+    let status = res.status();
+    let body = to_bytes(res.into_body()).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn call_backend() -> Result<(), ApiError> {
+    // Nothing listens on port 1, so this fails to connect.
+    let url = format!("http://127.0.0.1:1/v1/waitlist?api_key={SECRET}");
+    reqwest::Client::new().post(url).send().await?;
+    Ok(())
+}
+
+#[actix_web::test]
+async fn reqwest_errors_lose_their_url() {
+    let err = call_backend().await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ApiError::UpstreamConnect {
+                failure: UpstreamFailure::ConnectionRefused,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    // Not even the log message carries the key.
+    assert!(!err.to_string().contains(SECRET), "{err}");
+    assert!(!format!("{err:?}").contains(SECRET), "{err:?}");
+
+    let (status, body) = body_of(&err).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(!body.contains(SECRET), "{body}");
+    assert!(!body.contains("127.0.0.1"), "{body}");
+    assert!(body.contains("UPSTREAM_UNAVAILABLE"), "{body}");
+}
+
+fn parse_url(input: &str) -> Result<url::Url, ApiError> {
+    Ok(url::Url::parse(input)?)
+}
+
+#[actix_web::test]
+async fn url_parse_errors_are_internal() {
+    let err = parse_url(&format!("https://my-app.com:99999/?api_key={SECRET}")).unwrap_err();
+    assert!(matches!(err, ApiError::Url { .. }), "{err:?}");
+    assert!(err.source().is_some());
+
+    let (status, body) = body_of(&err).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!body.contains(SECRET), "{body}");
+    assert!(body.contains("INTERNAL_ERROR"), "{body}");
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Signup {
+    email: String,
+}
+
+fn parse_signup(input: &str) -> Result<Signup, ApiError> {
+    Ok(serde_json::from_str(input)?)
+}
+
+#[actix_web::test]
+async fn malformed_json_is_a_bad_request() {
+    let err = parse_signup(&format!(r#"{{"email": 5, "note": "{SECRET}"}}"#)).unwrap_err();
+    assert!(matches!(err, ApiError::Json { .. }), "{err:?}");
+
+    let (status, body) = body_of(&err).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(!body.contains(SECRET), "{body}");
+    assert!(!body.contains("invalid type"), "{body}");
+    assert!(body.contains("BAD_REQUEST"), "{body}");
+}
@@ -0,0 +1,105 @@
+//! Error bodies follow the `Accept` header, and none of the formats leak the
+//! API key.
+
+use actix_web::{
+    App,
+    http::{StatusCode, header},
+    test, web,
+};
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::middleware::{CorrelationId, ErrorNegotiation};
+use uncaught_exception::secrets::{REDACTED, SecretRegistry};
+
+const TEST_API_KEY: &str = "test-key-0c4e7a1b-negotiation-canary";
+
+// Returns the content type and body of the vulnerable handler's error, which
+// carries the (redacted) key in its message.
+async fn error_for(accept: Option<&str>) -> (String, String) {
+    SecretRegistry::global().register(TEST_API_KEY);
+    let state = AppState::builder()
+        .api_key(TEST_API_KEY)
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(ErrorNegotiation)
+            .wrap(CorrelationId)
+            .app_data(web::Data::new(state))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    let mut req = test::TestRequest::get()
+        .uri("/vulnerable/waitlist?email=attacker@evil.com")
+        .insert_header(("Host", "my-app.com:99999"));
+    if let Some(accept) = accept {
+        req = req.insert_header((header::ACCEPT, accept));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if accept.is_some() {
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept");
+    }
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(!body.contains(TEST_API_KEY), "{body}");
+    assert!(body.contains(REDACTED), "{body}");
+    (content_type, body)
+}
+
+#[actix_web::test]
+async fn json_is_the_default() {
+    for accept in [None, Some("*/*"), Some("image/png")] {
+        let (content_type, body) = error_for(accept).await;
+        assert_eq!(content_type, "application/json", "{accept:?}");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+    }
+}
+
+#[actix_web::test]
+async fn application_json_gives_json() {
+    let (content_type, _) = error_for(Some("application/json")).await;
+    assert_eq!(content_type, "application/json");
+}
+
+#[actix_web::test]
+async fn text_plain_gives_text() {
+    let (content_type, body) = error_for(Some("text/plain")).await;
+    assert!(content_type.starts_with("text/plain"), "{content_type}");
+    assert!(
+        body.starts_with("Failed to construct backend request."),
+        "{body}"
+    );
+    assert!(body.contains("Reference: "), "{body}");
+}
+
+#[actix_web::test]
+async fn text_html_gives_an_escaped_page() {
+    let (content_type, body) = error_for(Some("text/html")).await;
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    assert!(body.starts_with("<!DOCTYPE html>"), "{body}");
+    assert!(
+        body.contains("<h1>500 Internal Server Error</h1>"),
+        "{body}"
+    );
+    // The URL in the message is escaped, not interpreted.
+    assert!(
+        body.contains("URL: &#39;https://my-app.com:99999"),
+        "{body}"
+    );
+}
+
+#[actix_web::test]
+async fn q_values_pick_the_preferred_format() {
+    let (content_type, _) = error_for(Some("application/json;q=0.5, text/html")).await;
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    let (content_type, _) = error_for(Some("text/html;q=0, text/plain;q=0.1")).await;
+    assert!(content_type.starts_with("text/plain"), "{content_type}");
+}
@@ -0,0 +1,95 @@
+//! `GET /admin/events` streams a masked event for every accepted signup.
+
+mod common;
+
+use actix_web::{
+    body::MessageBody,
+    http::{StatusCode, header::CONTENT_TYPE},
+    test,
+};
+use std::pin::Pin;
+use std::time::Duration;
+use uncaught_exception::events::{CAPACITY, SignupEvents, sse_stream};
+use uncaught_exception::middleware::AuthConfig;
+
+use common::{FakeBackend, local_backend_builder, request_with_host, test_app};
+
+// Loopback, so the SSRF check passes without DNS.
+const HOST: &str = "127.0.0.1";
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+fn subscribe(token: &str) -> actix_http::Request {
+    test::TestRequest::get()
+        .uri("/admin/events")
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn a_signup_reaches_the_subscriber_masked() {
+    let state = local_backend_builder(HOST)
+        .backend_client(FakeBackend::new(|| Ok(())))
+        .auth(AuthConfig {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+        })
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+
+    let res = test::call_service(&app, subscribe(ADMIN_TOKEN)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+    let mut body = res.into_body();
+
+    let signup =
+        test::call_service(&app, request_with_host(HOST, "jane@good.com").to_request()).await;
+    assert_eq!(signup.status(), StatusCode::OK);
+
+    let chunk = actix_web::rt::time::timeout(
+        Duration::from_secs(1),
+        std::future::poll_fn(|cx| Pin::new(&mut body).poll_next(cx)),
+    )
+    .await
+    .expect("an event")
+    .unwrap()
+    .unwrap();
+    let frame = std::str::from_utf8(&chunk).unwrap();
+    assert!(frame.starts_with("event: signup\ndata: "), "{frame}");
+    assert!(frame.ends_with("\n\n"), "{frame}");
+    let data: serde_json::Value =
+        serde_json::from_str(frame["event: signup\ndata: ".len()..].trim()).unwrap();
+    assert_eq!(data["email"], "j***@good.com");
+    assert!(!frame.contains("jane"), "{frame}");
+}
+
+#[actix_web::test]
+async fn requires_the_admin_token() {
+    let state = local_backend_builder(HOST)
+        .auth(AuthConfig {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+        })
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+    let res = test::call_service(&app, subscribe("wrong-token")).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn a_slow_subscriber_skips_what_it_missed() {
+    use futures_util::StreamExt;
+
+    let events = SignupEvents::new();
+    let mut stream = Box::pin(sse_stream(events.subscribe()));
+    for i in 0..CAPACITY + 5 {
+        events.publish(&format!("user{i}@good.com"));
+    }
+    // The five oldest events were dropped; only the last `CAPACITY` arrive.
+    let received = (&mut stream).take(CAPACITY).count().await;
+    assert_eq!(received, CAPACITY);
+    let more = actix_web::rt::time::timeout(Duration::from_millis(50), stream.next()).await;
+    assert!(more.is_err(), "no event beyond the last {CAPACITY}");
+}
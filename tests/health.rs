@@ -0,0 +1,59 @@
+//! `GET /healthz` always answers, and `GET /readyz` says whether the backend
+//! can be reached.
+
+mod common;
+
+use std::net::TcpListener;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::{Value, json};
+use uncaught_exception::config::AppState;
+
+use common::{https_backend, local_backend_builder, test_app, test_state, trust_fixture_cert};
+
+async fn get(state: AppState, path: &str) -> (StatusCode, Value) {
+    let app = test_app(state).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[actix_web::test]
+async fn the_liveness_probe_always_answers() {
+    let (status, body) = get(test_state(), "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "ok" }));
+}
+
+#[actix_web::test]
+async fn ready_while_the_backend_answers() {
+    let host = https_backend();
+    let mut state = local_backend_builder(&host).build().unwrap();
+    trust_fixture_cert(&mut state);
+
+    // The backend has no route for `HEAD /`, but any answer will do.
+    let (status, body) = get(state, "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "ok", "backend": "reachable" }));
+}
+
+#[actix_web::test]
+async fn unavailable_while_the_backend_is_down() {
+    // A port that was just free, so nothing listens on it.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let state = local_backend_builder(&format!("127.0.0.1:{port}"))
+        .build()
+        .unwrap();
+
+    let (status, body) = get(state, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body,
+        json!({ "status": "unavailable", "backend": "unreachable" })
+    );
+}
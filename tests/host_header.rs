@@ -0,0 +1,113 @@
+//! Missing, empty, unreadable and duplicated `Host` headers are refused by both handlers.
+
+use actix_web::http::header::{HOST, HeaderValue};
+use actix_web::{App, http::StatusCode, test, web};
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::{secure_waitlist, vulnerable_waitlist};
+
+const PATHS: [&str; 2] = ["/vulnerable/waitlist", "/secure/waitlist"];
+
+fn test_state(default_host: Option<&str>) -> AppState {
+    let builder = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com");
+    match default_host {
+        Some(host) => builder.default_host(host),
+        None => builder,
+    }
+    .build()
+    .unwrap()
+}
+
+async fn get_status(default_host: Option<&str>, path: &str, hosts: &[&'static str]) -> StatusCode {
+    let mut req = test::TestRequest::get().uri(&format!("{}?email=user@good.com", path));
+    for host in hosts {
+        req = req.append_header(("Host", *host));
+    }
+    call(default_host, req).await
+}
+
+async fn call(default_host: Option<&str>, req: test::TestRequest) -> StatusCode {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(default_host)))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    test::call_service(&app, req.to_request()).await.status()
+}
+
+#[actix_web::test]
+async fn missing_host_is_a_bad_request() {
+    for path in PATHS {
+        assert_eq!(
+            get_status(None, path, &[]).await,
+            StatusCode::BAD_REQUEST,
+            "{path}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn empty_host_is_a_bad_request() {
+    for path in PATHS {
+        for host in ["", "   "] {
+            let status = get_status(None, path, &[host]).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{path} {host:?}");
+        }
+    }
+}
+
+#[actix_web::test]
+async fn non_utf8_host_is_a_bad_request() {
+    for path in PATHS {
+        let req = test::TestRequest::get()
+            .uri(&format!("{}?email=user@good.com", path))
+            .insert_header((HOST, HeaderValue::from_bytes(b"my-app.com\xff").unwrap()));
+        // Even with a default host: the header is there, just unreadable.
+        let status = call(Some("my-app.com"), req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+    }
+}
+
+#[actix_web::test]
+async fn duplicate_host_headers_are_a_bad_request() {
+    for path in PATHS {
+        let status = get_status(None, path, &["my-app.com", "evil.com"]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+        // Even two copies of an allowed host: which one a proxy used is unknowable.
+        let status = get_status(None, path, &["my-app.com", "my-app.com"]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+    }
+}
+
+#[actix_web::test]
+async fn default_host_fills_in_only_a_missing_header() {
+    // The vulnerable handler never calls the backend, so the default host
+    // getting through shows up as a 200.
+    let path = "/vulnerable/waitlist";
+    assert_eq!(
+        get_status(Some("my-app.com"), path, &[]).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_status(Some("my-app.com"), path, &[""]).await,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn a_host_with_control_characters_is_a_bad_request_before_the_whitelist() {
+    // The vulnerable handler checks no whitelist, so only the control
+    // characters can turn it into a 400.
+    let path = "/vulnerable/waitlist";
+    assert_eq!(get_status(None, path, &["evil.com"]).await, StatusCode::OK);
+    for path in PATHS {
+        let status = get_status(None, path, &["my-app.com\tX-Injected: 1"]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+    }
+    // Nor is a default host with a line break used.
+    let status = get_status(Some("my-app.com\r\nX-Injected: 1"), path, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
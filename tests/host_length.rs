@@ -0,0 +1,101 @@
+//! Hosts longer than `max_host_len` are refused with a 400 before they reach
+//! the whitelist, a backend URL or a log line.
+
+mod common;
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::{AppState, ConfigError};
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::host::{DEFAULT_MAX_HOST_LEN, ValidatedHost};
+
+use common::{ALLOWED_HOST, test_builder};
+
+// A name of exactly `len` bytes, in labels DNS would accept.
+fn host_of(len: usize) -> String {
+    let mut host = "a".repeat(63);
+    while host.len() < len {
+        host.push_str(".a");
+    }
+    host.truncate(len);
+    host
+}
+
+async fn call(state: AppState, path: &str, host: &str) -> (StatusCode, Value) {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route(
+                "/host",
+                web::get().to(|host: ValidatedHost| async move {
+                    HttpResponse::Ok().json(serde_json::json!({ "host": host.as_str() }))
+                }),
+            )
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(path)
+        .insert_header(("Host", host))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    (res.status(), test::read_body_json(res).await)
+}
+
+#[actix_web::test]
+async fn a_host_at_the_limit_is_accepted() {
+    let host = host_of(DEFAULT_MAX_HOST_LEN);
+    let state = test_builder().allowed_host(&host).build().unwrap();
+    let (status, body) = call(state, "/host", &host).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["host"], host);
+}
+
+#[actix_web::test]
+async fn a_host_over_the_limit_is_refused() {
+    let host = host_of(DEFAULT_MAX_HOST_LEN + 1);
+    let state = test_builder().allowed_host(&host).build().unwrap();
+    let (status, body) = call(state, "/host", &host).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "INVALID_HOST");
+}
+
+#[actix_web::test]
+async fn the_vulnerable_handler_never_builds_a_url_from_one() {
+    let host = host_of(64 * 1024);
+    let (status, body) = call(
+        test_builder().build().unwrap(),
+        "/vulnerable/waitlist?email=user@good.com",
+        &host,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Invalid 'Host' header provided.");
+}
+
+#[actix_web::test]
+async fn a_normal_host_is_unaffected_by_a_lower_limit() {
+    let state = test_builder().max_host_len(16).build().unwrap();
+    let (status, body) = call(state, "/host", ALLOWED_HOST).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["host"], ALLOWED_HOST);
+
+    let state = test_builder().max_host_len(16).build().unwrap();
+    let (status, _) = call(state, "/host", "a-rather-long.my-app.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn a_zero_limit_is_refused() {
+    let err = test_builder().max_host_len(0).build().err().unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "max_host_len",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,53 @@
+//! `HostMatcher` matches wildcard subdomains, explicit ports and hosts in any
+//! case, with or without a trailing dot.
+
+use uncaught_exception::host::HostMatcher;
+
+#[test]
+fn a_wildcard_matches_subdomains_but_not_the_domain() {
+    let matcher = HostMatcher::new(["*.my-app.com"], false);
+    assert!(matcher.is_allowed("prod.my-app.com"));
+    assert!(matcher.is_allowed("eu.prod.my-app.com"));
+    assert!(!matcher.is_allowed("my-app.com"));
+    assert!(!matcher.is_allowed("evil-my-app.com"));
+    assert!(!matcher.is_allowed("my-app.com.evil.com"));
+}
+
+#[test]
+fn a_port_must_match_unless_ports_are_ignored() {
+    let strict = HostMatcher::new(["my-app.com:8080", "other.com"], false);
+    assert!(strict.is_allowed("my-app.com:8080"));
+    assert!(!strict.is_allowed("my-app.com"));
+    assert!(!strict.is_allowed("my-app.com:9090"));
+    assert!(strict.is_allowed("other.com"));
+    assert!(!strict.is_allowed("other.com:8080"));
+
+    let lenient = HostMatcher::new(["my-app.com:8080", "*.my-app.com"], true);
+    assert!(lenient.is_allowed("my-app.com"));
+    assert!(lenient.is_allowed("my-app.com:9090"));
+    assert!(lenient.is_allowed("prod.my-app.com:8080"));
+}
+
+#[test]
+fn case_and_a_trailing_dot_do_not_matter() {
+    let matcher = HostMatcher::new(["My-App.com", "*.Example.ORG."], false);
+    assert!(matcher.is_allowed("my-app.com"));
+    assert!(matcher.is_allowed("MY-APP.COM"));
+    assert!(matcher.is_allowed("my-app.com."));
+    assert!(matcher.is_allowed("Www.example.org"));
+    assert!(matcher.is_allowed("www.EXAMPLE.org."));
+}
+
+#[test]
+fn malformed_hosts_are_refused() {
+    let matcher = HostMatcher::new(["my-app.com"], true);
+    for host in [
+        "",
+        "my-app.com:",
+        "my-app.com:port",
+        "user@my-app.com",
+        "my app.com",
+    ] {
+        assert!(!matcher.is_allowed(host), "{host:?}");
+    }
+}
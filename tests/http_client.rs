@@ -0,0 +1,56 @@
+//! A backend client that cannot be built fails `AppStateBuilder::build` with
+//! a `ConfigError` naming the cause, rather than a panic, and without the key.
+
+use uncaught_exception::backend::{BackendConfig, client_builder};
+use uncaught_exception::config::{AppState, ConfigError};
+
+const API_KEY: &str = "http-client-key-9f8e7d";
+
+#[actix_web::test]
+async fn a_client_that_fails_to_build_is_a_config_error() {
+    // reqwest only reports the bad header once the client is built.
+    let builder = client_builder(&BackendConfig::default()).user_agent("bad\nagent");
+    let err = AppState::builder()
+        .api_key(API_KEY)
+        .allowed_host("my-app.com")
+        .http_client(builder)
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err, ConfigError::HttpClient(_)), "{err}");
+    let message = err.to_string();
+    assert!(message.starts_with("failed to build the backend HTTP client: builder error: "));
+    assert!(!message.contains(API_KEY), "{message}");
+}
+
+#[actix_web::test]
+async fn an_invalid_pin_is_refused_before_the_client_is_built() {
+    let err = AppState::builder()
+        .api_key(API_KEY)
+        .allowed_host("my-app.com")
+        .tls_pin("not-base64!")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "backend.tls_pins",
+                ..
+            }
+        ),
+        "{err}"
+    );
+    assert!(!err.to_string().contains(API_KEY), "{err}");
+}
+
+#[actix_web::test]
+async fn the_given_settings_are_used_when_they_build() {
+    let state = AppState::builder()
+        .api_key(API_KEY)
+        .allowed_host("my-app.com")
+        .http_client(client_builder(&BackendConfig::default()).user_agent("waitlist-test"))
+        .build();
+    assert!(state.is_ok());
+}
@@ -0,0 +1,126 @@
+//! With `force_https`, plain-HTTP requests are redirected to HTTPS on the
+//! whitelisted host, except for the health checks.
+
+mod common;
+
+use std::path::PathBuf;
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::config::{AppState, ConfigError};
+use uncaught_exception::health::healthz;
+use uncaught_exception::middleware::HttpsRedirect;
+use uncaught_exception::server::{ServerConfig, TlsConfig};
+
+use common::test_builder;
+
+const PROXY: &str = "10.0.0.5:443";
+
+fn server(tls_port: Option<u16>) -> ServerConfig {
+    ServerConfig {
+        force_https: true,
+        tls: tls_port.map(|port| TlsConfig {
+            cert_path: PathBuf::from("cert.pem"),
+            key_path: PathBuf::from("key.pem"),
+            port,
+            disable_plain_http: false,
+        }),
+        ..ServerConfig::default()
+    }
+}
+
+// Calls `req` through the redirect, behind a trusted proxy unless the server
+// terminates TLS itself. Returns the status and `Location`.
+async fn call(tls_port: Option<u16>, req: test::TestRequest) -> (StatusCode, Option<String>) {
+    let state = test_builder()
+        .trusted_proxy("10.0.0.0/8")
+        .server(server(tls_port))
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(HttpsRedirect::new(&state.server))
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::post().to(HttpResponse::Ok))
+            .route("/healthz", web::get().to(healthz)),
+    )
+    .await;
+    let res = test::call_service(&app, req.to_request()).await;
+    let location = res
+        .headers()
+        .get("location")
+        .map(|v| v.to_str().unwrap().to_string());
+    (res.status(), location)
+}
+
+fn signup(host: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/secure/waitlist?email=user@good.com")
+        .peer_addr(PROXY.parse().unwrap())
+        .insert_header(("Host", host))
+}
+
+#[actix_web::test]
+async fn plain_http_is_redirected_keeping_the_path() {
+    let (status, location) = call(None, signup("My-App.com")).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        location.as_deref(),
+        Some("https://my-app.com/secure/waitlist?email=user@good.com")
+    );
+
+    // When this server serves HTTPS itself, to its TLS port.
+    let (status, location) = call(Some(8443), signup("my-app.com")).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        location.as_deref(),
+        Some("https://my-app.com:8443/secure/waitlist?email=user@good.com")
+    );
+}
+
+#[actix_web::test]
+async fn https_through_a_trusted_proxy_is_served() {
+    let req = signup("my-app.com").insert_header(("X-Forwarded-Proto", "https"));
+    assert_eq!(call(None, req).await, (StatusCode::OK, None));
+
+    // The same claim from anyone else is ignored.
+    let req = signup("my-app.com")
+        .peer_addr("203.0.113.9:50000".parse().unwrap())
+        .insert_header(("X-Forwarded-Proto", "https"));
+    assert_eq!(call(None, req).await.0, StatusCode::PERMANENT_REDIRECT);
+}
+
+#[actix_web::test]
+async fn a_host_off_the_whitelist_is_not_redirected_to() {
+    let (status, location) = call(None, signup("evil.com")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(location, None);
+}
+
+#[actix_web::test]
+async fn health_checks_are_exempt() {
+    let req = test::TestRequest::get()
+        .uri("/healthz")
+        .insert_header(("Host", "my-app.com"));
+    assert_eq!(call(None, req).await, (StatusCode::OK, None));
+}
+
+#[actix_web::test]
+async fn forcing_https_needs_a_way_to_serve_it() {
+    let err = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .server(server(None))
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "server.force_https",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
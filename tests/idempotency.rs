@@ -0,0 +1,192 @@
+//! `Idempotency-Key` on `POST /secure/waitlist`: a repeated request gets the
+//! first response back, and a reused key with a different body a 422.
+
+use std::sync::Arc;
+
+use actix_web::{App, HttpResponse, dev::ServiceResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::{json_config, secure_waitlist_json};
+use uncaught_exception::idempotency::{
+    Begin, IDEMPOTENT_REPLAYED, IdempotencyConfig, IdempotencyStore, MAX_KEYS, fingerprint,
+};
+use uncaught_exception::store::{InMemoryStore, WaitlistStore};
+
+// Nothing listens on port 1, so a request that gets as far as the backend call
+// fails fast with a 502.
+const ALLOWED_HOST: &str = "127.0.0.1:1";
+
+fn test_state(store: &InMemoryStore) -> AppState {
+    AppState::builder()
+        .api_key("test-key")
+        .allowed_host(ALLOWED_HOST)
+        .private_host(ALLOWED_HOST)
+        .max_retries(0)
+        .waitlist_store(Arc::new(store.clone()))
+        .build()
+        .unwrap()
+}
+
+fn signup(key: &str, email: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/secure/waitlist")
+        .insert_header(("Host", ALLOWED_HOST))
+        .insert_header(("Idempotency-Key", key))
+        .set_json(serde_json::json!({ "email": email }))
+        .to_request()
+}
+
+fn replayed(res: &ServiceResponse) -> bool {
+    res.headers().contains_key(IDEMPOTENT_REPLAYED)
+}
+
+#[actix_web::test]
+async fn a_repeated_request_gets_the_cached_response() {
+    let store = InMemoryStore::new();
+    store.insert("user@good.com").unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(&store)))
+            .app_data(json_config(1024))
+            .route("/secure/waitlist", web::post().to(secure_waitlist_json)),
+    )
+    .await;
+
+    let res = test::call_service(&app, signup("key-1", "user@good.com")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert!(!replayed(&res));
+
+    // Processed afresh, the signup would now reach the backend and fail.
+    store.remove("user@good.com").unwrap();
+    let res = test::call_service(&app, signup("key-1", "user@good.com")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert!(replayed(&res));
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "ALREADY_SIGNED_UP");
+}
+
+#[actix_web::test]
+async fn a_reused_key_with_a_different_body_is_refused_with_422() {
+    let store = InMemoryStore::new();
+    store.insert("user@good.com").unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(&store)))
+            .app_data(json_config(1024))
+            .route("/secure/waitlist", web::post().to(secure_waitlist_json)),
+    )
+    .await;
+
+    let res = test::call_service(&app, signup("key-2", "user@good.com")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let res = test::call_service(&app, signup("key-2", "other@good.com")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "IDEMPOTENCY_KEY_REUSED");
+}
+
+#[actix_web::test]
+async fn server_errors_are_not_cached() {
+    let store = InMemoryStore::new();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(&store)))
+            .app_data(json_config(1024))
+            .route("/secure/waitlist", web::post().to(secure_waitlist_json)),
+    )
+    .await;
+
+    for _ in 0..2 {
+        let res = test::call_service(&app, signup("key-3", "user@good.com")).await;
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert!(!replayed(&res));
+    }
+}
+
+#[actix_web::test]
+async fn malformed_keys_are_refused() {
+    let store = InMemoryStore::new();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(&store)))
+            .app_data(json_config(1024))
+            .route("/secure/waitlist", web::post().to(secure_waitlist_json)),
+    )
+    .await;
+
+    for key in [String::new(), "has space".to_string(), "k".repeat(256)] {
+        let res = test::call_service(&app, signup(&key, "user@good.com")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{key:?}");
+    }
+}
+
+#[actix_web::test]
+async fn a_key_in_flight_is_refused_until_released() {
+    let idempotency = IdempotencyStore::new(IdempotencyConfig::default());
+    let Ok(Begin::New(reservation)) = idempotency.begin("key", fingerprint("user@good.com")) else {
+        panic!("a new key must be reserved");
+    };
+    assert!(matches!(
+        idempotency.begin("key", fingerprint("user@good.com")),
+        Err(ApiError::IdempotencyKeyInUse { .. })
+    ));
+
+    // Dropped without completing, e.g. because the client went away.
+    drop(reservation);
+    let Ok(Begin::New(reservation)) = idempotency.begin("key", fingerprint("user@good.com")) else {
+        panic!("a released key must be reserved again");
+    };
+    let res = reservation
+        .complete(Ok(HttpResponse::Ok().body("done")))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let Ok(Begin::Replay(res)) = idempotency.begin("key", fingerprint("user@good.com")) else {
+        panic!("a completed key must be replayed");
+    };
+    assert_eq!(res.headers().get(IDEMPOTENT_REPLAYED).unwrap(), "true");
+    let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "done");
+}
+
+#[actix_web::test]
+async fn the_store_forgets_the_oldest_key_once_full() {
+    let idempotency = IdempotencyStore::new(IdempotencyConfig::default());
+    let email = fingerprint("user@good.com");
+    for i in 0..=MAX_KEYS {
+        let Ok(Begin::New(reservation)) = idempotency.begin(&format!("key-{i}"), email) else {
+            panic!("a new key must be reserved");
+        };
+        reservation
+            .complete(Ok(HttpResponse::Ok().finish()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(idempotency.len(), MAX_KEYS);
+
+    // The first key made room for the last, the second is still remembered.
+    assert!(matches!(
+        idempotency.begin("key-0", email),
+        Ok(Begin::New(_))
+    ));
+    assert!(matches!(
+        idempotency.begin("key-2", email),
+        Ok(Begin::Replay(_))
+    ));
+}
+
+#[actix_web::test]
+async fn the_fingerprint_tells_emails_apart() {
+    let idempotency = IdempotencyStore::new(IdempotencyConfig::default());
+    assert_eq!(fingerprint("user@good.com"), fingerprint("user@good.com"));
+    let Ok(Begin::New(_reservation)) = idempotency.begin("key", fingerprint("user@good.com"))
+    else {
+        panic!("a new key must be reserved");
+    };
+    assert!(matches!(
+        idempotency.begin("key", fingerprint("other@good.com")),
+        Err(ApiError::IdempotencyKeyReused { .. })
+    ));
+}
@@ -0,0 +1,147 @@
+//! Success and generic error messages follow `Accept-Language`, falling back
+//! to English.
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::messages::{
+    DEFAULT_GENERIC_ERROR, DEFAULT_VULNERABLE_SIGNUP_SUCCESS, Localizer, Messages,
+};
+use uncaught_exception::middleware::ErrorNegotiation;
+
+const SPANISH_SUCCESS: &str = "Gracias por tu interés. Te hemos añadido a la lista de espera.";
+
+fn resolve(localizer: &Localizer, accept_language: &str) -> Messages {
+    let req = test::TestRequest::default()
+        .insert_header(("Accept-Language", accept_language))
+        .to_http_request();
+    localizer.for_request(&req).clone()
+}
+
+#[actix_web::test]
+async fn english_gets_the_defaults() {
+    let localizer = Localizer::default();
+    assert_eq!(resolve(&localizer, "en"), Messages::default());
+    assert_eq!(resolve(&localizer, "en-GB"), Messages::default());
+}
+
+#[actix_web::test]
+async fn spanish_is_built_in() {
+    let localizer = Localizer::default();
+    assert_eq!(
+        resolve(&localizer, "es").vulnerable_signup_success,
+        SPANISH_SUCCESS
+    );
+    // A regional tag falls back to its language.
+    assert_eq!(
+        resolve(&localizer, "es-MX").vulnerable_signup_success,
+        SPANISH_SUCCESS
+    );
+}
+
+#[actix_web::test]
+async fn quality_values_decide() {
+    let localizer = Localizer::default();
+    assert_eq!(
+        resolve(&localizer, "en;q=0.5, es;q=0.9").vulnerable_signup_success,
+        SPANISH_SUCCESS
+    );
+    assert_eq!(
+        resolve(&localizer, "es;q=0.4, en;q=0.8"),
+        Messages::default()
+    );
+    // Unsupported languages are skipped, and `q=0` means "not this one".
+    assert_eq!(
+        resolve(&localizer, "fr, es;q=0.7").vulnerable_signup_success,
+        SPANISH_SUCCESS
+    );
+    assert_eq!(resolve(&localizer, "es;q=0, fr"), Messages::default());
+}
+
+#[actix_web::test]
+async fn unknown_tags_fall_back_to_english() {
+    let localizer = Localizer::default();
+    assert_eq!(resolve(&localizer, "tlh"), Messages::default());
+    assert_eq!(resolve(&localizer, "*"), Messages::default());
+    assert_eq!(resolve(&localizer, "not a tag!"), Messages::default());
+}
+
+#[actix_web::test]
+async fn configured_translations_are_used() {
+    let localizer = Localizer::default().translation(
+        "pt-BR",
+        Messages {
+            vulnerable_signup_success: "Obrigado!".to_string(),
+            ..Messages::default()
+        },
+    );
+    assert_eq!(
+        resolve(&localizer, "pt-br").vulnerable_signup_success,
+        "Obrigado!"
+    );
+    assert_eq!(resolve(&localizer, "pt-PT"), Messages::default());
+}
+
+#[actix_web::test]
+async fn the_handler_answers_in_the_preferred_language() {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    for (accept_language, expected) in [
+        ("es-ES,es;q=0.9", SPANISH_SUCCESS),
+        ("de", DEFAULT_VULNERABLE_SIGNUP_SUCCESS),
+    ] {
+        let req = test::TestRequest::get()
+            .uri("/vulnerable/waitlist?email=user@good.com")
+            .insert_header(("Host", "my-app.com"))
+            .insert_header(("Accept-Language", accept_language))
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, expected);
+    }
+}
+
+#[actix_web::test]
+async fn generic_errors_are_localized() {
+    let app = test::init_service(App::new().wrap(ErrorNegotiation).route(
+        "/fail",
+        web::get().to(|| async {
+            Err::<HttpResponse, _>(ApiError::Internal {
+                reason: "boom".to_string(),
+            })
+        }),
+    ))
+    .await;
+
+    for (accept_language, expected) in [
+        (
+            "es",
+            "¡Vaya! Algo salió mal. Por favor, inténtalo de nuevo más tarde.",
+        ),
+        ("en-US", DEFAULT_GENERIC_ERROR),
+    ] {
+        let req = test::TestRequest::get()
+            .uri("/fail")
+            .insert_header(("Accept-Language", accept_language))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let vary: Vec<_> = res
+            .headers()
+            .get_all("vary")
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        assert!(vary.iter().any(|v| v == "Accept-Language"), "{vary:?}");
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["message"], expected);
+    }
+}
@@ -0,0 +1,26 @@
+//! Which log filter the server starts with.
+
+use uncaught_exception::logging::{DEFAULT_FILTER, resolve_filter};
+
+#[test]
+fn defaults_to_info() {
+    assert_eq!(DEFAULT_FILTER, "info");
+    assert_eq!(resolve_filter(None, None), "info");
+    assert_eq!(resolve_filter(Some(""), Some("  ")), "info");
+}
+
+#[test]
+fn honours_rust_log() {
+    assert_eq!(resolve_filter(None, Some("debug")), "debug");
+    assert_eq!(
+        resolve_filter(None, Some("info,actix_web=warn")),
+        "info,actix_web=warn"
+    );
+}
+
+#[test]
+fn the_cli_flag_wins() {
+    assert_eq!(resolve_filter(Some("trace"), Some("debug")), "trace");
+    assert_eq!(resolve_filter(Some(" warn "), None), "warn");
+    assert_eq!(resolve_filter(Some(""), Some("debug")), "debug");
+}
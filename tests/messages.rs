@@ -0,0 +1,93 @@
+//! Response texts come from the configuration, with today's strings as the
+//! defaults.
+
+use actix_web::{App, ResponseError, body::to_bytes, http::StatusCode, test, web};
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::messages::{DEFAULT_VULNERABLE_SIGNUP_SUCCESS, Localizer, Messages};
+
+async fn signup(state: AppState) -> (StatusCode, String) {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/vulnerable/waitlist?email=user@good.com")
+        .insert_header(("Host", "my-app.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn builder() -> uncaught_exception::config::AppStateBuilder {
+    AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+}
+
+#[actix_web::test]
+async fn the_default_success_message_is_used_otherwise() {
+    let (status, body) = signup(builder().build().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, DEFAULT_VULNERABLE_SIGNUP_SUCCESS);
+}
+
+#[actix_web::test]
+async fn a_configured_success_message_is_returned() {
+    let messages = Messages {
+        vulnerable_signup_success: "¡Gracias! Ya estás en la lista.".to_string(),
+        ..Messages::default()
+    };
+    let (status, body) = signup(builder().messages(messages).build().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "¡Gracias! Ya estás en la lista.");
+}
+
+#[actix_web::test]
+async fn messages_are_read_from_the_config_file() {
+    let path = std::env::temp_dir().join(format!("messages-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+api_key = "test-key"
+allowed_hosts = ["my-app.com"]
+
+[messages]
+signup_success = "You're in."
+"#,
+    )
+    .unwrap();
+    let state = AppState::from_toml(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(state.localizer.fallback().signup_success, "You're in.");
+    assert_eq!(
+        state.localizer.fallback().vulnerable_signup_success,
+        DEFAULT_VULNERABLE_SIGNUP_SUCCESS
+    );
+}
+
+#[actix_web::test]
+async fn the_generic_error_message_is_configurable() {
+    // The only test in this binary that touches the global messages.
+    Localizer::new(Messages {
+        generic_error: "Something broke on our side.".to_string(),
+        ..Messages::default()
+    })
+    .set_global();
+
+    let err = ApiError::Internal {
+        reason: "database is on fire".to_string(),
+    };
+    let res = err.error_response();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = to_bytes(res.into_body()).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("Something broke on our side."), "{body}");
+    assert!(!body.contains("fire"), "{body}");
+}
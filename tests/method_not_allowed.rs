@@ -0,0 +1,77 @@
+//! A known path requested with a method it has no route for answers 405, with
+//! an `Allow` header naming the methods it does have.
+
+mod common;
+
+use actix_web::{
+    http::{Method, StatusCode, header::ALLOW},
+    test,
+};
+use serde_json::Value;
+use uncaught_exception::middleware::AuthConfig;
+
+use common::{ALLOWED_HOST, test_app, test_builder, test_state};
+
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+async fn call(method: Method, path: &str) -> (StatusCode, Option<String>, Value) {
+    let state = test_builder()
+        .auth(AuthConfig {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+        })
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+    let req = test::TestRequest::default()
+        .method(method)
+        .uri(path)
+        .insert_header(("Host", ALLOWED_HOST))
+        .insert_header(("Authorization", format!("Bearer {ADMIN_TOKEN}")))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let allow = res
+        .headers()
+        .get(ALLOW)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = test::read_body(res).await;
+    (status, allow, serde_json::from_slice(&body).unwrap())
+}
+
+#[actix_web::test]
+async fn the_signup_path_lists_get_and_post() {
+    let (status, allow, body) = call(Method::DELETE, "/secure/waitlist").await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(allow.as_deref(), Some("GET, POST"));
+    assert_eq!(body["error"]["code"], "METHOD_NOT_ALLOWED");
+    assert_eq!(
+        body["error"]["message"],
+        "This method is not allowed for this resource."
+    );
+}
+
+#[actix_web::test]
+async fn a_get_only_route_allows_get() {
+    let (status, allow, _) = call(Method::POST, "/healthz").await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(allow.as_deref(), Some("GET"));
+}
+
+#[actix_web::test]
+async fn an_admin_route_allows_post() {
+    let (status, allow, _) = call(Method::GET, "/admin/maintenance").await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(allow.as_deref(), Some("POST"));
+}
+
+#[actix_web::test]
+async fn an_unknown_path_is_still_a_404() {
+    let app = test_app(test_state()).await;
+    let req = test::TestRequest::post()
+        .uri("/no/such/path")
+        .insert_header(("Host", ALLOWED_HOST))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert!(res.headers().get(ALLOW).is_none());
+}
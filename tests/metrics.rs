@@ -0,0 +1,107 @@
+//! `GET /metrics` exports request counts and `secret_redactions_total`, and an
+//! unknown method is counted as `other`.
+
+use actix_web::http::Method;
+use actix_web::{App, http::StatusCode, test, web};
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::metrics::{Metrics, metrics};
+use uncaught_exception::middleware::RequestMetrics;
+use uncaught_exception::secrets::SecretRegistry;
+
+const TEST_API_KEY: &str = "test-key-9a8b7c-metrics-canary";
+
+fn test_state() -> AppState {
+    AppState::builder()
+        .api_key(TEST_API_KEY)
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap()
+}
+
+// The value of the sample line that starts with `series`, if any.
+fn sample(scrape: &str, series: &str) -> Option<f64> {
+    scrape
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.trim().parse().ok())
+}
+
+#[actix_web::test]
+async fn a_redaction_on_the_vulnerable_path_is_counted() {
+    SecretRegistry::global().register(TEST_API_KEY);
+    let registry = Metrics::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestMetrics::new(registry.clone()))
+            .app_data(web::Data::new(test_state()))
+            .app_data(web::Data::new(registry))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist))
+            .route("/metrics", web::get().to(metrics)),
+    )
+    .await;
+    let scrape = || async {
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+    let before = sample(&scrape().await, "secret_redactions_total").unwrap();
+
+    // An out-of-range port makes the backend URL, key and all, fail to
+    // parse, and the vulnerable handler reflects it.
+    let req = test::TestRequest::get()
+        .uri("/vulnerable/waitlist?email=attacker@evil.com")
+        .insert_header(("Host", "my-app.com:99999"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = test::read_body(res).await;
+    assert!(!String::from_utf8_lossy(&body).contains(TEST_API_KEY));
+
+    let after = scrape().await;
+    assert!(
+        sample(&after, "secret_redactions_total").unwrap() > before,
+        "{after}"
+    );
+    assert_eq!(
+        sample(
+            &after,
+            r#"http_requests_total{method="GET",route="/vulnerable/waitlist",status="500"}"#
+        ),
+        Some(1.0),
+        "{after}"
+    );
+}
+
+#[actix_web::test]
+async fn an_unknown_method_is_counted_as_other() {
+    let registry = Metrics::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestMetrics::new(registry.clone()))
+            .app_data(web::Data::new(registry.clone()))
+            .route("/metrics", web::get().to(metrics)),
+    )
+    .await;
+    for method in ["PURGE", "BREW"] {
+        let req = test::TestRequest::default()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri("/metrics")
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let scrape = registry.render().unwrap();
+    assert!(scrape.contains(r#"method="other""#), "{scrape}");
+    assert!(
+        !scrape.contains("PURGE") && !scrape.contains("BREW"),
+        "{scrape}"
+    );
+    assert_eq!(
+        sample(
+            &scrape,
+            r#"http_requests_total{method="other",route="/metrics",status="404"}"#
+        ),
+        Some(2.0),
+        "{scrape}"
+    );
+}
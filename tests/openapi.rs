@@ -0,0 +1,50 @@
+//! The OpenAPI spec is served and describes the waitlist endpoints and the
+//! sanitized error body.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::routes;
+
+#[actix_web::test]
+async fn the_spec_documents_the_waitlist_and_its_errors() {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .configure(|cfg| routes::configure(cfg, &state.auth)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/api-docs/openapi.json")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let spec: Value = test::read_body_json(res).await;
+
+    let waitlist = &spec["paths"]["/secure/waitlist"];
+    for method in ["get", "post"] {
+        let responses = &waitlist[method]["responses"];
+        for status in ["200", "400", "500"] {
+            assert!(responses[status].is_object(), "{method} {status}");
+        }
+        assert_eq!(
+            responses["400"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ErrorBody"
+        );
+    }
+    assert_eq!(waitlist["get"]["parameters"][0]["name"], "email");
+    assert_eq!(
+        spec["paths"]["/vulnerable/waitlist"].is_object(),
+        routes::INSECURE_DEMO
+    );
+
+    let detail = &spec["components"]["schemas"]["ErrorDetail"]["properties"];
+    for field in ["code", "message", "request_id"] {
+        assert!(detail[field].is_object(), "{field}");
+    }
+}
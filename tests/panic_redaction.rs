@@ -0,0 +1,100 @@
+//! A panic message is logged with every registered secret redacted, whether
+//! `CatchPanic` catches it or the panic hook reports it.
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing_subscriber::util::SubscriberInitExt;
+use uncaught_exception::middleware::{CatchPanic, install_panic_hook, panic_message};
+use uncaught_exception::secrets::SecretRegistry;
+
+const API_KEY: &str = "panic-test-key-0f1e2d3c";
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// The `log` records only reach a global subscriber, so every test shares it.
+fn captured() -> &'static Captured {
+    static CAPTURED: OnceLock<Captured> = OnceLock::new();
+    CAPTURED.get_or_init(|| {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .finish()
+            .init();
+        SecretRegistry::global().register(API_KEY);
+        captured
+    })
+}
+
+fn logged() -> String {
+    String::from_utf8_lossy(&captured().0.lock().unwrap()).into_owned()
+}
+
+async fn leaky_handler() -> HttpResponse {
+    panic!("failed to call https://my-app.com/v1/waitlist?api_key={API_KEY}&email=a@b.com")
+}
+
+#[actix_web::test]
+async fn a_caught_panic_is_logged_once_without_the_key() {
+    captured();
+    install_panic_hook();
+    let app = test::init_service(
+        App::new()
+            .wrap(CatchPanic)
+            .route("/boom", web::get().to(leaky_handler)),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/boom").to_request();
+    let res = test::try_call_service(&app, req).await;
+    let status = match res {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let logged = logged();
+    // By the hook, with its location; `CatchPanic` only adds that it answered.
+    let message = "failed to call https://my-app.com/v1/waitlist?api_key=[REDACTED]";
+    assert_eq!(logged.matches(message).count(), 1, "{logged}");
+    assert!(
+        logged.contains("Panicked at tests/panic_redaction.rs"),
+        "{logged}"
+    );
+    assert!(
+        logged.contains("Handler panicked; answering with a 500"),
+        "{logged}"
+    );
+    assert!(!logged.contains(API_KEY), "{logged}");
+}
+
+#[actix_web::test]
+async fn the_panic_hook_logs_without_the_key() {
+    captured();
+    install_panic_hook();
+    let _ = std::panic::catch_unwind(|| panic!("hook test: api_key={API_KEY}"));
+
+    let logged = logged();
+    assert!(logged.contains("hook test: api_key=[REDACTED]"), "{logged}");
+    assert!(!logged.contains(API_KEY), "{logged}");
+}
+
+#[actix_web::test]
+async fn non_string_payloads_are_not_guessed_at() {
+    captured();
+    assert_eq!(panic_message(&42_u32), "<non-string panic payload>");
+    assert_eq!(panic_message(&format!("key {API_KEY}")), "key [REDACTED]");
+}
@@ -0,0 +1,82 @@
+//! Email masking and query parameter redaction for log lines.
+
+use reqwest::Url;
+use uncaught_exception::pii::{mask_email, redact_url_for_logging, url_for_log};
+
+#[test]
+fn keeps_the_first_character_and_the_domain() {
+    assert_eq!(mask_email("jane@example.com"), "j***@example.com");
+}
+
+#[test]
+fn hides_short_local_parts_entirely() {
+    assert_eq!(mask_email("j@example.com"), "***@example.com");
+    assert_eq!(mask_email("jo@example.com"), "j***@example.com");
+    assert_eq!(mask_email("@example.com"), "***@example.com");
+}
+
+#[test]
+fn hides_subaddress_tags() {
+    assert_eq!(mask_email("user+tag@example.com"), "u***@example.com");
+}
+
+#[test]
+fn masks_values_without_an_at_sign() {
+    assert_eq!(mask_email("not-an-email"), "***");
+}
+
+#[test]
+fn masks_only_the_logged_copy_of_the_url() {
+    let url =
+        Url::parse("https://my-app.com/v1/waitlist?api_key=k&email=jane%40example.com").unwrap();
+    assert_eq!(
+        url_for_log(&url, false),
+        "https://my-app.com/v1/waitlist?api_key=k&email=j***%40example.com"
+    );
+    assert_eq!(url.query(), Some("api_key=k&email=jane%40example.com"));
+    assert_eq!(url_for_log(&url, true), url.as_str());
+}
+
+#[test]
+fn redacts_listed_query_parameters_case_insensitively() {
+    let params = vec!["api_key".to_string(), "Signature".to_string()];
+    let url = "https://my-app.com/v1/waitlist?API_KEY=k&signature=abc&email=jane%40example.com";
+    assert_eq!(
+        redact_url_for_logging(url, &params),
+        "https://my-app.com/v1/waitlist?API_KEY=[REDACTED]&signature=[REDACTED]&email=jane%40example.com"
+    );
+}
+
+#[test]
+fn redacts_email_only_when_listed() {
+    let url =
+        Url::parse("https://my-app.com/v1/waitlist?api_key=k&email=jane%40example.com").unwrap();
+    let params = vec!["api_key".to_string()];
+    assert_eq!(
+        redact_url_for_logging(&url_for_log(&url, false), &params),
+        "https://my-app.com/v1/waitlist?api_key=[REDACTED]&email=j***%40example.com"
+    );
+    assert_eq!(
+        redact_url_for_logging(&url_for_log(&url, true), &params),
+        "https://my-app.com/v1/waitlist?api_key=[REDACTED]&email=jane%40example.com"
+    );
+    let params = vec!["api_key".to_string(), "email".to_string()];
+    assert_eq!(
+        redact_url_for_logging(url.as_str(), &params),
+        "https://my-app.com/v1/waitlist?api_key=[REDACTED]&email=[REDACTED]"
+    );
+}
+
+#[test]
+fn redacts_urls_that_do_not_parse() {
+    // The URL recorded in `ApiError::UrlParse`: the port is out of range.
+    let url = "https://my-app.com:99999/v1/waitlist?api_key=k&email=a%40b.com#frag";
+    assert_eq!(
+        redact_url_for_logging(url, &["api_key".to_string()]),
+        "https://my-app.com:99999/v1/waitlist?api_key=[REDACTED]&email=a%40b.com#frag"
+    );
+    assert_eq!(
+        redact_url_for_logging("https://my-app.com/v1/waitlist", &["api_key".to_string()]),
+        "https://my-app.com/v1/waitlist"
+    );
+}
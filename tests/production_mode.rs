@@ -0,0 +1,55 @@
+//! In production mode the vulnerable handler's detailed error gets the same
+//! generic body as any internal error. The switch is process-wide, so every
+//! test here runs with it on.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::{ApiError, production_mode, set_production_mode};
+use uncaught_exception::handlers::vulnerable_waitlist;
+use uncaught_exception::messages::DEFAULT_GENERIC_ERROR;
+
+const TEST_API_KEY: &str = "test-key-5e2d9c1a-production-canary";
+
+#[actix_web::test]
+async fn the_vulnerable_handler_no_longer_leaks() {
+    set_production_mode(true);
+    let state = AppState::builder()
+        .api_key(TEST_API_KEY)
+        .allowed_host("my-app.com")
+        .production_mode(true)
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    // The port is out of range, so building the backend URL fails: the
+    // error that otherwise carries the URL, the key and the parser's message.
+    let req = test::TestRequest::get()
+        .uri("/vulnerable/waitlist?email=attacker@evil.com")
+        .insert_header(("Host", "my-app.com:99999"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+    assert_eq!(body["error"]["message"], DEFAULT_GENERIC_ERROR);
+    let body = body.to_string();
+    for leak in [TEST_API_KEY, "Failed to construct", "99999", "api_key"] {
+        assert!(!body.contains(leak), "{leak}: {body}");
+    }
+}
+
+#[actix_web::test]
+async fn a_detailed_error_is_generic_wherever_it_comes_from() {
+    set_production_mode(true);
+    assert!(production_mode());
+    let error = ApiError::Detailed {
+        message: "connection to 10.0.0.5:5432 refused".to_string(),
+    };
+    assert_eq!(error.public_message(), DEFAULT_GENERIC_ERROR);
+    assert_eq!(error.code(), "INTERNAL_ERROR");
+}
@@ -0,0 +1,73 @@
+//! Property-based checks of the two guarantees the crate is built around: the
+//! backend URL cannot be bent by its inputs, and secrets never leave in an
+//! error body.
+
+use proptest::prelude::*;
+use uncaught_exception::backend::{WAITLIST_PATH, build_backend_url};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::secrets::SecretRegistry;
+
+const API_KEY: &str = "test-key";
+
+// Mostly plausible hosts (to reach the success path), plus arbitrary strings.
+fn host() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9.-]{1,30}(:[0-9]{1,6})?",
+        "[a-z0-9.]{1,10}[@/\\\\?#%\\[\\] :\r\n\t\0][a-z0-9.]{0,10}",
+        any::<String>(),
+    ]
+}
+
+fn email() -> impl Strategy<Value = String> {
+    prop_oneof!["[a-z0-9._%+-]{1,20}@[a-z0-9.-]{1,20}", any::<String>()]
+}
+
+proptest! {
+    #[test]
+    fn backend_url_is_clean_or_refused(host in host(), email in email()) {
+        let Ok(url) = build_backend_url(&host, WAITLIST_PATH, API_KEY, &email) else {
+            return Ok(());
+        };
+        let text = url.as_str();
+        prop_assert!(!text.chars().any(|c| c.is_control()), "{text:?}");
+        prop_assert!(!text.contains(' '), "{text:?}");
+
+        let emails: Vec<String> = url
+            .query_pairs()
+            .filter(|(name, _)| name == "email")
+            .map(|(_, value)| value.into_owned())
+            .collect();
+        prop_assert_eq!(emails, vec![email]);
+        let keys: Vec<String> = url
+            .query_pairs()
+            .filter(|(name, _)| name == "api_key")
+            .map(|(_, value)| value.into_owned())
+            .collect();
+        prop_assert_eq!(keys, vec![API_KEY.to_string()]);
+        prop_assert_eq!(url.fragment(), None);
+    }
+
+    #[test]
+    fn sanitizer_never_leaks_the_secret(
+        // Shaped like a real key; a one-letter "secret" could reappear inside
+        // the `[REDACTED]` marker itself.
+        secret in "[A-Za-z0-9_-]{8,40}",
+        prefix in any::<String>(),
+        suffix in any::<String>(),
+        layout in 0..5u8,
+    ) {
+        prop_assume!(!"[REDACTED]".contains(secret.as_str()));
+        let message = match layout {
+            0 => format!("{prefix}{secret}{suffix}"),
+            1 => format!("{prefix}{secret}{secret}{suffix}"),
+            2 => format!("https://{prefix}/v1/waitlist?api_key={secret}&email={suffix}"),
+            3 => format!("{{\"key\":\"{secret}\",\"note\":{prefix:?}}}{suffix}"),
+            _ => format!("{secret}{prefix}{secret}{suffix}{secret}"),
+        };
+        let registry = SecretRegistry::new();
+        registry.register(secret.clone());
+
+        let sanitized = ApiError::Detailed { message }.sanitize_with(&registry);
+        prop_assert!(!sanitized.contains(&secret), "{sanitized:?}");
+    }
+}
@@ -0,0 +1,68 @@
+//! A query string the handlers cannot deserialize gets a generic `400`, not
+//! actix's description of what serde expected.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::{query_config, secure_waitlist, vulnerable_waitlist};
+
+async fn get(uri: &str) -> (StatusCode, Value) {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .app_data(query_config())
+            .route("/secure/waitlist", web::get().to(secure_waitlist))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Host", "my-app.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn a_missing_email_is_a_generic_400() {
+    for uri in ["/secure/waitlist", "/secure/waitlist?mail=user@good.com"] {
+        let (status, body) = get(uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        assert_eq!(body["error"]["code"], "BAD_REQUEST");
+        assert_eq!(
+            body["error"]["message"],
+            "The request could not be processed."
+        );
+        let text = body.to_string();
+        assert!(!text.contains("missing field"), "{text}");
+        assert!(!text.contains("deserialize"), "{text}");
+    }
+}
+
+#[actix_web::test]
+async fn the_vulnerable_handler_gets_the_same_body() {
+    let (status, body) = get("/vulnerable/waitlist").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "BAD_REQUEST");
+    assert!(!body.to_string().contains("email"), "{body}");
+}
+
+#[actix_web::test]
+async fn a_repeated_email_is_a_400() {
+    for uri in [
+        "/secure/waitlist?email=a@good.com&email=b@evil.com",
+        "/secure/waitlist?email=a@good.com&%65mail=b@evil.com",
+    ] {
+        let (status, body) = get(uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        assert_eq!(body["error"]["code"], "BAD_REQUEST");
+        assert!(!body.to_string().contains("evil.com"), "{body}");
+    }
+}
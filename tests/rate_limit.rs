@@ -0,0 +1,105 @@
+//! `RateLimiter` allows a client its burst, then answers `429` with a
+//! `Retry-After`, keyed on an address the client cannot pick.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::middleware::{MAX_TRACKED_CLIENTS, RateLimitConfig, RateLimiter};
+
+use common::test_builder;
+
+const PROXY: &str = "10.0.0.5:4000";
+
+// One request a minute, after a burst of three.
+fn config(trust_forwarded_for: bool) -> RateLimitConfig {
+    RateLimitConfig {
+        requests_per_second: 1.0 / 60.0,
+        burst: 3,
+        trust_forwarded_for,
+        ..RateLimitConfig::default()
+    }
+}
+
+fn request(peer: &str, forwarded_for: Option<&str>) -> actix_http::Request {
+    let req = test::TestRequest::get().peer_addr(peer.parse().unwrap());
+    match forwarded_for {
+        Some(ip) => req.insert_header(("X-Forwarded-For", ip)),
+        None => req,
+    }
+    .to_request()
+}
+
+#[actix_web::test]
+async fn the_request_after_the_burst_gets_a_429_with_retry_after() {
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimiter::new(config(false)))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    for _ in 0..3 {
+        let res = test::call_service(&app, request("203.0.113.7:4000", None)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    let res = test::call_service(&app, request("203.0.113.7:4000", None)).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers().get("retry-after").unwrap(), "60");
+
+    // Another client has a bucket of its own.
+    let res = test::call_service(&app, request("203.0.113.8:4000", None)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn forwarded_addresses_count_only_from_trusted_proxies() {
+    let state = test_builder().trusted_proxy("10.0.0.0/8").build().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .wrap(RateLimiter::new(config(true)))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    // A client rotating spoofed addresses is still one client.
+    for (i, expected) in [StatusCode::OK; 3]
+        .into_iter()
+        .chain([StatusCode::TOO_MANY_REQUESTS])
+        .enumerate()
+    {
+        let spoofed = format!("198.51.100.{i}");
+        let res = test::call_service(&app, request("203.0.113.7:4000", Some(&spoofed))).await;
+        assert_eq!(res.status(), expected, "{spoofed}");
+    }
+
+    // Behind the trusted proxy, each forwarded client has its own bucket.
+    for client in ["198.51.100.1", "198.51.100.2"] {
+        for _ in 0..3 {
+            let res = test::call_service(&app, request(PROXY, Some(client))).await;
+            assert_eq!(res.status(), StatusCode::OK, "{client}");
+        }
+        let res = test::call_service(&app, request(PROXY, Some(client))).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS, "{client}");
+    }
+}
+
+#[actix_web::test]
+async fn a_flood_of_addresses_tracks_a_bounded_number_of_clients() {
+    let limiter = RateLimiter::new(config(false));
+    let first = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    for _ in 0..3 {
+        limiter.check(first).unwrap();
+    }
+    assert!(limiter.check(first).is_err());
+
+    for i in 0..=MAX_TRACKED_CLIENTS as u32 {
+        limiter
+            .check(IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + i)))
+            .unwrap();
+    }
+    assert_eq!(limiter.tracked_clients(), MAX_TRACKED_CLIENTS);
+    // The least recently seen client was evicted, and starts over.
+    assert!(limiter.check(first).is_ok());
+}
@@ -0,0 +1,85 @@
+//! `CorrelationId` gives every request an ID, echoed in `X-Request-Id`, and
+//! the same one the handler and the error body see.
+
+use actix_web::dev::ServiceResponse;
+use actix_web::{App, HttpRequest, HttpResponse, test, web};
+use serde_json::Value;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::{CorrelationId, REQUEST_ID_HEADER, RequestId};
+
+// Answers with the ID the handler sees, or fails with an `ApiError`.
+async fn call(req: test::TestRequest) -> ServiceResponse {
+    let app = test::init_service(
+        App::new()
+            .wrap(CorrelationId)
+            .route(
+                "/id",
+                web::get().to(|req: HttpRequest| async move {
+                    HttpResponse::Ok().body(RequestId::of(&req).to_string())
+                }),
+            )
+            .route(
+                "/error",
+                web::get().to(|| async {
+                    Err::<HttpResponse, _>(ApiError::BadRequest {
+                        reason: "test".to_string(),
+                    })
+                }),
+            ),
+    )
+    .await;
+    test::call_service(&app, req.to_request()).await
+}
+
+fn header(res: &ServiceResponse) -> String {
+    res.headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("every response carries an ID")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn a_generated_id_is_the_one_the_handler_saw() {
+    let res = call(test::TestRequest::get().uri("/id")).await;
+    let id = header(&res);
+    assert!(uuid::Uuid::parse_str(&id).is_ok(), "{id}");
+    assert_eq!(test::read_body(res).await, id.as_bytes());
+
+    let other = header(&call(test::TestRequest::get().uri("/id")).await);
+    assert_ne!(id, other, "each request gets its own");
+}
+
+#[actix_web::test]
+async fn an_inbound_id_is_echoed() {
+    let req = test::TestRequest::get()
+        .uri("/id")
+        .insert_header(("X-Request-Id", "client-abc_123.4"));
+    let res = call(req).await;
+    assert_eq!(header(&res), "client-abc_123.4");
+    assert_eq!(test::read_body(res).await, "client-abc_123.4");
+}
+
+#[actix_web::test]
+async fn an_unsafe_inbound_id_is_replaced() {
+    let long = "a".repeat(129);
+    for inbound in ["has space", "quote\"d", long.as_str()] {
+        let req = test::TestRequest::get()
+            .uri("/id")
+            .insert_header(("X-Request-Id", inbound));
+        let id = header(&call(req).await);
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{inbound:?} -> {id}");
+    }
+}
+
+#[actix_web::test]
+async fn an_error_body_carries_the_same_id() {
+    let req = test::TestRequest::get()
+        .uri("/error")
+        .insert_header(("X-Request-Id", "error-ref-1"));
+    let res = call(req).await;
+    assert_eq!(header(&res), "error-ref-1");
+    let body: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"]["request_id"], "error-ref-1");
+}
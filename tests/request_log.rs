@@ -0,0 +1,70 @@
+//! Each request produces exactly one `request completed` JSON line, tagged
+//! with its correlation ID.
+
+use actix_web::{App, HttpResponse, test, web};
+use serde_json::Value;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use uncaught_exception::middleware::{CorrelationId, RequestTracing};
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn lines(&self) -> Vec<Value> {
+        let bytes = self.0.lock().unwrap();
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("every line is JSON"))
+            .collect()
+    }
+}
+
+#[actix_web::test]
+async fn logs_one_json_line_per_request() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_writer(move || writer.clone())
+        .finish();
+    // The test runtime is single-threaded, so a thread-local default suffices.
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestTracing)
+            .wrap(CorrelationId)
+            .route("/ping", web::get().to(HttpResponse::NoContent)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/ping")
+        .insert_header(("X-Request-Id", "test-request-1"))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let lines = captured.lines();
+    assert_eq!(lines.len(), 1, "{lines:?}");
+    let line = &lines[0];
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["message"], "request completed");
+    assert_eq!(line["route"], "/ping");
+    assert_eq!(line["status"], 204);
+    assert!(line["latency_ms"].as_f64().is_some_and(|ms| ms >= 0.0));
+    assert_eq!(line["span"]["request_id"], "test-request-1");
+    assert_eq!(line["span"]["method"], "GET");
+}
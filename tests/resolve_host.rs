@@ -0,0 +1,114 @@
+//! `resolve_host` only reads the host from the request, leaving the whitelist
+//! and the default host to its callers.
+
+use actix_web::http::header::{HOST, HeaderValue};
+use actix_web::test;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::host::{DEFAULT_MAX_HOST_LEN, is_header_safe, request_host, resolve_host};
+
+fn invalid(result: Result<&str, ApiError>) -> Option<String> {
+    match result {
+        Err(ApiError::InvalidHost { host }) => host,
+        other => panic!("expected InvalidHost, got {other:?}"),
+    }
+}
+
+#[actix_web::test]
+async fn a_present_host_is_returned_as_is() {
+    let req = test::TestRequest::default()
+        .insert_header((HOST, "evil.com:8080"))
+        .to_http_request();
+    assert_eq!(resolve_host(&req).unwrap(), "evil.com:8080");
+
+    // HTTP/2 carries it in the URI's authority instead.
+    let req = test::TestRequest::default()
+        .uri("https://my-app.com/secure/waitlist")
+        .to_http_request();
+    assert_eq!(resolve_host(&req).unwrap(), "my-app.com");
+}
+
+#[actix_web::test]
+async fn an_absent_host_is_invalid() {
+    let req = test::TestRequest::default().to_http_request();
+    assert_eq!(invalid(resolve_host(&req)), None);
+}
+
+#[actix_web::test]
+async fn an_empty_host_is_invalid() {
+    let req = test::TestRequest::default()
+        .insert_header((HOST, " "))
+        .to_http_request();
+    assert_eq!(invalid(resolve_host(&req)).as_deref(), Some(" "));
+}
+
+#[actix_web::test]
+async fn a_non_utf8_host_is_invalid() {
+    let req = test::TestRequest::default()
+        .insert_header((HOST, HeaderValue::from_bytes(b"caf\xe9.com").unwrap()))
+        .to_http_request();
+    assert_eq!(invalid(resolve_host(&req)), None);
+}
+
+#[actix_web::test]
+async fn duplicate_hosts_are_invalid() {
+    let req = test::TestRequest::default()
+        .append_header((HOST, "my-app.com"))
+        .append_header((HOST, "evil.com"))
+        .to_http_request();
+    assert_eq!(invalid(resolve_host(&req)), None);
+}
+
+#[actix_web::test]
+async fn the_default_host_only_replaces_an_absent_one() {
+    let req = test::TestRequest::default().to_http_request();
+    assert_eq!(
+        request_host(&req, Some("my-app.com"), DEFAULT_MAX_HOST_LEN).unwrap(),
+        "my-app.com"
+    );
+
+    let req = test::TestRequest::default()
+        .insert_header((HOST, ""))
+        .to_http_request();
+    assert_eq!(
+        invalid(request_host(&req, Some("my-app.com"), DEFAULT_MAX_HOST_LEN)).as_deref(),
+        Some("")
+    );
+}
+
+#[actix_web::test]
+async fn hosts_with_control_characters_are_not_header_safe() {
+    assert!(is_header_safe("my-app.com:8080"));
+    assert!(is_header_safe("xn--caf-dma.com"));
+    for host in [
+        "my-app.com\r\nSet-Cookie: session=evil",
+        "my-app.com\n",
+        "my-app.com\r",
+        "my\0app.com",
+        "my-app.com\u{7f}",
+        "my\tapp.com",
+    ] {
+        assert!(!is_header_safe(host), "{host:?}");
+    }
+}
+
+#[actix_web::test]
+async fn a_host_with_control_characters_is_invalid_and_not_echoed() {
+    // The header parser already refuses CR and LF; a tab still gets through.
+    let req = test::TestRequest::default()
+        .insert_header((HOST, "my-app.com\tevil.com"))
+        .to_http_request();
+    assert_eq!(invalid(resolve_host(&req)), None);
+}
+
+#[actix_web::test]
+async fn a_default_host_with_crlf_is_not_used() {
+    let req = test::TestRequest::default().to_http_request();
+    assert_eq!(
+        invalid(request_host(
+            &req,
+            Some("my-app.com\r\nX-Injected: 1"),
+            DEFAULT_MAX_HOST_LEN
+        )),
+        None
+    );
+}
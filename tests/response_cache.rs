@@ -0,0 +1,107 @@
+//! A signup the backend accepted recently is answered again without calling
+//! it, until the cache entry expires.
+
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use actix_web::{http::StatusCode, test};
+use uncaught_exception::config::ConfigError;
+use uncaught_exception::response_cache::{ResponseCache, ResponseCacheConfig};
+
+use common::{
+    counting_https_backend, local_backend_builder, request_with_host, test_app, test_builder,
+    trust_fixture_cert,
+};
+
+fn cache(ttl_ms: u64) -> ResponseCacheConfig {
+    ResponseCacheConfig {
+        capacity: 16,
+        ttl_ms,
+    }
+}
+
+#[actix_web::test]
+async fn a_repeated_signup_is_answered_from_the_cache() {
+    let (host, calls) = counting_https_backend();
+    let mut state = local_backend_builder(&host)
+        .response_cache(cache(60_000))
+        .build()
+        .unwrap();
+    trust_fixture_cert(&mut state);
+    let app = test_app(state).await;
+
+    let first =
+        test::call_service(&app, request_with_host(&host, "User@Good.com").to_request()).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let first = test::read_body(first).await;
+    // The same address, normalized.
+    let again =
+        test::call_service(&app, request_with_host(&host, "user@good.com").to_request()).await;
+    assert_eq!(again.status(), StatusCode::OK);
+    assert_eq!(test::read_body(again).await, first);
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn an_expired_entry_no_longer_answers() {
+    let (host, calls) = counting_https_backend();
+    let mut state = local_backend_builder(&host)
+        .response_cache(cache(50))
+        .build()
+        .unwrap();
+    trust_fixture_cert(&mut state);
+    let app = test_app(state).await;
+
+    let first =
+        test::call_service(&app, request_with_host(&host, "user@good.com").to_request()).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+
+    // Past the TTL the waitlist decides again, and it has the address already.
+    let again =
+        test::call_service(&app, request_with_host(&host, "user@good.com").to_request()).await;
+    assert_eq!(again.status(), StatusCode::CONFLICT);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn entries_expire_and_a_zero_capacity_caches_nothing() {
+    let cache_of = |config| {
+        let cache = ResponseCache::new(config);
+        cache.insert("user@good.com");
+        cache
+    };
+
+    let fresh = cache_of(cache(60_000));
+    assert!(fresh.contains("user@good.com"));
+    assert!(!fresh.contains("other@good.com"));
+
+    let expiring = cache_of(cache(20));
+    std::thread::sleep(Duration::from_millis(40));
+    assert!(!expiring.contains("user@good.com"));
+
+    let disabled = cache_of(ResponseCacheConfig::default());
+    assert!(!disabled.contains("user@good.com"));
+}
+
+#[actix_web::test]
+async fn a_zero_ttl_is_refused() {
+    let err = test_builder()
+        .response_cache(cache(0))
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidValue {
+                name: "response_cache.ttl_ms",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
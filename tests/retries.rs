@@ -0,0 +1,103 @@
+//! Backend attempts that fail before the signup can have been processed are
+//! retried; a timeout is not, and is told apart from running out of retries.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{ResponseError, http::StatusCode};
+use uncaught_exception::backend::{BackendConfig, client_builder, submit_waitlist};
+use uncaught_exception::error::ApiError;
+
+// A plain-HTTP backend that answers its n-th request with `statuses[n]`, and
+// every later one with the last of them. Returns its URL and a count of the
+// requests it answered.
+fn scripted_backend(statuses: &'static [u16]) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let _ = stream.read(&mut [0; 4096]);
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let status = statuses[n.min(statuses.len() - 1)];
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            );
+        }
+    });
+    (
+        format!("http://127.0.0.1:{port}/v1/waitlist?api_key=secret&email=user@good.com"),
+        calls,
+    )
+}
+
+// A backend that accepts connections and never answers.
+fn silent_backend() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    std::thread::spawn(move || {
+        let mut open = Vec::new();
+        for stream in listener.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            open.push(stream);
+        }
+    });
+    (
+        format!("http://127.0.0.1:{port}/v1/waitlist?api_key=secret&email=user@good.com"),
+        calls,
+    )
+}
+
+// Submits a signup to `url` through a client with the backend timeout of
+// 200 ms, retrying up to `max_retries` times.
+async fn submit(url: &str, max_retries: u32) -> Result<(), ApiError> {
+    let config = BackendConfig {
+        max_retries,
+        timeout_ms: 200,
+        ..BackendConfig::default()
+    };
+    let client = client_builder(&config).build().unwrap();
+    submit_waitlist(&client, &config, url.parse().unwrap()).await
+}
+
+#[actix_web::test]
+async fn two_server_errors_then_a_success_take_three_calls() {
+    let (url, calls) = scripted_backend(&[503, 500, 200]);
+    submit(&url, 2).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn running_out_of_retries_is_not_a_timeout() {
+    let (url, calls) = scripted_backend(&[503]);
+    let err = submit(&url, 2).await.unwrap_err();
+    assert!(
+        matches!(err, ApiError::RetriesExhausted { attempts: 3, .. }),
+        "{err}"
+    );
+    assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn a_timeout_is_reported_as_such_and_not_retried() {
+    let (url, calls) = silent_backend();
+    let err = submit(&url, 2).await.unwrap_err();
+    assert!(matches!(err, ApiError::UpstreamTimeout { .. }), "{err}");
+    assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn a_client_error_is_not_retried() {
+    let (url, calls) = scripted_backend(&[400, 200]);
+    let err = submit(&url, 2).await.unwrap_err();
+    assert!(matches!(err, ApiError::Upstream { .. }), "{err}");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,97 @@
+//! A fast-failed request says when to come back: `Retry-After` carries the
+//! rest of the circuit's cooldown, or the time until the next rate-limit
+//! token, in whole seconds rounded up.
+
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::header::HeaderMap;
+use actix_web::{App, HttpResponse, ResponseError, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::circuit_breaker::CircuitBreakerConfig;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::{RateLimitConfig, RateLimiter};
+
+use common::{FakeBackend, local_backend_builder, request_with_host, test_app};
+
+fn retry_after(headers: &HeaderMap) -> u64 {
+    headers
+        .get("retry-after")
+        .expect("a Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn an_open_circuit_gives_the_rest_of_its_cooldown() {
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::Upstream {
+            status: None,
+            reason: "connection refused".to_string(),
+        })
+    });
+    let state = local_backend_builder("127.0.0.1")
+        .backend_client(backend)
+        .circuit_breaker(CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 1,
+            cooldown_ms: 30_000,
+        })
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+    let get = || request_with_host("127.0.0.1", "user@good.com").to_request();
+
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert!(res.headers().get("retry-after").is_none());
+
+    actix_web::rt::time::sleep(Duration::from_millis(1_100)).await;
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let secs = retry_after(res.headers());
+    assert!((28..=29).contains(&secs), "{secs}");
+    // The body stays generic.
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "UPSTREAM_UNAVAILABLE");
+    assert!(!body.to_string().contains("retry"), "{body}");
+}
+
+#[actix_web::test]
+async fn a_circuit_waiting_on_its_trial_says_one_second() {
+    let res = ApiError::CircuitOpen {
+        retry_after: Duration::ZERO,
+    }
+    .error_response();
+    assert_eq!(res.headers().get("retry-after").unwrap(), "1");
+}
+
+#[actix_web::test]
+async fn the_rate_limiter_gives_the_time_to_the_next_token() {
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimiter::new(RateLimitConfig {
+                requests_per_second: 0.25,
+                burst: 1,
+                ..RateLimitConfig::default()
+            }))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let get = || {
+        test::TestRequest::get()
+            .peer_addr("203.0.113.7:4000".parse().unwrap())
+            .to_request()
+    };
+
+    assert_eq!(
+        test::call_service(&app, get()).await.status(),
+        StatusCode::OK
+    );
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after(res.headers()), 4);
+}
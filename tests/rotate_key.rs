@@ -0,0 +1,193 @@
+//! `POST /admin/rotate-key` swaps in a new API key for the backend calls that
+//! follow, without a restart, and refuses a key startup would refuse.
+
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test};
+use futures_util::future::LocalBoxFuture;
+use serde_json::{Value, json};
+use tokio::sync::Notify;
+use uncaught_exception::backend::BackendClient;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::AuthConfig;
+use uncaught_exception::middleware::RequestDeadline;
+use uncaught_exception::secrets::{REDACTED, Secret, SecretRegistry, SharedSecret};
+use uncaught_exception::startup::DEMO_API_KEY;
+
+use common::{local_backend_builder, recording_https_backend, request_with_host, test_app};
+
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+fn admin_state(host: &str) -> AppState {
+    local_backend_builder(host)
+        .auth(AuthConfig {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+        })
+        .build()
+        .unwrap()
+}
+
+async fn rotate(state: &AppState, body: Value) -> (StatusCode, Value) {
+    let app = test_app(state.clone()).await;
+    let req = test::TestRequest::post()
+        .uri("/admin/rotate-key")
+        .insert_header(("Authorization", format!("Bearer {ADMIN_TOKEN}")))
+        .set_json(body)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn backend_calls_after_a_rotation_use_the_new_key() {
+    let (host, queries) = recording_https_backend();
+    let mut state = admin_state(&host);
+    common::trust_fixture_cert(&mut state);
+    let app = test_app(state.clone()).await;
+
+    let res = test::call_service(&app, request_with_host(&host, "a@good.com").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let (status, body) = rotate(&state, json!({"api_key": "rotated-key-5c6d7e"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"api_key_length": 18}));
+
+    let res = test::call_service(&app, request_with_host(&host, "b@good.com").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let queries = queries.lock().unwrap().clone();
+    assert_eq!(queries.len(), 2);
+    assert!(queries[0].contains("api_key=test-key&"), "{queries:?}");
+    assert!(
+        queries[1].contains("api_key=rotated-key-5c6d7e&"),
+        "{queries:?}"
+    );
+}
+
+#[actix_web::test]
+async fn a_snapshot_taken_before_a_rotation_keeps_the_old_key() {
+    let key = SharedSecret::new(Secret::new("old-key"));
+    let in_flight = key.current();
+    let replaced = key.rotate(Secret::new("new-key"));
+    assert_eq!(in_flight.expose(), "old-key");
+    assert_eq!(replaced.expose(), "old-key");
+    assert_eq!(key.clone().current().expose(), "new-key");
+}
+
+#[actix_web::test]
+async fn the_new_key_is_scrubbed_and_so_is_the_old_one_without_a_copy() {
+    let state = AppState::builder()
+        .api_key("scrub-old-key-0f1e2d")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    SecretRegistry::global().register("scrub-old-key-0f1e2d");
+    state
+        .rotate_api_key(Secret::new("scrub-new-key-3c4b5a"))
+        .unwrap();
+    let registry = SecretRegistry::global();
+    assert_eq!(registry.redact("scrub-new-key-3c4b5a"), REDACTED);
+    assert_eq!(registry.redact("scrub-old-key-0f1e2d"), REDACTED);
+    assert!(registry.holds("scrub-new-key-3c4b5a"));
+    assert!(!registry.holds("scrub-old-key-0f1e2d"));
+}
+
+// Takes its key when the call starts, then waits to be released and fails
+// with an error that quotes the key, as a careless upstream error might.
+#[derive(Debug)]
+struct SlowBackend {
+    key: SharedSecret,
+    started: Notify,
+    release: Notify,
+}
+
+impl BackendClient for SlowBackend {
+    fn submit_waitlist<'a>(
+        &'a self,
+        _host: &'a str,
+        _email: &'a str,
+        _deadline: Option<RequestDeadline>,
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        let key = self.key.current();
+        Box::pin(async move {
+            self.started.notify_one();
+            self.release.notified().await;
+            Err(ApiError::Detailed {
+                message: format!("upstream refused key {}", key.expose()),
+            })
+        })
+    }
+
+    // Nothing is batched here.
+    fn submit_batch<'a>(
+        &'a self,
+        _host: &'a str,
+        _emails: &'a [&'a str],
+    ) -> LocalBoxFuture<'a, Result<(), ApiError>> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+#[actix_web::test]
+async fn a_call_that_fails_after_a_rotation_still_has_the_old_key_scrubbed() {
+    let mut state = admin_state("127.0.0.1");
+    state
+        .api_key
+        .rotate(Secret::new("in-flight-old-key-7a8b9c"));
+    SecretRegistry::global().register("in-flight-old-key-7a8b9c");
+    let backend = Arc::new(SlowBackend {
+        key: state.api_key.clone(),
+        started: Notify::new(),
+        release: Notify::new(),
+    });
+    state.backend_client = backend.clone();
+    let app = test_app(state.clone()).await;
+
+    let call = async {
+        let req = request_with_host("127.0.0.1", "a@good.com").to_request();
+        let res = test::call_service(&app, req).await;
+        (res.status(), test::read_body(res).await)
+    };
+    let rotate_mid_call = async {
+        backend.started.notified().await;
+        state
+            .rotate_api_key(Secret::new("in-flight-new-key-1d2e3f"))
+            .unwrap();
+        backend.release.notify_one();
+    };
+    let ((status, body), ()) = futures_util::join!(call, rotate_mid_call);
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(!body.contains("in-flight-old-key-7a8b9c"), "{body}");
+    assert!(!SecretRegistry::global().holds("in-flight-old-key-7a8b9c"));
+}
+
+#[actix_web::test]
+async fn an_empty_or_demo_key_is_refused_and_the_current_one_stays() {
+    let state = admin_state("127.0.0.1");
+    for key in ["", DEMO_API_KEY] {
+        let (status, body) = rotate(&state, json!({ "api_key": key })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "BAD_REQUEST");
+        assert_eq!(state.api_key.current().expose(), "test-key");
+    }
+}
+
+#[actix_web::test]
+async fn requires_the_admin_token() {
+    let state = admin_state("127.0.0.1");
+    let app = test_app(state.clone()).await;
+    let req = test::TestRequest::post()
+        .uri("/admin/rotate-key")
+        .set_json(json!({"api_key": "unauthorized-key"}))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(state.api_key.current().expose(), "test-key");
+}
@@ -0,0 +1,61 @@
+//! `/vulnerable/waitlist` is only mounted in builds with the `insecure-demo`
+//! feature.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use uncaught_exception::routes;
+
+use common::{ALLOWED_HOST, request_with_host, test_app, test_state};
+
+async fn status(path: &str) -> StatusCode {
+    let app = test_app(test_state()).await;
+    let req = test::TestRequest::get()
+        .uri(path)
+        .insert_header(("Host", "my-app.com:99999"))
+        .to_request();
+    test::call_service(&app, req).await.status()
+}
+
+#[actix_web::test]
+async fn the_other_routes_are_always_mounted() {
+    assert_eq!(status("/healthz").await, StatusCode::OK);
+    assert_eq!(
+        status("/secure/waitlist?email=user@good.com").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(status("/admin/config").await, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn the_secure_route_checks_host_and_email_before_anything_else() {
+    let app = test_app(test_state()).await;
+    for (host, email) in [
+        ("evil.com", "user@good.com"),
+        (ALLOWED_HOST, "not-an-email"),
+    ] {
+        let req = request_with_host(host, email).to_request();
+        let status = test::call_service(&app, req).await.status();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{host} {email}");
+    }
+}
+
+#[cfg(not(feature = "insecure-demo"))]
+#[actix_web::test]
+async fn the_vulnerable_route_is_absent_by_default() {
+    const { assert!(!routes::INSECURE_DEMO) };
+    assert_eq!(
+        status("/vulnerable/waitlist?email=user@good.com").await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[cfg(feature = "insecure-demo")]
+#[actix_web::test]
+async fn the_vulnerable_route_is_mounted_with_the_feature() {
+    const { assert!(routes::INSECURE_DEMO) };
+    assert_eq!(
+        status("/vulnerable/waitlist?email=user@good.com").await,
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
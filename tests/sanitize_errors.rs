@@ -0,0 +1,104 @@
+//! Error responses that did not come from `ApiError` get a generic body.
+
+use actix_web::{
+    App, HttpResponse,
+    http::{StatusCode, header},
+    test, web,
+};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::SafeErrorBody;
+use uncaught_exception::handlers::secure_waitlist;
+use uncaught_exception::middleware::{CorrelationId, ErrorNegotiation, sanitize_errors};
+
+async fn leaky() -> HttpResponse {
+    HttpResponse::InternalServerError()
+        .insert_header(("X-Debug", "kept"))
+        .body("panicked at src/db.rs:42: connection refused to 10.0.0.5")
+}
+
+async fn detailed() -> HttpResponse {
+    let mut res = HttpResponse::ServiceUnavailable().json(serde_json::json!({"status": "down"}));
+    res.extensions_mut().insert(SafeErrorBody);
+    res
+}
+
+async fn get(uri: &str, host: &str) -> (StatusCode, header::HeaderMap, String) {
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(sanitize_errors())
+            .wrap(ErrorNegotiation)
+            .wrap(CorrelationId)
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist))
+            .route("/leaky", web::get().to(leaky))
+            .route("/detailed", web::get().to(detailed)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Host", host))
+        .insert_header(("X-Request-Id", "sanitize-test"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    (status, headers, body)
+}
+
+#[actix_web::test]
+async fn extractor_errors_get_a_generic_body() {
+    // Without the handler, actix answers "Query deserialize error: missing
+    // field `email`".
+    let (status, headers, body) = get("/secure/waitlist", "my-app.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        headers.get(header::CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    assert!(!body.contains("deserialize"), "{body}");
+    assert!(!body.contains("missing field"), "{body}");
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"]["code"], "BAD_REQUEST");
+    assert_eq!(
+        body["error"]["message"],
+        "The request could not be processed."
+    );
+    assert_eq!(body["error"]["request_id"], "sanitize-test");
+}
+
+#[actix_web::test]
+async fn handler_bodies_are_replaced_but_headers_kept() {
+    let (status, headers, body) = get("/leaky", "my-app.com").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(headers.get("X-Debug").unwrap(), "kept");
+    assert!(!body.contains("10.0.0.5"), "{body}");
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+}
+
+#[actix_web::test]
+async fn unknown_routes_get_a_not_found_body() {
+    let (status, _, body) = get("/nope", "my-app.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"]["code"], "NOT_FOUND");
+}
+
+#[actix_web::test]
+async fn api_errors_and_opted_in_bodies_are_kept() {
+    let (status, _, body) = get("/secure/waitlist?email=user@good.com", "evil.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"]["code"], "INVALID_HOST");
+
+    let (status, _, body) = get("/detailed", "my-app.com").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, r#"{"status":"down"}"#);
+}
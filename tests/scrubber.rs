@@ -0,0 +1,109 @@
+//! With the log scrubber enabled, card numbers, UUID keys, bearer tokens and
+//! whatever the configured rules match never reach a log line.
+
+mod common;
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use uncaught_exception::config::ConfigError;
+use uncaught_exception::scrubber::{ScrubRule, Scrubber, ScrubberConfig, ScrubbingWriter};
+
+use common::test_builder;
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn enabled(rules: Vec<ScrubRule>) -> ScrubberConfig {
+    ScrubberConfig {
+        enabled: true,
+        rules,
+    }
+}
+
+fn scrubber(rules: Vec<ScrubRule>) -> Arc<Scrubber> {
+    Arc::new(Scrubber::from_config(&enabled(rules)).unwrap().unwrap())
+}
+
+#[actix_web::test]
+async fn a_logged_message_is_scrubbed() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let scrubber = scrubber(Vec::new());
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || ScrubbingWriter::new(writer.clone(), Some(scrubber.clone())))
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    tracing::info!(
+        "Signup from card 4111 1111 1111 1111 with api_key=88665751-288d-4175-852f-6519d79fdf1f"
+    );
+
+    let line = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(
+        line.contains("Signup from card [REDACTED] with api_key=[REDACTED]"),
+        "{line}"
+    );
+    assert!(!line.contains("4111"), "{line}");
+    assert!(!line.contains("88665751"), "{line}");
+}
+
+#[actix_web::test]
+async fn the_builtin_rules_leave_ordinary_text_alone() {
+    let scrubber = scrubber(Vec::new());
+    assert_eq!(
+        scrubber.scrub("Authorization: Bearer abc.def-ghi"),
+        "Authorization: Bearer [REDACTED]"
+    );
+    assert_eq!(scrubber.scrub("4111-1111-1111-1111"), "[REDACTED]");
+    // A request ID is a UUID too, but not a key.
+    let line = "[5d1c1f0e-2b7a-4c47-9a55-0d2f8f0b6a13] Rejected request on port 8080";
+    assert_eq!(scrubber.scrub(line), line);
+}
+
+#[actix_web::test]
+async fn configured_rules_apply_after_the_builtin_ones() {
+    let scrubber = scrubber(vec![ScrubRule::new(r"ssn=\d{3}-\d{2}-\d{4}", "ssn=[SSN]")]);
+    assert_eq!(scrubber.scrub("ssn=123-45-6789 ok"), "ssn=[SSN] ok");
+}
+
+#[actix_web::test]
+async fn scrubbing_is_off_unless_enabled() {
+    assert!(
+        Scrubber::from_config(&ScrubberConfig::default())
+            .unwrap()
+            .is_none()
+    );
+    assert!(test_builder().build().unwrap().scrubber.is_none());
+}
+
+#[actix_web::test]
+async fn an_invalid_rule_fails_the_build() {
+    let err = test_builder()
+        .log_scrubber(enabled(vec![ScrubRule::new("(unclosed", "x")]))
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            &err,
+            ConfigError::InvalidValue {
+                name: "log_scrubber.rules",
+                message,
+            } if message.starts_with("rules[0]:")
+        ),
+        "{err}"
+    );
+}
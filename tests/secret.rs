@@ -0,0 +1,37 @@
+//! The `Secret` wrapper keeps the API key out of debug output.
+
+use uncaught_exception::config::AppState;
+use uncaught_exception::secrets::{REDACTED, Secret, SecretRegistry};
+
+const KEY: &str = "sk-live-0123456789";
+
+#[test]
+fn debug_output_contains_no_key_material() {
+    let secret = Secret::new(KEY);
+    let debug = format!("{:?}", secret);
+    assert_eq!(debug, REDACTED);
+    assert!(!debug.contains("sk-live"));
+    assert_eq!(
+        format!("{:#?}", Some(secret.clone())),
+        format!("Some(\n    {REDACTED},\n)")
+    );
+    assert_eq!(secret.expose(), KEY);
+}
+
+#[test]
+fn registry_debug_output_hides_registered_secrets() {
+    let registry = SecretRegistry::new();
+    registry.register(KEY);
+    assert!(!format!("{:?}", registry).contains(KEY));
+}
+
+#[test]
+fn app_state_api_key_is_a_secret() {
+    let state = AppState::builder()
+        .api_key(KEY)
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap();
+    assert!(!format!("{:?}", state.api_key).contains(KEY));
+    assert_eq!(state.api_key.current().expose(), KEY);
+}
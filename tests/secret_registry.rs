@@ -0,0 +1,74 @@
+//! `ApiError::sanitize_with` scrubs whatever a `SecretRegistry` holds from the
+//! message the vulnerable handler would send, key and all.
+
+use uncaught_exception::backend::build_backend_url;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::secrets::{REDACTED, SecretRegistry};
+
+const API_KEY: &str = "registry-key-4e5f6a";
+
+// The error `/vulnerable/waitlist` returns for a host whose port is out of
+// range: the attempted URL, API key included, in a `Detailed` message.
+fn vulnerable_error() -> ApiError {
+    let Err(ApiError::UrlParse { url, source }) =
+        build_backend_url("my-app.com:99999", "/v1/waitlist", API_KEY, "user@good.com")
+    else {
+        panic!("the port is out of range");
+    };
+    ApiError::Detailed {
+        message: format!("Failed to construct backend request. URL: '{url}', Error: {source}"),
+    }
+}
+
+#[test]
+fn a_registered_secret_is_scrubbed_from_the_vulnerable_message() {
+    let registry = SecretRegistry::new();
+    registry.register(API_KEY);
+    let message = vulnerable_error().sanitize_with(&registry);
+    assert!(!message.contains(API_KEY), "{message}");
+    assert!(
+        message.contains(&format!("api_key={REDACTED}")),
+        "{message}"
+    );
+    assert_eq!(registry.redaction_count(), 1);
+}
+
+#[test]
+fn only_what_is_registered_is_scrubbed() {
+    let registry = SecretRegistry::new();
+    registry.register("user@good.com");
+    let message = vulnerable_error().sanitize_with(&registry);
+    // The email is gone, but this registry was never told about the key.
+    assert!(!message.contains("user@good.com"), "{message}");
+    assert!(message.contains(API_KEY), "{message}");
+}
+
+#[test]
+fn a_secret_that_contains_another_is_scrubbed_whole() {
+    let registry = SecretRegistry::new();
+    registry.register("registry-key");
+    registry.register(API_KEY);
+    let message = vulnerable_error().sanitize_with(&registry);
+    assert!(!message.contains("4e5f6a"), "{message}");
+}
+
+#[test]
+fn an_empty_registry_changes_nothing() {
+    let registry = SecretRegistry::new();
+    let error = vulnerable_error();
+    assert_eq!(error.sanitize_with(&registry), error.to_string());
+    assert_eq!(registry.redaction_count(), 0);
+}
+
+#[test]
+fn a_retired_secret_is_scrubbed_without_being_held() {
+    let registry = SecretRegistry::new();
+    registry.register("retired-key-8d9e0f");
+    registry.retire("retired-key-8d9e0f");
+    assert!(!registry.holds("retired-key-8d9e0f"));
+
+    let (message, found) =
+        registry.redact_counted("é retired-key-8d9e0f, then retired-key-8d9e0fretired-key-8d9e0f");
+    assert_eq!(message, format!("é {REDACTED}, then {REDACTED}{REDACTED}"));
+    assert_eq!(found, 3);
+}
@@ -0,0 +1,77 @@
+//! `SecurityHeaders` adds HSTS, `nosniff`, `X-Frame-Options`,
+//! `Referrer-Policy` and a CSP to every response, errors included, without
+//! overwriting one the handler set itself.
+
+use actix_web::dev::ServiceResponse;
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::{SecurityHeaders, SecurityHeadersConfig};
+
+const DEFAULTS: [(&str, &str); 5] = [
+    (
+        "strict-transport-security",
+        "max-age=31536000; includeSubDomains",
+    ),
+    ("x-content-type-options", "nosniff"),
+    ("x-frame-options", "DENY"),
+    ("referrer-policy", "no-referrer"),
+    (
+        "content-security-policy",
+        "default-src 'none'; frame-ancestors 'none'",
+    ),
+];
+
+async fn call(path: &str) -> ServiceResponse {
+    let app = test::init_service(
+        App::new()
+            .wrap(SecurityHeaders::new(&SecurityHeadersConfig::default()))
+            .route("/ok", web::get().to(HttpResponse::Ok))
+            .route(
+                "/error",
+                web::get().to(|| async {
+                    Err::<HttpResponse, _>(ApiError::BadRequest {
+                        reason: "test".to_string(),
+                    })
+                }),
+            )
+            .route(
+                "/framed",
+                web::get().to(|| async {
+                    HttpResponse::Ok()
+                        .insert_header(("X-Frame-Options", "SAMEORIGIN"))
+                        .finish()
+                }),
+            ),
+    )
+    .await;
+    test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await
+}
+
+fn assert_defaults(res: &ServiceResponse, except: &str) {
+    for (name, value) in DEFAULTS {
+        if name != except {
+            assert_eq!(res.headers().get(name).unwrap(), value, "{name}");
+        }
+    }
+}
+
+#[actix_web::test]
+async fn a_success_gets_every_header() {
+    let res = call("/ok").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_defaults(&res, "");
+}
+
+#[actix_web::test]
+async fn an_error_gets_every_header() {
+    let res = call("/error").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_defaults(&res, "");
+}
+
+#[actix_web::test]
+async fn a_header_the_handler_set_is_kept() {
+    let res = call("/framed").await;
+    assert_eq!(res.headers().get("x-frame-options").unwrap(), "SAMEORIGIN");
+    assert_defaults(&res, "x-frame-options");
+}
@@ -0,0 +1,203 @@
+//! The whole configuration, `[server]` included, loads from a TOML or JSON
+//! file, and `ServerConfig::validate` refuses settings no server can run with.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use uncaught_exception::cli::Cli;
+use uncaught_exception::config::{AppState, ConfigError};
+use uncaught_exception::server::{ServerConfig, TlsConfig};
+
+use common::test_builder;
+
+// A config file of its own for each test, since they run in parallel.
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("server-config-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn invalid_value(err: ConfigError) -> &'static str {
+    match err {
+        ConfigError::InvalidValue { name, .. } => name,
+        err => panic!("expected an invalid value, got {err}"),
+    }
+}
+
+#[actix_web::test]
+async fn a_full_json_config_loads() {
+    let path = config_file(
+        "full.json",
+        r#"{
+            "api_key": "json-key",
+            "allowed_hosts": ["my-app.com", "*.my-app.com"],
+            "backend": { "timeout_ms": 2000, "max_retries": 1 },
+            "rate_limit": { "enabled": false },
+            "server": {
+                "bind": "0.0.0.0",
+                "port": 9000,
+                "request_deadline_ms": 10000,
+                "workers": 2,
+                "keep_alive_secs": 0,
+                "client_request_timeout_ms": 1500,
+                "tls": { "cert_path": "cert.pem", "key_path": "key.pem", "port": 9443 }
+            }
+        }"#,
+    );
+    let state = AppState::load(Some(&path)).unwrap();
+
+    assert_eq!(state.api_key.current().expose(), "json-key");
+    assert_eq!(state.allowed_hosts, ["my-app.com", "*.my-app.com"]);
+    assert_eq!(state.backend.timeout_ms, 2000);
+    assert_eq!(state.backend.max_retries, 1);
+    assert!(!state.rate_limit.enabled);
+    assert_eq!(state.server.bind, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert_eq!(state.server.port, 9000);
+    assert_eq!(state.server.request_deadline_ms, 10000);
+    assert_eq!(state.server.workers, Some(2));
+    assert_eq!(state.server.keep_alive_secs, 0);
+    assert_eq!(state.server.client_request_timeout_ms, 1500);
+    assert_eq!(state.server.client_disconnect_timeout_ms, 1000);
+    let tls = state.server.tls.unwrap();
+    assert_eq!(tls.cert_path, Path::new("cert.pem"));
+    assert_eq!(tls.port, 9443);
+}
+
+#[actix_web::test]
+async fn the_example_toml_config_loads() {
+    let state = AppState::load(Some(Path::new("config.example.toml"))).unwrap();
+    assert_eq!(state.server, ServerConfig::default());
+}
+
+#[actix_web::test]
+async fn a_json_parse_error_does_not_quote_the_file() {
+    let path = config_file("wrong-type.json", r#"{"api_key": ["secret-in-a-list"]}"#);
+    let err = AppState::load(Some(&path)).err().unwrap();
+    assert!(matches!(err, ConfigError::Parse { .. }), "{err}");
+    assert!(!err.to_string().contains("secret-in-a-list"), "{err}");
+}
+
+#[actix_web::test]
+async fn validation_refuses_what_cannot_run() {
+    let tls = |port| TlsConfig {
+        cert_path: PathBuf::from("cert.pem"),
+        key_path: PathBuf::from("key.pem"),
+        port,
+        disable_plain_http: false,
+    };
+    let cases = [
+        (
+            ServerConfig {
+                max_body_bytes: 0,
+                ..ServerConfig::default()
+            },
+            "server.max_body_bytes",
+        ),
+        (
+            ServerConfig {
+                max_uri_bytes: 0,
+                ..ServerConfig::default()
+            },
+            "server.max_uri_bytes",
+        ),
+        (
+            ServerConfig {
+                route_deadlines_ms: [("/secure/waitlist".to_string(), 0)].into(),
+                ..ServerConfig::default()
+            },
+            "server.request_deadline_ms",
+        ),
+        (
+            ServerConfig {
+                port: 0,
+                ..ServerConfig::default()
+            },
+            "server.port",
+        ),
+        (
+            ServerConfig {
+                tls: Some(tls(8080)),
+                ..ServerConfig::default()
+            },
+            "server.tls.port",
+        ),
+        (
+            ServerConfig {
+                workers: Some(0),
+                ..ServerConfig::default()
+            },
+            "server.workers",
+        ),
+    ];
+    for (server, name) in cases {
+        assert_eq!(invalid_value(server.validate().unwrap_err()), name);
+        // The builder runs the same checks.
+        let err = test_builder().server(server).build().err().unwrap();
+        assert_eq!(invalid_value(err), name);
+    }
+
+    // Without a plain-HTTP listener, its port does not matter.
+    let https_only = ServerConfig {
+        port: 0,
+        tls: Some(TlsConfig {
+            disable_plain_http: true,
+            ..tls(8443)
+        }),
+        ..ServerConfig::default()
+    };
+    assert!(https_only.validate().is_ok());
+}
+
+fn cli(path: &Path, flags: &[&str]) -> Cli {
+    let config = ["uncaught_exception", "--config", path.to_str().unwrap()];
+    Cli::try_parse_from(config.into_iter().chain(flags.iter().copied())).unwrap()
+}
+
+#[actix_web::test]
+async fn the_flags_override_the_file_before_it_is_validated() {
+    // Serving HTTP and HTTPS on one port is refused, until `--tls` turns
+    // plain HTTP off.
+    let path = config_file(
+        "same-port.toml",
+        r#"
+            api_key = "toml-key"
+            allowed_hosts = ["my-app.com"]
+
+            [server]
+            port = 8443
+
+            [server.tls]
+            cert_path = "cert.pem"
+            key_path = "key.pem"
+            port = 8443
+        "#,
+    );
+    let err = AppState::from_cli(&cli(&path, &[])).err().unwrap();
+    assert_eq!(invalid_value(err), "server.tls.port");
+
+    let state =
+        AppState::from_cli(&cli(&path, &["--tls", "--bind", "0.0.0.0", "--production"])).unwrap();
+    assert!(state.server.tls.unwrap().disable_plain_http);
+    assert_eq!(state.server.bind, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert!(state.production_mode);
+
+    // And `--port` can move plain HTTP out of the way instead.
+    let state = AppState::from_cli(&cli(&path, &["--port", "8080"])).unwrap();
+    assert_eq!(state.server.port, 8080);
+}
+
+#[actix_web::test]
+async fn tls_only_without_a_certificate_is_refused() {
+    let path = config_file(
+        "no-cert.toml",
+        r#"
+            api_key = "toml-key"
+            allowed_hosts = ["my-app.com"]
+        "#,
+    );
+    let err = AppState::from_cli(&cli(&path, &["--tls"])).err().unwrap();
+    assert!(matches!(err, ConfigError::Tls(_)), "{err}");
+}
@@ -0,0 +1,61 @@
+//! `SecurityHeaders` removes the `Server` header by default, so no response
+//! names the software or its version, and replaces it when one is configured.
+
+use actix_web::http::header::SERVER;
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::middleware::{SecurityHeaders, SecurityHeadersConfig};
+
+// The `Server` header of a response from a handler that sets its own, and of
+// an error response.
+async fn server_headers(config: SecurityHeadersConfig) -> [Option<String>; 2] {
+    let app = test::init_service(
+        App::new()
+            .wrap(SecurityHeaders::new(&config))
+            .route(
+                "/ok",
+                web::get().to(|| async {
+                    HttpResponse::Ok()
+                        .insert_header((SERVER, "actix-web/4.11.0"))
+                        .finish()
+                }),
+            )
+            .route(
+                "/error",
+                web::get().to(|| async {
+                    Err::<HttpResponse, _>(ApiError::BadRequest {
+                        reason: "test".to_string(),
+                    })
+                }),
+            ),
+    )
+    .await;
+    let mut found = [None, None];
+    for (slot, path) in found.iter_mut().zip(["/ok", "/error"]) {
+        let res = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+        assert_ne!(res.status(), StatusCode::NOT_FOUND);
+        *slot = res
+            .headers()
+            .get(SERVER)
+            .map(|value| value.to_str().unwrap().to_string());
+    }
+    found
+}
+
+#[actix_web::test]
+async fn the_server_header_is_removed_by_default() {
+    assert_eq!(
+        server_headers(SecurityHeadersConfig::default()).await,
+        [None, None]
+    );
+}
+
+#[actix_web::test]
+async fn a_configured_server_header_replaces_the_handlers() {
+    let config = SecurityHeadersConfig {
+        server: Some("waitlist".to_string()),
+        ..SecurityHeadersConfig::default()
+    };
+    let expected = Some("waitlist".to_string());
+    assert_eq!(server_headers(config).await, [expected.clone(), expected]);
+}
@@ -0,0 +1,121 @@
+//! `apply_server_tuning` puts the keep-alive and client timeouts of
+//! `ServerConfig` on a real server: slow and idle clients are disconnected.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use actix_web::{App, HttpResponse, HttpServer, web};
+use uncaught_exception::server::{ServerConfig, apply_server_tuning};
+
+// Serves `GET /` on a free port with `config` applied.
+fn serve(config: &ServerConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+        .disable_signals();
+    let server = apply_server_tuning(server, config)
+        .listen(listener)
+        .unwrap()
+        .run();
+    actix_web::rt::spawn(server);
+    addr
+}
+
+fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+fn read_some(stream: &mut TcpStream) -> String {
+    let mut buf = [0; 1024];
+    let n = stream.read(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..n]).into_owned()
+}
+
+// Runs the blocking client `f` off the runtime thread, which the server needs.
+async fn client<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    actix_web::rt::task::spawn_blocking(f).await.unwrap()
+}
+
+fn tuned(config: ServerConfig) -> ServerConfig {
+    ServerConfig {
+        workers: Some(1),
+        ..config
+    }
+}
+
+#[actix_web::test]
+async fn a_client_slow_to_send_its_headers_gets_a_408() {
+    let addr = serve(&tuned(ServerConfig {
+        client_request_timeout_ms: 200,
+        ..ServerConfig::default()
+    }));
+    client(move || {
+        let mut stream = connect(addr);
+        // The head is never finished: the blank line is missing.
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n")
+            .unwrap();
+
+        let started = Instant::now();
+        let response = read_some(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 408"), "{response}");
+        // Written by actix itself, past every middleware, and still with no
+        // `Server` header to give the version away.
+        assert!(
+            !response.to_ascii_lowercase().contains("\r\nserver:"),
+            "{response}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(3));
+    })
+    .await;
+}
+
+#[actix_web::test]
+async fn an_idle_keep_alive_connection_is_closed() {
+    let addr = serve(&tuned(ServerConfig {
+        keep_alive_secs: 1,
+        ..ServerConfig::default()
+    }));
+    client(move || {
+        let mut stream = connect(addr);
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let response = read_some(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        // Nothing more is sent; the server hangs up once keep-alive runs out.
+        let started = Instant::now();
+        let mut buf = [0; 64];
+        match stream.read(&mut buf) {
+            Ok(0) => {}
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => {}
+            other => panic!("expected the connection to close, got {other:?}"),
+        }
+        assert!(started.elapsed() < Duration::from_secs(4));
+    })
+    .await;
+}
+
+#[actix_web::test]
+async fn without_keep_alive_every_response_closes_the_connection() {
+    let addr = serve(&tuned(ServerConfig {
+        keep_alive_secs: 0,
+        ..ServerConfig::default()
+    }));
+    client(move || {
+        let mut stream = connect(addr);
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let response = read_some(&mut stream).to_ascii_lowercase();
+        assert!(response.starts_with("http/1.1 200"), "{response}");
+        assert!(response.contains("connection: close"), "{response}");
+    })
+    .await;
+}
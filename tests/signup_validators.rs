@@ -0,0 +1,154 @@
+//! `AppState::signup_validators` runs the built-in checks, then those of the
+//! deployment, in order, stopping at the first that refuses a signup.
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::ResponseError;
+use actix_web::{http::StatusCode, test};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::WaitlistParams;
+use uncaught_exception::validation::{MxLookup, MxRecords, SignupValidator, ValidatorChain};
+
+use common::{FakeBackend, local_backend_builder, request_with_host, test_app};
+
+fn params(email: &str) -> WaitlistParams {
+    WaitlistParams {
+        email: email.to_string(),
+    }
+}
+
+fn status(result: Result<(), ApiError>) -> StatusCode {
+    result.err().map_or(StatusCode::OK, |e| e.status_code())
+}
+
+// Refuses the domains of throwaway inboxes, counting its calls.
+#[derive(Debug, Default)]
+struct NoDisposableDomains {
+    calls: AtomicUsize,
+}
+
+impl SignupValidator for NoDisposableDomains {
+    fn validate(&self, params: &WaitlistParams) -> Result<(), ApiError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if params.email.ends_with("@mailinator.com") {
+            return Err(ApiError::EmailDomainNotAllowed);
+        }
+        Ok(())
+    }
+}
+
+// Refuses every signup, counting its calls.
+#[derive(Debug, Default)]
+struct RefuseAll {
+    calls: AtomicUsize,
+}
+
+impl SignupValidator for RefuseAll {
+    fn validate(&self, _: &WaitlistParams) -> Result<(), ApiError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Err(ApiError::BadRequest {
+            reason: "refused".to_string(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FakeMx;
+
+impl MxLookup for FakeMx {
+    fn has_mx_records(&self, domain: &str) -> bool {
+        domain != "no-mail.com"
+    }
+}
+
+#[actix_web::test]
+async fn the_first_failure_short_circuits_the_chain() {
+    let disposable = Arc::new(NoDisposableDomains::default());
+    let refuse = Arc::new(RefuseAll::default());
+    let chain = ValidatorChain::new(vec![disposable.clone(), refuse.clone()]);
+
+    assert_eq!(
+        status(chain.validate(&params("user@mailinator.com"))),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(disposable.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(refuse.calls.load(Ordering::SeqCst), 0);
+
+    // Past the first, the second has its say.
+    assert_eq!(
+        status(chain.validate(&params("user@good.com"))),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(disposable.calls.load(Ordering::SeqCst), 2);
+    assert_eq!(refuse.calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn the_builtin_checks_come_with_their_statuses() {
+    let chain = ValidatorChain::builtin(["good.com"]);
+    assert_eq!(chain.len(), 3);
+    assert_eq!(status(chain.validate(&params(""))), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        status(chain.validate(&params("not an email"))),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status(chain.validate(&params("user@evil.com"))),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(chain.validate(&params("user@good.com"))),
+        StatusCode::OK
+    );
+
+    // Without domains, every one is allowed.
+    let chain = ValidatorChain::builtin(Vec::<String>::new());
+    assert_eq!(
+        status(chain.validate(&params("user@evil.com"))),
+        StatusCode::OK
+    );
+}
+
+#[actix_web::test]
+async fn the_mx_validator_refuses_a_domain_without_mail() {
+    let mx = MxRecords::new(Arc::new(FakeMx));
+    assert_eq!(
+        status(mx.validate(&params("user@no-mail.com"))),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status(mx.validate(&params("user@good.com"))),
+        StatusCode::OK
+    );
+}
+
+#[actix_web::test]
+async fn the_secure_handler_runs_the_deployments_validators_after_the_builtin_ones() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let disposable = Arc::new(NoDisposableDomains::default());
+    let mx = Arc::new(MxRecords::new(Arc::new(FakeMx)));
+    let state = local_backend_builder("127.0.0.1")
+        .backend_client(backend.clone())
+        .signup_validator(disposable.clone())
+        .signup_validator(mx)
+        .build()
+        .unwrap();
+    let app = test_app(state).await;
+
+    for (email, expected) in [
+        // Refused by the built-in syntax check: the others never see it.
+        ("not-an-email", StatusCode::BAD_REQUEST),
+        ("user@mailinator.com", StatusCode::FORBIDDEN),
+        ("user@no-mail.com", StatusCode::BAD_REQUEST),
+        ("user@good.com", StatusCode::OK),
+    ] {
+        let res =
+            test::call_service(&app, request_with_host("127.0.0.1", email).to_request()).await;
+        assert_eq!(res.status(), expected, "{email}");
+    }
+    assert_eq!(disposable.calls.load(Ordering::SeqCst), 3);
+    assert_eq!(backend.calls().len(), 1);
+}
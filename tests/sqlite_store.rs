@@ -0,0 +1,95 @@
+//! The SQLite store keeps signups across restarts and refuses duplicates.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{App, http::StatusCode, test, web};
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::secure_waitlist;
+use uncaught_exception::store::{SqliteWaitlistStore, WaitlistStore};
+
+fn temp_database(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[actix_web::test]
+async fn insert_reinsert_and_count() {
+    let store = SqliteWaitlistStore::open_in_memory().unwrap();
+    assert!(store.insert("user@good.com").unwrap());
+    assert!(!store.insert("user@good.com").unwrap());
+    assert!(store.insert("other@good.com").unwrap());
+    assert_eq!(store.count().unwrap(), 2);
+    assert!(store.contains("user@good.com").unwrap());
+    assert!(!store.contains("nobody@good.com").unwrap());
+
+    store.remove("user@good.com").unwrap();
+    assert!(!store.contains("user@good.com").unwrap());
+    assert_eq!(store.count().unwrap(), 1);
+}
+
+#[actix_web::test]
+async fn signups_survive_reopening_the_database() {
+    let path = temp_database("waitlist-reopen");
+    let path_str = path.to_str().unwrap();
+    {
+        let store = SqliteWaitlistStore::open(path_str).unwrap();
+        assert!(store.insert("user@good.com").unwrap());
+    }
+
+    let store = SqliteWaitlistStore::open(path_str).unwrap();
+    assert_eq!(store.count().unwrap(), 1);
+    assert!(!store.insert("user@good.com").unwrap());
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_web::test]
+async fn the_config_file_selects_the_database() {
+    let database = temp_database("waitlist-config");
+    let config = std::env::temp_dir().join(format!("waitlist-{}.toml", std::process::id()));
+    std::fs::write(
+        &config,
+        format!(
+            "api_key = \"test-key\"\nallowed_hosts = [\"my-app.com\"]\n\n[waitlist]\ndatabase = {:?}\n",
+            database.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let state = AppState::from_toml(&config).unwrap();
+    std::fs::remove_file(&config).unwrap();
+    assert!(state.waitlist.insert("user@good.com").unwrap());
+
+    let store = SqliteWaitlistStore::open(database.to_str().unwrap()).unwrap();
+    assert!(store.contains("user@good.com").unwrap());
+    drop((state, store));
+    std::fs::remove_file(&database).unwrap();
+}
+
+#[actix_web::test]
+async fn a_duplicate_in_the_database_is_refused_with_409() {
+    let store = SqliteWaitlistStore::open_in_memory().unwrap();
+    store.insert("user@good.com").unwrap();
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("127.0.0.1:1")
+        .private_host("127.0.0.1")
+        .waitlist_store(Arc::new(store))
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/secure/waitlist?email=User@good.com")
+        .insert_header(("Host", "127.0.0.1:1"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
@@ -0,0 +1,141 @@
+//! The SSRF guard on backend hosts, both before the call and when the HTTP
+//! client resolves the host to connect.
+
+use actix_web::{App, HttpResponse, HttpServer, web};
+use reqwest::dns::Resolve;
+use uncaught_exception::backend::{BackendConfig, client_builder};
+use uncaught_exception::host::{HostMatcher, UpstreamResolver, is_safe_upstream_host};
+
+fn no_exemptions() -> HostMatcher {
+    HostMatcher::default()
+}
+
+#[actix_web::test]
+async fn refuses_loopback() {
+    for host in ["127.0.0.1", "127.0.0.1:8080", "[::1]:443", "localhost"] {
+        assert!(
+            !is_safe_upstream_host(host, &no_exemptions()).await,
+            "{host}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn refuses_the_metadata_service_and_private_ranges() {
+    for host in [
+        "169.254.169.254",
+        "10.0.0.1",
+        "172.16.5.4:8443",
+        "192.168.1.1",
+        "[fd00::1]",
+        "[::ffff:169.254.169.254]",
+    ] {
+        assert!(
+            !is_safe_upstream_host(host, &no_exemptions()).await,
+            "{host}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn refuses_internal_ipv4_inside_ipv6() {
+    for host in [
+        // NAT64, to the metadata service.
+        "[64:ff9b::a9fe:a9fe]",
+        // 6to4, from 10.0.0.1.
+        "[2002:a00:1::1]",
+        // IPv4-compatible, to 127.0.0.1.
+        "[::7f00:1]",
+    ] {
+        assert!(
+            !is_safe_upstream_host(host, &no_exemptions()).await,
+            "{host}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn refuses_documentation_benchmarking_and_reserved_ranges() {
+    for host in ["[2001:db8::1]", "198.18.0.1", "198.19.255.254", "240.0.0.1"] {
+        assert!(
+            !is_safe_upstream_host(host, &no_exemptions()).await,
+            "{host}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn allows_public_addresses() {
+    for host in [
+        "1.1.1.1",
+        "8.8.8.8:443",
+        "[2606:4700:4700::1111]",
+        // NAT64 and 6to4 to 1.1.1.1.
+        "[64:ff9b::101:101]",
+        "[2002:101:101::1]",
+    ] {
+        assert!(
+            is_safe_upstream_host(host, &no_exemptions()).await,
+            "{host}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn allows_exempted_internal_hosts() {
+    let exempt = HostMatcher::new(["127.0.0.1"], true);
+    assert!(is_safe_upstream_host("127.0.0.1:8080", &exempt).await);
+    assert!(!is_safe_upstream_host("169.254.169.254", &exempt).await);
+}
+
+// A plain HTTP server on the loopback interface, reached below by name.
+fn loopback_server() -> u16 {
+    let server = HttpServer::new(|| App::new().route("/", web::post().to(HttpResponse::Ok)))
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let port = server.addrs()[0].port();
+    actix_web::rt::spawn(server.run());
+    port
+}
+
+#[actix_web::test]
+async fn the_client_checks_the_addresses_it_connects_to() {
+    // Whatever an earlier check saw, `localhost` resolves to loopback when
+    // the client connects, as a rebinding name would, and is refused then.
+    let port = loopback_server();
+    let url = format!("http://localhost:{port}/");
+    let client = client_builder(&BackendConfig::default()).build().unwrap();
+    let err = client.post(&url).send().await.unwrap_err();
+    assert!(err.is_connect(), "{err}");
+
+    let config = BackendConfig {
+        private_hosts: HostMatcher::new(["localhost"], true),
+        ..BackendConfig::default()
+    };
+    let client = client_builder(&config).build().unwrap();
+    let res = client.post(&url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn the_resolver_hands_out_only_public_addresses() {
+    let resolver = UpstreamResolver::new(no_exemptions());
+    assert!(
+        resolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .is_err()
+    );
+
+    let resolver = UpstreamResolver::new(HostMatcher::new(["localhost"], true));
+    let addrs = resolver
+        .resolve("localhost".parse().unwrap())
+        .await
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert!(
+        addrs.iter().all(|addr| addr.ip().is_loopback()),
+        "{addrs:?}"
+    );
+}
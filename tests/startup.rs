@@ -0,0 +1,101 @@
+//! `validate_startup_config` refuses the lesson's defaults before the server
+//! binds anything; `security_posture_summary` sums up what is switched on.
+
+mod common;
+
+use uncaught_exception::host::HostWhitelist;
+use uncaught_exception::middleware::RateLimitConfig;
+use uncaught_exception::routes;
+use uncaught_exception::server::TlsConfig;
+use uncaught_exception::startup::{
+    DEMO_API_KEY, StartupError, security_posture_summary, validate_startup_config,
+};
+
+use common::{test_builder, test_state};
+
+#[actix_web::test]
+async fn a_sound_configuration_passes() {
+    assert_eq!(validate_startup_config(&test_state(), false), Ok(()));
+}
+
+#[actix_web::test]
+async fn the_demo_api_key_is_refused() {
+    let state = test_builder().api_key(DEMO_API_KEY).build().unwrap();
+    for production in [false, true] {
+        assert_eq!(
+            validate_startup_config(&state, production),
+            Err(StartupError::DemoApiKey)
+        );
+    }
+}
+
+#[actix_web::test]
+async fn an_empty_whitelist_is_refused() {
+    // `build` already refuses one; this guards states assembled otherwise.
+    let mut state = test_state();
+    state.host_matcher = HostWhitelist::new(Vec::new(), false);
+    assert_eq!(
+        validate_startup_config(&state, false),
+        Err(StartupError::NoAllowedHosts)
+    );
+}
+
+#[actix_web::test]
+async fn production_refuses_only_the_insecure_build() {
+    let result = validate_startup_config(&test_state(), true);
+    if routes::INSECURE_DEMO {
+        assert_eq!(result, Err(StartupError::InsecureDemoInProduction));
+    } else {
+        assert_eq!(result, Ok(()));
+    }
+}
+
+#[cfg(feature = "insecure-demo")]
+#[actix_web::test]
+async fn the_insecure_build_still_starts_outside_production() {
+    assert_eq!(validate_startup_config(&test_state(), false), Ok(()));
+}
+
+#[actix_web::test]
+async fn the_posture_summary_reports_flags_and_counts() {
+    let mut state = test_builder()
+        .allowed_host("prod.my-app.com")
+        .allowed_host("*.my-app.com")
+        .production_mode(true)
+        .rate_limit(RateLimitConfig {
+            enabled: false,
+            ..RateLimitConfig::default()
+        })
+        .build()
+        .unwrap();
+    state.server.tls = Some(TlsConfig {
+        cert_path: "cert.pem".into(),
+        key_path: "key.pem".into(),
+        port: TlsConfig::DEFAULT_PORT,
+        disable_plain_http: false,
+    });
+    let summary = security_posture_summary(&state);
+    assert_eq!(summary.insecure_demo, routes::INSECURE_DEMO);
+    assert!(summary.production_mode);
+    assert!(summary.tls);
+    assert!(!summary.rate_limit);
+    assert_eq!(summary.allowed_hosts, 3);
+    assert!(!summary.demo_api_key);
+
+    let line = summary.to_string();
+    assert!(line.contains("production_mode=true tls=true rate_limit=false"));
+    assert!(line.contains("allowed_hosts=3 demo_api_key=false"));
+    assert!(!line.contains("test-key"), "{line}");
+}
+
+#[actix_web::test]
+async fn the_posture_summary_flags_the_demo_key() {
+    let state = test_builder().api_key(DEMO_API_KEY).build().unwrap();
+    let summary = security_posture_summary(&state);
+    assert!(summary.demo_api_key);
+    assert!(!summary.production_mode);
+    assert!(!summary.tls);
+    assert!(summary.rate_limit);
+    assert_eq!(summary.allowed_hosts, 1);
+    assert!(!summary.to_string().contains(DEMO_API_KEY));
+}
@@ -0,0 +1,139 @@
+//! With `tls_pins` set, the backend's certificate must carry one of the pinned
+//! public keys.
+//!
+//! The backend is a local HTTPS server with the self-signed certificate in
+//! `tests/fixtures`, generated with:
+//!
+//! ```text
+//! openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+//!     -keyout backend-key.pem -out backend-cert.pem -days 36500 \
+//!     -subj "/CN=127.0.0.1" -addext "subjectAltName=IP:127.0.0.1,DNS:localhost"
+//! ```
+
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::Value;
+use uncaught_exception::backend::{BackendConfig, submit_waitlist};
+use uncaught_exception::config::ConfigError;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::host::HostMatcher;
+use uncaught_exception::pinning::SpkiPin;
+
+use common::{
+    counting_https_backend, fixture, https_backend, local_backend_builder, request_with_host,
+    test_app, test_builder, trust_fixture_cert,
+};
+
+// The pin of the fixture certificate's key, computed with `openssl` as shown
+// in `uncaught_exception::pinning`.
+const FIXTURE_PIN: &str = "xsXycwKf/gzdqbc23i7uwl5k6LM9F0mKWsmMjDhr/r4=";
+const OTHER_PIN: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+// Signs up through the secure handler, with the backend pinned to `pins`.
+async fn sign_up(pins: &[&str]) -> (StatusCode, Value) {
+    let host = https_backend();
+    let mut builder = local_backend_builder(&host);
+    for pin in pins {
+        builder = builder.tls_pin(*pin);
+    }
+    let mut state = builder.build().unwrap();
+    trust_fixture_cert(&mut state);
+
+    let app = test_app(state).await;
+    let req = request_with_host(&host, "user@good.com").to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn a_matching_pin_lets_the_call_through() {
+    let (status, _) = sign_up(&[OTHER_PIN, FIXTURE_PIN]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn a_mismatch_fails_the_call_with_a_generic_502() {
+    let (status, body) = sign_up(&[OTHER_PIN]).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"]["code"], "UPSTREAM_UNAVAILABLE");
+    assert_eq!(
+        body["error"]["message"],
+        "The service is temporarily unavailable. Please try again later."
+    );
+    assert!(!body.to_string().contains(FIXTURE_PIN), "{body}");
+}
+
+#[actix_web::test]
+async fn a_mismatched_server_never_sees_the_request() {
+    let (host, calls) = counting_https_backend();
+    let pinned = |pin: &str| BackendConfig {
+        max_retries: 0,
+        private_hosts: HostMatcher::new(["127.0.0.1"], true),
+        tls_pins: vec![pin.parse().unwrap()],
+        ..BackendConfig::default()
+    };
+    let url = format!("https://{host}/v1/waitlist?api_key=secret&email=user@good.com")
+        .parse()
+        .unwrap();
+
+    // The handshake fails before the request is written.
+    let err = submit_waitlist(&reqwest::Client::new(), &pinned(OTHER_PIN), url)
+        .await
+        .unwrap_err();
+    match err {
+        ApiError::TlsPinMismatch { presented, .. } => {
+            assert_eq!(presented, Some(FIXTURE_PIN.parse().unwrap()));
+        }
+        err => panic!("{err}"),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // With the right pin, the same server answers, no client root needed.
+    let url = format!("https://{host}/v1/waitlist?api_key=secret&email=user@good.com")
+        .parse()
+        .unwrap();
+    submit_waitlist(&reqwest::Client::new(), &pinned(FIXTURE_PIN), url)
+        .await
+        .unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn without_pins_any_trusted_certificate_will_do() {
+    let (status, _) = sign_up(&[]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn the_pin_of_a_certificate_is_the_hash_of_its_key() {
+    let pem = std::fs::read(fixture("backend-cert.pem")).unwrap();
+    let der = rustls_pemfile::certs(&mut pem.as_slice())
+        .next()
+        .unwrap()
+        .unwrap();
+    let pin = SpkiPin::of_certificate(&der).unwrap();
+    assert_eq!(pin.to_string(), FIXTURE_PIN);
+    assert_eq!(FIXTURE_PIN.parse::<SpkiPin>(), Ok(pin));
+}
+
+#[actix_web::test]
+async fn malformed_pins_are_refused() {
+    for pin in ["not base64!", "c2hvcnQ="] {
+        let err = test_builder().tls_pin(pin).build().err().unwrap();
+        assert!(
+            matches!(
+                err,
+                ConfigError::InvalidValue {
+                    name: "backend.tls_pins",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+}
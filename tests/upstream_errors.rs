@@ -0,0 +1,183 @@
+//! Backend connection failures are told apart in the logs and the audit log,
+//! while clients only ever see the generic message of the status.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{ResponseError, body::to_bytes, http::StatusCode};
+use uncaught_exception::audit::{AuditEvent, AuditEventKind, AuditLogger, AuditSink};
+use uncaught_exception::backend::{
+    BackendConfig, UpstreamFailure, classify_failure, classify_reqwest_error, submit_waitlist,
+};
+use uncaught_exception::error::ApiError;
+
+const SECRET: &str = "upstream-canary-93ab";
+
+#[derive(Clone, Default)]
+struct MemorySink(Arc<Mutex<Vec<AuditEvent>>>);
+
+impl AuditSink for MemorySink {
+    fn record(&self, event: &AuditEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+// A server that answers every connection with plain HTTP, whatever the client
+// sends, so a TLS handshake against it fails. Returns its port and a count of
+// the connections it accepted.
+fn plain_http_server() -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+        }
+    });
+    (port, connections)
+}
+
+// A server that accepts connections and never says a word.
+fn silent_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let mut open = Vec::new();
+        for stream in listener.incoming().flatten() {
+            open.push(stream);
+        }
+    });
+    port
+}
+
+async fn send(url: String) -> reqwest::Error {
+    reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap()
+        .post(url)
+        .send()
+        .await
+        .unwrap_err()
+}
+
+// Checks what the client would see: `status` and a generic body that gives
+// away neither the cause nor the URL.
+async fn assert_generic(err: &ApiError, status: StatusCode, code: &str) {
+    let res = err.error_response();
+    assert_eq!(res.status(), status);
+    let body = to_bytes(res.into_body()).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains(code), "{body}");
+    for leak in [SECRET, "127.0.0.1", "invalid", "dns", "ssl", "refused"] {
+        assert!(!body.to_lowercase().contains(leak), "{leak} in {body}");
+    }
+    // The log message is detailed, but carries no key either.
+    assert!(!err.to_string().contains(SECRET), "{err}");
+}
+
+#[actix_web::test]
+async fn connection_refused() {
+    let e = send(format!("http://127.0.0.1:1/?api_key={SECRET}")).await;
+    assert_eq!(classify_failure(&e), UpstreamFailure::ConnectionRefused);
+
+    let err = classify_reqwest_error(&e);
+    assert!(
+        matches!(
+            err,
+            ApiError::UpstreamConnect {
+                failure: UpstreamFailure::ConnectionRefused,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert!(err.to_string().contains("connection_refused"), "{err}");
+    assert_generic(&err, StatusCode::BAD_GATEWAY, "UPSTREAM_UNAVAILABLE").await;
+}
+
+#[actix_web::test]
+async fn dns_failure() {
+    // `.invalid` never resolves (RFC 2606).
+    let e = send(format!("http://backend.invalid/?api_key={SECRET}")).await;
+    assert_eq!(classify_failure(&e), UpstreamFailure::Dns, "{e:?}");
+
+    let err = classify_reqwest_error(&e);
+    assert!(
+        matches!(
+            err,
+            ApiError::UpstreamConnect {
+                failure: UpstreamFailure::Dns,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert_generic(&err, StatusCode::BAD_GATEWAY, "UPSTREAM_UNAVAILABLE").await;
+}
+
+#[actix_web::test]
+async fn tls_failure() {
+    let (port, _) = plain_http_server();
+    let e = send(format!("https://127.0.0.1:{port}/?api_key={SECRET}")).await;
+    assert_eq!(classify_failure(&e), UpstreamFailure::Tls, "{e:?}");
+
+    let err = classify_reqwest_error(&e);
+    assert!(
+        matches!(
+            err,
+            ApiError::UpstreamConnect {
+                failure: UpstreamFailure::Tls,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert_generic(&err, StatusCode::BAD_GATEWAY, "UPSTREAM_UNAVAILABLE").await;
+}
+
+#[actix_web::test]
+async fn timeout() {
+    let port = silent_server();
+    let e = send(format!("http://127.0.0.1:{port}/?api_key={SECRET}")).await;
+    assert_eq!(classify_failure(&e), UpstreamFailure::Timeout);
+
+    let err = classify_reqwest_error(&e);
+    assert!(matches!(err, ApiError::UpstreamTimeout { .. }), "{err:?}");
+    assert_generic(&err, StatusCode::GATEWAY_TIMEOUT, "UPSTREAM_TIMEOUT").await;
+}
+
+#[actix_web::test]
+async fn tls_failures_are_audited_and_not_retried() {
+    let sink = MemorySink::default();
+    AuditLogger::new(sink.clone()).install();
+
+    let (port, connections) = plain_http_server();
+    let config = BackendConfig {
+        max_retries: 2,
+        ..BackendConfig::default()
+    };
+    let url = format!("https://127.0.0.1:{port}/v1/waitlist")
+        .parse()
+        .unwrap();
+    let err = submit_waitlist(&reqwest::Client::new(), &config, url)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::UpstreamConnect { .. }), "{err:?}");
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    let events = sink.0.lock().unwrap();
+    assert!(
+        events.iter().any(|e| e.kind
+            == AuditEventKind::UpstreamFailed {
+                failure: UpstreamFailure::Tls
+            }),
+        "{events:?}"
+    );
+}
@@ -0,0 +1,93 @@
+//! `UriLengthLimit` refuses request targets over `max_uri_bytes` with a 414
+//! and a generic body, before any handler runs.
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use uncaught_exception::middleware::{
+    ErrorNegotiation, SecurityHeaders, SecurityHeadersConfig, UriLengthLimit, sanitize_errors,
+};
+
+const LIMIT: usize = 100;
+
+// Calls `uri` through the limit; the count says whether the handler ran.
+async fn call(uri: &str) -> (StatusCode, Value, usize) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = test::init_service(App::new().wrap(UriLengthLimit::new(LIMIT)).route(
+        "/secure/waitlist",
+        web::get().to(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { HttpResponse::Ok().json(serde_json::json!({})) }
+        }),
+    ))
+    .await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = res.status();
+    let body = test::read_body_json(res).await;
+    (status, body, calls.load(Ordering::SeqCst))
+}
+
+#[actix_web::test]
+async fn a_normal_request_goes_through() {
+    let (status, _, calls) = call("/secure/waitlist?email=user@good.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(calls, 1);
+}
+
+#[actix_web::test]
+async fn an_over_length_query_is_a_414_before_the_handler() {
+    let email = format!("{}@good.com", "a".repeat(LIMIT));
+    let (status, body, calls) = call(&format!("/secure/waitlist?email={email}")).await;
+    assert_eq!(status, StatusCode::URI_TOO_LONG);
+    assert_eq!(calls, 0);
+    assert_eq!(body["error"]["code"], "URI_TOO_LONG");
+    assert_eq!(body["error"]["message"], "The request URI is too long.");
+    assert!(!body.to_string().contains(&email), "{body}");
+}
+
+#[actix_web::test]
+async fn the_limit_counts_path_and_query() {
+    let at_limit = format!("/secure/waitlist?e={}", "a".repeat(LIMIT - 19));
+    assert_eq!(at_limit.len(), LIMIT);
+    assert_eq!(call(&at_limit).await.0, StatusCode::OK);
+    assert_eq!(
+        call(&format!("{at_limit}a")).await.0,
+        StatusCode::URI_TOO_LONG
+    );
+}
+
+#[actix_web::test]
+async fn the_414_is_negotiated_and_carries_the_security_headers() {
+    // Mounted as in `main`: inside the layers that format and tag responses.
+    let app = test::init_service(
+        App::new()
+            .wrap(UriLengthLimit::new(LIMIT))
+            .wrap(SecurityHeaders::new(&SecurityHeadersConfig::default()))
+            .wrap(sanitize_errors())
+            .wrap(ErrorNegotiation)
+            .route("/secure/waitlist", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/secure/waitlist?e={}", "a".repeat(LIMIT)))
+        .insert_header(("Accept", "text/plain"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::URI_TOO_LONG);
+    let headers = res.headers();
+    assert!(
+        headers
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"),
+        "{headers:?}"
+    );
+    assert_eq!(headers.get("x-content-type-options").unwrap(), "nosniff");
+    assert!(headers.contains_key("strict-transport-security"));
+    assert!(headers.contains_key("content-security-policy"));
+}
@@ -0,0 +1,78 @@
+//! `ValidatedHost` only ever yields a whitelisted host.
+
+use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+use uncaught_exception::config::AppState;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::host::ValidatedHost;
+
+fn state() -> web::Data<AppState> {
+    web::Data::new(
+        AppState::builder()
+            .api_key("test-key")
+            .allowed_host("my-app.com")
+            .allowed_host("*.my-app.com")
+            .build()
+            .unwrap(),
+    )
+}
+
+fn extract(headers: &[(&str, &str)]) -> Result<ValidatedHost, ApiError> {
+    let mut req = test::TestRequest::get().app_data(state());
+    for header in headers {
+        req = req.append_header(*header);
+    }
+    ValidatedHost::of(&req.to_http_request())
+}
+
+#[actix_web::test]
+async fn allowed_hosts_are_extracted() {
+    for host in ["my-app.com", "MY-APP.com", "api.my-app.com"] {
+        let extracted = extract(&[("Host", host)]).unwrap();
+        assert_eq!(extracted.as_str(), host);
+    }
+}
+
+#[actix_web::test]
+async fn disallowed_hosts_are_refused() {
+    let err = extract(&[("Host", "evil.com")]).unwrap_err();
+    assert!(
+        matches!(&err, ApiError::InvalidHost { host: Some(h) } if h == "evil.com"),
+        "{err:?}"
+    );
+
+    let err = extract(&[]).unwrap_err();
+    assert!(
+        matches!(err, ApiError::InvalidHost { host: None }),
+        "{err:?}"
+    );
+
+    let err = extract(&[("Host", "my-app.com"), ("Host", "evil.com")]).unwrap_err();
+    assert!(
+        matches!(err, ApiError::InvalidHost { host: None }),
+        "{err:?}"
+    );
+}
+
+#[actix_web::test]
+async fn the_handler_only_runs_for_allowed_hosts() {
+    let app = test::init_service(App::new().app_data(state()).route(
+        "/",
+        web::get().to(|host: ValidatedHost| async move { HttpResponse::Ok().body(host.0) }),
+    ))
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header(("Host", "api.my-app.com"))
+        .to_request();
+    assert_eq!(test::call_and_read_body(&app, req).await, "api.my-app.com");
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header(("Host", "evil.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = test::read_body(res).await;
+    assert!(!String::from_utf8_lossy(&body).contains("evil.com"));
+}
@@ -0,0 +1,36 @@
+//! `GET /version` reports the crate version, commit and build time, and
+//! nothing else.
+
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use uncaught_exception::version::GIT_COMMIT;
+
+use common::{test_app, test_state};
+
+#[actix_web::test]
+async fn reports_the_crate_version_and_build() {
+    let app = test_app(test_state()).await;
+    let req = test::TestRequest::get().uri("/version").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(body["commit"], GIT_COMMIT);
+    assert!(!GIT_COMMIT.is_empty());
+    let built_at = body["built_at"].as_str().unwrap();
+    assert!(humantime::parse_rfc3339(built_at).is_ok(), "{built_at}");
+
+    let mut fields: Vec<_> = body.as_object().unwrap().keys().cloned().collect();
+    fields.sort();
+    assert_eq!(fields, ["built_at", "commit", "version"]);
+}
+
+#[actix_web::test]
+async fn only_get_is_served() {
+    let app = test_app(test_state()).await;
+    let req = test::TestRequest::post().uri("/version").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
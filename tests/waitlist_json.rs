@@ -0,0 +1,109 @@
+//! `POST /secure/waitlist` with a JSON body.
+
+use actix_web::{App, dev::ServiceResponse, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::{json_config, secure_waitlist_json};
+
+// Nothing listens on port 1, so a request that gets as far as the backend call
+// fails fast with a 502.
+const ALLOWED_HOST: &str = "127.0.0.1:1";
+
+const MAX_BODY_BYTES: usize = 1024;
+
+fn test_state() -> AppState {
+    AppState::builder()
+        .api_key("test-key")
+        .allowed_host(ALLOWED_HOST)
+        .private_host(ALLOWED_HOST)
+        .max_retries(0)
+        .build()
+        .unwrap()
+}
+
+async fn post(content_type: &str, body: impl Into<String>) -> ServiceResponse {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state()))
+            .app_data(json_config(MAX_BODY_BYTES))
+            .route("/secure/waitlist", web::post().to(secure_waitlist_json)),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/secure/waitlist")
+        .insert_header(("Host", ALLOWED_HOST))
+        .insert_header(("Content-Type", content_type))
+        .set_payload(body.into())
+        .to_request();
+    test::call_service(&app, req).await
+}
+
+#[actix_web::test]
+async fn valid_json_reaches_the_backend() {
+    let res = post("application/json", r#"{"email":"user@good.com"}"#).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[actix_web::test]
+async fn invalid_email_in_json_is_rejected() {
+    let res = post("application/json", r#"{"email":"not an email"}"#).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn malformed_json_is_rejected() {
+    for body in [r#"{"email":"#, r#"{"mail":"user@good.com"}"#, "[]"] {
+        let res = post("application/json", body).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{body}");
+    }
+}
+
+#[actix_web::test]
+async fn undeserializable_bodies_get_the_generic_400() {
+    for body in [
+        // Not JSON at all, echoing a would-be secret.
+        r#"{"email": "user@good.com", "note": "s3cr3t-canary"#,
+        // JSON, but without `email`.
+        r#"{"mail": "s3cr3t-canary@good.com"}"#,
+    ] {
+        let res = post("application/json", body).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{body}");
+        let json: Value = test::read_body_json(res).await;
+        assert_eq!(json["error"]["code"], "BAD_REQUEST", "{body}");
+        assert_eq!(
+            json["error"]["message"], "The request could not be processed.",
+            "{body}"
+        );
+        let text = json.to_string();
+        for leak in ["s3cr3t-canary", "email", "line", "column"] {
+            assert!(!text.contains(leak), "{body}: {text}");
+        }
+    }
+}
+
+#[actix_web::test]
+async fn other_content_types_are_unsupported() {
+    for content_type in ["text/plain", "application/x-www-form-urlencoded"] {
+        let res = post(content_type, r#"{"email":"user@good.com"}"#).await;
+        assert_eq!(
+            res.status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "{content_type}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn oversized_bodies_are_refused() {
+    let body = format!(
+        r#"{{"email":"user@good.com","padding":"{}"}}"#,
+        "x".repeat(MAX_BODY_BYTES)
+    );
+    let res = post("application/json", body).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let body = test::read_body(res).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], "PAYLOAD_TOO_LARGE");
+    assert_eq!(body["error"]["message"], "The request body is too large.");
+}
@@ -0,0 +1,124 @@
+//! The contrast this crate teaches: a crafted `Host` header makes the backend
+//! URL fail to parse, the vulnerable handler reflects that URL (API key
+//! included) and the secure one does not.
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::{secure_waitlist, vulnerable_waitlist};
+use uncaught_exception::middleware::CorrelationId;
+use uncaught_exception::secrets::{REDACTED, SecretRegistry};
+
+const TEST_API_KEY: &str = "test-key-5f1d2c9e-leak-canary";
+// Passes `build_backend_url`'s up-front checks, but the port is out of range,
+// so the URL parser rejects it.
+const BAD_HOST: &str = "my-app.com:99999";
+const EMAIL: &str = "attacker@evil.com";
+
+fn test_state() -> AppState {
+    AppState::builder()
+        .api_key(TEST_API_KEY)
+        .allowed_host("my-app.com")
+        .build()
+        .unwrap()
+}
+
+async fn get(path: &str) -> (StatusCode, Value) {
+    // As in `main`: the key is registered before the first request is served.
+    SecretRegistry::global().register(TEST_API_KEY);
+    let app = test::init_service(
+        App::new()
+            .wrap(CorrelationId)
+            .app_data(web::Data::new(test_state()))
+            .route("/vulnerable/waitlist", web::get().to(vulnerable_waitlist))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{}{}email={}",
+            path,
+            if path.contains('?') { '&' } else { '?' },
+            EMAIL
+        ))
+        .insert_header(("Host", BAD_HOST))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    let body = serde_json::from_slice(&body).expect("error bodies are JSON");
+    (status, body)
+}
+
+fn message(body: &Value) -> &str {
+    body["error"]["message"]
+        .as_str()
+        .expect("message is a string")
+}
+
+#[actix_web::test]
+async fn vulnerable_handler_leaks_the_backend_url() {
+    let (status, body) = get("/vulnerable/waitlist").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    // The bug: the internal URL, the position of the key in it and the parser
+    // error all reach the client.
+    let message = message(&body);
+    assert!(
+        message.contains("https://my-app.com:99999/v1/waitlist"),
+        "{message}"
+    );
+    assert!(
+        message.contains(&format!("api_key={}", REDACTED)),
+        "{message}"
+    );
+    assert!(message.contains("Error: invalid port number"), "{message}");
+
+    // Only the last-resort redaction keeps the key itself out.
+    assert!(!body.to_string().contains(TEST_API_KEY));
+}
+
+#[actix_web::test]
+async fn secure_handler_returns_only_the_generic_message() {
+    let (status, body) = get("/secure/waitlist").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "INVALID_HOST");
+    assert_eq!(message(&body), "Invalid 'Host' header provided.");
+
+    let raw = body.to_string();
+    for internal in [TEST_API_KEY, REDACTED, "api_key", "/v1/waitlist", BAD_HOST] {
+        assert!(!raw.contains(internal), "leaked {internal:?}: {raw}");
+    }
+}
+
+#[actix_web::test]
+async fn vulnerable_handler_in_leak_mode_leaks_as_by_default() {
+    let (status, body) = get("/vulnerable/waitlist?mode=leak").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(
+        message(&body).contains("https://my-app.com:99999/v1/waitlist"),
+        "{body}"
+    );
+}
+
+#[actix_web::test]
+async fn vulnerable_handler_in_fixed_mode_returns_only_the_generic_message() {
+    let (status, body) = get("/vulnerable/waitlist?mode=fixed").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        message(&body),
+        "Oops! Something went wrong. Please try again later."
+    );
+
+    let raw = body.to_string();
+    for internal in [TEST_API_KEY, REDACTED, "api_key", "/v1/waitlist", BAD_HOST] {
+        assert!(!raw.contains(internal), "leaked {internal:?}: {raw}");
+    }
+}
+
+#[actix_web::test]
+async fn an_unknown_mode_is_a_400() {
+    let (status, body) = get("/vulnerable/waitlist?mode=bogus").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "BAD_REQUEST");
+}
@@ -0,0 +1,143 @@
+//! `WaitlistRequest` hands the secure handlers a host and email that passed
+//! every check, or refuses the request with the matching `ApiError`.
+
+mod common;
+
+use actix_web::{FromRequest, http::StatusCode, test, web};
+use uncaught_exception::config::{AppState, AppStateBuilder};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::WaitlistRequest;
+
+use common::local_backend_builder;
+
+// Loopback, so the SSRF check passes without DNS when it is exempted.
+const HOST: &str = "127.0.0.1";
+
+async fn extract(
+    builder: AppStateBuilder,
+    req: test::TestRequest,
+) -> Result<WaitlistRequest, actix_web::Error> {
+    let state: AppState = builder.build().unwrap();
+    let (req, mut payload) = req.app_data(web::Data::new(state)).to_http_parts();
+    WaitlistRequest::from_request(&req, &mut payload).await
+}
+
+fn get(host: &str, query: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(&format!("/secure/waitlist?{query}"))
+        .insert_header(("Host", host))
+}
+
+fn api_error(err: &actix_web::Error) -> &ApiError {
+    err.as_error::<ApiError>().expect("an ApiError")
+}
+
+#[actix_web::test]
+async fn a_valid_query_signup_is_extracted() {
+    let signup = extract(
+        local_backend_builder(HOST),
+        get(HOST, "email=user@good.com"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        signup,
+        WaitlistRequest {
+            host: HOST.to_string(),
+            email: "user@good.com".to_string(),
+        }
+    );
+}
+
+#[actix_web::test]
+async fn a_valid_json_signup_is_extracted() {
+    let req = test::TestRequest::post()
+        .uri("/secure/waitlist")
+        .insert_header(("Host", HOST))
+        .set_json(serde_json::json!({ "email": "user@good.com" }));
+    let signup = extract(local_backend_builder(HOST), req).await.unwrap();
+    assert_eq!(signup.email, "user@good.com");
+}
+
+#[actix_web::test]
+async fn an_unlisted_host_is_refused() {
+    let err = extract(
+        local_backend_builder(HOST),
+        get("evil.com", "email=user@good.com"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(api_error(&err), ApiError::InvalidHost { .. }));
+    assert_eq!(
+        err.as_response_error().status_code(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn a_host_resolving_to_an_internal_address_is_refused() {
+    // Whitelisted, but not exempted from the SSRF guard.
+    let builder = AppState::builder().api_key("test-key").allowed_host(HOST);
+    let err = extract(builder, get(HOST, "email=user@good.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        api_error(&err),
+        ApiError::InvalidHost { host: Some(host) } if host == HOST
+    ));
+}
+
+#[actix_web::test]
+async fn a_malformed_email_is_refused() {
+    let err = extract(local_backend_builder(HOST), get(HOST, "email=not-an-email"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.as_response_error().status_code(),
+        StatusCode::BAD_REQUEST
+    );
+    assert!(!matches!(api_error(&err), ApiError::InvalidHost { .. }));
+}
+
+#[actix_web::test]
+async fn a_missing_email_is_refused() {
+    let err = extract(local_backend_builder(HOST), get(HOST, "mail=user@good.com"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.as_response_error().status_code(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn an_email_domain_not_allowed_is_refused() {
+    let builder = local_backend_builder(HOST).allowed_email_domain("good.com");
+    let err = extract(builder, get(HOST, "email=user@other.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(api_error(&err), ApiError::EmailDomainNotAllowed));
+    assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn a_repeated_email_is_refused() {
+    let err = extract(
+        local_backend_builder(HOST),
+        get(HOST, "email=a@good.com&email=b@good.com"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(api_error(&err), ApiError::BadRequest { .. }));
+}
+
+#[actix_web::test]
+async fn an_unlisted_host_is_refused_before_a_repeated_email() {
+    let err = extract(
+        local_backend_builder(HOST),
+        get("evil.com", "email=a@good.com&email=b@good.com"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(api_error(&err), ApiError::InvalidHost { .. }));
+}
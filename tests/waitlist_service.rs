@@ -0,0 +1,97 @@
+//! `WaitlistService` runs the secure signup flow as a `tower_service::Service`,
+//! driven here directly, without an actix request or app.
+
+mod common;
+
+use std::future::poll_fn;
+use std::sync::Arc;
+use tower_service::Service;
+use uncaught_exception::batch::BatchConfig;
+use uncaught_exception::config::AppStateBuilder;
+use uncaught_exception::error::ApiError;
+use uncaught_exception::handlers::WaitlistRequest;
+use uncaught_exception::service::{SignupCall, SignupOutcome, WaitlistService};
+
+use common::{FakeBackend, local_backend_builder};
+
+const HOST: &str = "127.0.0.1";
+
+fn service(builder: AppStateBuilder) -> WaitlistService {
+    WaitlistService::new(Arc::new(builder.build().unwrap()))
+}
+
+fn call(email: &str) -> SignupCall {
+    SignupCall::new(WaitlistRequest {
+        host: HOST.to_string(),
+        email: email.to_string(),
+    })
+}
+
+// `poll_ready`, then `call`, as any Tower caller would.
+async fn ready_call(
+    service: &mut WaitlistService,
+    call: SignupCall,
+) -> Result<SignupOutcome, ApiError> {
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    service.call(call).await
+}
+
+#[actix_web::test]
+async fn a_signup_is_submitted_to_the_backend() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let mut service = service(local_backend_builder(HOST).backend_client(backend.clone()));
+
+    let outcome = ready_call(&mut service, call("user@good.com")).await;
+    assert_eq!(outcome.unwrap(), SignupOutcome::Submitted);
+    assert_eq!(
+        backend.calls(),
+        vec![(HOST.to_string(), "user@good.com".to_string())]
+    );
+}
+
+#[actix_web::test]
+async fn a_dry_run_never_calls_the_backend() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let mut service = service(local_backend_builder(HOST).backend_client(backend.clone()));
+
+    let outcome = ready_call(&mut service, call("user@good.com").dry_run(true)).await;
+    assert_eq!(outcome.unwrap(), SignupOutcome::DryRun);
+    assert!(backend.calls().is_empty());
+}
+
+#[actix_web::test]
+async fn a_failed_call_is_returned_and_the_signup_taken_back() {
+    let backend = FakeBackend::new(|| {
+        Err(ApiError::UpstreamTimeout {
+            reason: "timed out".to_string(),
+        })
+    });
+    let mut service = service(local_backend_builder(HOST).backend_client(backend.clone()));
+
+    for _ in 0..2 {
+        let err = ready_call(&mut service, call("user@good.com"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::UpstreamTimeout { .. }));
+    }
+    // Not refused as a duplicate the second time.
+    assert_eq!(backend.calls().len(), 2);
+}
+
+#[actix_web::test]
+async fn with_batching_a_signup_is_queued() {
+    let backend = FakeBackend::new(|| Ok(()));
+    let mut service = service(
+        local_backend_builder(HOST)
+            .backend_client(backend.clone())
+            .batch(BatchConfig {
+                enabled: true,
+                ..BatchConfig::default()
+            }),
+    );
+
+    let outcome = ready_call(&mut service, call("user@good.com")).await;
+    assert_eq!(outcome.unwrap(), SignupOutcome::Queued);
+    let outcome = ready_call(&mut service, call("user@good.com")).await;
+    assert!(matches!(outcome, Err(ApiError::AlreadySignedUp)));
+}
@@ -0,0 +1,111 @@
+//! Signups are recorded once per address, and admins can count them.
+
+use std::sync::Arc;
+
+use actix_web::{App, http::StatusCode, test, web};
+use serde_json::Value;
+use uncaught_exception::admin::waitlist_count;
+use uncaught_exception::config::AppState;
+use uncaught_exception::handlers::secure_waitlist;
+use uncaught_exception::middleware::{AdminAuth, AuthConfig};
+use uncaught_exception::store::{InMemoryStore, WaitlistStore, normalize_email};
+
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+#[actix_web::test]
+async fn insert_reports_whether_the_address_was_new() {
+    let store = InMemoryStore::new();
+    assert!(store.insert("user@good.com").unwrap());
+    assert!(!store.insert("user@good.com").unwrap());
+    assert!(store.insert("other@good.com").unwrap());
+    assert_eq!(store.count().unwrap(), 2);
+
+    store.remove("user@good.com").unwrap();
+    assert_eq!(store.count().unwrap(), 1);
+    assert!(store.insert("user@good.com").unwrap());
+}
+
+#[actix_web::test]
+async fn addresses_are_normalized_before_they_are_compared() {
+    assert_eq!(normalize_email("  User@Good.COM "), "user@good.com");
+    assert_eq!(normalize_email("user@good.com"), "user@good.com");
+}
+
+#[actix_web::test]
+async fn a_duplicate_signup_is_refused_with_409() {
+    // Nothing listens on port 1, so a signup that gets as far as the backend
+    // fails with a 502 instead.
+    let store = InMemoryStore::new();
+    store.insert("user@good.com").unwrap();
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("127.0.0.1:1")
+        .private_host("127.0.0.1")
+        .max_retries(0)
+        .waitlist_store(Arc::new(store.clone()))
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .route("/secure/waitlist", web::get().to(secure_waitlist)),
+    )
+    .await;
+    let get = |email: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/secure/waitlist?email={email}"))
+            .insert_header(("Host", "127.0.0.1:1"))
+            .to_request()
+    };
+
+    let res = test::call_service(&app, get("USER@good.com")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "ALREADY_SIGNED_UP");
+
+    // A new address reaches the backend, and is taken back off the list when
+    // the backend call fails, so the client can simply retry.
+    let res = test::call_service(&app, get("new@good.com")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(store.count().unwrap(), 1);
+}
+
+#[actix_web::test]
+async fn the_count_endpoint_needs_the_admin_token() {
+    let auth = AuthConfig {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+    };
+    let store = InMemoryStore::new();
+    store.insert("a@good.com").unwrap();
+    store.insert("b@good.com").unwrap();
+    let state = AppState::builder()
+        .api_key("test-key")
+        .allowed_host("my-app.com")
+        .auth(auth.clone())
+        .waitlist_store(Arc::new(store))
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new().app_data(web::Data::new(state)).service(
+            web::scope("/admin")
+                .wrap(AdminAuth::new(&auth))
+                .route("/waitlist/count", web::get().to(waitlist_count)),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/admin/waitlist/count")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/waitlist/count")
+        .insert_header(("Authorization", format!("Bearer {ADMIN_TOKEN}")))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["count"], 2);
+}
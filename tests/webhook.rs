@@ -0,0 +1,121 @@
+//! Signups are announced to the webhook with a signed POST, which can fail
+//! without the signup failing.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, http::StatusCode, test, web};
+use uncaught_exception::error::ApiError;
+use uncaught_exception::host::HostMatcher;
+use uncaught_exception::webhook::{SIGNATURE_HEADER, Webhook, WebhookConfig, signature};
+
+use common::{
+    https_backend, local_backend_builder, request_with_host, test_app, trust_fixture_cert,
+};
+
+const SECRET: &str = "webhook-secret";
+
+// What the receiver got: the signature header and the body.
+type Received = Arc<Mutex<Vec<(Option<String>, Vec<u8>)>>>;
+
+// A webhook receiver on a local port that records every notification and
+// answers with `status`.
+fn receiver(status: StatusCode) -> (reqwest::Url, Received) {
+    let received = Received::default();
+    let recorded = received.clone();
+    let server = HttpServer::new(move || {
+        let recorded = recorded.clone();
+        App::new().default_service(web::to(move |req: HttpRequest, body: web::Bytes| {
+            let signature = req
+                .headers()
+                .get(SIGNATURE_HEADER)
+                .map(|v| v.to_str().unwrap().to_string());
+            recorded.lock().unwrap().push((signature, body.to_vec()));
+            async move { HttpResponse::build(status).finish() }
+        }))
+    })
+    .workers(1)
+    .bind("127.0.0.1:0")
+    .unwrap();
+    let port = server.addrs()[0].port();
+    actix_web::rt::spawn(server.run());
+    let url = format!("http://127.0.0.1:{port}/hooks/waitlist");
+    (url.parse().unwrap(), received)
+}
+
+async fn sign_up(webhook: reqwest::Url) -> (StatusCode, web::Bytes) {
+    let host = https_backend();
+    let mut state = local_backend_builder(&host)
+        .webhook(WebhookConfig::new(webhook, SECRET))
+        .build()
+        .unwrap();
+    trust_fixture_cert(&mut state);
+    let app = test_app(state).await;
+    let req = request_with_host(&host, "User@Good.com").to_request();
+    let res = test::call_service(&app, req).await;
+    (res.status(), test::read_body(res).await)
+}
+
+// Waits for the background delivery to reach the receiver.
+async fn wait_for(received: &Received) -> Vec<(Option<String>, Vec<u8>)> {
+    for _ in 0..50 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    received.lock().unwrap().clone()
+}
+
+#[actix_web::test]
+async fn the_signature_is_the_hmac_of_the_body() {
+    // echo -n '{"email":"user@good.com"}' | openssl dgst -sha256 -hmac webhook-secret
+    assert_eq!(
+        signature(SECRET, br#"{"email":"user@good.com"}"#),
+        "sha256=4a665eb54239aad60a188fa012d0532395298cd960d8170555537116833d785d"
+    );
+}
+
+#[actix_web::test]
+async fn a_signup_is_delivered_signed() {
+    let (url, received) = receiver(StatusCode::OK);
+    let (status, _) = sign_up(url).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let received = wait_for(&received).await;
+    assert_eq!(received.len(), 1);
+    let (header, body) = &received[0];
+    assert_eq!(body, br#"{"email":"user@good.com"}"#);
+    assert_eq!(header.as_deref(), Some(signature(SECRET, body).as_str()));
+}
+
+#[actix_web::test]
+async fn a_failing_webhook_does_not_fail_the_signup() {
+    let (url, received) = receiver(StatusCode::INTERNAL_SERVER_ERROR);
+    let (status, body) = sign_up(url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.is_empty());
+    assert_eq!(wait_for(&received).await.len(), 1);
+
+    // Nor does one that cannot be reached at all.
+    let (status, _) = sign_up("http://127.0.0.1:1/hooks".parse().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn an_internal_webhook_url_is_refused() {
+    let (url, received) = receiver(StatusCode::OK);
+    let webhook = Webhook::new(WebhookConfig::new(url, SECRET));
+    let err = webhook
+        .deliver(
+            &reqwest::Client::new(),
+            &HostMatcher::default(),
+            "user@good.com",
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::InvalidHost { .. }), "{err}");
+    assert!(received.lock().unwrap().is_empty());
+}